@@ -0,0 +1,17 @@
+-- Migration 009: Add Fingerprint History table
+-- 每次指纹变更记录一个修订版本（变更后的完整指纹），并记录来源与操作人
+
+CREATE TABLE IF NOT EXISTS fingerprint_history (
+    id TEXT PRIMARY KEY,                                   -- UUID
+    profile_id TEXT NOT NULL,                              -- Profile ID
+    revision INTEGER NOT NULL,                             -- Profile 内递增的修订号
+    fingerprint TEXT NOT NULL,                             -- 变更后的完整指纹 (JSON)
+    source TEXT NOT NULL,                                  -- 变更来源 (baseline/manual_edit/template/regenerate_on_start/revert)
+    author TEXT,                                           -- 操作人（可选）
+    launch_seed TEXT,                                      -- 启动时重新生成使用的启动种子
+    created_at TEXT NOT NULL,                              -- 创建时间 (RFC3339)
+    FOREIGN KEY (profile_id) REFERENCES profiles(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_fingerprint_history_profile ON fingerprint_history(profile_id, created_at DESC);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_fingerprint_history_revision ON fingerprint_history(profile_id, revision);
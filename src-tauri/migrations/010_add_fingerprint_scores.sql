@@ -0,0 +1,13 @@
+-- Migration 010: Add Fingerprint Realism Scores
+-- 每个环境最近一次指纹校验的真实度评分（保存与启动时更新）
+
+CREATE TABLE IF NOT EXISTS profile_fingerprint_scores (
+    profile_id TEXT PRIMARY KEY NOT NULL,
+    score INTEGER NOT NULL,                                -- 真实度评分 (0-100)
+    valid INTEGER NOT NULL,                                -- 是否无致命错误
+    result TEXT NOT NULL,                                  -- 完整校验结果 (JSON)
+    checked_at TEXT NOT NULL,                              -- 校验时间 (RFC3339)
+    FOREIGN KEY (profile_id) REFERENCES profiles(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_profile_fingerprint_scores_score ON profile_fingerprint_scores(score);
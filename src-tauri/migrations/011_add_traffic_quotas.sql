@@ -0,0 +1,19 @@
+-- Migration 011: Add Traffic Quotas
+-- 按环境/代理设置日/月流量配额，配额周期内的用量持久化，重启后继续累计
+
+CREATE TABLE IF NOT EXISTS traffic_quotas (
+    id TEXT PRIMARY KEY NOT NULL,
+    scope TEXT NOT NULL CHECK(scope IN ('profile', 'proxy')),  -- 配额对象类型
+    target_id TEXT NOT NULL,                               -- 环境 ID 或代理标识 (proxy_key)
+    period TEXT NOT NULL CHECK(period IN ('daily', 'monthly')),
+    limit_bytes INTEGER NOT NULL,                          -- 配额字节数（上行 + 下行）
+    warn_percent INTEGER NOT NULL DEFAULT 80,              -- 预警阈值（百分比）
+    stop_on_exceed INTEGER NOT NULL DEFAULT 0,             -- 超额时是否停止浏览器
+    usage_period TEXT,                                     -- 用量所在周期 (YYYY-MM-DD 或 YYYY-MM)
+    used_bytes INTEGER NOT NULL DEFAULT 0,                 -- 该周期内已用字节数
+    notified_period TEXT,                                  -- 最近一次通知所在周期
+    notified_level TEXT,                                   -- 最近一次通知级别 (warning/exceeded)
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    UNIQUE (scope, target_id, period)
+);
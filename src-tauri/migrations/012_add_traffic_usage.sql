@@ -0,0 +1,15 @@
+-- Migration 012: Add Traffic Usage
+-- 代理桥接流量按环境、代理、日期持久化，用于汇总查询与对账导出
+
+CREATE TABLE IF NOT EXISTS traffic_usage (
+    profile_id TEXT NOT NULL,
+    proxy_key TEXT NOT NULL,                               -- proxies.id，未登记的代理为 host:port
+    day TEXT NOT NULL,                                     -- 日期 (UTC, YYYY-MM-DD)
+    bytes_sent INTEGER NOT NULL DEFAULT 0,                 -- 上行字节数
+    bytes_received INTEGER NOT NULL DEFAULT 0,             -- 下行字节数
+    updated_at TEXT NOT NULL,                              -- 最近写入时间 (RFC3339)
+    PRIMARY KEY (profile_id, proxy_key, day)
+);
+
+CREATE INDEX IF NOT EXISTS idx_traffic_usage_day ON traffic_usage(day);
+CREATE INDEX IF NOT EXISTS idx_traffic_usage_proxy ON traffic_usage(proxy_key, day);
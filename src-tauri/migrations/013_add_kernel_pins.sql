@@ -0,0 +1,10 @@
+-- Migration 013: Add Kernel Version Pins
+-- 环境 / 分组固定内核版本，未固定时使用默认版本（settings.default_kernel_version）
+
+CREATE TABLE IF NOT EXISTS kernel_pins (
+    scope TEXT NOT NULL CHECK(scope IN ('profile', 'group')),  -- 固定对象类型
+    target_id TEXT NOT NULL,                               -- 环境 ID 或分组 ID
+    version TEXT NOT NULL,                                 -- 内核版本（可为主版本号，如 146）
+    updated_at TEXT NOT NULL,                              -- 最近修改时间 (RFC3339)
+    PRIMARY KEY (scope, target_id)
+);
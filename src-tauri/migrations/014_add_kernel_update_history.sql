@@ -0,0 +1,13 @@
+-- Migration 014: Add Kernel Update History
+-- 记录内核更新的安装 / 回滚结果，便于排查有问题的内核版本
+
+CREATE TABLE IF NOT EXISTS kernel_update_history (
+    id TEXT PRIMARY KEY NOT NULL,
+    version TEXT NOT NULL,                                 -- 新内核版本
+    previous_version TEXT,                                 -- 更新前的版本（首次安装为空）
+    status TEXT NOT NULL CHECK(status IN ('installed', 'rolled_back', 'failed')),
+    message TEXT,                                          -- 失败 / 回滚原因
+    created_at TEXT NOT NULL                               -- 记录时间 (RFC3339)
+);
+
+CREATE INDEX IF NOT EXISTS idx_kernel_update_history_created ON kernel_update_history(created_at DESC);
@@ -0,0 +1,29 @@
+-- Migration 015: Add Browser Crash Reports
+-- 环境的自动重启策略，以及崩溃记录（内核日志末尾与崩溃转储路径）
+
+CREATE TABLE IF NOT EXISTS profile_restart_policies (
+    profile_id TEXT PRIMARY KEY NOT NULL,
+    mode TEXT NOT NULL DEFAULT 'never' CHECK(mode IN ('never', 'on_crash')),
+    max_retries INTEGER NOT NULL DEFAULT 3,                -- 时间窗口内最多自动重启次数
+    backoff_secs INTEGER NOT NULL DEFAULT 5,               -- 首次重启前等待时间，之后每次翻倍
+    disabled_reason TEXT,                                  -- 崩溃循环时自动停用的原因
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY (profile_id) REFERENCES profiles(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS browser_crashes (
+    id TEXT PRIMARY KEY NOT NULL,
+    profile_id TEXT NOT NULL,
+    exit_code INTEGER,                                     -- 被信号终止时为空
+    exit_reason TEXT NOT NULL,
+    started_at TEXT NOT NULL,                              -- 本次运行的启动时间 (RFC3339)
+    crashed_at TEXT NOT NULL,                              -- 发现退出的时间 (RFC3339)
+    log_path TEXT,                                         -- chrome_debug.log 路径
+    log_tail TEXT,                                         -- 日志末尾
+    dump_paths TEXT NOT NULL DEFAULT '[]',                 -- 崩溃转储路径（JSON 数组）
+    action TEXT NOT NULL CHECK(action IN ('none', 'restart', 'crash_loop')),
+    attempt INTEGER NOT NULL DEFAULT 0,
+    FOREIGN KEY (profile_id) REFERENCES profiles(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_browser_crashes_profile ON browser_crashes(profile_id, crashed_at DESC);
@@ -9,11 +9,16 @@ use modules::group::{CreateGroupDto, Group, UpdateGroupDto};
 use modules::profile::{CreateProfileDto, Profile, ProfileStatus, UpdateProfileDto};
 use modules::proxy::{CreateProxyDto, Proxy, UpdateProxyDto}; // ✅ V5 升级
 use modules::tag::{CreateTagDto, Tag, UpdateTagDto}; // ✅ V5 解锁
+use modules::app_updater::{UpdateComponent, UpdateManifest};
+use modules::browser_manager::ShutdownOutcome;
+use modules::crash_report::{CrashReportService, RestartDecision, RestartPolicy};
 use modules::{
     BridgeStats, BrowserLauncher, BrowserManager, ConfigWriter, DownloadProgress, DownloadStatus,
-    GroupService, KernelDownloader, KernelVersionInfo, ProfileService, ProxyBridgeConfig,
+    FingerprintGenerator, FingerprintHistoryEntry, FingerprintHistoryService,
+    FingerprintScoreService, GroupService, ProfileFingerprintScore,
+    KernelDownloader, KernelRegistry, KernelVersionInfo, ProfileService, ProxyBridgeConfig,
     ProxyBridgeManager, ProxyService, RecycleBinService, RecycledProfile, TagService,
-    UpdateInfo, UpdateDownloadProgress,
+    TrafficService, UpdateInfo, UpdateDownloadProgress,
 };
 use sqlx::Row;
 use sqlx::SqlitePool;
@@ -45,6 +50,14 @@ pub struct AppState {
     extension_service: Arc<Mutex<modules::ExtensionService>>,
     proxy_bridge_manager: Arc<ProxyBridgeManager>,
     kernel_downloader: Arc<Mutex<KernelDownloader>>, // Kernel download manager
+    kernel_registry: Arc<Mutex<KernelRegistry>>, // 多版本内核注册表
+    update_manifests: Arc<Mutex<HashMap<String, UpdateManifest>>>, // 已校验签名的更新清单（launcher / kernel）
+    template_manager: Arc<Mutex<modules::fingerprint::TemplateManager>>, // 设备模板
+    fingerprint_history_service: Arc<Mutex<FingerprintHistoryService>>, // 指纹历史
+    fingerprint_validator: Arc<Mutex<modules::fingerprint::FingerprintValidator>>, // 指纹校验规则
+    fingerprint_score_service: Arc<Mutex<FingerprintScoreService>>, // 指纹真实度评分
+    traffic_service: Arc<Mutex<TrafficService>>, // 流量统计与配额
+    crash_report_service: Arc<Mutex<CrashReportService>>, // 崩溃记录与重启策略
     pool: SqlitePool,
     browser_manager: Arc<BrowserManager>,
     app_data_dir: PathBuf,
@@ -97,6 +110,31 @@ async fn set_setting_value(
         "user_data_dir" => {
             modules::settings::validate_user_data_dir(&value)?;
         }
+        modules::fingerprint_score::MIN_SCORE_SETTING_KEY => {
+            modules::settings::validate_min_fingerprint_score(&value)?;
+        }
+        modules::browser_manager::SHUTDOWN_TIMEOUT_SETTING_KEY => {
+            modules::settings::validate_shutdown_timeout(&value)?;
+        }
+        modules::proxy_bridge::CONNECTION_LOG_CAPACITY_SETTING_KEY => {
+            modules::settings::validate_connection_log_capacity(&value)?;
+        }
+        modules::kernel_registry::DEFAULT_KERNEL_VERSION_SETTING_KEY => {
+            let version = value.trim();
+            if !version.is_empty() && state.kernel_registry.lock().await.find(version).is_none() {
+                return Err(format!("内核版本 {} 未安装", version));
+            }
+        }
+        "update_channel_launcher" | "update_channel_kernel" => {
+            modules::app_updater::UpdateChannel::parse(&value).map_err(|e| e.to_string())?;
+        }
+        modules::app_updater::INSTALL_WINDOW_SETTING_KEY if !value.trim().is_empty() => {
+            modules::app_updater::InstallWindow::parse(&value).map_err(|e| e.to_string())?;
+        }
+        modules::proxy_bridge::PORT_RANGE_SETTING_KEY => {
+            let (start, end) = modules::proxy_bridge::parse_port_range(&value)?;
+            state.proxy_bridge_manager.set_port_range(start, end)?;
+        }
         _ => {}
     }
 
@@ -157,6 +195,39 @@ fn profile_user_data_dir(base_dir: &PathBuf, profile_id: &str) -> PathBuf {
     base_dir.join("profiles").join(profile_id)
 }
 
+/// 解析环境启动使用的内核，返回 (可执行文件路径, 内核版本)
+///
+/// 固定版本或默认版本未安装时报错；仅在未固定且未设置默认版本时使用自定义内核路径
+async fn resolve_launch_kernel(state: &AppState, profile_id: &str) -> Result<(String, Option<String>), String> {
+    let default_version = get_setting(&state.pool, modules::kernel_registry::DEFAULT_KERNEL_VERSION_SETTING_KEY)
+        .await?
+        .filter(|v| !v.trim().is_empty());
+    let registry = state.kernel_registry.lock().await;
+    let pinned = registry.pinned_version(profile_id).await.map_err(|e| e.to_string())?;
+
+    if pinned.is_none() && default_version.is_none() {
+        let kernel_path = get_setting(&state.pool, "kernel_path")
+            .await?
+            .unwrap_or_default();
+        if !kernel_path.trim().is_empty() {
+            let version = modules::kernel_registry::version_of_exe(Path::new(&kernel_path));
+            return Ok((kernel_path, version));
+        }
+    }
+
+    match registry
+        .resolve(profile_id, default_version.as_deref())
+        .await
+        .map_err(|e| e.to_string())?
+    {
+        Some(kernel) => {
+            info!("Using kernel {} ({}) for profile {}", kernel.version, kernel.resolved_by, profile_id);
+            Ok((kernel.exe_path, Some(kernel.version)))
+        }
+        None => Err("浏览器内核路径未设置，请先在设置中配置或下载内核".to_string()),
+    }
+}
+
 async fn do_launch_browser(profile_id: String, state: &AppState) -> Result<(), String> {
     // 检查是否已在运行
     if state.browser_manager.is_running(&profile_id).await {
@@ -180,31 +251,20 @@ async fn do_launch_browser(profile_id: String, state: &AppState) -> Result<(), S
         None,
     );
 
-    let kernel_path = get_setting(&state.pool, "kernel_path")
-        .await?
-        .unwrap_or_default();
-    
-    // Try to get kernel path:
-    // 1. From settings (user configured)
-    // 2. From bundled resources (auto-detect)
-    let final_kernel_path = if kernel_path.trim().is_empty() {
-        // Check if bundled kernel exists
-        if let Some(bundled_path) = KernelDownloader::get_bundled_kernel_path() {
-            info!("Using bundled kernel: {:?}", bundled_path);
-            bundled_path.display().to_string()
-        } else {
+    // 内核选择：环境固定 → 分组固定 → 默认版本 → 自定义内核路径 → 已安装的最新版本
+    let (final_kernel_path, kernel_version) = match resolve_launch_kernel(state, &profile_id).await {
+        Ok(kernel) => kernel,
+        Err(message) => {
             state.browser_manager.emit_progress(
                 profile_id.clone(),
                 "check_config",
-                "浏览器内核路径未设置",
+                "浏览器内核不可用",
                 10,
                 false,
-                Some("浏览器内核路径未设置，请先在设置中配置或下载内核".to_string()),
+                Some(message.clone()),
             );
-            return Err("浏览器内核路径未设置，请先在设置中配置或下载内核".to_string());
+            return Err(message);
         }
-    } else {
-        kernel_path
     };
 
     let user_data_dir_setting = get_setting(&state.pool, "user_data_dir")
@@ -219,7 +279,7 @@ async fn do_launch_browser(profile_id: String, state: &AppState) -> Result<(), S
     let profile_dir = profile_user_data_dir(&base_user_data_dir, &profile_id);
     std::fs::create_dir_all(&profile_dir).map_err(|e| format!("创建用户数据目录失败: {}", e))?;
 
-    let profile = {
+    let mut profile = {
         let service = state.profile_service.lock().await;
         service
             .get_profile(&profile_id)
@@ -227,6 +287,81 @@ async fn do_launch_browser(profile_id: String, state: &AppState) -> Result<(), S
             .map_err(|e| e.to_string())?
     };
 
+    // 启动时随机指纹：使用本次启动的种子重新生成，旧指纹存入历史便于复现
+    let random_on_start = profile
+        .preferences
+        .as_ref()
+        .map(|p| p.random_fingerprint_on_start)
+        .unwrap_or(false);
+    if random_on_start {
+        let launch_seed = uuid::Uuid::new_v4().to_string();
+        let regenerated = {
+            let manager = state.template_manager.lock().await;
+            FingerprintGenerator::with_templates(manager.clone())?
+                .regenerate_for_launch(&profile.fingerprint, &launch_seed)?
+        };
+
+        // 旧指纹在修订历史中保留，新指纹记录启动种子
+        state
+            .profile_service
+            .lock()
+            .await
+            .replace_fingerprint(
+                &profile_id,
+                &regenerated,
+                modules::fingerprint_history::SOURCE_REGENERATE_ON_START,
+                None,
+                Some(&launch_seed),
+            )
+            .await
+            .map_err(|e| format!("保存新指纹失败: {}", e))?;
+
+        info!("Regenerated fingerprint for profile {} (launch seed {})", profile_id, launch_seed);
+        profile.fingerprint = regenerated;
+    }
+
+    // 指纹浏览器版本必须与内核主版本一致，否则 UA 与实际内核特征不符
+    if let Some(version) = kernel_version.as_deref() {
+        if let Err(e) = modules::kernel_registry::check_fingerprint_compat(
+            version,
+            profile.fingerprint.browser_version.as_deref(),
+            &profile.fingerprint.user_agent,
+        ) {
+            let message = e.to_string();
+            state.browser_manager.emit_progress(
+                profile_id.clone(),
+                "check_config",
+                "指纹与内核版本不一致",
+                10,
+                false,
+                Some(message.clone()),
+            );
+            return Err(message);
+        }
+    }
+
+    // 指纹真实度评分：低于设置的阈值时阻止启动
+    let fingerprint_score = score_profile_fingerprint(state, &profile).await?;
+    let min_score = get_setting(&state.pool, modules::fingerprint_score::MIN_SCORE_SETTING_KEY)
+        .await?
+        .and_then(|v| v.trim().parse::<u32>().ok())
+        .unwrap_or(0);
+    if fingerprint_score.score < min_score {
+        let message = format!(
+            "指纹真实度评分 {} 低于启动阈值 {}，请调整指纹配置",
+            fingerprint_score.score, min_score
+        );
+        state.browser_manager.emit_progress(
+            profile_id.clone(),
+            "check_config",
+            "指纹真实度评分过低",
+            10,
+            false,
+            Some(message.clone()),
+        );
+        return Err(message);
+    }
+
     // ✅ Step 1 完成
     state.browser_manager.emit_progress(
         profile_id.clone(),
@@ -283,18 +418,109 @@ async fn do_launch_browser(profile_id: String, state: &AppState) -> Result<(), S
             modules::profile::ProxyType::Socks5 => "socks5",
         };
 
-        if ProxyBridgeManager::needs_bridge(
-            proxy_type,
-            &proxy_config.username,
-            &proxy_config.password,
-        ) {
+        // 流量配额：已超额且设置了超额停止的环境不允许启动
+        let (proxy_key, has_quota) = {
+            let traffic_service = state.traffic_service.lock().await;
+            let proxy_key = traffic_service
+                .resolve_proxy_key(&proxy_config.host, proxy_config.port)
+                .await
+                .map_err(|e| e.to_string())?;
+            let quotas = traffic_service
+                .quotas_for(&profile_id, &proxy_key)
+                .await
+                .map_err(|e| e.to_string())?;
+            for quota in quotas.iter().filter(|q| q.stop_on_exceed) {
+                let status = modules::traffic::quota_status(quota.clone(), chrono::Utc::now());
+                if status.level == modules::traffic::QuotaLevel::Exceeded {
+                    return Err(format!(
+                        "流量配额已用尽（{} {}），无法启动",
+                        status.quota.target_id, status.period_key
+                    ));
+                }
+            }
+            (proxy_key, !quotas.is_empty())
+        };
+
+        // 连接日志：按设置的容量记录每个目标（0 或未设置表示不记录）
+        let connection_log_capacity = get_setting(&state.pool, modules::proxy_bridge::CONNECTION_LOG_CAPACITY_SETTING_KEY)
+            .await?
+            .and_then(|v| v.trim().parse::<usize>().ok())
+            .unwrap_or(0);
+
+        // 备用代理：按顺序从代理表加载，跳过已过期或配置无效的代理
+        let mut fallbacks = Vec::new();
+        {
+            let proxy_service = state.proxy_service.lock().await;
+            for id in &proxy_config.fallback_proxy_ids {
+                let proxy = match proxy_service.get_proxy(id).await {
+                    Ok(proxy) => proxy,
+                    Err(e) => {
+                        tracing::warn!(profile_id = %profile_id, proxy_id = %id, error = %e, "备用代理不可用，已跳过");
+                        continue;
+                    }
+                };
+                let port = match proxy.port.trim().parse::<u16>() {
+                    Ok(port) if proxy.status != "expired" && proxy.proxy_type != "direct" => port,
+                    _ => {
+                        tracing::warn!(profile_id = %profile_id, proxy_id = %id, "备用代理已过期或配置无效，已跳过");
+                        continue;
+                    }
+                };
+                fallbacks.push(modules::proxy_bridge::ProxyHop {
+                    host: proxy.host,
+                    port,
+                    proxy_type: proxy.proxy_type,
+                    username: proxy.username,
+                    password: proxy.password,
+                });
+            }
+        }
+        let has_session_template = [&proxy_config.username, &proxy_config.password]
+            .iter()
+            .any(|v| v.as_deref().map_or(false, |v| v.contains(modules::proxy_bridge::SESSION_PLACEHOLDER)));
+
+        // 代理链、备用代理、会话轮换、限速、流量配额和连接日志都需要经由本地桥接
+        let needs_bridge = !proxy_config.chain.is_empty()
+            || !fallbacks.is_empty()
+            || has_session_template
+            || connection_log_capacity > 0
+            || proxy_config.upload_limit.is_some()
+            || proxy_config.download_limit.is_some()
+            || has_quota
+            || ProxyBridgeManager::needs_bridge(
+                proxy_type,
+                &proxy_config.username,
+                &proxy_config.password,
+            );
+        tracing::debug!(profile_id = %profile_id, proxy_key = %proxy_key, needs_bridge, "代理配置");
+
+        if needs_bridge {
             let bridge_config = ProxyBridgeConfig {
                 upstream_host: proxy_config.host.clone(),
                 upstream_port: proxy_config.port,
                 upstream_type: proxy_type.to_string(),
                 username: proxy_config.username.clone(),
                 password: proxy_config.password.clone(),
-                enable_udp: true,
+                // UDP ASSOCIATE 仅 SOCKS5 上游支持（用于 WebRTC）
+                enable_udp: proxy_type == "socks5",
+                chain: proxy_config
+                    .chain
+                    .iter()
+                    .map(|hop| modules::proxy_bridge::ProxyHop {
+                        host: hop.host.clone(),
+                        port: hop.port,
+                        proxy_type: hop.r#type.as_str().to_string(),
+                        username: hop.username.clone(),
+                        password: hop.password.clone(),
+                    })
+                    .collect(),
+                fallbacks,
+                upload_limit: proxy_config.upload_limit,
+                download_limit: proxy_config.download_limit,
+                // SOCKS5 入站本地认证（供外部脚本使用，浏览器走 HTTP 入站）
+                local_username: get_setting(&state.pool, modules::proxy_bridge::LOCAL_SOCKS_USERNAME_SETTING_KEY).await?,
+                local_password: get_setting(&state.pool, modules::proxy_bridge::LOCAL_SOCKS_PASSWORD_SETTING_KEY).await?,
+                connection_log_capacity,
             };
 
             let local_addr = state
@@ -458,6 +684,16 @@ async fn do_launch_browser(profile_id: String, state: &AppState) -> Result<(), S
     Ok(())
 }
 
+/// 读取浏览器正常关闭的等待时间
+async fn browser_shutdown_timeout(pool: &SqlitePool) -> Result<std::time::Duration, String> {
+    use modules::browser_manager::{DEFAULT_SHUTDOWN_TIMEOUT_SECS, SHUTDOWN_TIMEOUT_SETTING_KEY};
+    let secs = get_setting(pool, SHUTDOWN_TIMEOUT_SETTING_KEY)
+        .await?
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT_SECS);
+    Ok(std::time::Duration::from_secs(secs))
+}
+
 async fn do_stop_browser(profile_id: String, state: &AppState) -> Result<(), String> {
     use tracing::{debug, info, warn};
 
@@ -468,54 +704,14 @@ async fn do_stop_browser(profile_id: String, state: &AppState) -> Result<(), Str
         warn!(profile_id = %profile_id, error = %e, "停止代理桥接失败");
     }
 
-    // 从 BrowserManager 注销进程
-    let child_opt = state
-        .browser_manager
-        .unregister_process(&profile_id)
-        .await?;
-
-    // 如果有 child，则优雅关闭
-    if let Some(mut child) = child_opt {
-        let pid = child.id();
-        debug!(pid = pid, "发送关闭信号");
-
-        // 使用 spawn_blocking 包装同步等待
-        let wait_result = tokio::task::spawn_blocking(move || {
-            // 尝试等待进程，设置超时通过 try_wait 轮询实现
-            for _ in 0..30 {
-                // 最多等待3秒 (100ms * 30)
-                match child.try_wait() {
-                    Ok(Some(status)) => return Ok((child, Some(status))),
-                    Ok(None) => {
-                        std::thread::sleep(std::time::Duration::from_millis(100));
-                    }
-                    Err(e) => return Err((child, e)),
-                }
-            }
-            // 超时，返回 child 以便后续强制 kill
-            Ok((child, None))
-        })
-        .await
-        .map_err(|e| format!("等待任务失败: {}", e))?;
-
-        match wait_result {
-            Ok((_, Some(status))) => {
-                info!(
-                    pid = pid,
-                    exit_code = ?status.code(),
-                    "浏览器进程已正常退出"
-                );
-            }
-            Ok((mut child, None)) => {
-                // 超时，强制kill
-                warn!(pid = pid, "进程未在3秒内退出，执行强制终止");
-                child.kill().map_err(|e| format!("停止进程失败: {}", e))?;
-                let _ = child.wait();
-            }
-            Err((_, e)) => {
-                warn!(pid = pid, error = %e, "等待进程退出失败");
-            }
+    // 先请求正常关闭（Cookie / 会话写回磁盘），超时后强制终止
+    let timeout = browser_shutdown_timeout(&state.pool).await?;
+    match state.browser_manager.stop_process(&profile_id, timeout).await {
+        Some(ShutdownOutcome::Killed) => {
+            warn!(profile_id = %profile_id, timeout_secs = timeout.as_secs(), "浏览器未在超时内正常退出，已强制终止");
         }
+        Some(ShutdownOutcome::Graceful) => debug!(profile_id = %profile_id, "浏览器已正常退出"),
+        None => {}
     }
 
     // 更新数据库状态
@@ -564,26 +760,35 @@ async fn create_profile(
         .await
         .map_err(|e| e.to_string())?;
 
+    if let Err(e) = score_profile_fingerprint(&state, &profile).await {
+        tracing::warn!("指纹评分失败: {}", e);
+    }
+
     // 发射 profile:created 事件
     let _ = app.emit("profile:created", &profile);
 
     Ok(profile)
 }
 
-/// 更新环境
+/// 更新环境（author 为指纹变更的操作人，可选）
 #[tauri::command]
 async fn update_profile(
     id: String,
     data: UpdateProfileDto,
+    author: Option<String>,
     state: State<'_, AppState>,
     app: tauri::AppHandle,
 ) -> Result<Profile, String> {
     let service = state.profile_service.lock().await;
     let profile = service
-        .update_profile(&id, data)
+        .update_profile_by(&id, data, author.as_deref())
         .await
         .map_err(|e| e.to_string())?;
 
+    if let Err(e) = score_profile_fingerprint(&state, &profile).await {
+        tracing::warn!("指纹评分失败: {}", e);
+    }
+
     // 发射 profile:updated 事件
     let _ = app.emit("profile:updated", &profile);
 
@@ -645,6 +850,138 @@ async fn stop_browser(profile_id: String, state: State<'_, AppState>) -> Result<
     do_stop_browser(profile_id, state.inner()).await
 }
 
+/// 获取浏览器最近一次的退出原因（graceful_shutdown / forced_kill / normal_exit / crashed_*）
+#[tauri::command]
+async fn get_browser_exit_reason(profile_id: String, state: State<'_, AppState>) -> Result<Option<String>, String> {
+    Ok(state.browser_manager.exit_reason(&profile_id).await)
+}
+
+/// 获取环境的自动重启策略
+#[tauri::command]
+async fn get_restart_policy(profile_id: String, state: State<'_, AppState>) -> Result<RestartPolicy, String> {
+    let service = state.crash_report_service.lock().await;
+    service.get_policy(&profile_id).await.map_err(|e| e.to_string())
+}
+
+/// 设置环境的自动重启策略（同时解除崩溃循环导致的停用）
+#[tauri::command]
+async fn set_restart_policy(
+    profile_id: String,
+    policy: RestartPolicy,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let service = state.crash_report_service.lock().await;
+    service.set_policy(&profile_id, &policy).await.map_err(|e| e.to_string())
+}
+
+/// 获取崩溃历史（可按环境过滤）
+#[tauri::command]
+async fn get_crash_history(
+    profile_id: Option<String>,
+    limit: Option<u32>,
+    state: State<'_, AppState>,
+) -> Result<Vec<modules::crash_report::CrashRecord>, String> {
+    let service = state.crash_report_service.lock().await;
+    service
+        .crash_history(profile_id.as_deref(), limit.unwrap_or(50))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 环境的用户数据目录
+async fn resolve_profile_data_dir(state: &AppState, profile_id: &str) -> Result<PathBuf, String> {
+    let user_data_dir_setting = get_setting(&state.pool, "user_data_dir")
+        .await?
+        .unwrap_or_default();
+    let base_user_data_dir = if user_data_dir_setting.trim().is_empty() {
+        state.app_data_dir.clone()
+    } else {
+        PathBuf::from(user_data_dir_setting)
+    };
+    Ok(profile_user_data_dir(&base_user_data_dir, profile_id))
+}
+
+/// 处理进程监控发现的崩溃
+async fn handle_browser_crashes(app: &tauri::AppHandle) -> Result<(), String> {
+    let crashes = app.state::<AppState>().browser_manager.take_crashes().await;
+    for crash in crashes {
+        if let Err(e) = handle_browser_crash(app, &crash).await {
+            tracing::error!(profile_id = %crash.profile_id, "记录浏览器崩溃失败: {}", e);
+        }
+    }
+    Ok(())
+}
+
+/// 记录崩溃现场，并按重启策略自动重启或在崩溃循环时通知用户
+async fn handle_browser_crash(
+    app: &tauri::AppHandle,
+    crash: &modules::browser_manager::CrashedProcess,
+) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    let profile_dir = resolve_profile_data_dir(state.inner(), &crash.profile_id).await?;
+    let started_at = crash.started_at;
+    let artifacts = tokio::task::spawn_blocking(move || {
+        modules::crash_report::collect_crash_artifacts(&profile_dir, started_at)
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let (record, decision) = state
+        .crash_report_service
+        .lock()
+        .await
+        .record_crash(crash, artifacts)
+        .await
+        .map_err(|e| e.to_string())?;
+    tracing::warn!(
+        profile_id = %crash.profile_id,
+        exit_reason = %crash.exit_reason,
+        dumps = record.dump_paths.len(),
+        action = %record.action,
+        "浏览器崩溃"
+    );
+    let _ = app.emit(modules::crash_report::BROWSER_CRASHED_EVENT, &record);
+
+    match decision {
+        RestartDecision::None => {}
+        RestartDecision::CrashLoop { crashes } => {
+            let message = format!("浏览器反复崩溃（{} 次），已停用自动重启", crashes);
+            let _ = app.emit(
+                modules::crash_report::CRASH_LOOP_EVENT,
+                serde_json::json!({ "profileId": crash.profile_id, "crashes": crashes }),
+            );
+            state.browser_manager.emit_error(crash.profile_id.clone(), message);
+        }
+        RestartDecision::Restart { attempt, delay } => {
+            let app = app.clone();
+            let profile_id = crash.profile_id.clone();
+            tauri::async_runtime::spawn(async move {
+                tokio::time::sleep(delay).await;
+                let state = app.state::<AppState>();
+                // 等待期间用户可能已手动启动或关闭了自动重启
+                let still_enabled = state
+                    .crash_report_service
+                    .lock()
+                    .await
+                    .get_policy(&profile_id)
+                    .await
+                    .map(|policy| policy.mode == modules::crash_report::RestartMode::OnCrash && policy.disabled_reason.is_none())
+                    .unwrap_or(false);
+                if !still_enabled || state.browser_manager.is_running(&profile_id).await {
+                    return;
+                }
+                tracing::info!(profile_id = %profile_id, attempt = attempt, "崩溃后自动重启浏览器");
+                if let Err(e) = do_launch_browser(profile_id.clone(), state.inner()).await {
+                    state
+                        .browser_manager
+                        .emit_error(profile_id, format!("崩溃后自动重启失败: {}", e));
+                }
+            });
+        }
+    }
+    Ok(())
+}
+
 /// 批量启动浏览器
 #[tauri::command]
 async fn batch_launch_browsers(
@@ -921,70 +1258,58 @@ async fn delete_group(
 // Fingerprint Commands - 指纹生成
 // ============================================================================
 
+/// 设备模板文件路径
+fn device_templates_path(app_data_dir: &Path) -> PathBuf {
+    app_data_dir
+        .join("data")
+        .join("templates")
+        .join("device_templates.json")
+}
+
 /// 生成随机指纹
 #[tauri::command]
 async fn generate_random_fingerprint(
     profile_id: String,
     platform: Option<String>,
     browser_version: Option<String>,
+    template_id: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<modules::config_writer::FingerprintFileConfig, String> {
-    use modules::FingerprintGenerator;
-
-    // 获取模板文件路径
-    let template_path = state
-        .app_data_dir
-        .join("data")
-        .join("templates")
-        .join("device_templates.json");
-
-    // 如果模板文件不存在，从内嵌资源复制
-    if !template_path.exists() {
-        std::fs::create_dir_all(template_path.parent().unwrap())
-            .map_err(|e| format!("创建模板目录失败: {}", e))?;
-
-        // 这里应该从资源文件复制，暂时返回错误提示
-        return Err(format!("模板文件不存在: {:?}", template_path));
-    }
-
-    // 创建生成器并生成指纹
-    let generator = FingerprintGenerator::new(template_path)
+    // 使用内存中的模板（首次运行时已从内置资源释放）
+    let template_manager = state.template_manager.lock().await.clone();
+    let generator = FingerprintGenerator::with_templates(template_manager)
         .map_err(|e| format!("创建指纹生成器失败: {}", e))?;
 
-    // 传递平台和浏览器版本参数
-    let platform_str = platform.as_deref();
-    let browser_version_str = browser_version.as_deref();
-
-    let fingerprint = generator.generate(&profile_id, platform_str, browser_version_str);
-
-    Ok(fingerprint)
+    // 传递模板、平台和浏览器版本参数
+    generator.generate_with_template(
+        &profile_id,
+        template_id.as_deref(),
+        platform.as_deref(),
+        browser_version.as_deref(),
+    )
 }
 
 /// 获取设备模板列表
 #[tauri::command]
 async fn get_template_list(state: State<'_, AppState>) -> Result<Vec<TemplateInfo>, String> {
-    use modules::fingerprint::TemplateManager;
-
-    let template_path = state
-        .app_data_dir
-        .join("data")
-        .join("templates")
-        .join("device_templates.json");
-
-    if !template_path.exists() {
-        return Err(format!("模板文件不存在: {:?}", template_path));
-    }
+    let manager = state.template_manager.lock().await;
 
-    let manager = TemplateManager::load_from_file(template_path)
-        .map_err(|e| format!("加载模板失败: {}", e))?;
-
-    let templates = manager.get_all_templates();
-    let template_infos: Vec<TemplateInfo> = templates
+    let total_weight = manager.enabled_weight_total();
+    let template_infos: Vec<TemplateInfo> = manager
+        .get_all_templates()
         .iter()
         .map(|t| TemplateInfo {
             id: t.id.clone(),
             description: t.description.clone(),
             weight: t.weight,
+            probability: if t.enabled && total_weight > 0.0 {
+                t.weight / total_weight
+            } else {
+                0.0
+            },
+            enabled: t.enabled,
+            revision: t.revision,
+            source: t.source.clone(),
             os_name: t.os.name.clone(),
             os_version: t.os.version.clone(),
             cpu_vendor: t.cpu.vendor.clone(),
@@ -995,119 +1320,375 @@ async fn get_template_list(state: State<'_, AppState>) -> Result<Vec<TemplateInf
     Ok(template_infos)
 }
 
-/// 校验指纹配置
+/// 获取设备模板详情
 #[tauri::command]
-async fn validate_fingerprint(
-    config: modules::config_writer::FingerprintFileConfig,
-) -> Result<modules::fingerprint::ValidationResult, String> {
-    use modules::fingerprint::FingerprintValidator;
-
-    let result = FingerprintValidator::validate(&config);
-    Ok(result)
-}
-
-/// 模板信息（简化版，用于前端显示）
-#[derive(serde::Serialize, Clone)]
-struct TemplateInfo {
+async fn get_device_template(
     id: String,
-    description: String,
-    weight: f32,
-    os_name: String,
-    os_version: String,
-    cpu_vendor: String,
-    gpu_vendor: String,
+    state: State<'_, AppState>,
+) -> Result<modules::fingerprint::templates::DeviceTemplate, String> {
+    let manager = state.template_manager.lock().await;
+    manager
+        .get_template_by_id(&id)
+        .cloned()
+        .ok_or_else(|| format!("模板不存在: {}", id))
 }
 
-// ==================== Tag IPC Commands ====================
-// ✅ V5 解锁 - Tag 功能已实现
-
-/// 获取所有标签
-#[tauri::command]
-async fn get_tags(state: State<'_, AppState>) -> Result<Vec<Tag>, String> {
-    let service = state.tag_service.lock().await;
-    service.list_tags().await.map_err(|e| e.to_string())
+/// 修改设备模板并持久化（失败时不影响内存中的模板）
+async fn modify_device_templates<T>(
+    state: &AppState,
+    f: impl FnOnce(&mut modules::fingerprint::TemplateManager) -> Result<T, String>,
+) -> Result<T, String> {
+    let mut manager = state.template_manager.lock().await;
+    let mut updated = manager.clone();
+    let result = f(&mut updated)?;
+    updated.save_to_file(device_templates_path(&state.app_data_dir))?;
+    *manager = updated;
+    Ok(result)
 }
 
-/// 创建标签
+/// 添加设备模板
 #[tauri::command]
-async fn create_tag(
-    data: CreateTagDto,
+async fn create_device_template(
+    template: modules::fingerprint::templates::DeviceTemplate,
     state: State<'_, AppState>,
-    app: tauri::AppHandle,
-) -> Result<Tag, String> {
-    let service = state.tag_service.lock().await;
-    let tag = service.create_tag(data).await.map_err(|e| e.to_string())?;
-
-    // 发射 tag:created 事件
-    let _ = app.emit("tag:created", &tag);
-
-    Ok(tag)
+) -> Result<(), String> {
+    modify_device_templates(state.inner(), |manager| manager.add_template(template)).await
 }
 
-/// 更新标签
+/// 编辑设备模板
 #[tauri::command]
-async fn update_tag(
+async fn update_device_template(
     id: String,
-    data: UpdateTagDto,
+    template: modules::fingerprint::templates::DeviceTemplate,
     state: State<'_, AppState>,
-    app: tauri::AppHandle,
-) -> Result<Tag, String> {
-    let service = state.tag_service.lock().await;
-    let tag = service
-        .update_tag(&id, data)
-        .await
-        .map_err(|e| e.to_string())?;
-
-    // 发射 tag:updated 事件
-    let _ = app.emit("tag:updated", &tag);
-
-    Ok(tag)
+) -> Result<modules::fingerprint::templates::DeviceTemplate, String> {
+    modify_device_templates(state.inner(), |manager| {
+        manager.update_template(&id, template).cloned()
+    })
+    .await
 }
 
-/// 删除标签
+/// 启用/禁用设备模板
 #[tauri::command]
-async fn delete_tag(
+async fn set_device_template_enabled(
     id: String,
+    enabled: bool,
     state: State<'_, AppState>,
-    app: tauri::AppHandle,
 ) -> Result<(), String> {
-    let service = state.tag_service.lock().await;
-    service.delete_tag(&id).await.map_err(|e| e.to_string())?;
-
-    // 发射 tag:deleted 事件
-    #[derive(serde::Serialize, Clone)]
-    struct DeletedEvent {
-        id: String,
-    }
-    let _ = app.emit("tag:deleted", DeletedEvent { id });
-
-    Ok(())
+    modify_device_templates(state.inner(), |manager| manager.set_enabled(&id, enabled)).await
 }
 
-// ==================== RecycleBin IPC Commands ====================
-// ✅ V5 解锁 - RecycleBin 功能已实现
-
-/// 获取回收站列表
+/// 设置设备模板权重
 #[tauri::command]
-async fn get_recycle_bin(state: State<'_, AppState>) -> Result<Vec<RecycledProfile>, String> {
-    let service = state.recycle_bin_service.lock().await;
-    service.list_recycled().await.map_err(|e| e.to_string())
+async fn set_device_template_weight(
+    id: String,
+    weight: f32,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    modify_device_templates(state.inner(), |manager| manager.set_weight(&id, weight)).await
 }
 
-/// 恢复窗口
+/// 删除设备模板
 #[tauri::command]
-async fn restore_profile(id: String, state: State<'_, AppState>) -> Result<(), String> {
-    let service = state.recycle_bin_service.lock().await;
-    service
-        .restore_profile(&id)
-        .await
-        .map_err(|e| e.to_string())
+async fn delete_device_template(id: String, state: State<'_, AppState>) -> Result<(), String> {
+    modify_device_templates(state.inner(), |manager| {
+        manager.remove_template(&id).map(|_| ())
+    })
+    .await
 }
 
-/// 批量恢复窗口
+/// 导入社区模板包（校验 Schema 后合并）
 #[tauri::command]
-async fn batch_restore_profiles(
-    ids: Vec<String>,
+async fn import_device_template_pack(
+    file_path: String,
+    overwrite: bool,
+    state: State<'_, AppState>,
+) -> Result<modules::fingerprint::templates::TemplateImportSummary, String> {
+    let content = std::fs::read_to_string(&file_path)
+        .map_err(|e| format!("读取模板包失败: {}", e))?;
+
+    let summary = modify_device_templates(state.inner(), |manager| {
+        manager.import_pack(&content, overwrite)
+    })
+    .await?;
+
+    info!(
+        added = summary.added.len(),
+        updated = summary.updated.len(),
+        skipped = summary.skipped.len(),
+        "模板包导入完成: {}",
+        file_path
+    );
+    Ok(summary)
+}
+
+/// 恢复内置设备模板（覆盖用户修改）
+#[tauri::command]
+async fn reset_device_templates(state: State<'_, AppState>) -> Result<(), String> {
+    let builtin = modules::fingerprint::TemplateManager::load_builtin()?;
+    modify_device_templates(state.inner(), |manager| {
+        *manager = builtin;
+        Ok(())
+    })
+    .await
+}
+
+/// 获取 Profile 的指纹历史（最新在前）
+#[tauri::command]
+async fn get_fingerprint_history(
+    profile_id: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<FingerprintHistoryEntry>, String> {
+    let service = state.fingerprint_history_service.lock().await;
+    service.list(&profile_id).await.map_err(|e| e.to_string())
+}
+
+/// 逐字段对比两个指纹修订版本
+#[tauri::command]
+async fn diff_fingerprint_revisions(
+    from_revision_id: String,
+    to_revision_id: String,
+    state: State<'_, AppState>,
+) -> Result<modules::fingerprint_history::FingerprintRevisionDiff, String> {
+    let service = state.fingerprint_history_service.lock().await;
+    service
+        .diff(&from_revision_id, &to_revision_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 将环境指纹回滚到指定修订版本（回滚本身也会记录为新版本）
+#[tauri::command]
+async fn revert_fingerprint_revision(
+    profile_id: String,
+    revision_id: String,
+    author: Option<String>,
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+) -> Result<Profile, String> {
+    let entry = {
+        let service = state.fingerprint_history_service.lock().await;
+        service.get(&revision_id).await.map_err(|e| e.to_string())?
+    };
+    if entry.profile_id != profile_id {
+        return Err("指纹修订版本不属于该环境".to_string());
+    }
+
+    let service = state.profile_service.lock().await;
+    service
+        .replace_fingerprint(
+            &profile_id,
+            &entry.fingerprint,
+            modules::fingerprint_history::SOURCE_REVERT,
+            author.as_deref(),
+            None,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+    let profile = service.get_profile(&profile_id).await.map_err(|e| e.to_string())?;
+
+    if let Err(e) = score_profile_fingerprint(&state, &profile).await {
+        tracing::warn!("指纹评分失败: {}", e);
+    }
+
+    let _ = app.emit("profile:updated", &profile);
+
+    Ok(profile)
+}
+
+/// 校验指纹配置
+#[tauri::command]
+async fn validate_fingerprint(
+    config: modules::config_writer::FingerprintFileConfig,
+    state: State<'_, AppState>,
+) -> Result<modules::fingerprint::ValidationResult, String> {
+    let result = state.fingerprint_validator.lock().await.check(&config);
+    Ok(result)
+}
+
+/// 用户自定义指纹校验规则文件（优先 TOML，其次 JSON）
+fn fingerprint_rules_path(app_data_dir: &Path) -> Option<PathBuf> {
+    ["fingerprint_rules.toml", "fingerprint_rules.json"]
+        .iter()
+        .map(|name| app_data_dir.join(name))
+        .find(|path| path.exists())
+}
+
+/// 加载指纹校验器：存在自定义规则文件时使用自定义规则，否则使用内置规则
+fn load_fingerprint_validator(app_data_dir: &Path) -> Result<modules::fingerprint::FingerprintValidator, String> {
+    use modules::fingerprint::FingerprintValidator;
+
+    match fingerprint_rules_path(app_data_dir) {
+        Some(path) => FingerprintValidator::load_from_file(&path),
+        None => Ok(FingerprintValidator::builtin()),
+    }
+}
+
+/// 校验 Profile 指纹并保存真实度评分
+async fn score_profile_fingerprint(
+    state: &AppState,
+    profile: &Profile,
+) -> Result<ProfileFingerprintScore, String> {
+    let config = ConfigWriter::build_fingerprint_config(&profile.id, &profile.fingerprint)?;
+    let result = state.fingerprint_validator.lock().await.check(&config);
+    state
+        .fingerprint_score_service
+        .lock()
+        .await
+        .save(&profile.id, &result)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 获取 Profile 最近一次指纹评分
+#[tauri::command]
+async fn get_profile_fingerprint_score(
+    profile_id: String,
+    state: State<'_, AppState>,
+) -> Result<Option<ProfileFingerprintScore>, String> {
+    let service = state.fingerprint_score_service.lock().await;
+    service.get(&profile_id).await.map_err(|e| e.to_string())
+}
+
+/// 为所有环境重新计算指纹评分
+#[tauri::command]
+async fn score_all_profiles(state: State<'_, AppState>) -> Result<Vec<ProfileFingerprintScore>, String> {
+    let profiles = {
+        let service = state.profile_service.lock().await;
+        service.list_profiles().await.map_err(|e| e.to_string())?
+    };
+
+    let mut scores = Vec::with_capacity(profiles.len());
+    for profile in &profiles {
+        scores.push(score_profile_fingerprint(&state, profile).await?);
+    }
+    Ok(scores)
+}
+
+/// 获取当前使用的指纹校验规则集
+#[tauri::command]
+async fn get_fingerprint_rules(
+    state: State<'_, AppState>,
+) -> Result<modules::fingerprint::ValidationRuleSet, String> {
+    Ok(state.fingerprint_validator.lock().await.rules().clone())
+}
+
+/// 重新加载指纹校验规则（应用数据目录下的 fingerprint_rules.toml / .json），返回规则数量
+#[tauri::command]
+async fn reload_fingerprint_rules(state: State<'_, AppState>) -> Result<usize, String> {
+    let validator = load_fingerprint_validator(&state.app_data_dir)?;
+    let count = validator.rules().rules.len();
+    *state.fingerprint_validator.lock().await = validator;
+    Ok(count)
+}
+
+/// 模板信息（简化版，用于前端显示）
+#[derive(serde::Serialize, Clone)]
+struct TemplateInfo {
+    id: String,
+    description: String,
+    weight: f32,
+    /// 在启用模板中的实际选中概率
+    probability: f32,
+    enabled: bool,
+    revision: u32,
+    source: Option<String>,
+    os_name: String,
+    os_version: String,
+    cpu_vendor: String,
+    gpu_vendor: String,
+}
+
+// ==================== Tag IPC Commands ====================
+// ✅ V5 解锁 - Tag 功能已实现
+
+/// 获取所有标签
+#[tauri::command]
+async fn get_tags(state: State<'_, AppState>) -> Result<Vec<Tag>, String> {
+    let service = state.tag_service.lock().await;
+    service.list_tags().await.map_err(|e| e.to_string())
+}
+
+/// 创建标签
+#[tauri::command]
+async fn create_tag(
+    data: CreateTagDto,
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+) -> Result<Tag, String> {
+    let service = state.tag_service.lock().await;
+    let tag = service.create_tag(data).await.map_err(|e| e.to_string())?;
+
+    // 发射 tag:created 事件
+    let _ = app.emit("tag:created", &tag);
+
+    Ok(tag)
+}
+
+/// 更新标签
+#[tauri::command]
+async fn update_tag(
+    id: String,
+    data: UpdateTagDto,
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+) -> Result<Tag, String> {
+    let service = state.tag_service.lock().await;
+    let tag = service
+        .update_tag(&id, data)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // 发射 tag:updated 事件
+    let _ = app.emit("tag:updated", &tag);
+
+    Ok(tag)
+}
+
+/// 删除标签
+#[tauri::command]
+async fn delete_tag(
+    id: String,
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+) -> Result<(), String> {
+    let service = state.tag_service.lock().await;
+    service.delete_tag(&id).await.map_err(|e| e.to_string())?;
+
+    // 发射 tag:deleted 事件
+    #[derive(serde::Serialize, Clone)]
+    struct DeletedEvent {
+        id: String,
+    }
+    let _ = app.emit("tag:deleted", DeletedEvent { id });
+
+    Ok(())
+}
+
+// ==================== RecycleBin IPC Commands ====================
+// ✅ V5 解锁 - RecycleBin 功能已实现
+
+/// 获取回收站列表
+#[tauri::command]
+async fn get_recycle_bin(state: State<'_, AppState>) -> Result<Vec<RecycledProfile>, String> {
+    let service = state.recycle_bin_service.lock().await;
+    service.list_recycled().await.map_err(|e| e.to_string())
+}
+
+/// 恢复窗口
+#[tauri::command]
+async fn restore_profile(id: String, state: State<'_, AppState>) -> Result<(), String> {
+    let service = state.recycle_bin_service.lock().await;
+    service
+        .restore_profile(&id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 批量恢复窗口
+#[tauri::command]
+async fn batch_restore_profiles(
+    ids: Vec<String>,
     state: State<'_, AppState>,
 ) -> Result<modules::BatchResult, String> {
     let service = state.recycle_bin_service.lock().await;
@@ -1287,6 +1868,15 @@ async fn test_proxy_config(
     Ok(result)
 }
 
+/// 检测代理链（逐跳延迟 + 最终出口 IP）
+#[tauri::command]
+async fn test_proxy_chain(
+    hops: Vec<modules::proxy_bridge::ProxyHop>,
+) -> Result<modules::proxy_checker::ProxyChainCheckResult, String> {
+    let checker = modules::ProxyChecker::new().with_timeout(10);
+    Ok(checker.check_chain("chain", &hops).await)
+}
+
 /// 批量检测代理
 #[tauri::command]
 async fn batch_test_proxies(
@@ -2262,71 +2852,304 @@ async fn get_all_proxy_bridge_stats(
     Ok(state.proxy_bridge_manager.get_all_stats().await)
 }
 
-// ==================== Kernel Download IPC Commands ====================
-
-/// Check if kernel is installed
+/// 调整运行中桥接的限速（字节/秒，None 表示不限速）
 #[tauri::command]
-async fn is_kernel_installed(state: State<'_, AppState>) -> Result<bool, String> {
-    let downloader = state.kernel_downloader.lock().await;
-    Ok(downloader.is_kernel_installed())
+async fn set_proxy_bridge_rate_limits(
+    profile_id: String,
+    upload_limit: Option<u64>,
+    download_limit: Option<u64>,
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    Ok(state
+        .proxy_bridge_manager
+        .set_rate_limits(&profile_id, upload_limit, download_limit)
+        .await)
 }
 
-/// Get installed kernel version info
+/// 设置流量配额
 #[tauri::command]
-async fn get_kernel_version(
+async fn set_traffic_quota(
+    dto: modules::traffic::SetTrafficQuotaDto,
     state: State<'_, AppState>,
-) -> Result<Option<KernelVersionInfo>, String> {
-    let downloader = state.kernel_downloader.lock().await;
-    Ok(downloader.get_installed_version())
+) -> Result<modules::traffic::TrafficQuota, String> {
+    let service = state.traffic_service.lock().await;
+    service.set_quota(dto).await.map_err(|e| e.to_string())
 }
 
-/// Get kernel download progress
+/// 删除流量配额
 #[tauri::command]
-async fn get_kernel_download_progress(
+async fn delete_traffic_quota(id: String, state: State<'_, AppState>) -> Result<(), String> {
+    let service = state.traffic_service.lock().await;
+    service.delete_quota(&id).await.map_err(|e| e.to_string())
+}
+
+/// 获取所有流量配额及当前用量
+#[tauri::command]
+async fn list_traffic_quotas(
     state: State<'_, AppState>,
-) -> Result<DownloadProgress, String> {
-    let downloader = state.kernel_downloader.lock().await;
-    Ok(downloader.get_progress().await)
+) -> Result<Vec<modules::traffic::TrafficQuotaStatus>, String> {
+    let service = state.traffic_service.lock().await;
+    service
+        .list_quota_statuses(chrono::Utc::now())
+        .await
+        .map_err(|e| e.to_string())
 }
 
-/// Download and install kernel
+/// 按环境 / 分组 / 标签 / 代理 / 日期汇总流量（查询前先写入未落库的流量）
 #[tauri::command]
-async fn download_kernel(
-    download_url: String,
+async fn query_traffic_usage(
     app: tauri::AppHandle,
+    query: modules::traffic::TrafficUsageQuery,
     state: State<'_, AppState>,
-) -> Result<(), String> {
-    let downloader = state.kernel_downloader.clone();
-
-    // Spawn download task
-    let app_for_progress = app.clone();
-    let app_for_result = app.clone();
+) -> Result<Vec<modules::traffic::TrafficUsageRow>, String> {
+    flush_traffic(&app).await?;
+    let service = state.traffic_service.lock().await;
+    service.aggregate_usage(&query).await.map_err(|e| e.to_string())
+}
 
-    tauri::async_runtime::spawn(async move {
-        let result = {
-            let dl = downloader.lock().await;
-            dl.download_and_install(&download_url, move |progress| {
-                // Emit progress event to frontend
-                let _ = app_for_progress.emit("kernel:download-progress", &progress);
-            })
-            .await
-        };
+/// 导出流量按日明细 CSV（用于与代理商对账），返回写入的文件路径
+#[tauri::command]
+async fn export_traffic_usage_csv(
+    app: tauri::AppHandle,
+    query: modules::traffic::TrafficUsageQuery,
+    file_path: String,
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    flush_traffic(&app).await?;
+    let csv = {
+        let service = state.traffic_service.lock().await;
+        service.export_usage_csv(&query).await.map_err(|e| e.to_string())?
+    };
+    tokio::fs::write(&file_path, csv)
+        .await
+        .map_err(|e| format!("写入 CSV 文件失败: {}", e))?;
+    Ok(file_path)
+}
 
-        match result {
-            Ok(_) => {
-                let _ = app_for_result.emit("kernel:download-complete", ());
-            }
-            Err(e) => {
-                let _ = app_for_result.emit("kernel:download-error", e.to_string());
+/// 将桥接流量写入数据库（单个事务，失败时放回增量等待下次写入），
+/// 返回运行中桥接正在使用的 (环境 ID, 代理标识)
+async fn persist_traffic(
+    state: &AppState,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<Vec<(String, String)>, String> {
+    let deltas = state.proxy_bridge_manager.take_traffic_deltas().await;
+    let result = {
+        let service = state.traffic_service.lock().await;
+        async {
+            let mut records = Vec::with_capacity(deltas.len());
+            let mut running = Vec::new();
+            for delta in &deltas {
+                let proxy_key = service
+                    .resolve_proxy_key(&delta.upstream_host, delta.upstream_port)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                if delta.active {
+                    running.push((delta.profile_id.clone(), proxy_key.clone()));
+                }
+                records.push(modules::traffic::TrafficRecord {
+                    profile_id: delta.profile_id.clone(),
+                    proxy_key,
+                    bytes_sent: delta.bytes_sent,
+                    bytes_received: delta.bytes_received,
+                });
             }
+            service.record_traffic(&records, now).await.map_err(|e| e.to_string())?;
+            Ok::<_, String>(running)
         }
-    });
+        .await
+    };
 
-    Ok(())
+    if result.is_err() {
+        state.proxy_bridge_manager.requeue_traffic_deltas(deltas).await;
+    }
+    result
 }
 
-/// Uninstall kernel
-#[tauri::command]
+/// 将桥接流量写入数据库并检查配额：越过阈值时发送事件，超额且设置了停止的环境关闭浏览器
+async fn flush_traffic(app: &tauri::AppHandle) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    let now = chrono::Utc::now();
+
+    let running = persist_traffic(&state, now).await?;
+    let crossed = state
+        .traffic_service
+        .lock()
+        .await
+        .check_quotas(now)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    for status in crossed {
+        tracing::warn!(
+            scope = %status.quota.scope,
+            target = %status.quota.target_id,
+            used = status.used_bytes,
+            limit = status.quota.limit_bytes,
+            "流量配额越过阈值"
+        );
+        let _ = app.emit(modules::traffic::TRAFFIC_QUOTA_EVENT, &status);
+
+        if status.level != modules::traffic::QuotaLevel::Exceeded || !status.quota.stop_on_exceed {
+            continue;
+        }
+        for (profile_id, proxy_key) in &running {
+            let affected = match status.quota.scope.as_str() {
+                modules::traffic::SCOPE_PROFILE => profile_id == &status.quota.target_id,
+                _ => proxy_key == &status.quota.target_id,
+            };
+            if affected {
+                tracing::warn!(profile_id = %profile_id, "流量配额已用尽，停止浏览器");
+                if let Err(e) = do_stop_browser(profile_id.clone(), &state).await {
+                    tracing::error!(profile_id = %profile_id, error = %e, "停止浏览器失败");
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// 获取环境最近的代理连接记录（新的在前，需在设置中启用连接日志）
+#[tauri::command]
+async fn get_proxy_connection_log(
+    profile_id: String,
+    limit: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<Vec<modules::proxy_bridge::ConnectionRecord>, String> {
+    Ok(state
+        .proxy_bridge_manager
+        .get_connection_log(&profile_id, limit.unwrap_or(200))
+        .await)
+}
+
+/// 获取环境访问最多的目标域名
+#[tauri::command]
+async fn get_proxy_top_domains(
+    profile_id: String,
+    limit: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<Vec<modules::proxy_bridge::DomainStats>, String> {
+    Ok(state
+        .proxy_bridge_manager
+        .get_top_domains(&profile_id, limit.unwrap_or(50))
+        .await)
+}
+
+/// 清空环境的代理连接记录
+#[tauri::command]
+async fn clear_proxy_connection_log(profile_id: String, state: State<'_, AppState>) -> Result<(), String> {
+    state.proxy_bridge_manager.clear_connection_log(&profile_id).await;
+    Ok(())
+}
+
+/// 轮换环境代理会话（用户名 / 密码中的 {rand}），浏览器无需重启，返回新的会话 ID
+#[tauri::command]
+async fn rotate_proxy_session(profile_id: String, state: State<'_, AppState>) -> Result<String, String> {
+    state.proxy_bridge_manager.rotate_session(&profile_id).await
+}
+
+/// 获取环境出口代理（主代理与备用代理）的健康状态
+#[tauri::command]
+async fn get_proxy_upstream_health(
+    profile_id: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<modules::proxy_bridge::UpstreamHealth>, String> {
+    state
+        .proxy_bridge_manager
+        .get_upstream_health(&profile_id)
+        .await
+        .ok_or_else(|| "该环境没有运行中的代理桥接".to_string())
+}
+
+/// 将桥接观察到的出口代理可用性变化写回代理表状态
+async fn sync_proxy_status(app: &tauri::AppHandle) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    let changes = state.proxy_bridge_manager.take_upstream_status_changes().await;
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    let service = state.proxy_service.lock().await;
+    for change in changes {
+        let Some(id) = service
+            .find_id_by_address(&change.host, change.port)
+            .await
+            .map_err(|e| e.to_string())?
+        else {
+            continue;
+        };
+        let status = if change.healthy {
+            modules::proxy::ProxyStatus::Active
+        } else {
+            tracing::warn!(
+                profile_id = %change.profile_id,
+                proxy_id = %id,
+                error = ?change.error,
+                "代理连续连接失败，标记为异常"
+            );
+            modules::proxy::ProxyStatus::Error
+        };
+        service.update_status(&id, status).await.map_err(|e| e.to_string())?;
+        let _ = app.emit(modules::proxy::PROXY_STATUS_EVENT, serde_json::json!({ "id": id, "healthy": change.healthy }));
+    }
+
+    Ok(())
+}
+
+/// 获取代理桥接本地端点（HTTP / SOCKS5 / UDP）
+#[tauri::command]
+async fn get_proxy_bridge_endpoints(
+    profile_id: String,
+    state: State<'_, AppState>,
+) -> Result<Option<modules::proxy_bridge::BridgeEndpoints>, String> {
+    Ok(state
+        .proxy_bridge_manager
+        .get_bridge_endpoints(&profile_id)
+        .await)
+}
+
+// ==================== Kernel Download IPC Commands ====================
+
+/// Check if kernel is installed
+#[tauri::command]
+async fn is_kernel_installed(state: State<'_, AppState>) -> Result<bool, String> {
+    let downloader = state.kernel_downloader.lock().await;
+    Ok(downloader.is_kernel_installed())
+}
+
+/// Get installed kernel version info
+#[tauri::command]
+async fn get_kernel_version(
+    state: State<'_, AppState>,
+) -> Result<Option<KernelVersionInfo>, String> {
+    let downloader = state.kernel_downloader.lock().await;
+    Ok(downloader.get_installed_version())
+}
+
+/// Get kernel download progress
+#[tauri::command]
+async fn get_kernel_download_progress(
+    state: State<'_, AppState>,
+) -> Result<DownloadProgress, String> {
+    let downloader = state.kernel_downloader.lock().await;
+    Ok(downloader.get_progress().await)
+}
+
+/// Download and install kernel
+///
+/// 版本与 SHA256 取自已通过签名校验的内核更新清单，安装流程同 download_kernel_version
+#[tauri::command]
+async fn download_kernel(
+    download_url: String,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let manifest = verified_update_manifest(&state, UpdateComponent::Kernel, None).await?;
+    download_kernel_version(manifest.version, download_url, None, app, state).await
+}
+
+/// Uninstall kernel
+#[tauri::command]
 async fn uninstall_kernel(state: State<'_, AppState>) -> Result<(), String> {
     let downloader = state.kernel_downloader.lock().await;
     downloader.uninstall().await.map_err(|e| e.to_string())
@@ -2359,6 +3182,130 @@ async fn get_bundled_kernel_path_by_version(version: String) -> Result<Option<St
         .map(|p| p.display().to_string()))
 }
 
+/// 列出所有可用内核版本（已安装 + 内嵌，按版本降序）
+#[tauri::command]
+async fn list_kernel_versions(
+    state: State<'_, AppState>,
+) -> Result<Vec<modules::InstalledKernel>, String> {
+    Ok(state.kernel_registry.lock().await.list_installed())
+}
+
+/// 列出环境 / 分组的内核版本固定
+#[tauri::command]
+async fn list_kernel_pins(state: State<'_, AppState>) -> Result<Vec<modules::KernelPin>, String> {
+    let registry = state.kernel_registry.lock().await;
+    registry.list_pins().await.map_err(|e| e.to_string())
+}
+
+/// 固定环境的内核版本（version 为空时解除固定）
+#[tauri::command]
+async fn set_profile_kernel_version(
+    profile_id: String,
+    version: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let registry = state.kernel_registry.lock().await;
+    registry
+        .set_pin(modules::kernel_registry::PIN_SCOPE_PROFILE, &profile_id, version.as_deref())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 固定分组的内核版本（version 为空时解除固定）
+#[tauri::command]
+async fn set_group_kernel_version(
+    group_id: String,
+    version: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let registry = state.kernel_registry.lock().await;
+    registry
+        .set_pin(modules::kernel_registry::PIN_SCOPE_GROUP, &group_id, version.as_deref())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 下载并安装指定版本的内核（与其他版本并存）
+///
+/// 只接受已通过签名校验的内核更新清单中的版本，按清单中的 SHA256 校验下载内容；
+/// 主地址失败后依次尝试 mirrors。下载完成后经暂存目录解压、替换并冒烟测试，结果写入更新历史
+#[tauri::command]
+async fn download_kernel_version(
+    version: String,
+    download_url: String,
+    mirrors: Option<Vec<String>>,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let version = version.trim().to_string();
+    if modules::kernel_registry::major_version(&version).is_none() {
+        return Err(format!("无效的内核版本: {}", version));
+    }
+    let manifest = verified_update_manifest(&state, UpdateComponent::Kernel, None).await?;
+    if manifest.version != version {
+        return Err(format!(
+            "内核 {} 没有已通过签名校验的更新清单（当前清单版本 {}），请重新检查更新",
+            version, manifest.version
+        ));
+    }
+    let mirrors = mirrors.unwrap_or_default();
+    let info = KernelVersionInfo {
+        version: manifest.version.clone(),
+        build_date: "unknown".to_string(),
+        platform: manifest.platform.clone().unwrap_or_default(),
+        source: "remote".to_string(),
+        files_count: 0,
+        total_size_bytes: manifest.file_size.unwrap_or(0),
+        sha256: Some(manifest.file_hash.clone()),
+        signature: None,
+        mirrors: mirrors.clone(),
+        channel: Some(update_channel(&state, UpdateComponent::Kernel).await?.kernel_channel().to_string()),
+    };
+    let install_dir = state.kernel_registry.lock().await.install_dir().to_path_buf();
+    let downloader = KernelDownloader::new(install_dir, version.clone());
+    let mut urls = vec![download_url];
+    urls.extend(mirrors);
+
+    let app_for_progress = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let result = downloader
+            .download_and_install_verified(&urls, &info, move |progress| {
+                let _ = app_for_progress.emit("kernel:download-progress", &progress);
+            })
+            .await;
+
+        match result {
+            Ok(outcome) => {
+                let state = app.state::<AppState>();
+                if let Err(e) = state.kernel_registry.lock().await.record_update(&outcome).await {
+                    tracing::error!("记录内核更新历史失败: {}", e);
+                }
+                match outcome.status {
+                    modules::app_updater::KernelUpdateStatus::Installed => {
+                        let _ = app.emit("kernel:download-complete", &version);
+                    }
+                    _ => {
+                        let message = outcome.message.unwrap_or_else(|| "内核安装失败".to_string());
+                        let _ = app.emit("kernel:download-error", message);
+                    }
+                }
+            }
+            Err(e) => {
+                let _ = app.emit("kernel:download-error", e.to_string());
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// 删除指定版本的已安装内核
+#[tauri::command]
+async fn uninstall_kernel_version(version: String, state: State<'_, AppState>) -> Result<(), String> {
+    let registry = state.kernel_registry.lock().await;
+    registry.uninstall(&version).await.map_err(|e| e.to_string())
+}
+
 /// 手动触发内核检查和解压 (用于登录后延迟检查)
 /// 
 /// 当用户登录/注册完成进入主页面时,如果启动时内核解压未触发或未完成,
@@ -2450,19 +3397,95 @@ async fn check_app_update(
         .unwrap_or_else(|| modules::app_updater::DEFAULT_UPDATE_SERVER.to_string());
 
     let current_version = env!("CARGO_PKG_VERSION");
-    
-    // 检测系统架构
-    let arch = if cfg!(target_pointer_width = "64") {
-        "x86_64"
-    } else {
-        "x86"
-    };
 
-    modules::app_updater::check_launcher_update(
-        &server, current_version, "windows", arch,
+    let channel = update_channel(&state, UpdateComponent::Launcher).await?;
+    let info = modules::app_updater::check_launcher_update(
+        &server,
+        current_version,
+        modules::app_updater::HOST_PLATFORM,
+        modules::app_updater::host_arch(),
+        &channel,
     )
     .await
-    .map_err(|e| e.to_string())
+    .map_err(|e| e.to_string())?;
+    let info = modules::app_updater::apply_skipped_versions(
+        info,
+        &skipped_update_versions(&state, UpdateComponent::Launcher).await?,
+    );
+
+    remember_update_manifest(&state, &info, UpdateComponent::Launcher).await?;
+    Ok(info)
+}
+
+/// 读取组件的更新通道（未设置时为 stable）
+async fn update_channel(
+    state: &AppState,
+    component: UpdateComponent,
+) -> Result<modules::app_updater::UpdateChannel, String> {
+    let value = get_setting(&state.pool, component.channel_setting_key())
+        .await?
+        .unwrap_or_default();
+    modules::app_updater::UpdateChannel::parse(&value).map_err(|e| e.to_string())
+}
+
+/// 读取组件已跳过的版本
+async fn skipped_update_versions(state: &AppState, component: UpdateComponent) -> Result<Vec<String>, String> {
+    let value = get_setting(&state.pool, component.skipped_versions_setting_key())
+        .await?
+        .unwrap_or_default();
+    Ok(modules::app_updater::parse_skipped_versions(&value))
+}
+
+/// 校验更新信息的签名清单并保存，后续下载 / 安装以清单为准
+async fn remember_update_manifest(
+    state: &AppState,
+    info: &UpdateInfo,
+    component: UpdateComponent,
+) -> Result<(), String> {
+    let key = component.as_str();
+    if !info.has_update {
+        state.update_manifests.lock().await.remove(key);
+        return Ok(());
+    }
+    match modules::app_updater::verify_update_info(info, component) {
+        Ok(manifest) => {
+            state.update_manifests.lock().await.insert(key.to_string(), manifest);
+            Ok(())
+        }
+        Err(e) => {
+            state.update_manifests.lock().await.remove(key);
+            tracing::error!("{} 更新签名校验失败: {:#}", key, e);
+            Err(format!("更新签名校验失败，已阻止本次更新: {:#}", e))
+        }
+    }
+}
+
+/// 获取已校验的更新清单，并确认前端传入的文件哈希、清单的平台 / 架构与本机一致
+async fn verified_update_manifest(
+    state: &AppState,
+    component: UpdateComponent,
+    file_hash: Option<&str>,
+) -> Result<UpdateManifest, String> {
+    let manifest = state
+        .update_manifests
+        .lock()
+        .await
+        .get(component.as_str())
+        .cloned()
+        .ok_or_else(|| "没有已通过签名校验的更新，请重新检查更新".to_string())?;
+    if let Some(hash) = file_hash {
+        use modules::app_updater::normalize_hash;
+        if normalize_hash(hash) != normalize_hash(&manifest.file_hash) {
+            return Err("文件哈希与签名清单不一致，已拒绝".to_string());
+        }
+    }
+    modules::app_updater::check_manifest_target(
+        &manifest,
+        modules::app_updater::HOST_PLATFORM,
+        modules::app_updater::host_arch(),
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(manifest)
 }
 
 /// 检查内核更新
@@ -2474,31 +3497,41 @@ async fn check_kernel_update_api(
         .await?
         .unwrap_or_else(|| modules::app_updater::DEFAULT_UPDATE_SERVER.to_string());
 
+    // beta 通道以已安装的最高版本为准（含 beta 内核）；其他通道报告默认内核，
+    // 未设置默认内核时为最新的非 beta 内核（beta 内核与 stable 内核并存）
+    let channel = update_channel(&state, UpdateComponent::Kernel).await?;
+    let default_version = get_setting(&state.pool, modules::kernel_registry::DEFAULT_KERNEL_VERSION_SETTING_KEY)
+        .await?
+        .filter(|v| !v.trim().is_empty());
     let kernel_version = {
-        let downloader = state.kernel_downloader.lock().await;
-        downloader.get_installed_version()
-            .map(|v| v.version)
-            .unwrap_or_else(|| "0.0.0".to_string())
+        let registry = state.kernel_registry.lock().await;
+        let kernels = registry.list_installed();
+        let current = match (&channel, default_version.as_deref()) {
+            (modules::app_updater::UpdateChannel::Beta, _) => kernels.first(),
+            (_, default_version) => modules::kernel_registry::select_kernel(&kernels, default_version),
+        };
+        current.map(|k| k.version.clone()).unwrap_or_else(|| "0.0.0".to_string())
     };
 
     let launcher_version = env!("CARGO_PKG_VERSION");
-    
-    // 检测系统架构
-    let arch = if cfg!(target_pointer_width = "64") {
-        "x86_64"
-    } else {
-        "x86"
-    };
 
-    modules::app_updater::check_kernel_update(
+    let info = modules::app_updater::check_kernel_update(
         &server,
         &kernel_version,
-        "windows",
-        arch,
+        modules::app_updater::HOST_PLATFORM,
+        modules::app_updater::host_arch(),
         launcher_version,
+        &channel,
     )
     .await
-    .map_err(|e| e.to_string())
+    .map_err(|e| e.to_string())?;
+    let info = modules::app_updater::apply_skipped_versions(
+        info,
+        &skipped_update_versions(&state, UpdateComponent::Kernel).await?,
+    );
+
+    remember_update_manifest(&state, &info, UpdateComponent::Kernel).await?;
+    Ok(info)
 }
 
 /// 获取内核下载信息（通过后端代理请求）
@@ -2749,17 +3782,19 @@ async fn notification_delete(
 #[tauri::command]
 async fn download_app_update(
     app: tauri::AppHandle,
+    state: State<'_, AppState>,
     url: String,
     file_hash: String,
 ) -> Result<String, String> {
+    let manifest = verified_update_manifest(&state, UpdateComponent::Launcher, Some(&file_hash)).await?;
     let temp_dir = modules::app_updater::get_update_temp_dir();
 
     let app_clone = app.clone();
     let dest = modules::app_updater::download_and_verify(
         &url,
-        &file_hash,
+        &manifest.file_hash,
         &temp_dir,
-        modules::app_updater::UpdateComponent::Launcher,
+        UpdateComponent::Launcher,
         move |progress| {
             let _ = app_clone.emit("update:download-progress", &progress);
         },
@@ -2774,17 +3809,19 @@ async fn download_app_update(
 #[tauri::command]
 async fn download_kernel_update(
     app: tauri::AppHandle,
+    state: State<'_, AppState>,
     url: String,
     file_hash: String,
 ) -> Result<String, String> {
+    let manifest = verified_update_manifest(&state, UpdateComponent::Kernel, Some(&file_hash)).await?;
     let temp_dir = modules::app_updater::get_update_temp_dir();
 
     let app_clone = app.clone();
     let dest = modules::app_updater::download_and_verify(
         &url,
-        &file_hash,
+        &manifest.file_hash,
         &temp_dir,
-        modules::app_updater::UpdateComponent::Kernel,
+        UpdateComponent::Kernel,
         move |progress| {
             let _ = app_clone.emit("update:download-progress", &progress);
         },
@@ -2796,6 +3833,8 @@ async fn download_kernel_update(
 }
 
 /// 安装启动器更新（优雅关闭 → 启动安装器 → 重启）
+///
+/// 浏览器已关闭但安装器未能启动时，立即按会话快照重新打开这些环境（同时删除快照）
 #[tauri::command]
 async fn install_app_update(
     app: tauri::AppHandle,
@@ -2803,17 +3842,95 @@ async fn install_app_update(
     file_path: String,
 ) -> Result<(), String> {
     let path = std::path::PathBuf::from(&file_path);
-    modules::app_updater::install_launcher_update(
+    let manifest = verified_update_manifest(&state, UpdateComponent::Launcher, None).await?;
+    let result = modules::app_updater::install_launcher_update(
         &path,
+        &manifest,
         &state.browser_manager,
         &state.proxy_bridge_manager,
         &app,
+        &state.app_data_dir.join(modules::app_updater::SESSION_RESTORE_FILE),
+        browser_shutdown_timeout(&state.pool).await?,
     )
-    .await
-    .map_err(|e| e.to_string())
+    .await;
+    if let Err(e) = result {
+        tracing::error!("启动器更新安装失败，恢复更新前的环境: {:#}", e);
+        restore_update_session(&app).await;
+        return Err(e.to_string());
+    }
+    Ok(())
+}
+
+/// 打开离线更新包（隔离网络通过 U 盘分发），校验通过后返回版本与更新说明
+///
+/// 校验通过的清单与在线检查更新一样被保存，随后用解出的 file_path 调用
+/// install_app_update / install_kernel_update_cmd 安装
+#[tauri::command]
+async fn open_offline_update_package(
+    component: UpdateComponent,
+    package_path: String,
+    state: State<'_, AppState>,
+) -> Result<modules::app_updater::OfflinePackage, String> {
+    let extract_dir = modules::app_updater::get_update_temp_dir().join("offline");
+    let package = modules::app_updater::open_offline_package(Path::new(&package_path), component, &extract_dir)
+        .await
+        .map_err(|e| {
+            tracing::error!("离线更新包校验失败: {:#}", e);
+            format!("离线更新包校验失败，已拒绝: {:#}", e)
+        })?;
+    state
+        .update_manifests
+        .lock()
+        .await
+        .insert(component.as_str().to_string(), package.manifest.clone());
+    Ok(package)
+}
+
+/// 启动器更新后重新打开更新前运行中的环境，并尽量还原窗口位置
+async fn restore_update_session(app: &tauri::AppHandle) {
+    let state = app.state::<AppState>();
+    let path = state.app_data_dir.join(modules::app_updater::SESSION_RESTORE_FILE);
+    let Some(session) = modules::app_updater::take_update_session(&path).await else {
+        return;
+    };
+    tracing::info!(
+        "恢复 {} 更新前运行的 {} 个环境",
+        session.from_version,
+        session.profiles.len()
+    );
+
+    let mut restored = Vec::new();
+    for entry in session.profiles {
+        if let Err(e) = do_launch_browser(entry.profile_id.clone(), state.inner()).await {
+            tracing::warn!("恢复环境 {} 失败: {}", entry.profile_id, e);
+            state
+                .browser_manager
+                .emit_error(entry.profile_id.clone(), format!("更新后恢复环境失败: {}", e));
+            continue;
+        }
+        if let (Some(bounds), Some(pid)) = (entry.bounds, state.browser_manager.get_pid(&entry.profile_id).await) {
+            let windows = modules::window_helper::collect_window_details_with_retry(&[pid], 20, 500).await;
+            if let Some(window) = windows.first() {
+                if let Err(e) = modules::window_helper::set_window_bounds(
+                    window.hwnd_ptr,
+                    bounds.x,
+                    bounds.y,
+                    bounds.width,
+                    bounds.height,
+                ) {
+                    tracing::warn!("还原环境 {} 窗口位置失败: {}", entry.profile_id, e);
+                }
+            }
+        }
+        restored.push(entry.profile_id);
+    }
+    let _ = app.emit(modules::app_updater::SESSION_RESTORED_EVENT, &restored);
 }
 
 /// 安装内核更新（需要先关闭所有浏览器）
+///
+/// 新内核安装到 kernel/{version}，冒烟测试失败时自动恢复旧内核；结果写入更新历史。
+/// beta 通道安装的内核带有 beta 标记，不会成为未固定环境使用的“最新版本”
 #[tauri::command]
 async fn install_kernel_update_cmd(
     app: tauri::AppHandle,
@@ -2822,20 +3939,160 @@ async fn install_kernel_update_cmd(
     new_version: String,
 ) -> Result<(), String> {
     let zip_path = std::path::PathBuf::from(&file_path);
-    let kernel_dir = state.app_data_dir.join("kernel").join("win32");
+    let manifest = verified_update_manifest(&state, UpdateComponent::Kernel, None).await?;
+    if manifest.version != new_version {
+        return Err(format!(
+            "安装版本 {} 与签名清单版本 {} 不一致，已拒绝",
+            new_version, manifest.version
+        ));
+    }
+    let kernel_dir = state
+        .kernel_registry
+        .lock()
+        .await
+        .install_dir()
+        .join(&manifest.version);
+
+    let channel = update_channel(&state, UpdateComponent::Kernel).await?;
 
     let app_clone = app.clone();
-    modules::app_updater::install_kernel_update(
+    let outcome = modules::app_updater::install_kernel_update(
         &zip_path,
         &kernel_dir,
-        &new_version,
+        &manifest,
+        &channel,
         &state.browser_manager,
         move |progress| {
             let _ = app_clone.emit("update:download-progress", &progress);
         },
     )
     .await
-    .map_err(|e| e.to_string())
+    .map_err(|e| e.to_string())?;
+
+    let registry = state.kernel_registry.lock().await;
+    if let Err(e) = registry.record_update(&outcome).await {
+        tracing::error!("记录内核更新历史失败: {}", e);
+    }
+    match outcome.status {
+        modules::app_updater::KernelUpdateStatus::Installed => Ok(()),
+        _ => Err(outcome.message.unwrap_or_else(|| "内核更新失败".to_string())),
+    }
+}
+
+/// 获取内核更新历史（安装 / 回滚记录）
+#[tauri::command]
+async fn get_kernel_update_history(
+    limit: Option<u32>,
+    state: State<'_, AppState>,
+) -> Result<Vec<modules::kernel_registry::KernelUpdateRecord>, String> {
+    let registry = state.kernel_registry.lock().await;
+    registry
+        .update_history(limit.unwrap_or(50))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 跳过指定版本的更新（强制更新不可跳过）
+#[tauri::command]
+async fn skip_update_version(
+    component: UpdateComponent,
+    version: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let version = version.trim().to_string();
+    if version.is_empty() {
+        return Err("版本号不能为空".to_string());
+    }
+    let mut skipped = skipped_update_versions(&state, component).await?;
+    if !skipped.contains(&version) {
+        skipped.push(version);
+    }
+    set_setting(&state.pool, component.skipped_versions_setting_key(), &skipped.join(",")).await?;
+    state.update_manifests.lock().await.remove(component.as_str());
+    Ok(())
+}
+
+/// 读取延期安装记录
+async fn load_update_deferrals(pool: &SqlitePool) -> Result<Vec<modules::app_updater::UpdateDeferral>, String> {
+    let value = get_setting(pool, modules::app_updater::DEFERRALS_SETTING_KEY)
+        .await?
+        .unwrap_or_default();
+    if value.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    serde_json::from_str(&value).map_err(|e| format!("延期安装记录格式错误: {}", e))
+}
+
+async fn save_update_deferrals(
+    pool: &SqlitePool,
+    deferrals: &[modules::app_updater::UpdateDeferral],
+) -> Result<(), String> {
+    let value = serde_json::to_string(deferrals).map_err(|e| e.to_string())?;
+    set_setting(pool, modules::app_updater::DEFERRALS_SETTING_KEY, &value).await
+}
+
+/// 延期安装更新：所有浏览器关闭后或进入安装时间窗口后再提醒
+#[tauri::command]
+async fn defer_update(
+    component: UpdateComponent,
+    version: String,
+    until: modules::app_updater::DeferUntil,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    if until == modules::app_updater::DeferUntil::InstallWindow
+        && get_setting(&state.pool, modules::app_updater::INSTALL_WINDOW_SETTING_KEY)
+            .await?
+            .is_none_or(|v| v.trim().is_empty())
+    {
+        return Err("请先在设置中配置安装时间窗口".to_string());
+    }
+    let mut deferrals = load_update_deferrals(&state.pool).await?;
+    deferrals.retain(|d| d.component != component);
+    deferrals.push(modules::app_updater::UpdateDeferral {
+        component,
+        version,
+        until,
+        created_at: chrono::Utc::now().to_rfc3339(),
+    });
+    save_update_deferrals(&state.pool, &deferrals).await
+}
+
+/// 取消延期安装
+#[tauri::command]
+async fn cancel_update_deferral(component: UpdateComponent, state: State<'_, AppState>) -> Result<(), String> {
+    let mut deferrals = load_update_deferrals(&state.pool).await?;
+    deferrals.retain(|d| d.component != component);
+    save_update_deferrals(&state.pool, &deferrals).await
+}
+
+/// 检查延期的更新是否满足安装条件，满足时通知前端并移除记录
+///
+/// 只发送 update:ready 提醒，不在后台自动安装（安装前需要用户确认并关闭浏览器）
+async fn check_update_deferrals(app: &tauri::AppHandle) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    let deferrals = load_update_deferrals(&state.pool).await?;
+    if deferrals.is_empty() {
+        return Ok(());
+    }
+    let window = get_setting(&state.pool, modules::app_updater::INSTALL_WINDOW_SETTING_KEY)
+        .await?
+        .filter(|v| !v.trim().is_empty())
+        .and_then(|v| modules::app_updater::InstallWindow::parse(&v).ok());
+    let running = state.browser_manager.get_running_profiles().await.len();
+    let now = chrono::Local::now().time();
+
+    let (due, pending): (Vec<_>, Vec<_>) = deferrals
+        .into_iter()
+        .partition(|d| modules::app_updater::deferral_due(d, running, window.as_ref(), now));
+    if due.is_empty() {
+        return Ok(());
+    }
+    save_update_deferrals(&state.pool, &pending).await?;
+    for deferral in due {
+        tracing::info!("延期的 {} 更新 {} 已满足安装条件", deferral.component.as_str(), deferral.version);
+        let _ = app.emit(modules::app_updater::UPDATE_READY_EVENT, &deferral);
+    }
+    Ok(())
 }
 
 /// 获取运行中的浏览器数量（前端在安装更新前调用检查）
@@ -2866,17 +4123,16 @@ pub fn run() {
             let app_data_dir = app.path().app_data_dir()?;
             std::fs::create_dir_all(&app_data_dir)?;
 
-            // 初始化模板文件：如果不存在则从内嵌资源复制
-            let template_dir = app_data_dir.join("data").join("templates");
-            let template_file = template_dir.join("device_templates.json");
-            if !template_file.exists() {
-                std::fs::create_dir_all(&template_dir)?;
-                // 内嵌模板文件内容
-                const EMBEDDED_TEMPLATE: &str =
-                    include_str!("../data/templates/device_templates.json");
-                std::fs::write(&template_file, EMBEDDED_TEMPLATE)?;
-                tracing::info!("已初始化设备模板文件: {:?}", template_file);
-            }
+            // 初始化模板文件：如果不存在则释放内置模板
+            let template_manager = {
+                use modules::fingerprint::TemplateManager;
+                let template_file = device_templates_path(&app_data_dir);
+                TemplateManager::load_or_init(&template_file).or_else(|e| {
+                    // 用户模板文件损坏时回退到内置模板，不覆盖原文件
+                    tracing::error!("加载设备模板失败，使用内置模板: {}", e);
+                    TemplateManager::load_builtin()
+                })?
+            };
 
             let db_path = app_data_dir.join("browser-manager.db");
             let db = tauri::async_runtime::block_on(async { init_database(&db_path).await })
@@ -2889,12 +4145,35 @@ pub fn run() {
             let recycle_bin_service = RecycleBinService::new(pool.clone()); // ✅ V5 解锁 + 缓存删除（动态读取设置）
             let proxy_service = ProxyService::new(pool.clone()); // ✅ V5 升级
             let extension_service = modules::ExtensionService::new(pool.clone());
+            let fingerprint_history_service = FingerprintHistoryService::new(pool.clone());
+            let fingerprint_score_service = FingerprintScoreService::new(pool.clone());
+            let traffic_service = TrafficService::new(pool.clone());
+            let crash_report_service = CrashReportService::new(pool.clone());
+            let fingerprint_validator = load_fingerprint_validator(&app_data_dir).unwrap_or_else(|e| {
+                // 自定义规则无效时回退到内置规则
+                tracing::error!("加载指纹校验规则失败，使用内置规则: {}", e);
+                modules::fingerprint::FingerprintValidator::builtin()
+            });
             let proxy_bridge_manager = Arc::new(ProxyBridgeManager::new());
+            // 应用已保存的桥接端口范围（无效时使用默认范围）
+            if let Ok(Some(range)) = tauri::async_runtime::block_on(get_setting(&pool, modules::proxy_bridge::PORT_RANGE_SETTING_KEY)) {
+                if let Err(e) = modules::proxy_bridge::parse_port_range(&range)
+                    .and_then(|(start, end)| proxy_bridge_manager.set_port_range(start, end))
+                {
+                    tracing::warn!("代理桥接端口范围设置无效，使用默认范围: {}", e);
+                }
+            }
 
             // Initialize kernel downloader
-            let kernel_base_dir = app_data_dir.join("kernel").join("win32");
+            let kernel_base_dir = app_data_dir
+                .join("kernel")
+                .join(modules::kernel_downloader::KERNEL_PLATFORM_DIR);
+            if let Ok(resource_dir) = app.path().resource_dir() {
+                KernelDownloader::set_resource_dir(resource_dir);
+            }
             let kernel_version = "146".to_string(); // 默认使用 Chromium 146
             let kernel_downloader = Arc::new(Mutex::new(KernelDownloader::new(kernel_base_dir.clone(), kernel_version.clone())));
+            let kernel_registry = KernelRegistry::new(pool.clone(), kernel_base_dir.clone());
 
             // 首次运行时自动解压内嵌的内核压缩包
             // 检查多个可能的路径 (开发模式和生产模式)
@@ -2988,11 +4267,55 @@ pub fn run() {
                 extension_service: Arc::new(Mutex::new(extension_service)),
                 proxy_bridge_manager,
                 kernel_downloader,
+                kernel_registry: Arc::new(Mutex::new(kernel_registry)),
+                update_manifests: Arc::new(Mutex::new(HashMap::new())),
+                template_manager: Arc::new(Mutex::new(template_manager)),
+                fingerprint_history_service: Arc::new(Mutex::new(fingerprint_history_service)),
+                fingerprint_validator: Arc::new(Mutex::new(fingerprint_validator)),
+                fingerprint_score_service: Arc::new(Mutex::new(fingerprint_score_service)),
+                traffic_service: Arc::new(Mutex::new(traffic_service)),
+                crash_report_service: Arc::new(Mutex::new(crash_report_service)),
                 pool,
                 browser_manager,
                 app_data_dir,
             });
 
+            // 定期持久化桥接流量、检查配额、回写代理可用性，并检查延期的更新
+            let app_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let interval = std::time::Duration::from_secs(modules::traffic::TRAFFIC_FLUSH_INTERVAL_SECS);
+                loop {
+                    tokio::time::sleep(interval).await;
+                    if let Err(e) = flush_traffic(&app_handle).await {
+                        tracing::error!("写入流量统计失败: {}", e);
+                    }
+                    if let Err(e) = sync_proxy_status(&app_handle).await {
+                        tracing::error!("更新代理状态失败: {}", e);
+                    }
+                    if let Err(e) = check_update_deferrals(&app_handle).await {
+                        tracing::error!("检查延期更新失败: {}", e);
+                    }
+                }
+            });
+
+            // 处理浏览器崩溃（记录崩溃现场，按重启策略自动重启）
+            let app_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let interval = std::time::Duration::from_secs(modules::crash_report::CRASH_CHECK_INTERVAL_SECS);
+                loop {
+                    tokio::time::sleep(interval).await;
+                    if let Err(e) = handle_browser_crashes(&app_handle).await {
+                        tracing::error!("处理浏览器崩溃失败: {}", e);
+                    }
+                }
+            });
+
+            // 启动器更新后恢复之前运行中的环境
+            let app_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                restore_update_session(&app_handle).await;
+            });
+
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -3007,7 +4330,22 @@ pub fn run() {
             // Fingerprint commands - 指纹生成
             generate_random_fingerprint,
             get_template_list,
+            get_device_template,
+            create_device_template,
+            update_device_template,
+            set_device_template_enabled,
+            set_device_template_weight,
+            delete_device_template,
+            import_device_template_pack,
+            reset_device_templates,
+            get_fingerprint_history,
+            diff_fingerprint_revisions,
+            revert_fingerprint_revision,
             validate_fingerprint,
+            get_profile_fingerprint_score,
+            score_all_profiles,
+            get_fingerprint_rules,
+            reload_fingerprint_rules,
             // Group commands
             get_groups,
             get_group,
@@ -3034,6 +4372,7 @@ pub fn run() {
             delete_proxy,
             test_proxy,
             test_proxy_config,
+            test_proxy_chain,
             batch_test_proxies,
             test_all_proxies,
             set_proxy_auto_check,
@@ -3045,6 +4384,10 @@ pub fn run() {
             // Browser commands
             launch_browser,
             stop_browser,
+            get_browser_exit_reason,
+            get_restart_policy,
+            set_restart_policy,
+            get_crash_history,
             batch_launch_browsers,
             batch_stop_browsers,
             batch_move_to_group,
@@ -3086,6 +4429,18 @@ pub fn run() {
             // Proxy Bridge commands
             get_proxy_bridge_stats,
             get_all_proxy_bridge_stats,
+            get_proxy_bridge_endpoints,
+            set_proxy_bridge_rate_limits,
+            set_traffic_quota,
+            delete_traffic_quota,
+            list_traffic_quotas,
+            query_traffic_usage,
+            export_traffic_usage_csv,
+            get_proxy_connection_log,
+            get_proxy_top_domains,
+            clear_proxy_connection_log,
+            rotate_proxy_session,
+            get_proxy_upstream_health,
             // Kernel download commands
             is_kernel_installed,
             get_kernel_version,
@@ -3096,6 +4451,12 @@ pub fn run() {
             get_bundled_kernel_path,
             list_bundled_kernel_versions,
             get_bundled_kernel_path_by_version,
+            list_kernel_versions,
+            list_kernel_pins,
+            set_profile_kernel_version,
+            set_group_kernel_version,
+            download_kernel_version,
+            uninstall_kernel_version,
             trigger_kernel_extraction, // 手动触发内核解压
             // App update commands - 应用自动更新
             check_app_update,
@@ -3105,6 +4466,11 @@ pub fn run() {
             download_kernel_update,
             install_app_update,
             install_kernel_update_cmd,
+            get_kernel_update_history,
+            open_offline_update_package,
+            skip_update_version,
+            defer_update,
+            cancel_update_deferral,
             get_running_browser_count,
             // Auth commands - 用户认证
             auth_login,
@@ -3123,6 +4489,18 @@ pub fn run() {
             notification_mark_all_read,
             notification_delete,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app_handle, event| {
+            // 退出前写入尚未持久化的桥接流量
+            if let tauri::RunEvent::Exit = event {
+                if let Some(state) = app_handle.try_state::<AppState>() {
+                    tauri::async_runtime::block_on(async {
+                        if let Err(e) = persist_traffic(&state, chrono::Utc::now()).await {
+                            tracing::error!("退出前写入流量统计失败: {}", e);
+                        }
+                    });
+                }
+            }
+        });
 }
@@ -2,6 +2,7 @@
 //! 
 //! 负责启动器和内核的版本检测、下载、SHA256校验、安装。
 //! 所有网络请求通过 Rust reqwest 发起，前端仅通过 Tauri IPC 调用。
+//! 更新信息附带 Ed25519 签名的清单（manifest），安装前必须通过内嵌公钥校验。
 
 use anyhow::{Context, Result};
 use futures_util::StreamExt;
@@ -12,6 +13,7 @@ use tokio::fs::{self, File};
 use tokio::io::AsyncWriteExt;
 use tracing::{info, warn, error};
 use super::config;  // 导入统一配置
+use super::signing::{self, TrustedKey};
 
 // ==================== 常量 ====================
 
@@ -22,7 +24,7 @@ pub const DEFAULT_UPDATE_SERVER: &str = config::API_BASE_URL;
 // ==================== 数据结构 ====================
 
 /// 更新组件类型
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum UpdateComponent {
     Launcher,
@@ -62,6 +64,35 @@ pub struct UpdateInfo {
     pub file_hash: Option<String>,
     #[serde(default)]
     pub downloads: Option<Vec<DownloadSource>>,
+    /// 签名清单原文（JSON，见 `UpdateManifest`）
+    #[serde(default)]
+    pub manifest: Option<String>,
+    /// 清单的 Ed25519 签名（base64）
+    #[serde(default)]
+    pub signature: Option<String>,
+    /// 签名密钥 ID
+    #[serde(default)]
+    pub key_id: Option<String>,
+    /// 该版本已被用户跳过（客户端填写）
+    #[serde(default)]
+    pub skipped: bool,
+}
+
+/// 签名的更新清单：安装包的版本与 SHA256 以清单为准
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateManifest {
+    pub component: UpdateComponent,
+    pub version: String,
+    pub file_hash: String,
+    #[serde(default)]
+    pub file_size: Option<u64>,
+    #[serde(default)]
+    pub platform: Option<String>,
+    #[serde(default)]
+    pub arch: Option<String>,
+    /// 过期时间 (RFC3339)，过期后拒绝安装，防止重放旧清单
+    #[serde(default)]
+    pub expires_at: Option<String>,
 }
 
 /// API 统一响应
@@ -96,6 +127,267 @@ pub enum UpdateDownloadStatus {
     Failed,
 }
 
+// ==================== 更新通道、跳过版本与延期安装 ====================
+
+/// 安装时间窗口设置项（本地时间 "HH:MM-HH:MM"，可跨午夜，为空表示不限制）
+///
+/// 窗口只决定何时提醒，不会自动安装：进入窗口后发送 update:ready，由用户确认安装
+pub const INSTALL_WINDOW_SETTING_KEY: &str = "update_install_window";
+
+/// 延期安装记录设置项（JSON 数组）
+pub const DEFERRALS_SETTING_KEY: &str = "update_deferrals";
+
+/// 延期的更新已满足安装条件时通知前端（仅提醒，安装仍由前端发起）
+pub const UPDATE_READY_EVENT: &str = "update:ready";
+
+impl UpdateComponent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UpdateComponent::Launcher => "launcher",
+            UpdateComponent::Kernel => "kernel",
+        }
+    }
+
+    /// 更新通道设置项
+    pub fn channel_setting_key(&self) -> &'static str {
+        match self {
+            UpdateComponent::Launcher => "update_channel_launcher",
+            UpdateComponent::Kernel => "update_channel_kernel",
+        }
+    }
+
+    /// 已跳过版本设置项（逗号分隔）
+    pub fn skipped_versions_setting_key(&self) -> &'static str {
+        match self {
+            UpdateComponent::Launcher => "update_skipped_versions_launcher",
+            UpdateComponent::Kernel => "update_skipped_versions_kernel",
+        }
+    }
+}
+
+/// 更新通道
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum UpdateChannel {
+    #[default]
+    Stable,
+    Beta,
+    /// 固定到指定版本（不再跟随最新版本）
+    Pinned(String),
+}
+
+impl UpdateChannel {
+    /// 解析设置值："stable" / "beta" / "pinned:<version>"，为空时为 stable
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim() {
+            "" | "stable" => Ok(UpdateChannel::Stable),
+            "beta" => Ok(UpdateChannel::Beta),
+            other => match other.strip_prefix("pinned:").map(str::trim) {
+                Some(version) if !version.is_empty() => Ok(UpdateChannel::Pinned(version.to_string())),
+                _ => Err(anyhow::anyhow!(
+                    "无效的更新通道: {}（可选 stable / beta / pinned:<版本号>）",
+                    other
+                )),
+            },
+        }
+    }
+
+    /// 安装内核时记录的通道标记（beta 内核只对固定了该版本的环境生效）
+    pub fn kernel_channel(&self) -> &'static str {
+        match self {
+            UpdateChannel::Beta => "beta",
+            _ => "stable",
+        }
+    }
+
+    /// 更新检查接口的查询参数（由 reqwest 负责编码）
+    pub fn query(&self) -> Vec<(&'static str, &str)> {
+        match self {
+            UpdateChannel::Stable => vec![("channel", "stable")],
+            UpdateChannel::Beta => vec![("channel", "beta")],
+            UpdateChannel::Pinned(version) => vec![("channel", "pinned"), ("pinned_version", version.as_str())],
+        }
+    }
+}
+
+/// 解析逗号分隔的已跳过版本
+pub fn parse_skipped_versions(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// 跳过用户选择忽略的版本（强制更新不可跳过）
+pub fn apply_skipped_versions(mut info: UpdateInfo, skipped: &[String]) -> UpdateInfo {
+    let version = info.version.clone().or_else(|| info.latest_version.clone());
+    let mandatory = info.mandatory.unwrap_or(false);
+    if let Some(version) = version {
+        if info.has_update && !mandatory && skipped.iter().any(|v| v == &version) {
+            info!("已跳过版本 {}，不提示更新", version);
+            info.has_update = false;
+            info.skipped = true;
+        }
+    }
+    info
+}
+
+/// 安装时间窗口（本地时间）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InstallWindow {
+    pub start: chrono::NaiveTime,
+    pub end: chrono::NaiveTime,
+}
+
+impl InstallWindow {
+    /// 解析 "HH:MM-HH:MM"
+    pub fn parse(value: &str) -> Result<Self> {
+        let (start, end) = value
+            .trim()
+            .split_once('-')
+            .ok_or_else(|| anyhow::anyhow!("安装时间窗口格式应为 HH:MM-HH:MM"))?;
+        let parse = |s: &str| {
+            chrono::NaiveTime::parse_from_str(s.trim(), "%H:%M")
+                .map_err(|_| anyhow::anyhow!("无效的时间: {}", s.trim()))
+        };
+        let window = Self { start: parse(start)?, end: parse(end)? };
+        if window.start == window.end {
+            return Err(anyhow::anyhow!("安装时间窗口的开始和结束时间不能相同"));
+        }
+        Ok(window)
+    }
+
+    /// 时间是否在窗口内（支持跨午夜，如 22:00-06:00）
+    pub fn contains(&self, time: chrono::NaiveTime) -> bool {
+        if self.start < self.end {
+            time >= self.start && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+/// 延期条件
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeferUntil {
+    /// 所有浏览器关闭后提醒
+    BrowsersClosed,
+    /// 进入安装时间窗口后提醒
+    InstallWindow,
+}
+
+/// 延期安装记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateDeferral {
+    pub component: UpdateComponent,
+    pub version: String,
+    pub until: DeferUntil,
+    pub created_at: String,
+}
+
+/// 延期的更新是否已满足提醒条件
+///
+/// 两种延期都要求无运行中的浏览器（内核更新的前提条件）；设置了安装窗口时还需处于窗口内。
+/// 满足条件只触发提醒，不会自动下载或安装
+pub fn deferral_due(
+    deferral: &UpdateDeferral,
+    running_browsers: usize,
+    window: Option<&InstallWindow>,
+    now: chrono::NaiveTime,
+) -> bool {
+    let in_window = window.map(|w| w.contains(now)).unwrap_or(true);
+    match deferral.until {
+        DeferUntil::BrowsersClosed => running_browsers == 0 && in_window,
+        DeferUntil::InstallWindow => in_window && (deferral.component == UpdateComponent::Launcher || running_browsers == 0),
+    }
+}
+
+// ==================== 更新前后的会话保存与恢复 ====================
+
+/// 会话快照文件（位于应用数据目录）
+pub const SESSION_RESTORE_FILE: &str = "update_session.json";
+
+/// 超过该时长的会话快照不再恢复（更新失败后用户隔天手动启动时不应自动打开浏览器）
+pub const SESSION_RESTORE_MAX_AGE_HOURS: i64 = 24;
+
+/// 会话恢复完成后通知前端
+pub const SESSION_RESTORED_EVENT: &str = "update:session-restored";
+
+/// 窗口位置与大小
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WindowBounds {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// 更新前运行中的环境
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionProfile {
+    pub profile_id: String,
+    #[serde(default)]
+    pub bounds: Option<WindowBounds>,
+}
+
+/// 启动器更新前的会话快照，更新后的启动器启动时据此重新打开环境
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateSession {
+    pub created_at: String,
+    pub from_version: String,
+    pub profiles: Vec<SessionProfile>,
+}
+
+/// 记录运行中的环境及其主窗口位置
+pub fn capture_update_session(running: &std::collections::HashMap<String, u32>) -> UpdateSession {
+    let mut profiles: Vec<SessionProfile> = running
+        .iter()
+        .map(|(profile_id, pid)| SessionProfile {
+            profile_id: profile_id.clone(),
+            bounds: super::window_helper::collect_window_details_for_pids(&[*pid])
+                .first()
+                .map(|w| WindowBounds { x: w.x, y: w.y, width: w.width, height: w.height }),
+        })
+        .collect();
+    profiles.sort_by(|a, b| a.profile_id.cmp(&b.profile_id));
+    UpdateSession {
+        created_at: chrono::Utc::now().to_rfc3339(),
+        from_version: env!("CARGO_PKG_VERSION").to_string(),
+        profiles,
+    }
+}
+
+/// 保存会话快照
+pub async fn save_update_session(path: &Path, session: &UpdateSession) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    fs::write(path, serde_json::to_vec_pretty(session)?)
+        .await
+        .context("保存会话快照失败")
+}
+
+/// 读取并删除会话快照（只恢复一次），过期或损坏的快照被忽略
+pub async fn take_update_session(path: &Path) -> Option<UpdateSession> {
+    let content = fs::read(path).await.ok()?;
+    let _ = fs::remove_file(path).await;
+    let session: UpdateSession = match serde_json::from_slice(&content) {
+        Ok(session) => session,
+        Err(e) => {
+            warn!("会话快照格式错误，已忽略: {}", e);
+            return None;
+        }
+    };
+    let created_at = chrono::DateTime::parse_from_rfc3339(&session.created_at).ok()?;
+    if chrono::Utc::now().signed_duration_since(created_at) > chrono::Duration::hours(SESSION_RESTORE_MAX_AGE_HOURS) {
+        info!("会话快照已过期 ({})，不再恢复", session.created_at);
+        return None;
+    }
+    Some(session)
+}
+
 // ==================== 版本检测 ====================
 
 /// 检查启动器更新
@@ -104,21 +396,20 @@ pub async fn check_launcher_update(
     current_version: &str,
     platform: &str,
     arch: &str,
+    channel: &UpdateChannel,
 ) -> Result<UpdateInfo> {
     let client = reqwest::Client::builder()
         .timeout(std::time::Duration::from_secs(config::REQUEST_TIMEOUT_SECS))
         .build()?;
 
-    // 使用统一配置构建 URL（如果 server_url 是默认值，可以用配置函数）
-    let url = format!(
-        "{}/api/v1/updates/launcher?version={}&platform={}&arch={}",
-        server_url, current_version, platform, arch
-    );
+    let url = format!("{}/api/v1/updates/launcher", server_url);
 
-    info!("Checking launcher update: {}", url);
+    info!("Checking launcher update: {} (version {})", url, current_version);
 
     let resp: ApiResponse<UpdateInfo> = client
         .get(&url)
+        .query(&[("version", current_version), ("platform", platform), ("arch", arch)])
+        .query(&channel.query())
         .send()
         .await
         .context("请求更新服务器失败")?
@@ -140,20 +431,25 @@ pub async fn check_kernel_update(
     platform: &str,
     arch: &str,
     launcher_version: &str,
+    channel: &UpdateChannel,
 ) -> Result<UpdateInfo> {
     let client = reqwest::Client::builder()
         .timeout(std::time::Duration::from_secs(10))
         .build()?;
 
-    let url = format!(
-        "{}/api/v1/updates/kernel?version={}&platform={}&arch={}&launcher_version={}",
-        server_url, current_kernel_version, platform, arch, launcher_version
-    );
+    let url = format!("{}/api/v1/updates/kernel", server_url);
 
-    info!("Checking kernel update: {}", url);
+    info!("Checking kernel update: {} (version {})", url, current_kernel_version);
 
     let resp: ApiResponse<UpdateInfo> = client
         .get(&url)
+        .query(&[
+            ("version", current_kernel_version),
+            ("platform", platform),
+            ("arch", arch),
+            ("launcher_version", launcher_version),
+        ])
+        .query(&channel.query())
         .send()
         .await
         .context("请求更新服务器失败")?
@@ -168,6 +464,121 @@ pub async fn check_kernel_update(
     resp.data.context("响应数据为空")
 }
 
+// ==================== 清单签名校验 ====================
+
+/// 本机平台（与更新检查接口的 platform 参数一致）
+pub const HOST_PLATFORM: &str = "windows";
+
+/// 本机架构（与更新检查接口的 arch 参数一致）
+pub fn host_arch() -> &'static str {
+    if cfg!(target_pointer_width = "64") {
+        "x86_64"
+    } else {
+        "x86"
+    }
+}
+
+/// 平台 / 架构名称归一化（兼容 win64、x64、amd64 等写法）
+fn normalize_target(value: &str) -> String {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "win" | "win32" | "win64" | "windows" => "windows".to_string(),
+        "mac" | "macos" | "darwin" | "osx" => "macos".to_string(),
+        "x64" | "amd64" | "x86_64" => "x86_64".to_string(),
+        "x86" | "i386" | "i686" | "ia32" => "x86".to_string(),
+        "arm64" | "aarch64" => "aarch64".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// 确认签名清单的 platform / arch 与本机一致（清单未声明时不限制）
+pub fn check_manifest_target(manifest: &UpdateManifest, platform: &str, arch: &str) -> Result<()> {
+    let targets = [("平台", manifest.platform.as_deref(), platform), ("架构", manifest.arch.as_deref(), arch)];
+    for (label, declared, host) in targets {
+        if let Some(declared) = declared.filter(|v| !v.trim().is_empty()) {
+            if normalize_target(declared) != normalize_target(host) {
+                return Err(anyhow::anyhow!(
+                    "更新包{} {} 与本机 {} 不匹配，已拒绝安装",
+                    label,
+                    declared,
+                    host
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 去掉 "sha256:" 前缀并转小写
+pub fn normalize_hash(hash: &str) -> String {
+    hash.trim().trim_start_matches("sha256:").to_ascii_lowercase()
+}
+
+/// 使用内嵌公钥校验更新信息中的签名清单
+pub fn verify_update_info(info: &UpdateInfo, component: UpdateComponent) -> Result<UpdateManifest> {
+    verify_update_info_with_keys(info, component, &signing::trusted_keys())
+}
+
+/// 校验签名清单，并确认清单与更新信息中展示的版本 / 哈希一致
+pub fn verify_update_info_with_keys(
+    info: &UpdateInfo,
+    component: UpdateComponent,
+    keys: &[TrustedKey],
+) -> Result<UpdateManifest> {
+    let (Some(manifest_text), Some(signature)) = (info.manifest.as_deref(), info.signature.as_deref()) else {
+        return Err(anyhow::anyhow!("更新信息未签名，已拒绝"));
+    };
+
+    let key_id = signing::verify_with_key_id(keys, info.key_id.as_deref(), manifest_text.as_bytes(), signature)
+        .context("更新清单签名校验失败")?;
+
+    let manifest: UpdateManifest = serde_json::from_str(manifest_text).context("解析更新清单失败")?;
+    if manifest.component != component {
+        return Err(anyhow::anyhow!("更新清单组件不匹配"));
+    }
+    let advertised_version = info.version.as_deref().or(info.latest_version.as_deref());
+    if let Some(version) = advertised_version {
+        if version != manifest.version {
+            return Err(anyhow::anyhow!(
+                "更新版本 {} 与签名清单版本 {} 不一致",
+                version,
+                manifest.version
+            ));
+        }
+    }
+    if let Some(hash) = info.file_hash.as_deref() {
+        if normalize_hash(hash) != normalize_hash(&manifest.file_hash) {
+            return Err(anyhow::anyhow!("更新文件哈希与签名清单不一致"));
+        }
+    }
+    if let Some(expires_at) = manifest.expires_at.as_deref() {
+        let expires_at = chrono::DateTime::parse_from_rfc3339(expires_at).context("更新清单过期时间格式错误")?;
+        if expires_at < chrono::Utc::now() {
+            return Err(anyhow::anyhow!("更新清单已过期"));
+        }
+    }
+
+    info!("更新清单签名校验通过: {:?} {} (key {})", component, manifest.version, key_id);
+    Ok(manifest)
+}
+
+/// 校验已下载文件与签名清单一致（安装前调用）
+pub async fn verify_file_against_manifest(path: &Path, manifest: &UpdateManifest) -> Result<()> {
+    let path_owned = path.to_path_buf();
+    let actual = tokio::task::spawn_blocking(move || {
+        let mut file = std::fs::File::open(&path_owned).context("打开安装包失败")?;
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut file, &mut hasher)?;
+        Ok::<_, anyhow::Error>(format!("{:x}", hasher.finalize()))
+    })
+    .await??;
+
+    if actual != normalize_hash(&manifest.file_hash) {
+        error!("安装包与签名清单不一致: {:?}", path);
+        return Err(anyhow::anyhow!("安装包与签名清单不一致，已拒绝安装"));
+    }
+    Ok(())
+}
+
 // ==================== 内核下载信息（不含鉴权版本，后续可扩展） ====================
 
 /// 获取内核下载信息
@@ -248,10 +659,7 @@ where
     let mut hasher = Sha256::new();
     let start_time = std::time::Instant::now();
     let mut last_emit = std::time::Instant::now();
-    let component_str = match component {
-        UpdateComponent::Launcher => "launcher",
-        UpdateComponent::Kernel => "kernel",
-    };
+    let component_str = component.as_str();
 
     while let Some(chunk) = stream.next().await {
         let chunk = chunk.context("读取数据块失败")?;
@@ -338,31 +746,42 @@ where
 // ==================== 启动器安装 ====================
 
 /// 安装启动器更新
-/// 流程：检查运行实例 → 关闭浏览器 → 关闭代理桥 → 启动安装器 → 优雅退出
+/// 流程：记录运行中的环境 → 正常关闭浏览器（超时后强制终止）→ 关闭代理桥 → 启动安装器 → 优雅退出
+/// 新版本启动后通过 `take_update_session` 恢复之前运行的环境；
+/// 返回错误时快照仍保留在 `session_path`，由调用方立即恢复
 pub async fn install_launcher_update(
     installer_path: &Path,
+    manifest: &UpdateManifest,
     browser_manager: &super::BrowserManager,
     proxy_bridge_manager: &super::ProxyBridgeManager,
     app_handle: &tauri::AppHandle,
+    session_path: &Path,
+    shutdown_timeout: std::time::Duration,
 ) -> Result<()> {
     use tauri::Emitter;
 
-    // Step 1: 检查是否有运行中的浏览器实例
+    // 安装包必须与签名清单一致
+    if manifest.component != UpdateComponent::Launcher {
+        return Err(anyhow::anyhow!("更新清单组件不匹配"));
+    }
+    verify_file_against_manifest(installer_path, manifest).await?;
+
+    // Step 1: 记录运行中的环境（更新后自动恢复），再逐个正常关闭浏览器
     let running = browser_manager.get_running_profiles().await;
     if !running.is_empty() {
+        let session = capture_update_session(&running);
+        save_update_session(session_path, &session).await?;
+
         info!("正在关闭 {} 个运行中的浏览器实例...", running.len());
-        for (profile_id, _pid) in &running {
-            if let Some(mut child) = browser_manager
-                .unregister_process(profile_id)
-                .await
-                .unwrap_or(None)
-            {
-                let _ = child.kill();
-                let _ = child.wait();
+        let shutdowns = running.keys().map(|profile_id| async move {
+            let outcome = browser_manager.stop_process(profile_id, shutdown_timeout).await?;
+            Some((profile_id.clone(), outcome))
+        });
+        for (profile_id, outcome) in futures::future::join_all(shutdowns).await.into_iter().flatten() {
+            if outcome == super::browser_manager::ShutdownOutcome::Killed {
+                warn!("环境 {} 的浏览器未能正常退出，已强制终止", profile_id);
             }
         }
-        // 等待进程完全退出
-        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
     }
 
     // Step 2: 关闭所有代理桥
@@ -392,19 +811,67 @@ pub async fn install_launcher_update(
 
 // ==================== 内核更新 ====================
 
+/// 内核更新结果状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KernelUpdateStatus {
+    /// 新内核通过冒烟测试并生效
+    Installed,
+    /// 新内核未通过冒烟测试，已恢复旧内核
+    RolledBack,
+    /// 解压 / 替换失败，旧内核未被改动或已恢复
+    Failed,
+}
+
+impl KernelUpdateStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KernelUpdateStatus::Installed => "installed",
+            KernelUpdateStatus::RolledBack => "rolled_back",
+            KernelUpdateStatus::Failed => "failed",
+        }
+    }
+}
+
+/// 内核更新结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KernelUpdateOutcome {
+    pub version: String,
+    pub previous_version: Option<String>,
+    pub status: KernelUpdateStatus,
+    pub message: Option<String>,
+}
+
+/// 读取内核目录中记录的版本
+fn installed_kernel_version(kernel_dir: &Path) -> Option<String> {
+    let content = std::fs::read_to_string(kernel_dir.join("kernel_version.json")).ok()?;
+    let info: serde_json::Value = serde_json::from_str(&content).ok()?;
+    info.get("version")?.as_str().map(str::to_string)
+}
+
 /// 安装内核更新
 /// 前置条件：必须无运行中的浏览器实例
-/// 流程：解压到临时目录 → 备份旧内核 → 替换 → 写入版本信息
+/// 流程：解压到暂存目录 → 备份旧内核 → 替换 → 冒烟测试 → 失败时自动恢复备份
+///
+/// 安装包校验失败或仍有浏览器运行时返回 Err；其余情况返回结果（含回滚），由调用方记录历史
 pub async fn install_kernel_update<F>(
     zip_path: &Path,
     kernel_dir: &Path,
-    new_version: &str,
+    manifest: &UpdateManifest,
+    channel: &UpdateChannel,
     browser_manager: &super::BrowserManager,
     progress_callback: F,
-) -> Result<()>
+) -> Result<KernelUpdateOutcome>
 where
     F: Fn(UpdateDownloadProgress) + Send + 'static,
 {
+    // 安装包必须与签名清单一致
+    if manifest.component != UpdateComponent::Kernel {
+        return Err(anyhow::anyhow!("更新清单组件不匹配"));
+    }
+    verify_file_against_manifest(zip_path, manifest).await?;
+    let new_version = manifest.version.as_str();
+
     // 前置检查：必须无运行中的浏览器
     let running = browser_manager.get_running_profiles().await;
     if !running.is_empty() {
@@ -418,28 +885,64 @@ where
         component: "kernel".to_string(),
         downloaded: 0, total: 0, speed: 0, percent: 0,
         status: UpdateDownloadStatus::Extracting,
-        message: "正在解压内核文件...".to_string(),
+        message: "正在解压并检测新内核...".to_string(),
     });
 
-    // Step 1: 备份旧内核（如果存在）
-    let backup_dir = kernel_dir.parent()
-        .unwrap_or(kernel_dir)
-        .join("kernel_backup");
-
-    if kernel_dir.exists() {
-        info!("备份旧内核到: {:?}", backup_dir);
-        if backup_dir.exists() {
-            fs::remove_dir_all(&backup_dir).await?;
-        }
-        fs::rename(kernel_dir, &backup_dir).await
-            .context("备份旧内核失败")?;
+    let version_info = super::kernel_downloader::KernelVersionInfo {
+        version: new_version.to_string(),
+        build_date: chrono::Utc::now().to_rfc3339(),
+        platform: "win64".to_string(),
+        source: "auto_update".to_string(),
+        files_count: 0,
+        total_size_bytes: manifest.file_size.unwrap_or(0),
+        sha256: Some(manifest.file_hash.clone()),
+        signature: None,
+        mirrors: Vec::new(),
+        channel: Some(channel.kernel_channel().to_string()),
+    };
+    let outcome = install_kernel_package(zip_path, kernel_dir, &version_info).await?;
+    if outcome.status == KernelUpdateStatus::Installed {
+        info!("内核更新完成: {}", new_version);
     }
 
-    // Step 2: 解压新内核
-    fs::create_dir_all(kernel_dir).await?;
+    progress_callback(UpdateDownloadProgress {
+        component: "kernel".to_string(),
+        downloaded: 0, total: 0, speed: 0, percent: 100,
+        status: if outcome.status == KernelUpdateStatus::Installed {
+            UpdateDownloadStatus::Completed
+        } else {
+            UpdateDownloadStatus::Failed
+        },
+        message: match outcome.status {
+            KernelUpdateStatus::Installed => format!("内核已更新到 {}", new_version),
+            _ => outcome.message.clone().unwrap_or_default(),
+        },
+    });
+
+    Ok(outcome)
+}
+
+/// 安装已校验的内核压缩包到 kernel_dir
+/// 流程：解压到暂存目录（旧内核保持不变）→ 写入版本信息 → 备份并替换 → 冒烟测试 → 失败时自动恢复备份
+///
+/// 中断或失败时不会在 kernel_dir 留下半成品目录；安装成功后删除压缩包
+pub async fn install_kernel_package(
+    zip_path: &Path,
+    kernel_dir: &Path,
+    version_info: &super::kernel_downloader::KernelVersionInfo,
+) -> Result<KernelUpdateOutcome> {
+    let new_version = version_info.version.as_str();
+    let parent = kernel_dir.parent().unwrap_or(kernel_dir);
+    let staging_dir = parent.join(format!(".staging-{}", new_version));
+
+    // Step 1: 解压到暂存目录（旧内核保持不变）
+    if staging_dir.exists() {
+        fs::remove_dir_all(&staging_dir).await?;
+    }
+    fs::create_dir_all(&staging_dir).await?;
 
     let zip_path_owned = zip_path.to_path_buf();
-    let dest_dir_owned = kernel_dir.to_path_buf();
+    let dest_dir_owned = staging_dir.clone();
     let extract_result = tokio::task::spawn_blocking(move || {
         let file = std::fs::File::open(&zip_path_owned)?;
         let mut archive = zip::ZipArchive::new(file)?;
@@ -458,47 +961,233 @@ where
                 let mut outfile = std::fs::File::create(&outpath)?;
                 std::io::copy(&mut entry, &mut outfile)?;
             }
+
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                if let Some(mode) = entry.unix_mode() {
+                    std::fs::set_permissions(&outpath, std::fs::Permissions::from_mode(mode))?;
+                }
+            }
         }
+        #[cfg(unix)]
+        super::kernel_downloader::fix_executable_permissions(&dest_dir_owned)?;
         Ok::<_, anyhow::Error>(())
     }).await?;
 
     if let Err(e) = extract_result {
-        // 解压失败：回滚
-        error!("解压失败，正在回滚: {}", e);
-        let _ = fs::remove_dir_all(kernel_dir).await;
-        if backup_dir.exists() {
-            let _ = fs::rename(&backup_dir, kernel_dir).await;
-        }
-        return Err(e.context("解压内核失败，已回滚到旧版本"));
+        error!("解压失败，旧内核保持不变: {}", e);
+        let _ = fs::remove_dir_all(&staging_dir).await;
+        return Ok(KernelUpdateOutcome {
+            version: new_version.to_string(),
+            previous_version: installed_kernel_version(kernel_dir),
+            status: KernelUpdateStatus::Failed,
+            message: Some(format!("解压内核失败: {}", e)),
+        });
     }
 
-    // Step 3: 写入版本信息
-    let version_info = serde_json::json!({
-        "version": new_version,
-        "build_date": chrono::Utc::now().to_rfc3339(),
-        "platform": "win64",
-        "source": "auto_update",
-    });
-    let version_file = kernel_dir.join("kernel_version.json");
-    fs::write(&version_file, serde_json::to_string_pretty(&version_info)?)
-        .await?;
+    // Step 2: 写入版本信息
+    fs::write(
+        staging_dir.join("kernel_version.json"),
+        serde_json::to_string_pretty(version_info)?,
+    )
+    .await?;
+
+    // Step 3: 替换并冒烟测试，失败时恢复备份
+    let outcome = activate_staged_kernel(&staging_dir, kernel_dir, new_version).await;
+    if outcome.status == KernelUpdateStatus::Installed {
+        let _ = fs::remove_file(zip_path).await;
+    }
+    Ok(outcome)
+}
+
+/// 用暂存目录替换内核目录并进行冒烟测试；测试失败时删除新内核并恢复备份
+async fn activate_staged_kernel(staging_dir: &Path, kernel_dir: &Path, new_version: &str) -> KernelUpdateOutcome {
+    let previous_version = installed_kernel_version(kernel_dir);
+    let backup_dir = kernel_dir
+        .parent()
+        .unwrap_or(kernel_dir)
+        .join(format!(".backup-{}", new_version));
+    let outcome = |status, message: Option<String>| KernelUpdateOutcome {
+        version: new_version.to_string(),
+        previous_version: previous_version.clone(),
+        status,
+        message,
+    };
 
-    // Step 4: 清理备份和临时文件
+    // 备份旧内核（如果存在）
     if backup_dir.exists() {
         let _ = fs::remove_dir_all(&backup_dir).await;
     }
-    let _ = fs::remove_file(zip_path).await;
+    let had_previous = kernel_dir.exists();
+    if had_previous {
+        info!("备份旧内核到: {:?}", backup_dir);
+        if let Err(e) = fs::rename(kernel_dir, &backup_dir).await {
+            let _ = fs::remove_dir_all(staging_dir).await;
+            return outcome(KernelUpdateStatus::Failed, Some(format!("备份旧内核失败: {}", e)));
+        }
+    }
+
+    let restore = || async {
+        let _ = fs::remove_dir_all(kernel_dir).await;
+        if had_previous {
+            if let Err(e) = fs::rename(&backup_dir, kernel_dir).await {
+                error!("恢复旧内核失败: {}", e);
+            }
+        }
+    };
+
+    if let Err(e) = fs::rename(staging_dir, kernel_dir).await {
+        let _ = fs::remove_dir_all(staging_dir).await;
+        restore().await;
+        return outcome(KernelUpdateStatus::Failed, Some(format!("替换内核失败: {}", e)));
+    }
 
-    info!("内核更新完成: {}", new_version);
+    let exe = super::kernel_downloader::kernel_exe_path(kernel_dir);
+    match super::kernel_health::smoke_test_kernel(&exe, new_version).await {
+        Ok(_) => {
+            if had_previous {
+                let _ = fs::remove_dir_all(&backup_dir).await;
+            }
+            outcome(KernelUpdateStatus::Installed, None)
+        }
+        Err(e) => {
+            error!("新内核 {} 未通过冒烟测试，正在回滚: {:#}", new_version, e);
+            restore().await;
+            outcome(
+                KernelUpdateStatus::RolledBack,
+                Some(format!("新内核未通过检测，已自动回滚: {:#}", e)),
+            )
+        }
+    }
+}
 
-    progress_callback(UpdateDownloadProgress {
-        component: "kernel".to_string(),
-        downloaded: 0, total: 0, speed: 0, percent: 100,
-        status: UpdateDownloadStatus::Completed,
-        message: format!("内核已更新到 {}", new_version),
-    });
+// ==================== 离线更新包 ====================
+//
+// 隔离网络中通过 U 盘分发的更新包（zip），与在线更新使用同一份签名清单：
+//   package.json      {"file": "<安装包文件名>", "signature": "<manifest.json 的签名>", "key_id": "..."}
+//   manifest.json     签名的 UpdateManifest 原文
+//   RELEASE_NOTES.md  更新说明（可选）
+//   <file>            启动器安装器或内核 zip
+
+const OFFLINE_PACKAGE_INDEX: &str = "package.json";
+const OFFLINE_PACKAGE_MANIFEST: &str = "manifest.json";
+const OFFLINE_PACKAGE_NOTES: &str = "RELEASE_NOTES.md";
+
+#[derive(Debug, Deserialize)]
+struct OfflinePackageIndex {
+    file: String,
+    signature: String,
+    #[serde(default)]
+    key_id: Option<String>,
+}
 
-    Ok(())
+/// 已校验的离线更新包
+#[derive(Debug, Clone, Serialize)]
+pub struct OfflinePackage {
+    pub component: UpdateComponent,
+    pub version: String,
+    pub release_notes: Option<String>,
+    /// 解出的安装包路径（传给安装命令）
+    pub file_path: String,
+    #[serde(skip)]
+    pub manifest: UpdateManifest,
+}
+
+/// 打开离线更新包：解出安装包，校验清单签名与安装包哈希
+pub async fn open_offline_package(
+    package_path: &Path,
+    component: UpdateComponent,
+    extract_dir: &Path,
+) -> Result<OfflinePackage> {
+    open_offline_package_with_keys(package_path, component, extract_dir, &signing::trusted_keys()).await
+}
+
+pub async fn open_offline_package_with_keys(
+    package_path: &Path,
+    component: UpdateComponent,
+    extract_dir: &Path,
+    keys: &[TrustedKey],
+) -> Result<OfflinePackage> {
+    fs::create_dir_all(extract_dir).await?;
+    let package_owned = package_path.to_path_buf();
+    let extract_owned = extract_dir.to_path_buf();
+    let (index, manifest_text, release_notes, payload_path) = tokio::task::spawn_blocking(move || {
+        let file = std::fs::File::open(&package_owned).context("打开离线更新包失败")?;
+        let mut archive = zip::ZipArchive::new(file).context("离线更新包不是有效的 zip 文件")?;
+        let read_text = |archive: &mut zip::ZipArchive<std::fs::File>, name: &str| -> Result<Option<String>> {
+            match archive.by_name(name) {
+                Ok(mut entry) => {
+                    let mut text = String::new();
+                    std::io::Read::read_to_string(&mut entry, &mut text)?;
+                    Ok(Some(text))
+                }
+                Err(zip::result::ZipError::FileNotFound) => Ok(None),
+                Err(e) => Err(e.into()),
+            }
+        };
+
+        let index: OfflinePackageIndex = serde_json::from_str(
+            &read_text(&mut archive, OFFLINE_PACKAGE_INDEX)?
+                .ok_or_else(|| anyhow::anyhow!("离线更新包缺少 {}", OFFLINE_PACKAGE_INDEX))?,
+        )
+        .context("解析离线更新包索引失败")?;
+        let manifest_text = read_text(&mut archive, OFFLINE_PACKAGE_MANIFEST)?
+            .ok_or_else(|| anyhow::anyhow!("离线更新包缺少 {}", OFFLINE_PACKAGE_MANIFEST))?;
+        let release_notes = read_text(&mut archive, OFFLINE_PACKAGE_NOTES)?;
+
+        // 安装包只能位于包的根目录，防止路径穿越
+        if Path::new(&index.file).file_name().and_then(|n| n.to_str()) != Some(index.file.as_str()) {
+            return Err(anyhow::anyhow!("离线更新包中的安装包文件名无效: {}", index.file));
+        }
+        let payload_path = extract_owned.join(&index.file);
+        let mut entry = archive
+            .by_name(&index.file)
+            .map_err(|_| anyhow::anyhow!("离线更新包缺少安装包 {}", index.file))?;
+        let mut out = std::fs::File::create(&payload_path)?;
+        std::io::copy(&mut entry, &mut out)?;
+        Ok::<_, anyhow::Error>((index, manifest_text, release_notes, payload_path))
+    })
+    .await??;
+
+    // 与在线更新相同的校验：清单签名 → 组件 / 过期时间 → 安装包哈希
+    let info = UpdateInfo {
+        has_update: true,
+        version: None,
+        current_version: String::new(),
+        latest_version: None,
+        release_date: None,
+        release_notes: release_notes.clone(),
+        mandatory: None,
+        min_version: None,
+        file_size: None,
+        file_hash: None,
+        downloads: None,
+        manifest: Some(manifest_text),
+        signature: Some(index.signature),
+        key_id: index.key_id,
+        skipped: false,
+    };
+    let manifest = match verify_update_info_with_keys(&info, component, keys) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            let _ = fs::remove_file(&payload_path).await;
+            return Err(e);
+        }
+    };
+    if let Err(e) = verify_file_against_manifest(&payload_path, &manifest).await {
+        let _ = fs::remove_file(&payload_path).await;
+        return Err(e);
+    }
+
+    info!("离线更新包校验通过: {:?} {}", component, manifest.version);
+    Ok(OfflinePackage {
+        component,
+        version: manifest.version.clone(),
+        release_notes,
+        file_path: payload_path.display().to_string(),
+        manifest,
+    })
 }
 
 // ==================== 辅助函数 ====================
@@ -516,3 +1205,294 @@ pub async fn cleanup_temp_dir() -> Result<()> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::signing::tests::{sign, test_key_pair};
+
+    fn signed_info(manifest: &serde_json::Value, pair: &ring::signature::Ed25519KeyPair, key_id: &str) -> UpdateInfo {
+        let manifest_text = manifest.to_string();
+        UpdateInfo {
+            has_update: true,
+            version: Some(manifest["version"].as_str().unwrap().to_string()),
+            current_version: "1.0.0".to_string(),
+            latest_version: None,
+            release_date: None,
+            release_notes: None,
+            mandatory: None,
+            min_version: None,
+            file_size: None,
+            file_hash: Some(format!("sha256:{}", manifest["file_hash"].as_str().unwrap())),
+            downloads: None,
+            signature: Some(sign(pair, manifest_text.as_bytes())),
+            manifest: Some(manifest_text),
+            key_id: Some(key_id.to_string()),
+            skipped: false,
+        }
+    }
+
+    #[test]
+    fn test_verify_signed_manifest() {
+        let (pair, key) = test_key_pair("2026");
+        let manifest = serde_json::json!({
+            "component": "kernel",
+            "version": "146.0.7680.80",
+            "file_hash": format!("{:x}", Sha256::digest(b"kernel")),
+        });
+        let info = signed_info(&manifest, &pair, "2026");
+        let keys = vec![key];
+
+        let verified = verify_update_info_with_keys(&info, UpdateComponent::Kernel, &keys).unwrap();
+        assert_eq!(verified.version, "146.0.7680.80");
+        assert!(verify_update_info_with_keys(&info, UpdateComponent::Launcher, &keys).is_err());
+
+        // 篡改展示版本、清单内容，或缺少签名
+        let mut tampered = info.clone();
+        tampered.version = Some("999".to_string());
+        assert!(verify_update_info_with_keys(&tampered, UpdateComponent::Kernel, &keys).is_err());
+        let mut tampered = info.clone();
+        tampered.manifest = Some(tampered.manifest.unwrap().replace("146.0.7680.80", "147.0.0.1"));
+        tampered.version = Some("147.0.0.1".to_string());
+        assert!(verify_update_info_with_keys(&tampered, UpdateComponent::Kernel, &keys).is_err());
+        let mut unsigned = info.clone();
+        unsigned.signature = None;
+        assert!(verify_update_info_with_keys(&unsigned, UpdateComponent::Kernel, &keys).is_err());
+
+        // 未知密钥签发
+        let (other_pair, _) = test_key_pair("other");
+        let foreign = signed_info(&manifest, &other_pair, "other");
+        assert!(verify_update_info_with_keys(&foreign, UpdateComponent::Kernel, &keys).is_err());
+    }
+
+    #[test]
+    fn test_expired_manifest_is_rejected() {
+        let (pair, key) = test_key_pair("2026");
+        let manifest = serde_json::json!({
+            "component": "launcher",
+            "version": "2.0.0",
+            "file_hash": "00",
+            "expires_at": "2020-01-01T00:00:00Z",
+        });
+        let info = signed_info(&manifest, &pair, "2026");
+        let err = verify_update_info_with_keys(&info, UpdateComponent::Launcher, &[key]).unwrap_err();
+        assert!(err.to_string().contains("过期"));
+    }
+
+    #[test]
+    fn test_manifest_target_must_match_host() {
+        let mut manifest: UpdateManifest = serde_json::from_value(serde_json::json!({
+            "component": "kernel", "version": "147.0.1", "file_hash": "00",
+        }))
+        .unwrap();
+        assert!(check_manifest_target(&manifest, "windows", "x86_64").is_ok());
+
+        manifest.platform = Some("win64".to_string());
+        manifest.arch = Some("x64".to_string());
+        assert!(check_manifest_target(&manifest, "windows", "x86_64").is_ok());
+        assert!(check_manifest_target(&manifest, "windows", "x86").is_err());
+        manifest.platform = Some("macos".to_string());
+        let err = check_manifest_target(&manifest, "windows", "x86_64").unwrap_err();
+        assert!(err.to_string().contains("平台"));
+    }
+
+    #[tokio::test]
+    async fn test_broken_kernel_package_leaves_no_partial_install() {
+        let base = tempfile::tempdir().unwrap();
+        let zip_path = base.path().join("kernel.zip");
+        std::fs::write(&zip_path, b"PK\x03\x04 truncated").unwrap();
+        let kernel_dir = base.path().join("147.0.1");
+        let info: crate::modules::kernel_downloader::KernelVersionInfo = serde_json::from_value(serde_json::json!({
+            "version": "147.0.1", "build_date": "", "platform": "win64", "source": "remote",
+            "files_count": 0, "total_size_bytes": 0,
+        }))
+        .unwrap();
+
+        let outcome = install_kernel_package(&zip_path, &kernel_dir, &info).await.unwrap();
+        assert_eq!(outcome.status, KernelUpdateStatus::Failed);
+        // 解压失败时不会留下半成品目录（注册表不会把它当作已安装版本）
+        assert!(!kernel_dir.exists());
+        assert!(!base.path().join(".staging-147.0.1").exists());
+        assert!(zip_path.exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_failed_smoke_test_restores_previous_kernel() {
+        use crate::modules::kernel_health::tests::write_fake_kernel;
+
+        let base = tempfile::tempdir().unwrap();
+        let kernel_dir = base.path().join("146.0.7680.80");
+        write_fake_kernel(&kernel_dir, "146.0.7680.80", true);
+        std::fs::write(kernel_dir.join("kernel_version.json"), r#"{"version":"146.0.7680.80"}"#).unwrap();
+
+        // 新内核未应用指纹配置：回滚到旧内核
+        let staging = base.path().join(".staging-a");
+        write_fake_kernel(&staging, "146.0.7680.80", false);
+        let outcome = activate_staged_kernel(&staging, &kernel_dir, "146.0.7680.80").await;
+        assert_eq!(outcome.status, KernelUpdateStatus::RolledBack);
+        assert_eq!(outcome.previous_version.as_deref(), Some("146.0.7680.80"));
+        assert_eq!(installed_kernel_version(&kernel_dir).as_deref(), Some("146.0.7680.80"));
+        assert!(!staging.exists());
+
+        // 正常的新内核：替换成功并清理备份
+        let staging = base.path().join(".staging-b");
+        write_fake_kernel(&staging, "146.0.7680.80", true);
+        let outcome = activate_staged_kernel(&staging, &kernel_dir, "146.0.7680.80").await;
+        assert_eq!(outcome.status, KernelUpdateStatus::Installed);
+        assert!(installed_kernel_version(&kernel_dir).is_none());
+        assert!(!base.path().join(".backup-146.0.7680.80").exists());
+    }
+
+    #[tokio::test]
+    async fn test_file_must_match_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("setup.exe");
+        std::fs::write(&path, b"installer").unwrap();
+        let mut manifest = UpdateManifest {
+            component: UpdateComponent::Launcher,
+            version: "2.0.0".to_string(),
+            file_hash: format!("sha256:{:x}", Sha256::digest(b"installer")),
+            file_size: None,
+            platform: None,
+            arch: None,
+            expires_at: None,
+        };
+        assert!(verify_file_against_manifest(&path, &manifest).await.is_ok());
+        manifest.file_hash = format!("{:x}", Sha256::digest(b"other"));
+        assert!(verify_file_against_manifest(&path, &manifest).await.is_err());
+    }
+
+    #[test]
+    fn test_update_channel_and_skipped_versions() {
+        assert_eq!(UpdateChannel::parse("").unwrap(), UpdateChannel::Stable);
+        assert_eq!(UpdateChannel::parse("beta").unwrap().query(), vec![("channel", "beta")]);
+        let pinned = UpdateChannel::parse("pinned:146.0.7680.80").unwrap();
+        assert_eq!(pinned.query(), vec![("channel", "pinned"), ("pinned_version", "146.0.7680.80")]);
+        assert!(UpdateChannel::parse("pinned:").is_err());
+        assert!(UpdateChannel::parse("nightly").is_err());
+
+        let (pair, _) = test_key_pair("2026");
+        let manifest = serde_json::json!({ "component": "kernel", "version": "147.0.1", "file_hash": "00" });
+        let skipped = parse_skipped_versions("146.0.1, 147.0.1");
+        let info = apply_skipped_versions(signed_info(&manifest, &pair, "2026"), &skipped);
+        assert!(!info.has_update && info.skipped);
+
+        let mut mandatory = signed_info(&manifest, &pair, "2026");
+        mandatory.mandatory = Some(true);
+        assert!(apply_skipped_versions(mandatory, &skipped).has_update);
+    }
+
+    #[test]
+    fn test_deferral_due() {
+        let time = |s: &str| chrono::NaiveTime::parse_from_str(s, "%H:%M").unwrap();
+        let night = InstallWindow::parse("22:00-06:00").unwrap();
+        assert!(night.contains(time("23:30")) && night.contains(time("05:59")));
+        assert!(!night.contains(time("12:00")));
+        assert!(InstallWindow::parse("10:00-10:00").is_err());
+        assert!(InstallWindow::parse("25:00-06:00").is_err());
+
+        let deferral = |component, until| UpdateDeferral {
+            component,
+            version: "2.0.0".to_string(),
+            until,
+            created_at: String::new(),
+        };
+        let closed = deferral(UpdateComponent::Kernel, DeferUntil::BrowsersClosed);
+        assert!(!deferral_due(&closed, 2, None, time("12:00")));
+        assert!(deferral_due(&closed, 0, None, time("12:00")));
+        assert!(!deferral_due(&closed, 0, Some(&night), time("12:00")));
+
+        let launcher = deferral(UpdateComponent::Launcher, DeferUntil::InstallWindow);
+        assert!(deferral_due(&launcher, 3, Some(&night), time("23:00")));
+        let kernel = deferral(UpdateComponent::Kernel, DeferUntil::InstallWindow);
+        assert!(!deferral_due(&kernel, 3, Some(&night), time("23:00")));
+    }
+
+    #[tokio::test]
+    async fn test_update_session_restored_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SESSION_RESTORE_FILE);
+        let mut session = UpdateSession {
+            created_at: chrono::Utc::now().to_rfc3339(),
+            from_version: "1.1.0".to_string(),
+            profiles: vec![SessionProfile {
+                profile_id: "p1".to_string(),
+                bounds: Some(WindowBounds { x: 10, y: 20, width: 1280, height: 800 }),
+            }],
+        };
+        save_update_session(&path, &session).await.unwrap();
+        let restored = take_update_session(&path).await.unwrap();
+        assert_eq!(restored.profiles[0].bounds, session.profiles[0].bounds);
+        assert!(take_update_session(&path).await.is_none());
+
+        // 过期快照不再恢复
+        session.created_at = (chrono::Utc::now() - chrono::Duration::days(2)).to_rfc3339();
+        save_update_session(&path, &session).await.unwrap();
+        assert!(take_update_session(&path).await.is_none());
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_offline_package_validated_like_online_update() {
+        use std::io::Write;
+
+        let (pair, key) = test_key_pair("2026");
+        let dir = tempfile::tempdir().unwrap();
+        let build_package = |name: &str, file: &str, payload: &[u8], signed_payload: &[u8]| {
+            let manifest = serde_json::json!({
+                "component": "kernel",
+                "version": "146.0.7680.80",
+                "file_hash": format!("{:x}", Sha256::digest(signed_payload)),
+            })
+            .to_string();
+            let index = serde_json::json!({
+                "file": file,
+                "signature": sign(&pair, manifest.as_bytes()),
+                "key_id": "2026",
+            });
+            let path = dir.path().join(name);
+            let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+            let options = zip::write::SimpleFileOptions::default();
+            for (entry, content) in [
+                (OFFLINE_PACKAGE_INDEX, index.to_string().into_bytes()),
+                (OFFLINE_PACKAGE_MANIFEST, manifest.into_bytes()),
+                (OFFLINE_PACKAGE_NOTES, b"fixes".to_vec()),
+                ("chromium.zip", payload.to_vec()),
+            ] {
+                zip.start_file(entry, options).unwrap();
+                zip.write_all(&content).unwrap();
+            }
+            zip.finish().unwrap();
+            path
+        };
+        let extract_dir = dir.path().join("offline");
+        let keys = vec![key];
+
+        let good = build_package("good.zip", "chromium.zip", b"kernel", b"kernel");
+        let package = open_offline_package_with_keys(&good, UpdateComponent::Kernel, &extract_dir, &keys)
+            .await
+            .unwrap();
+        assert_eq!(package.version, "146.0.7680.80");
+        assert_eq!(package.release_notes.as_deref(), Some("fixes"));
+        assert!(Path::new(&package.file_path).exists());
+        assert!(open_offline_package_with_keys(&good, UpdateComponent::Launcher, &extract_dir, &keys)
+            .await
+            .is_err());
+        assert!(open_offline_package_with_keys(&good, UpdateComponent::Kernel, &extract_dir, &[])
+            .await
+            .is_err());
+
+        // 安装包被替换 / 文件名带路径时拒绝
+        let tampered = build_package("tampered.zip", "chromium.zip", b"evil", b"kernel");
+        let err = open_offline_package_with_keys(&tampered, UpdateComponent::Kernel, &extract_dir, &keys)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("不一致"));
+        assert!(!extract_dir.join("chromium.zip").exists());
+        let traversal = build_package("traversal.zip", "../chromium.zip", b"kernel", b"kernel");
+        assert!(open_offline_package_with_keys(&traversal, UpdateComponent::Kernel, &extract_dir, &keys)
+            .await
+            .is_err());
+    }
+}
@@ -1,8 +1,11 @@
 use anyhow::{Result, Context};
 use std::process::{Command, Child};
 use std::path::PathBuf;
+use std::time::Duration;
 use tracing::info;
 
+use crate::modules::browser_manager::{shutdown_child_blocking, ShutdownOutcome};
+
 /// 浏览器启动器
 /// 
 /// 遵循方案A规范：
@@ -82,18 +85,16 @@ impl BrowserLauncher {
     }
 
     /// 停止浏览器实例
-    pub fn stop(&self, mut child: Child) -> Result<()> {
+    ///
+    /// 先请求正常关闭（Unix: SIGTERM，Windows: WM_CLOSE），超时后再强制终止
+    pub fn stop(&self, mut child: Child, timeout: Duration) -> ShutdownOutcome {
         info!("Stopping browser with PID: {:?}", child.id());
         
-        child.kill()
-            .context("Failed to kill browser process")?;
-        
-        child.wait()
-            .context("Failed to wait for browser process")?;
+        let outcome = shutdown_child_blocking(&mut child, timeout);
         
-        info!("Browser stopped successfully");
+        info!("Browser stopped: {}", outcome.as_str());
         
-        Ok(())
+        outcome
     }
 }
 
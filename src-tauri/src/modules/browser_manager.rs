@@ -27,10 +27,20 @@ pub struct ProcessInfo {
     pub status: ProcessStatus,
     pub started_at: DateTime<Utc>,
     pub stopped_at: Option<DateTime<Utc>>,
-    pub exit_reason: Option<String>, // 退出原因：manual_stop, crashed, normal_exit
+    pub exit_reason: Option<String>, // 退出原因：graceful_shutdown, forced_kill, crashed, normal_exit
     pub child: Option<Child>,
 }
 
+/// 异常退出的浏览器进程（由进程监控发现，等待崩溃处理）
+#[derive(Debug, Clone)]
+pub struct CrashedProcess {
+    pub profile_id: String,
+    pub exit_code: Option<i32>,
+    pub exit_reason: String,
+    pub started_at: DateTime<Utc>,
+    pub stopped_at: DateTime<Utc>,
+}
+
 /// Profile 状态变化事件
 #[derive(Debug, Clone, Serialize)]
 pub struct ProfileStatusChangedEvent {
@@ -127,9 +137,92 @@ impl EventEmitter {
     }
 }
 
+/// 等待浏览器正常退出的超时设置项（秒）
+pub const SHUTDOWN_TIMEOUT_SETTING_KEY: &str = "browser_shutdown_timeout_secs";
+
+/// 默认等待正常退出的时间（秒）
+pub const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 10;
+
+/// 等待正常退出的最长时间（秒）
+pub const MAX_SHUTDOWN_TIMEOUT_SECS: u64 = 300;
+
+/// 浏览器关闭结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownOutcome {
+    /// 收到关闭请求后自行退出
+    Graceful,
+    /// 超时未退出，已强制终止
+    Killed,
+}
+
+impl ShutdownOutcome {
+    /// 记录到 `ProcessInfo.exit_reason` 的值
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ShutdownOutcome::Graceful => "graceful_shutdown",
+            ShutdownOutcome::Killed => "forced_kill",
+        }
+    }
+}
+
+/// 请求浏览器正常关闭：Unix 发送 SIGTERM，Windows 向主窗口发送 WM_CLOSE
+pub fn request_close(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) == 0 }
+    }
+    #[cfg(windows)]
+    {
+        super::window_helper::close_windows_for_pid(pid) > 0
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = pid;
+        false
+    }
+}
+
+/// 先请求正常关闭并等待退出，超时后再强制终止（阻塞当前线程）
+///
+/// Chromium 只有正常退出时才会把 Cookie 与会话写回磁盘，直接 kill 可能损坏 Cookie 数据库
+pub fn shutdown_child_blocking(child: &mut Child, timeout: std::time::Duration) -> ShutdownOutcome {
+    let pid = child.id();
+    if !request_close(pid) {
+        warn!(pid = pid, "发送关闭请求失败，等待超时后强制终止");
+    }
+
+    let deadline = std::time::Instant::now() + timeout;
+    while std::time::Instant::now() < deadline {
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                info!(pid = pid, exit_code = ?status.code(), "浏览器进程已正常退出");
+                return ShutdownOutcome::Graceful;
+            }
+            Ok(None) => std::thread::sleep(std::time::Duration::from_millis(100)),
+            Err(e) => {
+                warn!(pid = pid, error = %e, "等待进程退出失败");
+                break;
+            }
+        }
+    }
+    warn!(pid = pid, timeout_secs = timeout.as_secs(), "进程未在超时内退出，执行强制终止");
+    let _ = child.kill();
+    let _ = child.wait();
+    ShutdownOutcome::Killed
+}
+
+/// `shutdown_child_blocking` 的异步版本
+pub async fn shutdown_child(mut child: Child, timeout: std::time::Duration) -> ShutdownOutcome {
+    tokio::task::spawn_blocking(move || shutdown_child_blocking(&mut child, timeout))
+        .await
+        .unwrap_or(ShutdownOutcome::Killed)
+}
+
 /// 浏览器管理器
 pub struct BrowserManager {
     processes: Arc<Mutex<HashMap<String, ProcessInfo>>>,
+    /// 待处理的崩溃
+    crashes: Arc<Mutex<Vec<CrashedProcess>>>,
     /// 限制同时启动的浏览器数量（防止系统卡死）
     launch_semaphore: Arc<Semaphore>,
     event_emitter: Arc<EventEmitter>,
@@ -139,17 +232,18 @@ impl BrowserManager {
     pub fn new(app_handle: tauri::AppHandle) -> Self {
         Self {
             processes: Arc::new(Mutex::new(HashMap::new())),
+            crashes: Arc::new(Mutex::new(Vec::new())),
             // 最多同时启动 3 个浏览器
             launch_semaphore: Arc::new(Semaphore::new(3)),
             event_emitter: Arc::new(EventEmitter::new(app_handle)),
         }
     }
     
-    /// 检查进程是否已在运行
+    /// 检查进程是否已在运行（正在关闭的进程也算运行中，避免同一环境同时启动第二个进程）
     pub async fn is_running(&self, profile_id: &str) -> bool {
         let processes = self.processes.lock().await;
         if let Some(info) = processes.get(profile_id) {
-            matches!(info.status, ProcessStatus::Running | ProcessStatus::Starting)
+            matches!(info.status, ProcessStatus::Running | ProcessStatus::Starting | ProcessStatus::Stopping)
         } else {
             false
         }
@@ -180,22 +274,44 @@ impl BrowserManager {
         Ok(())
     }
     
-    /// 注销进程（停止时调用）
-    pub async fn unregister_process(&self, profile_id: &str) -> Result<Option<Child>, String> {
+    /// 停止进程：先请求正常关闭，超时后强制终止，结果记录在 exit_reason
+    ///
+    /// 已停止的进程信息保留在表中，便于查询最近一次的退出原因
+    pub async fn stop_process(&self, profile_id: &str, timeout: std::time::Duration) -> Option<ShutdownOutcome> {
+        let child = {
+            let mut processes = self.processes.lock().await;
+            let info = processes.get_mut(profile_id)?;
+            info.status = ProcessStatus::Stopping;
+            info.child.take()
+        };
+
+        // 等待期间不持有锁，进程监控会跳过已取走 child 的记录
+        let outcome = match child {
+            Some(child) => Some(shutdown_child(child, timeout).await),
+            None => None,
+        };
+
         let mut processes = self.processes.lock().await;
-        
-        if let Some(mut info) = processes.remove(profile_id) {
+        if let Some(info) = processes.get_mut(profile_id) {
             info.status = ProcessStatus::Stopped;
             info.stopped_at = Some(Utc::now());
-            info.exit_reason = Some("manual_stop".to_string());
-            
-            // 发送状态变化事件
-            self.event_emitter.emit_status_changed(profile_id.to_string(), "stopped");
-            
-            Ok(info.child)
-        } else {
-            Ok(None)
+            // 没有 child 时进程已由监控处理或已停止，保留原有的退出原因
+            match outcome {
+                Some(outcome) => info.exit_reason = Some(outcome.as_str().to_string()),
+                None if info.exit_reason.is_none() => info.exit_reason = Some("normal_exit".to_string()),
+                None => {}
+            }
         }
+
+        // 发送状态变化事件
+        self.event_emitter.emit_status_changed(profile_id.to_string(), "stopped");
+        outcome
+    }
+
+    /// 最近一次退出原因
+    pub async fn exit_reason(&self, profile_id: &str) -> Option<String> {
+        let processes = self.processes.lock().await;
+        processes.get(profile_id).and_then(|info| info.exit_reason.clone())
     }
     
     /// 更新进程状态
@@ -251,6 +367,11 @@ impl BrowserManager {
             .collect()
     }
     
+    /// 取出待处理的崩溃
+    pub async fn take_crashes(&self) -> Vec<CrashedProcess> {
+        std::mem::take(&mut *self.crashes.lock().await)
+    }
+    
     /// 发送错误事件
     pub fn emit_error(&self, profile_id: String, error: String) {
         self.event_emitter.emit_browser_error(profile_id, error);
@@ -284,7 +405,6 @@ pub async fn start_process_monitor(
             ticker.tick().await;
             
             let mut processes = manager.processes.lock().await;
-            let mut to_remove = Vec::new();
             
             for (profile_id, info) in processes.iter_mut() {
                 if let Some(child) = &mut info.child {
@@ -310,21 +430,22 @@ pub async fn start_process_monitor(
                                 "浏览器进程已退出"
                             );
                             
-                            // 记录退出信息
+                            // 记录退出信息（保留记录，便于查询退出原因）
+                            let stopped_at = Utc::now();
                             info.status = ProcessStatus::Stopped;
-                            info.stopped_at = Some(Utc::now());
+                            info.stopped_at = Some(stopped_at);
                             info.exit_reason = Some(exit_reason.clone());
                             info.child = None;
                             
                             // 更新数据库状态
                             let profile_id_clone = profile_id.clone();
                             let pool_clone = Arc::clone(&pool);
-                            let stopped_at = Utc::now().to_rfc3339();
+                            let updated_at = stopped_at.to_rfc3339();
                             tokio::spawn(async move {
                                 let _ = sqlx::query(
                                     "UPDATE profiles SET status = 'stopped', updated_at = ? WHERE id = ?"
                                 )
-                                .bind(&stopped_at)
+                                .bind(&updated_at)
                                 .bind(&profile_id_clone)
                                 .execute(&*pool_clone)
                                 .await;
@@ -336,15 +457,20 @@ pub async fn start_process_monitor(
                                 "stopped"
                             );
                             
-                            // 如果是崩溃，发送错误事件
+                            // 如果是崩溃，发送错误事件并交给崩溃处理（记录 / 自动重启）
                             if exit_reason.starts_with("crashed") {
                                 manager.event_emitter.emit_browser_error(
                                     profile_id.clone(),
                                     format!("浏览器异常退出: {}", exit_reason)
                                 );
+                                manager.crashes.lock().await.push(CrashedProcess {
+                                    profile_id: profile_id.clone(),
+                                    exit_code,
+                                    exit_reason: exit_reason.clone(),
+                                    started_at: info.started_at,
+                                    stopped_at,
+                                });
                             }
-                            
-                            to_remove.push(profile_id.clone());
                         }
                         Ok(None) => {
                             // 进程仍在运行
@@ -356,11 +482,27 @@ pub async fn start_process_monitor(
                     }
                 }
             }
-            
-            // 清理已退出的进程
-            for profile_id in to_remove {
-                processes.remove(&profile_id);
-            }
         }
     });
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::process::Command;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_shutdown_child_prefers_graceful_exit() {
+        let child = Command::new("sleep").arg("30").spawn().unwrap();
+        assert_eq!(shutdown_child(child, Duration::from_secs(5)).await, ShutdownOutcome::Graceful);
+
+        // 忽略 SIGTERM 的进程在超时后被强制终止
+        let child = Command::new("sh")
+            .args(["-c", "trap '' TERM; sleep 2"])
+            .spawn()
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(shutdown_child(child, Duration::from_millis(300)).await, ShutdownOutcome::Killed);
+    }
+}
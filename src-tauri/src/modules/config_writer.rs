@@ -1,6 +1,7 @@
 // 配置文件写入器 - 生成 bm_fingerprint.json 和 bm_cloud.json
 // 格式与 Chromium 内核 fingerprint_browser 模块兼容
 
+use crate::modules::fingerprint::timezone::TimezoneResolver;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 use std::fs;
@@ -78,14 +79,31 @@ impl Default for KernelResolutionConfig {
 pub struct KernelTimeZoneConfig {
     #[serde(rename = "type")]
     pub config_type: i32,
+    /// 当前 UTC 偏移，如 "GMT+08:00"（按 IANA 时区的夏令时规则计算）
     pub gmt: String,
+    /// IANA 时区名称，如 "Asia/Shanghai"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zone: Option<String>,
+}
+
+impl KernelTimeZoneConfig {
+    /// 根据 IANA 时区名称生成配置，未知时区返回 None
+    pub fn from_zone(zone: &str) -> Option<Self> {
+        let gmt = TimezoneResolver::current_gmt(zone)?;
+        Some(Self {
+            config_type: 2,
+            gmt,
+            zone: Some(zone.trim().to_string()),
+        })
+    }
 }
 
 impl Default for KernelTimeZoneConfig {
     fn default() -> Self {
         Self {
             config_type: 2,
-            gmt: "Asia/Shanghai".to_string(),
+            gmt: "GMT+08:00".to_string(),
+            zone: Some("Asia/Shanghai".to_string()),
         }
     }
 }
@@ -540,6 +558,12 @@ impl Default for KernelWebGpuConfig {
 pub struct FingerprintFileConfig {
     pub init: i32,
     pub seed: String,
+    /// 生成该指纹所用的设备模板 ID（仅用于追溯，内核不读取）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template_id: Option<String>,
+    /// 生成时设备模板的修订号
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template_revision: Option<u32>,
     pub device: KernelDeviceConfig,
     pub ua: KernelUaConfig,
     #[serde(rename = "resourceInfo")]
@@ -576,6 +600,8 @@ impl Default for FingerprintFileConfig {
         Self {
             init: 2,
             seed: "12345678901234567890".to_string(),
+            template_id: None,
+            template_revision: None,
             device: KernelDeviceConfig::default(),
             ua: KernelUaConfig::default(),
             resource_info: KernelResourceInfoConfig::default(),
@@ -1112,7 +1138,7 @@ impl ConfigWriter {
         fingerprint: &crate::modules::profile::Fingerprint,
     ) -> Result<(), String> {
         // 1. 生成指纹配置
-        let fp_config = Self::build_fingerprint_config(profile_id, fingerprint);
+        let fp_config = Self::build_fingerprint_config(profile_id, fingerprint)?;
         Self::write_fingerprint_config(user_data_dir, &fp_config)?;
         
         // 2. 生成云端配置
@@ -1123,16 +1149,22 @@ impl ConfigWriter {
     }
     
     /// 从 Profile Fingerprint 构建完整的配置文件 - 匹配内核格式
-    fn build_fingerprint_config(
+    ///
+    /// 时区无法解析时报错（与指纹生成一致），不会回退到看似合理的默认时区
+    pub fn build_fingerprint_config(
         profile_id: &str,
         fp: &crate::modules::profile::Fingerprint,
-    ) -> FingerprintFileConfig {
+    ) -> Result<FingerprintFileConfig, String> {
         // 解析屏幕分辨率
         let (width, height) = Self::parse_screen_resolution(&fp.screen_resolution);
+        let time_zone = KernelTimeZoneConfig::from_zone(&fp.timezone)
+            .ok_or_else(|| format!("无法解析时区: {}", fp.timezone))?;
         
-        FingerprintFileConfig {
+        Ok(FingerprintFileConfig {
             init: 2,
-            seed: profile_id.to_string(),
+            seed: fp.launch_seed.clone().unwrap_or_else(|| profile_id.to_string()),
+            template_id: fp.template_id.clone(),
+            template_revision: fp.template_revision,
             
             device: KernelDeviceConfig {
                 config_type: 2,
@@ -1162,10 +1194,7 @@ impl ConfigWriter {
                 avail_height: height.saturating_sub(40),
             },
             
-            time_zone: KernelTimeZoneConfig {
-                config_type: 2,
-                gmt: fp.timezone.clone(),
-            },
+            time_zone,
             
             language: KernelLanguageConfig {
                 config_type: 2,
@@ -1219,7 +1248,7 @@ impl ConfigWriter {
             },
             open_port: KernelOpenPortConfig::default(),
             webgpu: KernelWebGpuConfig::default(),
-        }
+        })
     }
     
     /// 解析屏幕分辨率字符串
@@ -1243,17 +1272,4 @@ impl ConfigWriter {
             _ => "Win32".to_string(),
         }
     }
-    
-    /// 时区转偏移量（分钟）
-    fn timezone_to_offset(timezone: &str) -> i32 {
-        match timezone {
-            "Asia/Shanghai" | "Asia/Hong_Kong" | "Asia/Taipei" => -480,
-            "Asia/Tokyo" => -540,
-            "America/New_York" => 300,
-            "America/Los_Angeles" => 480,
-            "Europe/London" => 0,
-            "Europe/Paris" | "Europe/Berlin" => -60,
-            _ => -480, // 默认东八区
-        }
-    }
 }
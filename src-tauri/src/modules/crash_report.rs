@@ -0,0 +1,499 @@
+// Crash Report - 浏览器崩溃记录与自动重启策略
+// 进程监控发现异常退出后，收集用户数据目录中的 chrome_debug.log 与崩溃转储写入崩溃记录，
+// 按环境的重启策略（不重启 / 崩溃后重启，含最大重试次数与指数退避）决定是否自动重启；
+// 短时间内反复崩溃视为崩溃循环，自动停用重启并通知用户
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use uuid::Uuid;
+
+use super::browser_manager::CrashedProcess;
+
+/// 崩溃处理间隔（秒，与进程监控一致）
+pub const CRASH_CHECK_INTERVAL_SECS: u64 = 5;
+
+/// 崩溃记录事件
+pub const BROWSER_CRASHED_EVENT: &str = "browser:crashed";
+
+/// 崩溃循环事件（已停用自动重启）
+pub const CRASH_LOOP_EVENT: &str = "browser:crash-loop";
+
+/// 统计连续崩溃的时间窗口（秒）
+pub const CRASH_LOOP_WINDOW_SECS: i64 = 600;
+
+/// 重启退避的最长等待时间（秒）
+pub const MAX_BACKOFF_SECS: u64 = 300;
+
+/// 最多重试次数上限
+pub const MAX_RESTART_RETRIES: u32 = 20;
+
+/// 内核日志文件名（--enable-logging 写入用户数据目录）
+const KERNEL_LOG_FILE: &str = "chrome_debug.log";
+
+/// 崩溃记录保存的日志末尾长度（字节）
+const LOG_TAIL_BYTES: u64 = 16 * 1024;
+
+/// 崩溃转储所在目录（相对用户数据目录）
+const CRASH_DUMP_DIRS: &[&str] = &[
+    "Crashpad/reports",
+    "Crashpad/pending",
+    "Crashpad/completed",
+    "Crashpad/new",
+    "Crash Reports",
+];
+
+/// 重启模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestartMode {
+    /// 不自动重启
+    #[default]
+    Never,
+    /// 崩溃后自动重启
+    OnCrash,
+}
+
+impl RestartMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RestartMode::Never => "never",
+            RestartMode::OnCrash => "on_crash",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "on_crash" => RestartMode::OnCrash,
+            _ => RestartMode::Never,
+        }
+    }
+}
+
+/// 环境的重启策略
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RestartPolicy {
+    pub mode: RestartMode,
+    /// 时间窗口内最多自动重启次数，超过视为崩溃循环
+    pub max_retries: u32,
+    /// 首次重启前的等待时间（秒），之后每次翻倍
+    pub backoff_secs: u64,
+    /// 因崩溃循环被自动停用的原因（重新设置策略时清除）
+    #[serde(default)]
+    pub disabled_reason: Option<String>,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            mode: RestartMode::Never,
+            max_retries: 3,
+            backoff_secs: 5,
+            disabled_reason: None,
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// 第 attempt 次重启前的等待时间（指数退避）
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+        Duration::from_secs(self.backoff_secs.saturating_mul(factor).min(MAX_BACKOFF_SECS))
+    }
+}
+
+/// 崩溃后的处理
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestartDecision {
+    /// 未启用自动重启
+    None,
+    /// 等待后重启（第 attempt 次）
+    Restart { attempt: u32, delay: Duration },
+    /// 崩溃循环，停用自动重启
+    CrashLoop { crashes: u32 },
+}
+
+impl RestartDecision {
+    fn action(&self) -> &'static str {
+        match self {
+            RestartDecision::None => "none",
+            RestartDecision::Restart { .. } => "restart",
+            RestartDecision::CrashLoop { .. } => "crash_loop",
+        }
+    }
+
+    fn attempt(&self) -> u32 {
+        match self {
+            RestartDecision::Restart { attempt, .. } => *attempt,
+            RestartDecision::CrashLoop { crashes } => *crashes,
+            RestartDecision::None => 0,
+        }
+    }
+}
+
+/// 根据策略与时间窗口内的崩溃次数（含本次）决定是否重启
+pub fn restart_decision(policy: &RestartPolicy, recent_crashes: u32) -> RestartDecision {
+    if policy.mode != RestartMode::OnCrash || policy.disabled_reason.is_some() {
+        return RestartDecision::None;
+    }
+    if recent_crashes > policy.max_retries {
+        return RestartDecision::CrashLoop { crashes: recent_crashes };
+    }
+    let attempt = recent_crashes.max(1);
+    RestartDecision::Restart { attempt, delay: policy.backoff(attempt) }
+}
+
+/// 从用户数据目录收集的崩溃现场
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CrashArtifacts {
+    pub log_path: Option<String>,
+    pub log_tail: Option<String>,
+    pub dump_paths: Vec<String>,
+}
+
+/// 收集内核日志末尾与本次运行期间产生的崩溃转储
+pub fn collect_crash_artifacts(user_data_dir: &Path, since: DateTime<Utc>) -> CrashArtifacts {
+    let log_path = user_data_dir.join(KERNEL_LOG_FILE);
+    let log_tail = read_log_tail(&log_path);
+
+    let since: std::time::SystemTime = since.into();
+    let mut dump_paths: Vec<PathBuf> = CRASH_DUMP_DIRS
+        .iter()
+        .filter_map(|dir| std::fs::read_dir(user_data_dir.join(dir)).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let path = entry.path();
+            path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("dmp"))
+                && entry
+                    .metadata()
+                    .and_then(|m| m.modified())
+                    .map(|modified| modified >= since)
+                    .unwrap_or(false)
+        })
+        .map(|entry| entry.path())
+        .collect();
+    dump_paths.sort();
+
+    CrashArtifacts {
+        log_path: log_tail.as_ref().map(|_| log_path.display().to_string()),
+        log_tail,
+        dump_paths: dump_paths.iter().map(|p| p.display().to_string()).collect(),
+    }
+}
+
+/// 读取日志末尾（从完整的一行开始）
+fn read_log_tail(path: &Path) -> Option<String> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let mut file = std::fs::File::open(path).ok()?;
+    let len = file.metadata().ok()?.len();
+    let start = len.saturating_sub(LOG_TAIL_BYTES);
+    file.seek(SeekFrom::Start(start)).ok()?;
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf).ok()?;
+    let text = String::from_utf8_lossy(&buf);
+    let text = if start > 0 {
+        text.split_once('\n').map(|(_, rest)| rest).unwrap_or(&text)
+    } else {
+        &text
+    };
+    Some(text.to_string())
+}
+
+/// 崩溃记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashRecord {
+    pub id: String,
+    pub profile_id: String,
+    pub exit_code: Option<i32>,
+    pub exit_reason: String,
+    pub started_at: String,
+    pub crashed_at: String,
+    pub log_path: Option<String>,
+    pub log_tail: Option<String>,
+    pub dump_paths: Vec<String>,
+    /// 处理方式：none / restart / crash_loop
+    pub action: String,
+    /// 重启次数（崩溃循环时为窗口内的崩溃次数）
+    pub attempt: u32,
+}
+
+/// 崩溃记录与重启策略
+pub struct CrashReportService {
+    pool: SqlitePool,
+}
+
+impl CrashReportService {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// 获取环境的重启策略（未设置时为不重启）
+    pub async fn get_policy(&self, profile_id: &str) -> Result<RestartPolicy> {
+        let row = sqlx::query(
+            "SELECT mode, max_retries, backoff_secs, disabled_reason FROM profile_restart_policies WHERE profile_id = ?",
+        )
+        .bind(profile_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some(row) = row else {
+            return Ok(RestartPolicy::default());
+        };
+        Ok(RestartPolicy {
+            mode: RestartMode::parse(&row.try_get::<String, _>("mode")?),
+            max_retries: row.try_get::<i64, _>("max_retries")? as u32,
+            backoff_secs: row.try_get::<i64, _>("backoff_secs")? as u64,
+            disabled_reason: row.try_get("disabled_reason")?,
+        })
+    }
+
+    /// 设置环境的重启策略（同时清除崩溃循环停用状态，崩溃计数从此刻重新开始）
+    pub async fn set_policy(&self, profile_id: &str, policy: &RestartPolicy) -> Result<()> {
+        if policy.max_retries == 0 || policy.max_retries > MAX_RESTART_RETRIES {
+            return Err(anyhow!("最大重试次数必须是 1-{}", MAX_RESTART_RETRIES));
+        }
+        if policy.backoff_secs > MAX_BACKOFF_SECS {
+            return Err(anyhow!("重启等待时间不能超过 {} 秒", MAX_BACKOFF_SECS));
+        }
+        self.save_policy(profile_id, policy.mode, policy.max_retries, policy.backoff_secs, None)
+            .await
+    }
+
+    async fn save_policy(
+        &self,
+        profile_id: &str,
+        mode: RestartMode,
+        max_retries: u32,
+        backoff_secs: u64,
+        disabled_reason: Option<&str>,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO profile_restart_policies (profile_id, mode, max_retries, backoff_secs, disabled_reason, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT(profile_id) DO UPDATE SET
+                mode = excluded.mode,
+                max_retries = excluded.max_retries,
+                backoff_secs = excluded.backoff_secs,
+                disabled_reason = excluded.disabled_reason,
+                updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(profile_id)
+        .bind(mode.as_str())
+        .bind(max_retries as i64)
+        .bind(backoff_secs as i64)
+        .bind(disabled_reason)
+        .bind(Utc::now().to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// 记录崩溃并决定后续处理；崩溃循环时停用该环境的自动重启
+    ///
+    /// 只统计时间窗口内、且在策略最近一次设置之后的崩溃，重新启用自动重启后不会立即再次判定为崩溃循环
+    pub async fn record_crash(
+        &self,
+        crash: &CrashedProcess,
+        artifacts: CrashArtifacts,
+    ) -> Result<(CrashRecord, RestartDecision)> {
+        let policy = self.get_policy(&crash.profile_id).await?;
+        let policy_updated_at: Option<String> =
+            sqlx::query_scalar("SELECT updated_at FROM profile_restart_policies WHERE profile_id = ?")
+                .bind(&crash.profile_id)
+                .fetch_optional(&self.pool)
+                .await?;
+        let policy_updated_at = policy_updated_at
+            .and_then(|value| DateTime::parse_from_rfc3339(&value).ok())
+            .map(|value| value.with_timezone(&Utc));
+        let window_start = (crash.stopped_at - chrono::Duration::seconds(CRASH_LOOP_WINDOW_SECS))
+            .max(policy_updated_at.unwrap_or(DateTime::<Utc>::MIN_UTC))
+            .to_rfc3339();
+        let previous: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM browser_crashes WHERE profile_id = ? AND crashed_at >= ?",
+        )
+        .bind(&crash.profile_id)
+        .bind(&window_start)
+        .fetch_one(&self.pool)
+        .await?;
+
+        let decision = restart_decision(&policy, previous as u32 + 1);
+        if let RestartDecision::CrashLoop { crashes } = decision {
+            let reason = format!("{} 分钟内崩溃 {} 次", CRASH_LOOP_WINDOW_SECS / 60, crashes);
+            self.save_policy(
+                &crash.profile_id,
+                policy.mode,
+                policy.max_retries,
+                policy.backoff_secs,
+                Some(&reason),
+            )
+            .await?;
+        }
+
+        let record = CrashRecord {
+            id: Uuid::new_v4().to_string(),
+            profile_id: crash.profile_id.clone(),
+            exit_code: crash.exit_code,
+            exit_reason: crash.exit_reason.clone(),
+            started_at: crash.started_at.to_rfc3339(),
+            crashed_at: crash.stopped_at.to_rfc3339(),
+            log_path: artifacts.log_path,
+            log_tail: artifacts.log_tail,
+            dump_paths: artifacts.dump_paths,
+            action: decision.action().to_string(),
+            attempt: decision.attempt(),
+        };
+        sqlx::query(
+            r#"
+            INSERT INTO browser_crashes
+                (id, profile_id, exit_code, exit_reason, started_at, crashed_at, log_path, log_tail, dump_paths, action, attempt)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&record.id)
+        .bind(&record.profile_id)
+        .bind(record.exit_code)
+        .bind(&record.exit_reason)
+        .bind(&record.started_at)
+        .bind(&record.crashed_at)
+        .bind(&record.log_path)
+        .bind(&record.log_tail)
+        .bind(serde_json::to_string(&record.dump_paths)?)
+        .bind(&record.action)
+        .bind(record.attempt as i64)
+        .execute(&self.pool)
+        .await?;
+
+        Ok((record, decision))
+    }
+
+    /// 崩溃历史（最新在前），可按环境过滤
+    pub async fn crash_history(&self, profile_id: Option<&str>, limit: u32) -> Result<Vec<CrashRecord>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, profile_id, exit_code, exit_reason, started_at, crashed_at, log_path, log_tail, dump_paths, action, attempt
+            FROM browser_crashes
+            WHERE (?1 IS NULL OR profile_id = ?1)
+            ORDER BY crashed_at DESC
+            LIMIT ?2
+            "#,
+        )
+        .bind(profile_id)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                let dump_paths: String = row.try_get("dump_paths")?;
+                Ok(CrashRecord {
+                    id: row.try_get("id")?,
+                    profile_id: row.try_get("profile_id")?,
+                    exit_code: row.try_get("exit_code")?,
+                    exit_reason: row.try_get("exit_reason")?,
+                    started_at: row.try_get("started_at")?,
+                    crashed_at: row.try_get("crashed_at")?,
+                    log_path: row.try_get("log_path")?,
+                    log_tail: row.try_get("log_tail")?,
+                    dump_paths: serde_json::from_str(&dump_paths).unwrap_or_default(),
+                    action: row.try_get("action")?,
+                    attempt: row.try_get::<i64, _>("attempt")? as u32,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restart_decision_with_backoff_and_crash_loop() {
+        let policy = RestartPolicy {
+            mode: RestartMode::OnCrash,
+            max_retries: 3,
+            backoff_secs: 5,
+            disabled_reason: None,
+        };
+        assert_eq!(
+            restart_decision(&policy, 1),
+            RestartDecision::Restart { attempt: 1, delay: Duration::from_secs(5) }
+        );
+        assert_eq!(
+            restart_decision(&policy, 3),
+            RestartDecision::Restart { attempt: 3, delay: Duration::from_secs(20) }
+        );
+        assert_eq!(restart_decision(&policy, 4), RestartDecision::CrashLoop { crashes: 4 });
+        assert_eq!(policy.backoff(30), Duration::from_secs(MAX_BACKOFF_SECS));
+
+        assert_eq!(restart_decision(&RestartPolicy::default(), 1), RestartDecision::None);
+        let disabled = RestartPolicy { disabled_reason: Some("crash loop".to_string()), ..policy };
+        assert_eq!(restart_decision(&disabled, 1), RestartDecision::None);
+    }
+
+    #[tokio::test]
+    async fn test_reenabled_policy_resets_crash_window() {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::query("CREATE TABLE profiles (id TEXT PRIMARY KEY)").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO profiles (id) VALUES ('p1')").execute(&pool).await.unwrap();
+        sqlx::raw_sql(include_str!("../../migrations/015_add_browser_crashes.sql"))
+            .execute(&pool)
+            .await
+            .unwrap();
+        let service = CrashReportService::new(pool);
+        let policy = RestartPolicy { mode: RestartMode::OnCrash, max_retries: 2, ..RestartPolicy::default() };
+        service.set_policy("p1", &policy).await.unwrap();
+
+        let crash = || CrashedProcess {
+            profile_id: "p1".to_string(),
+            exit_code: Some(1),
+            exit_reason: "crashed".to_string(),
+            started_at: Utc::now(),
+            stopped_at: Utc::now(),
+        };
+        let mut decisions = Vec::new();
+        for _ in 0..3 {
+            decisions.push(service.record_crash(&crash(), CrashArtifacts::default()).await.unwrap().1);
+        }
+        assert_eq!(decisions[2], RestartDecision::CrashLoop { crashes: 3 });
+        assert!(service.get_policy("p1").await.unwrap().disabled_reason.is_some());
+
+        // 重新启用后，之前窗口内的崩溃不再计入
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        service.set_policy("p1", &policy).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        let (_, decision) = service.record_crash(&crash(), CrashArtifacts::default()).await.unwrap();
+        assert!(matches!(decision, RestartDecision::Restart { attempt: 1, .. }));
+    }
+
+    #[test]
+    fn test_collect_crash_artifacts() {
+        let dir = tempfile::tempdir().unwrap();
+        let started_at = Utc::now() - chrono::Duration::minutes(1);
+        assert_eq!(collect_crash_artifacts(dir.path(), started_at), CrashArtifacts::default());
+
+        let long_line = "x".repeat(LOG_TAIL_BYTES as usize);
+        std::fs::write(dir.path().join(KERNEL_LOG_FILE), format!("{}\n[FATAL] renderer crashed\n", long_line)).unwrap();
+        let reports = dir.path().join("Crashpad/reports");
+        std::fs::create_dir_all(&reports).unwrap();
+        std::fs::write(reports.join("a.dmp"), b"dump").unwrap();
+        std::fs::write(reports.join("a.meta"), b"meta").unwrap();
+
+        let artifacts = collect_crash_artifacts(dir.path(), started_at);
+        assert_eq!(artifacts.log_tail.as_deref(), Some("[FATAL] renderer crashed\n"));
+        assert_eq!(artifacts.dump_paths, vec![reports.join("a.dmp").display().to_string()]);
+
+        // 本次启动之前的转储不计入
+        let later = Utc::now() + chrono::Duration::minutes(1);
+        assert!(collect_crash_artifacts(dir.path(), later).dump_paths.is_empty());
+    }
+}
@@ -29,6 +29,7 @@ use crate::modules::config_writer::{
     KernelOpenPortConfig,
     KernelWebGpuConfig,
 };
+use crate::modules::profile::Fingerprint;
 use rand::{SeedableRng};
 use rand::rngs::StdRng;
 use std::path::Path;
@@ -45,6 +46,14 @@ impl FingerprintGenerator {
     /// * `template_path` - 设备模板文件路径
     pub fn new<P: AsRef<Path>>(template_path: P) -> Result<Self, String> {
         let template_manager = TemplateManager::load_from_file(template_path)?;
+        Self::with_templates(template_manager)
+    }
+    
+    /// 使用已加载的模板管理器创建指纹生成器
+    pub fn with_templates(template_manager: TemplateManager) -> Result<Self, String> {
+        if !template_manager.has_available_templates() {
+            return Err("没有可用的设备模板（全部被禁用或权重为 0）".to_string());
+        }
         Ok(Self { template_manager })
     }
     
@@ -58,19 +67,97 @@ impl FingerprintGenerator {
     /// # Returns
     /// 完整的指纹配置，可直接写入 bm_fingerprint.json
     pub fn generate(&self, profile_id: &str, platform: Option<&str>, browser_version: Option<&str>) -> FingerprintFileConfig {
+        self.generate_with_template(profile_id, None, platform, browser_version)
+            .expect("随机选择模板且使用内置时区，不会失败")
+    }
+    
+    /// 使用指定模板生成指纹配置
+    /// 
+    /// # Arguments
+    /// * `template_id` - 设备模板 ID（为 None 时按权重随机选择）
+    /// 
+    /// # Errors
+    /// 指定的模板不存在时返回错误
+    pub fn generate_with_template(
+        &self,
+        profile_id: &str,
+        template_id: Option<&str>,
+        platform: Option<&str>,
+        browser_version: Option<&str>,
+    ) -> Result<FingerprintFileConfig, String> {
+        self.generate_seeded(profile_id, template_id, platform, browser_version)
+    }
+    
+    /// 为单次启动重新生成指纹（启动时随机指纹）
+    /// 
+    /// 使用启动种子代替 Profile ID 派生随机数，保留固定字段：
+    /// 平台、浏览器版本、语言、时区、地理位置、WebRTC 公网 IP（由代理决定）
+    /// 
+    /// # Arguments
+    /// * `current` - 当前指纹
+    /// * `launch_seed` - 本次启动的种子（记录在指纹中，可用于复现）
+    pub fn regenerate_for_launch(&self, current: &Fingerprint, launch_seed: &str) -> Result<Fingerprint, String> {
+        let platform = Self::normalize_platform(&current.platform);
+        let browser_version = current
+            .browser_version
+            .as_deref()
+            .and_then(|v| v.split('.').next())
+            .filter(|v| !v.is_empty())
+            .map(|v| v.to_string())
+            .or_else(|| Self::extract_major_version(&current.user_agent));
+        
+        let generated = self.generate_seeded(launch_seed, None, Some(platform), browser_version.as_deref())?;
+        let width = generated.resolution.monitor_width;
+        let height = generated.resolution.monitor_height;
+        
+        let mut fingerprint = current.clone();
+        fingerprint.seed = SeedManager::from_profile_id(launch_seed).master_seed() as i64;
+        fingerprint.launch_seed = Some(launch_seed.to_string());
+        fingerprint.user_agent = generated.ua.user_agent;
+        fingerprint.hardware_concurrency = generated.resource_info.cpu as i32;
+        fingerprint.device_memory = generated.resource_info.memory as i32;
+        fingerprint.screen_resolution = format!("{}x{}", width, height);
+        fingerprint.screen_width = Some(width);
+        fingerprint.screen_height = Some(height);
+        fingerprint.webgl_vendor = Some(generated.webgl_device.vendors);
+        fingerprint.webgl_renderer = Some(generated.webgl_device.renderer);
+        fingerprint.device_name = Some(generated.device.name);
+        fingerprint.mac_address = Some(generated.device.mac_address);
+        fingerprint.template_id = generated.template_id;
+        fingerprint.template_revision = generated.template_revision;
+        
+        Ok(fingerprint)
+    }
+    
+    /// 按种子生成指纹配置（种子同时写入内核 seed 字段）
+    fn generate_seeded(
+        &self,
+        seed_key: &str,
+        template_id: Option<&str>,
+        platform: Option<&str>,
+        browser_version: Option<&str>,
+    ) -> Result<FingerprintFileConfig, String> {
         // 获取平台和版本参数
         let target_platform = platform.unwrap_or("windows");
         let target_version = browser_version.unwrap_or("139");
         
         // 1. 创建种子管理器
-        let mut seed_manager = SeedManager::from_profile_id(profile_id);
+        let mut seed_manager = SeedManager::from_profile_id(seed_key);
         let derived_seeds = seed_manager.generate_all_seeds();
         
         // 2. 创建随机数生成器
         let mut rng = StdRng::seed_from_u64(derived_seeds.master);
         
-        // 3. 选择设备模板
-        let template = self.template_manager.pick_template(&mut rng);
+        // 3. 选择设备模板（优先选择与目标平台一致的模板）
+        let template = match template_id {
+            Some(id) => self.template_manager
+                .get_template_by_id(id)
+                .ok_or_else(|| format!("设备模板不存在: {}", id))?,
+            None => self.template_manager.pick_template_for_platform(
+                &self.get_navigator_platform(target_platform),
+                &mut rng,
+            ),
+        };
         
         // 4. 从模板中随机选择具体配置
         let cores = self.pick_random(&template.cpu.cores, &mut rng);
@@ -83,6 +170,8 @@ impl FingerprintGenerator {
         
         // 根据平台选择一致的时区和语言（保持地理一致性）
         let (timezone, language_primary, language_fallback) = self.get_locale_for_platform(target_platform);
+        let time_zone = KernelTimeZoneConfig::from_zone(&timezone)
+            .ok_or_else(|| format!("无法解析时区: {}", timezone))?;
         
         // 生成完整语言列表
         let mut languages = vec![language_primary.clone()];
@@ -141,13 +230,15 @@ impl FingerprintGenerator {
         ];
         
         // 6. 构建完整配置（匹配内核格式）
-        FingerprintFileConfig {
+        Ok(FingerprintFileConfig {
             init: 2,
-            seed: profile_id.to_string(),
+            seed: seed_key.to_string(),
+            template_id: Some(template.id.clone()),
+            template_revision: Some(template.revision),
             
             device: KernelDeviceConfig {
                 config_type: 2,
-                name: format!("DESKTOP-{}", &profile_id[..8].to_uppercase()),
+                name: format!("DESKTOP-{}", &seed_key[..8].to_uppercase()),
                 mac_address: {
                     let mac_bytes: Vec<u8> = (0..6)
                         .map(|i| ((derived_seeds.master >> (i * 8)) & 0xFF) as u8)
@@ -180,10 +271,7 @@ impl FingerprintGenerator {
                 avail_height: resolution.height.saturating_sub(40),
             },
             
-            time_zone: KernelTimeZoneConfig {
-                config_type: 2,
-                gmt: timezone.clone(),
-            },
+            time_zone,
             
             language: KernelLanguageConfig {
                 config_type: 2,
@@ -228,7 +316,7 @@ impl FingerprintGenerator {
             open_port: KernelOpenPortConfig::default(),
             
             webgpu: KernelWebGpuConfig::default(),
-        }
+        })
     }
     
     /// 从模板随机选择
@@ -236,22 +324,26 @@ impl FingerprintGenerator {
         self.template_manager.pick_random(options, rng)
     }
     
-    /// 时区 ID 转偏移量（分钟）
-    fn timezone_to_offset(&self, timezone: &str) -> i32 {
-        match timezone {
-            "Asia/Shanghai" | "Asia/Hong_Kong" => -480,
-            "Asia/Tokyo" => -540,
-            "Asia/Seoul" => -540,
-            "America/New_York" => 300,
-            "America/Los_Angeles" => 480,
-            "America/Chicago" => 360,
-            "Europe/London" => 0,
-            "Europe/Paris" | "Europe/Berlin" => -60,
-            "UTC" => 0,
-            _ => -480, // 默认 UTC+8
+    /// 将 Profile 中的平台名称规范化为生成器平台参数
+    fn normalize_platform(platform: &str) -> &'static str {
+        match platform.to_lowercase().as_str() {
+            "macos" | "mac" | "macintel" => "macos",
+            "linux" | "linux x86_64" => "linux",
+            "android" => "android",
+            "ios" | "iphone" => "ios",
+            _ => "windows",
         }
     }
     
+    /// 从 User-Agent 提取 Chrome 主版本号
+    fn extract_major_version(user_agent: &str) -> Option<String> {
+        let start = user_agent.find("Chrome/").or_else(|| user_agent.find("CriOS/"))?;
+        let version = &user_agent[start..];
+        let version = &version[version.find('/')? + 1..];
+        let major: String = version.chars().take_while(|c| c.is_ascii_digit()).collect();
+        if major.is_empty() { None } else { Some(major) }
+    }
+    
     /// 根据平台和版本生成 User-Agent
     fn generate_user_agent(&self, platform: &str, version: &str, _resolution: &ResolutionOption) -> String {
         match platform {
@@ -346,3 +438,56 @@ impl FingerprintGenerator {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn current_fingerprint() -> Fingerprint {
+        serde_json::from_value(serde_json::json!({
+            "seed": 1,
+            "platform": "windows",
+            "browser": "chrome",
+            "user_agent": "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/139.0.0.0 Safari/537.36",
+            "hardware_concurrency": 8,
+            "device_memory": 8,
+            "screen_resolution": "1920x1080",
+            "timezone": "America/New_York",
+            "language": "en-US",
+            "canvas_noise": true,
+            "webgl_noise": true,
+            "audio_noise": true,
+            "browser_version": "139",
+            "webrtc_public_ip": "203.0.113.7",
+            "geolocation_latitude": 40.71,
+            "geolocation_longitude": -74.0
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_regenerate_for_launch_keeps_pinned_fields() {
+        let generator = FingerprintGenerator::with_templates(TemplateManager::load_builtin().unwrap()).unwrap();
+        let current = current_fingerprint();
+
+        let first = generator.regenerate_for_launch(&current, "launch-seed-0001").unwrap();
+        let second = generator.regenerate_for_launch(&current, "launch-seed-0002").unwrap();
+
+        assert_eq!(first.launch_seed.as_deref(), Some("launch-seed-0001"));
+        assert_ne!(first.seed, second.seed);
+        assert_eq!(first.platform, current.platform);
+        assert_eq!(first.language, current.language);
+        assert_eq!(first.timezone, current.timezone);
+        assert_eq!(first.webrtc_public_ip, current.webrtc_public_ip);
+        assert_eq!(first.geolocation_latitude, current.geolocation_latitude);
+        assert!(first.user_agent.contains("Windows NT"));
+        assert!(first.user_agent.contains("Chrome/139"));
+        assert!(first.template_id.is_some());
+
+        // 相同启动种子可复现
+        let replay = generator.regenerate_for_launch(&current, "launch-seed-0001").unwrap();
+        assert_eq!(replay.seed, first.seed);
+        assert_eq!(replay.webgl_renderer, first.webgl_renderer);
+        assert_eq!(replay.mac_address, first.mac_address);
+    }
+}
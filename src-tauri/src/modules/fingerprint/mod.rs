@@ -5,10 +5,14 @@ pub mod templates;
 pub mod generator;
 pub mod noise;
 pub mod validator;  // 重新启用，已适配新结构体
+pub mod rules;  // 校验规则集
+pub mod timezone;
 
 // 导出主要类型
 pub use seed_manager::SeedManager;
 pub use templates::TemplateManager;
 pub use generator::FingerprintGenerator;
 pub use validator::{FingerprintValidator, ValidationResult};
+pub use rules::ValidationRuleSet;
+pub use timezone::TimezoneResolver;
 
@@ -0,0 +1,332 @@
+// Validation Rules - 指纹校验规则集
+// 校验规则以 JSON / TOML 文件描述（条件、严重程度、扣分权重），由校验器加载执行
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::validator::IssueSeverity;
+
+/// 规则文件 Schema 标识
+pub const RULES_SCHEMA: &str = "fingerprint_rules_v1";
+
+/// 内置默认规则（编译时嵌入）
+pub const BUILTIN_RULES_JSON: &str = include_str!("../../../data/validation/fingerprint_rules.json");
+
+/// 规则求值使用的事实（字段名 -> 值），缺失的事实使所有比较结果为 false
+pub type RuleFacts = HashMap<&'static str, Value>;
+
+/// 规则集文件根结构
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationRuleSet {
+    #[serde(rename = "$schema")]
+    pub schema: String,
+    pub version: String,
+    #[serde(default)]
+    pub description: String,
+    pub rules: Vec<ValidationRule>,
+}
+
+/// 单条校验规则
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationRule {
+    /// 规则唯一标识
+    pub id: String,
+    /// 问题代码（多条规则可共用）
+    pub code: String,
+    #[serde(deserialize_with = "deserialize_severity")]
+    pub severity: IssueSeverity,
+    /// 命中时从真实度评分（满分 100）中扣除的分数
+    pub weight: f32,
+    pub field: String,
+    /// 问题描述，支持 {fact} 占位符
+    pub message: String,
+    pub when: RuleCondition,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// 规则条件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RuleCondition {
+    All { all: Vec<RuleCondition> },
+    Any { any: Vec<RuleCondition> },
+    Not { not: Box<RuleCondition> },
+    Compare { fact: String, op: CompareOp, value: Value },
+}
+
+/// 比较运算符
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    In,
+    NotIn,
+    /// 字符串包含（value 为数组时任一匹配即可）
+    Contains,
+    /// 字符串前缀（value 为数组时任一匹配即可）
+    StartsWith,
+}
+
+/// 规则文件中的严重程度同时接受小写（error）与结果中的写法（Error）
+fn deserialize_severity<'de, D>(deserializer: D) -> Result<IssueSeverity, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    match s.to_lowercase().as_str() {
+        "error" => Ok(IssueSeverity::Error),
+        "warning" => Ok(IssueSeverity::Warning),
+        "info" => Ok(IssueSeverity::Info),
+        _ => Err(serde::de::Error::custom(format!("未知的严重程度: {}", s))),
+    }
+}
+
+impl ValidationRuleSet {
+    /// 加载内置规则
+    pub fn load_builtin() -> Result<Self, String> {
+        Self::from_json_str(BUILTIN_RULES_JSON)
+    }
+
+    /// 从 JSON 字符串加载
+    pub fn from_json_str(content: &str) -> Result<Self, String> {
+        let rule_set: Self = serde_json::from_str(content)
+            .map_err(|e| format!("解析规则 JSON 失败: {}", e))?;
+        rule_set.validate()?;
+        Ok(rule_set)
+    }
+
+    /// 从 TOML 字符串加载
+    pub fn from_toml_str(content: &str) -> Result<Self, String> {
+        let rule_set: Self = toml::from_str(content)
+            .map_err(|e| format!("解析规则 TOML 失败: {}", e))?;
+        rule_set.validate()?;
+        Ok(rule_set)
+    }
+
+    /// 从文件加载（按扩展名区分 .toml / .json）
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("读取规则文件失败: {}", e))?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Self::from_toml_str(&content),
+            _ => Self::from_json_str(&content),
+        }
+    }
+
+    /// 校验规则集结构
+    pub fn validate(&self) -> Result<(), String> {
+        if self.schema != RULES_SCHEMA {
+            return Err(format!("无效的规则文件格式: {}", self.schema));
+        }
+
+        let mut ids = HashSet::new();
+        for rule in &self.rules {
+            if rule.id.trim().is_empty() || rule.code.trim().is_empty() {
+                return Err("规则 id 和 code 不能为空".to_string());
+            }
+            if !ids.insert(rule.id.as_str()) {
+                return Err(format!("规则 id 重复: {}", rule.id));
+            }
+            if !rule.weight.is_finite() || rule.weight < 0.0 {
+                return Err(format!("规则 {} 的权重必须为非负数", rule.id));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 检查规则引用的事实是否都在已知列表中
+    pub fn check_facts(&self, known: &[&str]) -> Result<(), String> {
+        for rule in &self.rules {
+            let mut facts = Vec::new();
+            rule.when.collect_facts(&mut facts);
+            if let Some(unknown) = facts.iter().find(|f| !known.contains(&f.as_str())) {
+                return Err(format!("规则 {} 引用了未知字段: {}", rule.id, unknown));
+            }
+        }
+        Ok(())
+    }
+
+    /// 已启用的规则
+    pub fn enabled_rules(&self) -> impl Iterator<Item = &ValidationRule> {
+        self.rules.iter().filter(|r| r.enabled)
+    }
+}
+
+impl ValidationRule {
+    /// 规则是否命中
+    pub fn matches(&self, facts: &RuleFacts) -> bool {
+        self.when.evaluate(facts)
+    }
+
+    /// 用事实值替换消息中的 {fact} 占位符
+    pub fn render_message(&self, facts: &RuleFacts) -> String {
+        let mut message = self.message.clone();
+        for (name, value) in facts {
+            let placeholder = format!("{{{}}}", name);
+            if message.contains(&placeholder) {
+                message = message.replace(&placeholder, &display_value(value));
+            }
+        }
+        message
+    }
+}
+
+impl RuleCondition {
+    /// 条件求值
+    pub fn evaluate(&self, facts: &RuleFacts) -> bool {
+        match self {
+            RuleCondition::All { all } => all.iter().all(|c| c.evaluate(facts)),
+            RuleCondition::Any { any } => any.iter().any(|c| c.evaluate(facts)),
+            RuleCondition::Not { not } => !not.evaluate(facts),
+            RuleCondition::Compare { fact, op, value } => match facts.get(fact.as_str()) {
+                Some(actual) => compare(actual, *op, value),
+                None => false,
+            },
+        }
+    }
+
+    fn collect_facts(&self, out: &mut Vec<String>) {
+        match self {
+            RuleCondition::All { all } => all.iter().for_each(|c| c.collect_facts(out)),
+            RuleCondition::Any { any } => any.iter().for_each(|c| c.collect_facts(out)),
+            RuleCondition::Not { not } => not.collect_facts(out),
+            RuleCondition::Compare { fact, .. } => out.push(fact.clone()),
+        }
+    }
+}
+
+fn compare(actual: &Value, op: CompareOp, expected: &Value) -> bool {
+    match op {
+        CompareOp::Eq => values_equal(actual, expected),
+        CompareOp::Ne => !values_equal(actual, expected),
+        CompareOp::Gt | CompareOp::Gte | CompareOp::Lt | CompareOp::Lte => {
+            match (actual.as_f64(), expected.as_f64()) {
+                (Some(a), Some(b)) => match op {
+                    CompareOp::Gt => a > b,
+                    CompareOp::Gte => a >= b,
+                    CompareOp::Lt => a < b,
+                    _ => a <= b,
+                },
+                _ => false,
+            }
+        }
+        CompareOp::In => expected
+            .as_array()
+            .map(|list| list.iter().any(|v| values_equal(actual, v)))
+            .unwrap_or(false),
+        CompareOp::NotIn => expected
+            .as_array()
+            .map(|list| !list.iter().any(|v| values_equal(actual, v)))
+            .unwrap_or(false),
+        CompareOp::Contains => string_match(actual, expected, |a, e| a.contains(e)),
+        CompareOp::StartsWith => string_match(actual, expected, |a, e| a.starts_with(e)),
+    }
+}
+
+fn values_equal(a: &Value, b: &Value) -> bool {
+    match (a.as_f64(), b.as_f64()) {
+        (Some(x), Some(y)) => x == y,
+        _ => a == b,
+    }
+}
+
+fn string_match(actual: &Value, expected: &Value, f: impl Fn(&str, &str) -> bool) -> bool {
+    let Some(actual) = actual.as_str() else {
+        return false;
+    };
+    match expected {
+        Value::String(e) => f(actual, e),
+        Value::Array(list) => list.iter().filter_map(|v| v.as_str()).any(|e| f(actual, e)),
+        _ => false,
+    }
+}
+
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => match n.as_f64() {
+            Some(f) if f.fract() == 0.0 => format!("{}", f as i64),
+            _ => n.to_string(),
+        },
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_builtin_rules_load() {
+        let rules = ValidationRuleSet::load_builtin().unwrap();
+        assert!(!rules.rules.is_empty());
+    }
+
+    #[test]
+    fn test_condition_evaluation() {
+        let mut facts = RuleFacts::new();
+        facts.insert("cpu", json!(16));
+        facts.insert("timezone", json!("Asia/Shanghai"));
+
+        let cond: RuleCondition = serde_json::from_value(json!({ "all": [
+            { "fact": "cpu", "op": "gte", "value": 12 },
+            { "fact": "timezone", "op": "contains", "value": ["Shanghai", "Hong_Kong"] },
+            { "not": { "fact": "language", "op": "starts_with", "value": "zh" } }
+        ] }))
+        .unwrap();
+        // language 缺失：比较为 false，取反后为 true
+        assert!(cond.evaluate(&facts));
+
+        facts.insert("language", json!("zh-CN"));
+        assert!(!cond.evaluate(&facts));
+    }
+
+    #[test]
+    fn test_toml_rules() {
+        let content = r#"
+"$schema" = "fingerprint_rules_v1"
+version = "1.0.0"
+
+[[rules]]
+id = "few_cores"
+code = "FEW_CORES"
+severity = "info"
+weight = 2
+field = "resourceInfo.cpu"
+message = "CPU 核心数较少: {cpu}"
+when = { fact = "cpu", op = "lte", value = 2 }
+"#;
+        let rules = ValidationRuleSet::from_toml_str(content).unwrap();
+        let rule = &rules.rules[0];
+        assert_eq!(rule.severity, IssueSeverity::Info);
+
+        let mut facts = RuleFacts::new();
+        facts.insert("cpu", json!(2));
+        assert!(rule.matches(&facts));
+        assert_eq!(rule.render_message(&facts), "CPU 核心数较少: 2");
+    }
+
+    #[test]
+    fn test_duplicate_rule_id_rejected() {
+        let mut rules = ValidationRuleSet::load_builtin().unwrap();
+        let first = rules.rules[0].clone();
+        rules.rules.push(first);
+        assert!(rules.validate().is_err());
+    }
+}
@@ -6,6 +6,12 @@ use rand::{Rng, SeedableRng};
 use rand::rngs::StdRng;
 use std::path::Path;
 
+/// 模板文件 Schema 标识
+pub const TEMPLATE_SCHEMA: &str = "device_template_v1";
+
+/// 内置默认模板（编译时嵌入，首次运行时释放到应用数据目录）
+pub const BUILTIN_TEMPLATES_JSON: &str = include_str!("../../../data/templates/device_templates.json");
+
 /// 模板文件根结构
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct DeviceTemplateFile {
@@ -23,6 +29,15 @@ pub struct DeviceTemplate {
     pub id: String,
     pub description: String,
     pub weight: f32,
+    /// 是否参与随机选择
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// 模板修订号，每次编辑递增
+    #[serde(default = "default_revision")]
+    pub revision: u32,
+    /// 模板来源（builtin / 导入的模板包名称 / custom）
+    #[serde(default)]
+    pub source: Option<String>,
     pub os: OsConfig,
     pub cpu: CpuConfig,
     pub memory: MemoryConfig,
@@ -104,8 +119,29 @@ pub struct LanguageOption {
     pub fallback: Vec<String>,
 }
 
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_revision() -> u32 {
+    1
+}
+
+/// 模板包导入结果
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TemplateImportSummary {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub skipped: Vec<String>,
+}
+
 /// 模板管理器
+#[derive(Debug, Clone)]
 pub struct TemplateManager {
+    schema: String,
+    version: String,
+    created_at: String,
+    description: String,
     templates: Vec<DeviceTemplate>,
 }
 
@@ -115,26 +151,170 @@ impl TemplateManager {
         let content = std::fs::read_to_string(path)
             .map_err(|e| format!("读取模板文件失败: {}", e))?;
         
-        let file_root: DeviceTemplateFile = serde_json::from_str(&content)
+        Self::load_from_str(&content)
+    }
+    
+    /// 从 JSON 字符串加载模板（含 Schema 校验）
+    pub fn load_from_str(content: &str) -> Result<Self, String> {
+        let file_root: DeviceTemplateFile = serde_json::from_str(content)
             .map_err(|e| format!("解析模板文件失败: {}", e))?;
         
-        // 验证权重总和
-        let total_weight: f32 = file_root.templates.iter().map(|t| t.weight).sum();
-        if (total_weight - 1.0).abs() > 0.01 {
-            return Err(format!("模板权重总和必须为 1.0，当前为 {}", total_weight));
+        Self::validate_file(&file_root)?;
+        
+        Ok(Self::from_file(file_root))
+    }
+    
+    /// 加载内置模板
+    pub fn load_builtin() -> Result<Self, String> {
+        let mut manager = Self::load_from_str(BUILTIN_TEMPLATES_JSON)?;
+        for template in &mut manager.templates {
+            template.source.get_or_insert_with(|| "builtin".to_string());
+        }
+        Ok(manager)
+    }
+    
+    /// 确保模板文件存在：不存在时释放内置模板
+    /// 
+    /// # Returns
+    /// 是否释放了内置模板
+    pub fn ensure_template_file<P: AsRef<Path>>(path: P) -> Result<bool, String> {
+        let path = path.as_ref();
+        if path.exists() {
+            return Ok(false);
+        }
+        
+        Self::load_builtin()?.save_to_file(path)?;
+        tracing::info!("已初始化设备模板文件: {:?}", path);
+        Ok(true)
+    }
+    
+    /// 加载模板文件，不存在时先释放内置模板
+    pub fn load_or_init<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        Self::ensure_template_file(&path)?;
+        Self::load_from_file(path)
+    }
+    
+    /// 保存到文件
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("创建模板目录失败: {}", e))?;
+        }
+        
+        let json = serde_json::to_string_pretty(&self.to_file())
+            .map_err(|e| format!("序列化模板失败: {}", e))?;
+        
+        // 先写临时文件再替换，避免写入中断导致模板文件损坏
+        let tmp_path = path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, json)
+            .map_err(|e| format!("写入模板文件失败: {}", e))?;
+        std::fs::rename(&tmp_path, path)
+            .map_err(|e| format!("替换模板文件失败: {}", e))?;
+        
+        Ok(())
+    }
+    
+    /// 校验模板文件结构
+    pub fn validate_file(file: &DeviceTemplateFile) -> Result<(), String> {
+        if file.schema != TEMPLATE_SCHEMA {
+            return Err(format!("不支持的模板 Schema: {}（期望 {}）", file.schema, TEMPLATE_SCHEMA));
         }
         
-        Ok(Self { 
-            templates: file_root.templates 
-        })
+        let mut ids = std::collections::HashSet::new();
+        for template in &file.templates {
+            Self::validate_template(template)?;
+            if !ids.insert(template.id.as_str()) {
+                return Err(format!("模板 ID 重复: {}", template.id));
+            }
+        }
+        
+        Ok(())
     }
     
-    /// 按权重随机选择模板
+    /// 校验单个模板
+    pub fn validate_template(template: &DeviceTemplate) -> Result<(), String> {
+        let id = template.id.trim();
+        if id.is_empty() {
+            return Err("模板 ID 不能为空".to_string());
+        }
+        if !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(format!("模板 ID 只能包含字母、数字、下划线和连字符: {}", id));
+        }
+        if !template.weight.is_finite() || template.weight < 0.0 {
+            return Err(format!("模板 {} 权重无效: {}", id, template.weight));
+        }
+        if template.cpu.cores.is_empty() || template.cpu.cores.contains(&0) {
+            return Err(format!("模板 {} 的 CPU 核心数列表无效", id));
+        }
+        if template.memory.options_gb.is_empty() || template.memory.options_gb.contains(&0) {
+            return Err(format!("模板 {} 的内存选项无效", id));
+        }
+        if template.gpu.models.is_empty() {
+            return Err(format!("模板 {} 缺少 GPU 型号", id));
+        }
+        if template.screen.resolutions.is_empty() {
+            return Err(format!("模板 {} 缺少屏幕分辨率", id));
+        }
+        if template.screen.resolutions.iter().any(|r| r.width == 0 || r.height == 0 || r.weight < 0.0) {
+            return Err(format!("模板 {} 的屏幕分辨率配置无效", id));
+        }
+        let [min_version, max_version] = template.browser.chrome_version_range;
+        if min_version > max_version {
+            return Err(format!("模板 {} 的 Chrome 版本范围无效: {}-{}", id, min_version, max_version));
+        }
+        Ok(())
+    }
+    
+    /// 从模板列表创建
+    pub fn from_templates(templates: Vec<DeviceTemplate>) -> Self {
+        Self {
+            schema: TEMPLATE_SCHEMA.to_string(),
+            version: "1.0.0".to_string(),
+            created_at: chrono::Utc::now().format("%Y-%m-%d").to_string(),
+            description: String::new(),
+            templates,
+        }
+    }
+    
+    /// 从模板文件结构创建
+    fn from_file(file: DeviceTemplateFile) -> Self {
+        Self {
+            schema: file.schema,
+            version: file.version,
+            created_at: file.created_at,
+            description: file.description,
+            templates: file.templates,
+        }
+    }
+    
+    /// 转换为模板文件结构
+    pub fn to_file(&self) -> DeviceTemplateFile {
+        DeviceTemplateFile {
+            schema: self.schema.clone(),
+            version: self.version.clone(),
+            created_at: self.created_at.clone(),
+            description: self.description.clone(),
+            templates: self.templates.clone(),
+        }
+    }
+    
+    /// 按权重随机选择模板（仅在启用且权重大于 0 的模板中选择）
     pub fn pick_template(&self, rng: &mut StdRng) -> &DeviceTemplate {
-        let total_weight: f32 = self.templates.iter().map(|t| t.weight).sum();
+        let candidates: Vec<&DeviceTemplate> = self.templates
+            .iter()
+            .filter(|t| t.enabled && t.weight > 0.0)
+            .collect();
+        let candidates = if candidates.is_empty() {
+            self.templates.iter().collect()
+        } else {
+            candidates
+        };
+        
+        let total_weight: f32 = candidates.iter().map(|t| t.weight).sum();
         let mut pick = rng.gen::<f32>() * total_weight;
         
-        for template in &self.templates {
+        for template in &candidates {
             pick -= template.weight;
             if pick <= 0.0 {
                 return template;
@@ -142,7 +322,35 @@ impl TemplateManager {
         }
         
         // 回退到第一个模板
-        &self.templates[0]
+        candidates[0]
+    }
+    
+    /// 按权重随机选择与目标平台（navigator.platform）一致的模板，没有匹配时从全部模板中选择
+    pub fn pick_template_for_platform(&self, navigator_platform: &str, rng: &mut StdRng) -> &DeviceTemplate {
+        let matching: Vec<&DeviceTemplate> = self.templates
+            .iter()
+            .filter(|t| t.enabled && t.weight > 0.0 && t.os.platform.eq_ignore_ascii_case(navigator_platform))
+            .collect();
+        if matching.is_empty() {
+            return self.pick_template(rng);
+        }
+        
+        let total_weight: f32 = matching.iter().map(|t| t.weight).sum();
+        let mut pick = rng.gen::<f32>() * total_weight;
+        
+        for template in &matching {
+            pick -= template.weight;
+            if pick <= 0.0 {
+                return template;
+            }
+        }
+        
+        matching[0]
+    }
+    
+    /// 是否存在可参与随机选择的模板
+    pub fn has_available_templates(&self) -> bool {
+        self.templates.iter().any(|t| t.enabled && t.weight > 0.0)
     }
     
     /// 按 ID 获取模板
@@ -155,6 +363,117 @@ impl TemplateManager {
         &self.templates
     }
     
+    /// 启用模板的权重总和（用于计算选中概率）
+    pub fn enabled_weight_total(&self) -> f32 {
+        self.templates
+            .iter()
+            .filter(|t| t.enabled)
+            .map(|t| t.weight)
+            .sum()
+    }
+    
+    /// 添加模板
+    pub fn add_template(&mut self, mut template: DeviceTemplate) -> Result<(), String> {
+        Self::validate_template(&template)?;
+        if self.get_template_by_id(&template.id).is_some() {
+            return Err(format!("模板已存在: {}", template.id));
+        }
+        
+        template.revision = 1;
+        template.source.get_or_insert_with(|| "custom".to_string());
+        self.templates.push(template);
+        Ok(())
+    }
+    
+    /// 编辑模板（ID 不可修改，修订号自动递增）
+    pub fn update_template(&mut self, id: &str, mut template: DeviceTemplate) -> Result<&DeviceTemplate, String> {
+        template.id = id.to_string();
+        Self::validate_template(&template)?;
+        
+        let existing = self.template_mut(id)?;
+        template.revision = existing.revision + 1;
+        if template.source.is_none() {
+            template.source = existing.source.clone();
+        }
+        *existing = template;
+        Ok(existing)
+    }
+    
+    /// 启用/禁用模板
+    pub fn set_enabled(&mut self, id: &str, enabled: bool) -> Result<(), String> {
+        let template = self.template_mut(id)?;
+        if template.enabled != enabled {
+            template.enabled = enabled;
+            template.revision += 1;
+        }
+        Ok(())
+    }
+    
+    /// 设置模板权重
+    pub fn set_weight(&mut self, id: &str, weight: f32) -> Result<(), String> {
+        if !weight.is_finite() || weight < 0.0 {
+            return Err(format!("权重无效: {}", weight));
+        }
+        
+        let template = self.template_mut(id)?;
+        template.weight = weight;
+        template.revision += 1;
+        Ok(())
+    }
+    
+    /// 删除模板
+    pub fn remove_template(&mut self, id: &str) -> Result<DeviceTemplate, String> {
+        let index = self.templates
+            .iter()
+            .position(|t| t.id == id)
+            .ok_or_else(|| format!("模板不存在: {}", id))?;
+        Ok(self.templates.remove(index))
+    }
+    
+    /// 导入并合并社区模板包
+    /// 
+    /// - 新 ID 直接添加
+    /// - 已存在的 ID：仅当 `overwrite` 为 true 且模板包中的修订号更高时覆盖
+    pub fn import_pack(&mut self, content: &str, overwrite: bool) -> Result<TemplateImportSummary, String> {
+        let pack: DeviceTemplateFile = serde_json::from_str(content)
+            .map_err(|e| format!("解析模板包失败: {}", e))?;
+        Self::validate_file(&pack)?;
+        
+        let pack_source = if pack.description.trim().is_empty() {
+            format!("pack:{}", pack.version)
+        } else {
+            format!("pack:{}", pack.description.trim())
+        };
+        
+        let mut summary = TemplateImportSummary::default();
+        for mut template in pack.templates {
+            template.source.get_or_insert_with(|| pack_source.clone());
+            
+            match self.templates.iter_mut().find(|t| t.id == template.id) {
+                None => {
+                    summary.added.push(template.id.clone());
+                    self.templates.push(template);
+                }
+                Some(existing) if overwrite && template.revision > existing.revision => {
+                    summary.updated.push(template.id.clone());
+                    *existing = template;
+                }
+                Some(_) => {
+                    summary.skipped.push(template.id.clone());
+                }
+            }
+        }
+        
+        Ok(summary)
+    }
+    
+    fn template_mut(&mut self, id: &str) -> Result<&mut DeviceTemplate, String> {
+        self.templates
+            .iter_mut()
+            .find(|t| t.id == id)
+            .ok_or_else(|| format!("模板不存在: {}", id))
+    }
+    
     /// 从模板随机选择配置项
     pub fn pick_random<T: Clone>(&self, options: &[T], rng: &mut StdRng) -> T {
         if options.is_empty() {
@@ -187,53 +506,117 @@ impl TemplateManager {
 mod tests {
     use super::*;
     
+    fn test_template(id: &str, weight: f32) -> DeviceTemplate {
+        DeviceTemplate {
+            id: id.to_string(),
+            description: format!("Test {}", id),
+            weight,
+            enabled: true,
+            revision: 1,
+            source: None,
+            os: OsConfig {
+                name: "Windows".to_string(),
+                version: "10.0".to_string(),
+                platform: "Win32".to_string(),
+            },
+            cpu: CpuConfig {
+                vendor: "Intel".to_string(),
+                cores: vec![4],
+                frequency_range: [2000, 3000],
+            },
+            memory: MemoryConfig {
+                options_gb: vec![8, 16],
+            },
+            gpu: GpuConfig {
+                vendor: "NVIDIA".to_string(),
+                models: vec![GpuModel {
+                    name: "GeForce GTX 1650".to_string(),
+                    vram_gb: 4,
+                    webgl_vendor: "Google Inc. (NVIDIA)".to_string(),
+                    webgl_renderer: "ANGLE (NVIDIA, NVIDIA GeForce GTX 1650 Direct3D11 vs_5_0 ps_5_0, D3D11)".to_string(),
+                    unmasked_vendor: "NVIDIA Corporation".to_string(),
+                    unmasked_renderer: "NVIDIA GeForce GTX 1650/PCIe/SSE2".to_string(),
+                }],
+            },
+            screen: ScreenConfig {
+                resolutions: vec![ResolutionOption { width: 1920, height: 1080, weight: 1.0 }],
+            },
+            browser: BrowserConfig {
+                user_agents: vec![],
+                chrome_version_range: [131, 134],
+            },
+            fonts: FontsConfig {
+                mode: "subset".to_string(),
+                common_fonts: vec![],
+            },
+            locale: LocaleConfig {
+                timezones: vec![],
+                languages: vec![],
+            },
+        }
+    }
+    
     #[test]
     fn test_pick_template() {
-        // 创建测试模板
-        let templates = vec![
-            DeviceTemplate {
-                id: "template1".to_string(),
-                description: "Test 1".to_string(),
-                weight: 0.5,
-                os: OsConfig {
-                    name: "Windows".to_string(),
-                    version: "10.0".to_string(),
-                    platform: "Win32".to_string(),
-                },
-                cpu: CpuConfig {
-                    vendor: "Intel".to_string(),
-                    cores: vec![4],
-                    frequency_range: [2000, 3000],
-                },
-                memory: MemoryConfig {
-                    options_gb: vec![8, 16],
-                },
-                gpu: GpuConfig {
-                    vendor: "NVIDIA".to_string(),
-                    models: vec![],
-                },
-                screen: ScreenConfig {
-                    resolutions: vec![],
-                },
-                browser: BrowserConfig {
-                    user_agents: vec![],
-                    chrome_version_range: [131, 134],
-                },
-                fonts: FontsConfig {
-                    mode: "subset".to_string(),
-                    common_fonts: vec![],
-                },
-                locale: LocaleConfig {
-                    timezones: vec![],
-                    languages: vec![],
-                },
-            },
-        ];
-        
-        let manager = TemplateManager { templates };
+        let manager = TemplateManager::from_templates(vec![test_template("template1", 0.5)]);
         let mut rng = StdRng::seed_from_u64(12345);
         
         let picked = manager.pick_template(&mut rng);
         assert_eq!(picked.id, "template1");
     }
+    
+    #[test]
+    fn test_builtin_templates_load() {
+        let manager = TemplateManager::load_builtin().unwrap();
+        assert!(manager.has_available_templates());
+        assert!(manager.get_all_templates().iter().all(|t| t.source.as_deref() == Some("builtin")));
+    }
+    
+    #[test]
+    fn test_disabled_template_never_picked() {
+        let mut manager = TemplateManager::from_templates(vec![
+            test_template("a", 0.9),
+            test_template("b", 0.1),
+        ]);
+        manager.set_enabled("a", false).unwrap();
+        
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..50 {
+            assert_eq!(manager.pick_template(&mut rng).id, "b");
+        }
+        assert_eq!(manager.get_template_by_id("a").unwrap().revision, 2);
+    }
+    
+    #[test]
+    fn test_import_pack_merge() {
+        let mut manager = TemplateManager::from_templates(vec![test_template("a", 0.5)]);
+        
+        let mut newer = test_template("a", 0.2);
+        newer.revision = 3;
+        let mut pack = TemplateManager::from_templates(vec![newer, test_template("c", 0.3)]).to_file();
+        pack.description = "community".to_string();
+        let content = serde_json::to_string(&pack).unwrap();
+        
+        let summary = manager.import_pack(&content, false).unwrap();
+        assert_eq!(summary.added, vec!["c".to_string()]);
+        assert_eq!(summary.skipped, vec!["a".to_string()]);
+        assert_eq!(manager.get_template_by_id("c").unwrap().source.as_deref(), Some("pack:community"));
+        
+        let summary = manager.import_pack(&content, true).unwrap();
+        assert_eq!(summary.updated, vec!["a".to_string()]);
+        assert_eq!(manager.get_template_by_id("a").unwrap().weight, 0.2);
+    }
+    
+    #[test]
+    fn test_import_pack_rejects_invalid_schema() {
+        let mut manager = TemplateManager::from_templates(vec![test_template("a", 0.5)]);
+        
+        let mut pack = TemplateManager::from_templates(vec![test_template("b", 0.5)]).to_file();
+        pack.schema = "device_template_v0".to_string();
+        assert!(manager.import_pack(&serde_json::to_string(&pack).unwrap(), false).is_err());
+        
+        let pack = TemplateManager::from_templates(vec![test_template("b", 0.5), test_template("b", 0.5)]).to_file();
+        assert!(manager.import_pack(&serde_json::to_string(&pack).unwrap(), false).is_err());
+        assert_eq!(manager.get_all_templates().len(), 1);
+    }
 }
@@ -0,0 +1,102 @@
+// Timezone Resolver - 时区解析
+// 基于内置的 IANA 时区数据库（chrono-tz，离线可用）计算考虑夏令时的 UTC 偏移
+
+use chrono::{DateTime, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// 时区解析器
+pub struct TimezoneResolver;
+
+impl TimezoneResolver {
+    /// 查找 IANA 时区（如 "America/New_York"）
+    pub fn lookup(name: &str) -> Option<Tz> {
+        name.trim().parse::<Tz>().ok()
+    }
+
+    /// 是否为已知的 IANA 时区名称
+    pub fn is_known(name: &str) -> bool {
+        Self::lookup(name).is_some()
+    }
+
+    /// 指定时刻的 UTC 偏移（分钟，东为正），自动处理夏令时
+    pub fn utc_offset_minutes_at(name: &str, at: DateTime<Utc>) -> Option<i32> {
+        let tz = Self::lookup(name)?;
+        let offset = tz.offset_from_utc_datetime(&at.naive_utc());
+        Some(offset.fix().local_minus_utc() / 60)
+    }
+
+    /// 当前时刻的 UTC 偏移（分钟，东为正）
+    pub fn current_utc_offset_minutes(name: &str) -> Option<i32> {
+        Self::utc_offset_minutes_at(name, Utc::now())
+    }
+
+    /// 当前时刻的 GMT 字符串（如 "GMT+08:00"、"GMT-04:00"）
+    pub fn current_gmt(name: &str) -> Option<String> {
+        Self::current_utc_offset_minutes(name).map(Self::format_gmt)
+    }
+
+    /// UTC 偏移（分钟，东为正）格式化为 "GMT±HH:MM"
+    pub fn format_gmt(offset_minutes: i32) -> String {
+        let sign = if offset_minutes < 0 { '-' } else { '+' };
+        let abs = offset_minutes.abs();
+        format!("GMT{}{:02}:{:02}", sign, abs / 60, abs % 60)
+    }
+
+    /// 解析 "GMT±HH:MM" 字符串为 UTC 偏移（分钟，东为正）
+    pub fn parse_gmt(s: &str) -> Option<i32> {
+        let rest = s.trim().strip_prefix("GMT")?;
+        if rest.is_empty() {
+            return Some(0);
+        }
+
+        let sign = match rest.chars().next()? {
+            '+' => 1,
+            '-' => -1,
+            _ => return None,
+        };
+        let (hours, minutes) = rest[1..].split_once(':')?;
+        let hours: i32 = hours.parse().ok()?;
+        let minutes: i32 = minutes.parse().ok()?;
+        if hours > 14 || minutes >= 60 {
+            return None;
+        }
+        Some(sign * (hours * 60 + minutes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_offset_zone() {
+        assert_eq!(TimezoneResolver::current_utc_offset_minutes("Asia/Shanghai"), Some(480));
+        assert_eq!(TimezoneResolver::current_gmt("Asia/Kolkata").as_deref(), Some("GMT+05:30"));
+    }
+
+    #[test]
+    fn test_dst_aware_offset() {
+        let winter = Utc.with_ymd_and_hms(2026, 1, 15, 12, 0, 0).unwrap();
+        let summer = Utc.with_ymd_and_hms(2026, 7, 15, 12, 0, 0).unwrap();
+
+        assert_eq!(TimezoneResolver::utc_offset_minutes_at("America/New_York", winter), Some(-300));
+        assert_eq!(TimezoneResolver::utc_offset_minutes_at("America/New_York", summer), Some(-240));
+        assert_eq!(TimezoneResolver::utc_offset_minutes_at("Europe/Berlin", winter), Some(60));
+        assert_eq!(TimezoneResolver::utc_offset_minutes_at("Europe/Berlin", summer), Some(120));
+    }
+
+    #[test]
+    fn test_unknown_zone() {
+        assert!(!TimezoneResolver::is_known("Mars/Olympus_Mons"));
+        assert_eq!(TimezoneResolver::current_gmt("Mars/Olympus_Mons"), None);
+    }
+
+    #[test]
+    fn test_gmt_round_trip() {
+        for offset in [-600, -240, 0, 330, 480, 545] {
+            let gmt = TimezoneResolver::format_gmt(offset);
+            assert_eq!(TimezoneResolver::parse_gmt(&gmt), Some(offset));
+        }
+        assert_eq!(TimezoneResolver::parse_gmt("Asia/Shanghai"), None);
+    }
+}
@@ -1,15 +1,40 @@
 // 指纹一致性校验器
-// 检查生成的指纹配置是否符合真实设备特征
+// 按规则集检查生成的指纹配置是否符合真实设备特征，并给出真实度评分
 
 use crate::modules::config_writer::*;
+use super::rules::{RuleFacts, ValidationRule, ValidationRuleSet};
+use super::timezone::TimezoneResolver;
 use serde::{Serialize, Deserialize};
+use serde_json::json;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// 满分真实度评分
+pub const MAX_REALISM_SCORE: u32 = 100;
+
+/// 规则可引用的事实字段
+pub const KNOWN_FACTS: &[&str] = &[
+    "cpu", "memory", "width", "height", "pixels", "color_depth",
+    "user_agent", "chrome_version",
+    "gmt", "zone", "timezone", "language",
+    "zone_known", "gmt_valid", "timezone_offset_matches", "expected_gmt",
+];
 
 /// 校验结果
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ValidationResult {
     pub valid: bool,
+    /// 真实度评分（0-100），每条命中的规则按权重扣分
+    #[serde(default = "default_score")]
+    pub score: u32,
     pub errors: Vec<ValidationIssue>,
     pub warnings: Vec<ValidationIssue>,
+    #[serde(default)]
+    pub infos: Vec<ValidationIssue>,
+}
+
+fn default_score() -> u32 {
+    MAX_REALISM_SCORE
 }
 
 /// 校验问题
@@ -33,8 +58,10 @@ impl ValidationResult {
     pub fn new() -> Self {
         Self {
             valid: true,
+            score: MAX_REALISM_SCORE,
             errors: Vec::new(),
             warnings: Vec::new(),
+            infos: Vec::new(),
         }
     }
     
@@ -56,183 +83,136 @@ impl ValidationResult {
             field: field.to_string(),
         });
     }
+    
+    pub fn add_info(&mut self, code: &str, message: String, field: &str) {
+        self.infos.push(ValidationIssue {
+            code: code.to_string(),
+            message,
+            severity: IssueSeverity::Info,
+            field: field.to_string(),
+        });
+    }
+    
+    /// 记录命中的规则并扣分
+    fn add_rule_hit(&mut self, rule: &ValidationRule, facts: &RuleFacts, penalty: &mut f32) {
+        let message = rule.render_message(facts);
+        match rule.severity {
+            IssueSeverity::Error => self.add_error(&rule.code, message, &rule.field),
+            IssueSeverity::Warning => self.add_warning(&rule.code, message, &rule.field),
+            IssueSeverity::Info => self.add_info(&rule.code, message, &rule.field),
+        }
+        *penalty += rule.weight;
+    }
 }
 
 /// 指纹一致性校验器
-pub struct FingerprintValidator;
+#[derive(Debug, Clone)]
+pub struct FingerprintValidator {
+    rules: ValidationRuleSet,
+}
 
 impl FingerprintValidator {
-    /// 校验指纹配置
-    pub fn validate(config: &FingerprintFileConfig) -> ValidationResult {
-        let mut result = ValidationResult::new();
-        
-        // 1. 设备档次一致性
-        Self::validate_device_tier(&config, &mut result);
-        
-        // 2. 版本一致性
-        Self::validate_version_consistency(&config, &mut result);
-        
-        // 3. 地理位置一致性
-        Self::validate_geo_consistency(&config, &mut result);
-        
-        // 4. 技术参数合理性
-        Self::validate_technical_params(&config, &mut result);
-        
-        result
+    /// 使用规则集创建校验器
+    pub fn new(rules: ValidationRuleSet) -> Result<Self, String> {
+        rules.validate()?;
+        rules.check_facts(KNOWN_FACTS)?;
+        Ok(Self { rules })
     }
     
-    /// 校验设备档次一致性
-    fn validate_device_tier(config: &FingerprintFileConfig, result: &mut ValidationResult) {
-        let cores = config.resource_info.cpu;
-        let memory = config.resource_info.memory as u32;
-        let resolution_pixels = config.resolution.monitor_width * config.resolution.monitor_height;
-        
-        // 规则 1: 高端 CPU (12+ 核) + 低端内存 (8GB)
-        if cores >= 12 && memory <= 8 {
-            result.add_warning(
-                "TIER_MISMATCH_CPU_MEMORY",
-                format!("高端CPU ({} 核) 但内存较低 ({} GB)，不太常见", cores, memory),
-                "resourceInfo.cpu,resourceInfo.memory"
-            );
-        }
-        
-        // 规则 2: 低端 CPU (4 核) + 高端内存 (32GB+)
-        if cores <= 4 && memory >= 32 {
-            result.add_warning(
-                "TIER_MISMATCH_CPU_MEMORY",
-                format!("低端CPU ({} 核) 但内存较高 ({} GB)，不太常见", cores, memory),
-                "resourceInfo.cpu,resourceInfo.memory"
-            );
-        }
-        
-        // 规则 3: 低端设备 + 4K 分辨率
-        if cores <= 4 && memory <= 8 && resolution_pixels >= 3840 * 2160 {
-            result.add_warning(
-                "TIER_MISMATCH_LOW_END_4K",
-                format!("低端设备 ({} 核, {} GB) 但使用 4K 分辨率，不常见", cores, memory),
-                "resolution"
-            );
-        }
-        
-        // 规则 4: 高端设备 + 低分辨率
-        if cores >= 12 && memory >= 32 && resolution_pixels <= 1366 * 768 {
-            result.add_warning(
-                "TIER_MISMATCH_HIGH_END_LOW_RES",
-                format!("高端设备 ({} 核, {} GB) 但使用低分辨率 ({}x{})，不常见", 
-                    cores, memory, config.resolution.monitor_width, config.resolution.monitor_height),
-                "resolution"
-            );
-        }
+    /// 使用内置规则创建校验器
+    pub fn builtin() -> Self {
+        static BUILTIN: OnceLock<FingerprintValidator> = OnceLock::new();
+        BUILTIN
+            .get_or_init(|| {
+                let rules = ValidationRuleSet::load_builtin().expect("内置校验规则无效");
+                Self::new(rules).expect("内置校验规则无效")
+            })
+            .clone()
     }
     
-    /// 校验版本一致性
-    fn validate_version_consistency(config: &FingerprintFileConfig, result: &mut ValidationResult) {
-        // 提取 User-Agent 中的 Chrome 版本
-        let ua = &config.ua.user_agent;
-        let chrome_version = Self::extract_chrome_version(ua);
-        
-        // 规则 1: 过旧版本检查（Chrome < 100 在 2026 年不合理）
-        if let Some(ref major) = chrome_version {
-            if let Ok(version_num) = major.parse::<u32>() {
-                if version_num < 100 {
-                    result.add_warning(
-                        "VERSION_TOO_OLD",
-                        format!("Chrome 版本过旧 ({}), 2026年应该 >= 130", major),
-                        "ua.userAgent"
-                    );
-                }
-            }
-        }
+    /// 从规则文件创建校验器（.json / .toml）
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        Self::new(ValidationRuleSet::load_from_file(path)?)
     }
     
-    /// 校验地理位置一致性
-    fn validate_geo_consistency(config: &FingerprintFileConfig, result: &mut ValidationResult) {
-        let timezone = &config.time_zone.gmt;
-        let language = config.language.languages.first().map(|s| s.as_str()).unwrap_or("en-US");
+    /// 当前规则集
+    pub fn rules(&self) -> &ValidationRuleSet {
+        &self.rules
+    }
+    
+    /// 使用内置规则校验指纹配置
+    pub fn validate(config: &FingerprintFileConfig) -> ValidationResult {
+        Self::builtin().check(config)
+    }
+    
+    /// 按规则集校验指纹配置并计算真实度评分
+    pub fn check(&self, config: &FingerprintFileConfig) -> ValidationResult {
+        let facts = Self::collect_facts(config);
+        let mut result = ValidationResult::new();
+        let mut penalty = 0.0f32;
         
-        // 规则 1: 中国时区但非中文语言
-        if timezone.contains("Shanghai") || timezone.contains("Hong_Kong") {
-            if !language.starts_with("zh") {
-                result.add_warning(
-                    "GEO_MISMATCH_TIMEZONE_LANG",
-                    format!("中国时区 ({}) 但语言不是中文 ({})", timezone, language),
-                    "timeZone,language"
-                );
+        for rule in self.rules.enabled_rules() {
+            if rule.matches(&facts) {
+                result.add_rule_hit(rule, &facts, &mut penalty);
             }
         }
         
-        // 规则 2: 美国时区但非英文
-        if timezone.contains("America/") && !language.starts_with("en") {
-            result.add_warning(
-                "GEO_MISMATCH_TIMEZONE_LANG",
-                format!("美国时区 ({}) 但语言不是英文 ({})", timezone, language),
-                "timeZone,language"
-            );
-        }
-        
-        // 规则 3: 欧洲时区但语言不匹配
-        if timezone.contains("Europe/Paris") || timezone.contains("Europe/Berlin") {
-            if !language.starts_with("fr") && !language.starts_with("de") && !language.starts_with("en") {
-                result.add_warning(
-                    "GEO_MISMATCH_TIMEZONE_LANG",
-                    format!("欧洲时区 ({}) 但语言不常见 ({})", timezone, language),
-                    "timeZone,language"
-                );
-            }
-        }
+        let score = (MAX_REALISM_SCORE as f32 - penalty).round().max(0.0);
+        result.score = score as u32;
+        result
     }
     
-    /// 校验技术参数合理性
-    fn validate_technical_params(config: &FingerprintFileConfig, result: &mut ValidationResult) {
-        // 规则 1: 硬件并发数合理性（1-32）
-        let cores = config.resource_info.cpu;
-        if cores == 0 || cores > 128 {
-            result.add_error(
-                "INVALID_CORES",
-                format!("CPU 核心数不合理: {}", cores),
-                "resourceInfo.cpu"
-            );
-        }
+    /// 从指纹配置提取规则求值所需的事实
+    fn collect_facts(config: &FingerprintFileConfig) -> RuleFacts {
+        let mut facts = RuleFacts::new();
         
-        // 规则 2: 内存合理性
+        // 设备档次
+        let cores = config.resource_info.cpu;
         let memory = config.resource_info.memory as u32;
-        let valid_memory: [u32; 7] = [2, 4, 8, 16, 32, 64, 128];
-        if !valid_memory.contains(&memory) {
-            result.add_warning(
-                "UNUSUAL_MEMORY",
-                format!("内存容量不常见: {} GB（常见值：2/4/8/16/32/64）", memory),
-                "resourceInfo.memory"
-            );
-        }
-        
-        // 规则 3: 屏幕分辨率合理性
         let width = config.resolution.monitor_width;
         let height = config.resolution.monitor_height;
+        facts.insert("cpu", json!(cores));
+        facts.insert("memory", json!(memory));
+        facts.insert("width", json!(width));
+        facts.insert("height", json!(height));
+        facts.insert("pixels", json!(width as u64 * height as u64));
+        facts.insert("color_depth", json!(config.resolution.color_depth));
         
-        if width < 800 || height < 600 {
-            result.add_error(
-                "INVALID_RESOLUTION",
-                format!("屏幕分辨率过低: {}x{}", width, height),
-                "resolution"
-            );
+        // 版本
+        let ua = &config.ua.user_agent;
+        facts.insert("user_agent", json!(ua));
+        if let Some(version) = Self::extract_chrome_version(ua).and_then(|v| v.parse::<u32>().ok()) {
+            facts.insert("chrome_version", json!(version));
         }
         
-        if width > 7680 || height > 4320 {
-            result.add_warning(
-                "UNUSUAL_RESOLUTION",
-                format!("屏幕分辨率过高: {}x{} (8K+)", width, height),
-                "resolution"
-            );
+        // 时区与语言
+        let gmt = &config.time_zone.gmt;
+        let gmt_offset = TimezoneResolver::parse_gmt(gmt);
+        facts.insert("gmt", json!(gmt));
+        facts.insert("gmt_valid", json!(gmt_offset.is_some() || TimezoneResolver::is_known(gmt)));
+        facts.insert("timezone", json!(Self::timezone_name(config)));
+        if let Some(ref zone) = config.time_zone.zone {
+            facts.insert("zone", json!(zone));
+            facts.insert("zone_known", json!(TimezoneResolver::is_known(zone)));
+            
+            // gmt 偏移与时区当前偏移（含夏令时）是否一致
+            if let (Some(expected), Some(offset)) = (TimezoneResolver::current_utc_offset_minutes(zone), gmt_offset) {
+                facts.insert("expected_gmt", json!(TimezoneResolver::format_gmt(expected)));
+                facts.insert("timezone_offset_matches", json!(expected == offset));
+            }
         }
+        let language = config.language.languages.first().map(|s| s.as_str()).unwrap_or("en-US");
+        facts.insert("language", json!(language));
         
-        // 规则 4: 色深合理性
-        if config.resolution.color_depth != 24 && config.resolution.color_depth != 32 {
-            result.add_warning(
-                "UNUSUAL_COLOR_DEPTH",
-                format!("色深不常见: {} (常见：24或32)", config.resolution.color_depth),
-                "resolution.colorDepth"
-            );
+        facts
+    }
+    
+    /// 时区名称：优先使用 gmt 中的 IANA 名称（旧格式），否则使用 zone 字段
+    fn timezone_name(config: &FingerprintFileConfig) -> &str {
+        if TimezoneResolver::is_known(&config.time_zone.gmt) {
+            return &config.time_zone.gmt;
         }
+        config.time_zone.zone.as_deref().unwrap_or(&config.time_zone.gmt)
     }
     
     /// 从 User-Agent 提取 Chrome 版本号（主版本）
@@ -296,4 +276,86 @@ mod tests {
         let result = FingerprintValidator::validate(&config);
         assert!(result.warnings.iter().any(|w| w.code == "GEO_MISMATCH_TIMEZONE_LANG"));
     }
+    
+    #[test]
+    fn test_unknown_timezone() {
+        let mut config = FingerprintFileConfig::default();
+        config.time_zone.gmt = "Mars/Olympus_Mons".to_string();
+        config.time_zone.zone = None;
+        
+        let result = FingerprintValidator::validate(&config);
+        assert!(!result.valid);
+        assert!(result.errors.iter().any(|e| e.code == "UNKNOWN_TIMEZONE"));
+        
+        let config = FingerprintFileConfig {
+            time_zone: KernelTimeZoneConfig {
+                config_type: 2,
+                gmt: "GMT+08:00".to_string(),
+                zone: Some("Asia/Atlantis".to_string()),
+            },
+            ..Default::default()
+        };
+        let result = FingerprintValidator::validate(&config);
+        assert!(result.errors.iter().any(|e| e.code == "UNKNOWN_TIMEZONE"));
+    }
+    
+    #[test]
+    fn test_geo_mismatch_uses_zone_name() {
+        let mut config = FingerprintFileConfig::default();
+        config.time_zone = KernelTimeZoneConfig::from_zone("America/New_York").unwrap();
+        config.language.languages = vec!["zh-CN".to_string()];
+        
+        let result = FingerprintValidator::validate(&config);
+        assert!(result.valid);
+        assert!(result.warnings.iter().any(|w| w.code == "GEO_MISMATCH_TIMEZONE_LANG"));
+    }
+    
+    #[test]
+    fn test_realism_score() {
+        let mut config = FingerprintFileConfig::default();
+        config.resource_info.cpu = 8;
+        config.resource_info.memory = 16.0;
+        config.language.languages = vec!["zh-CN".to_string()];
+        let clean = FingerprintValidator::validate(&config);
+        assert_eq!(clean.score, MAX_REALISM_SCORE);
+        
+        config.resource_info.cpu = 16;
+        config.resource_info.memory = 8.0;
+        config.language.languages = vec!["en-US".to_string()];
+        let flagged = FingerprintValidator::validate(&config);
+        assert!(flagged.score < clean.score);
+        assert_eq!(flagged.score, 75);
+    }
+    
+    #[test]
+    fn test_custom_rule_set() {
+        let rules = crate::modules::fingerprint::rules::ValidationRuleSet::from_json_str(r#"{
+            "$schema": "fingerprint_rules_v1",
+            "version": "1.0.0",
+            "rules": [{
+                "id": "no_eight_cores",
+                "code": "EIGHT_CORES",
+                "severity": "error",
+                "weight": 60,
+                "field": "resourceInfo.cpu",
+                "message": "{cpu} 核",
+                "when": { "fact": "cpu", "op": "eq", "value": 8 }
+            }]
+        }"#).unwrap();
+        let validator = FingerprintValidator::new(rules).unwrap();
+        
+        let mut config = FingerprintFileConfig::default();
+        config.resource_info.cpu = 8;
+        let result = validator.check(&config);
+        assert!(!result.valid);
+        assert_eq!(result.score, 40);
+        assert_eq!(result.errors[0].message, "8 核");
+    }
+    
+    #[test]
+    fn test_unknown_fact_rejected() {
+        let mut rules = crate::modules::fingerprint::rules::ValidationRuleSet::load_builtin().unwrap();
+        rules.rules[0].when = serde_json::from_value(json!({ "fact": "gpu_cores", "op": "gt", "value": 1 })).unwrap();
+        assert!(FingerprintValidator::new(rules).is_err());
+    }
 }
@@ -0,0 +1,313 @@
+// Fingerprint History - 指纹修订历史
+// 每次指纹变更（手动编辑、模板生成、启动时重新生成、回滚）都记录一个修订版本，
+// 支持逐字段对比两个修订版本以及回滚到指定版本
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqliteConnection, SqlitePool};
+
+use crate::modules::profile::Fingerprint;
+
+/// 变更前未被记录的指纹（首次记录时的基线）
+pub const SOURCE_BASELINE: &str = "baseline";
+/// 手动编辑
+pub const SOURCE_MANUAL_EDIT: &str = "manual_edit";
+/// 由设备模板生成
+pub const SOURCE_TEMPLATE: &str = "template";
+/// 启动时重新生成
+pub const SOURCE_REGENERATE_ON_START: &str = "regenerate_on_start";
+/// 回滚到历史版本
+pub const SOURCE_REVERT: &str = "revert";
+
+/// 指纹修订记录（保存变更后的完整指纹）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FingerprintHistoryEntry {
+    pub id: String,
+    pub profile_id: String,
+    pub revision: i64,
+    pub fingerprint: Fingerprint,
+    pub source: String,
+    pub author: Option<String>,
+    pub launch_seed: Option<String>,
+    pub created_at: String,
+}
+
+/// 单个字段的变更
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FingerprintFieldChange {
+    pub field: String,
+    pub old_value: serde_json::Value,
+    pub new_value: serde_json::Value,
+}
+
+/// 两个修订版本的对比结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FingerprintRevisionDiff {
+    pub profile_id: String,
+    pub from_revision: i64,
+    pub to_revision: i64,
+    pub changes: Vec<FingerprintFieldChange>,
+}
+
+/// 逐字段对比两个指纹（字段名按字母排序）
+pub fn diff_fingerprints(old: &Fingerprint, new: &Fingerprint) -> Vec<FingerprintFieldChange> {
+    let old_value = serde_json::to_value(old).unwrap_or_default();
+    let new_value = serde_json::to_value(new).unwrap_or_default();
+    let empty = serde_json::Map::new();
+    let old_map = old_value.as_object().unwrap_or(&empty);
+    let new_map = new_value.as_object().unwrap_or(&empty);
+
+    let fields: BTreeSet<&String> = old_map.keys().chain(new_map.keys()).collect();
+    fields
+        .into_iter()
+        .filter_map(|field| {
+            let old_field = old_map.get(field).cloned().unwrap_or(serde_json::Value::Null);
+            let new_field = new_map.get(field).cloned().unwrap_or(serde_json::Value::Null);
+            (old_field != new_field).then(|| FingerprintFieldChange {
+                field: field.clone(),
+                old_value: old_field,
+                new_value: new_field,
+            })
+        })
+        .collect()
+}
+
+/// 判断一次编辑的来源：设备模板发生变化视为模板生成，否则为手动编辑
+pub fn source_for_edit(before: &Fingerprint, after: &Fingerprint) -> &'static str {
+    let template_changed = after.template_id.is_some()
+        && (before.template_id != after.template_id || before.template_revision != after.template_revision);
+    if template_changed {
+        SOURCE_TEMPLATE
+    } else {
+        SOURCE_MANUAL_EDIT
+    }
+}
+
+fn same_fingerprint(a: &Fingerprint, b: &Fingerprint) -> bool {
+    diff_fingerprints(a, b).is_empty()
+}
+
+/// 记录一次指纹变更（在调用方的事务中执行）
+///
+/// 若变更前的指纹尚未被记录（历史为空或与最新版本不一致），先以 `baseline` 记录；
+/// 指纹未发生变化时不产生新版本，返回 `None`
+pub async fn record_revision(
+    conn: &mut SqliteConnection,
+    profile_id: &str,
+    previous: Option<&Fingerprint>,
+    fingerprint: &Fingerprint,
+    source: &str,
+    author: Option<&str>,
+    launch_seed: Option<&str>,
+) -> Result<Option<FingerprintHistoryEntry>> {
+    let latest = sqlx::query(
+        r#"
+        SELECT revision, fingerprint FROM fingerprint_history
+        WHERE profile_id = ?
+        ORDER BY revision DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(profile_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let (mut revision, latest_fp) = match latest {
+        Some(row) => {
+            let fingerprint_str: String = row.try_get("fingerprint")?;
+            let fp: Fingerprint = serde_json::from_str(&fingerprint_str)?;
+            (row.try_get::<i64, _>("revision")?, Some(fp))
+        }
+        None => (0, None),
+    };
+
+    if let Some(previous) = previous {
+        let tracked = latest_fp.as_ref().map(|fp| same_fingerprint(fp, previous)).unwrap_or(false);
+        if !tracked {
+            revision += 1;
+            insert_entry(&mut *conn, profile_id, revision, previous, SOURCE_BASELINE, None, None).await?;
+        }
+        if same_fingerprint(previous, fingerprint) {
+            return Ok(None);
+        }
+    } else if latest_fp.as_ref().map(|fp| same_fingerprint(fp, fingerprint)).unwrap_or(false) {
+        return Ok(None);
+    }
+
+    revision += 1;
+    let entry = insert_entry(conn, profile_id, revision, fingerprint, source, author, launch_seed).await?;
+    Ok(Some(entry))
+}
+
+async fn insert_entry(
+    conn: &mut SqliteConnection,
+    profile_id: &str,
+    revision: i64,
+    fingerprint: &Fingerprint,
+    source: &str,
+    author: Option<&str>,
+    launch_seed: Option<&str>,
+) -> Result<FingerprintHistoryEntry> {
+    let entry = FingerprintHistoryEntry {
+        id: uuid::Uuid::new_v4().to_string(),
+        profile_id: profile_id.to_string(),
+        revision,
+        fingerprint: fingerprint.clone(),
+        source: source.to_string(),
+        author: author.map(|s| s.to_string()),
+        launch_seed: launch_seed.map(|s| s.to_string()),
+        created_at: Utc::now().to_rfc3339(),
+    };
+
+    sqlx::query(
+        r#"
+        INSERT INTO fingerprint_history (id, profile_id, revision, fingerprint, source, author, launch_seed, created_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(&entry.id)
+    .bind(&entry.profile_id)
+    .bind(entry.revision)
+    .bind(serde_json::to_string(&entry.fingerprint)?)
+    .bind(&entry.source)
+    .bind(&entry.author)
+    .bind(&entry.launch_seed)
+    .bind(&entry.created_at)
+    .execute(conn)
+    .await?;
+
+    Ok(entry)
+}
+
+/// 指纹历史服务
+pub struct FingerprintHistoryService {
+    pool: SqlitePool,
+}
+
+impl FingerprintHistoryService {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// 获取 Profile 的指纹修订历史（最新在前）
+    pub async fn list(&self, profile_id: &str) -> Result<Vec<FingerprintHistoryEntry>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, profile_id, revision, fingerprint, source, author, launch_seed, created_at
+            FROM fingerprint_history
+            WHERE profile_id = ?
+            ORDER BY revision DESC
+            "#,
+        )
+        .bind(profile_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(Self::row_to_entry).collect()
+    }
+
+    /// 获取单条修订记录
+    pub async fn get(&self, id: &str) -> Result<FingerprintHistoryEntry> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, profile_id, revision, fingerprint, source, author, launch_seed, created_at
+            FROM fingerprint_history
+            WHERE id = ?
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("指纹修订记录不存在: {}", id))?;
+
+        Self::row_to_entry(&row)
+    }
+
+    /// 逐字段对比同一 Profile 的两个修订版本
+    pub async fn diff(&self, from_id: &str, to_id: &str) -> Result<FingerprintRevisionDiff> {
+        let from = self.get(from_id).await?;
+        let to = self.get(to_id).await?;
+        if from.profile_id != to.profile_id {
+            return Err(anyhow::anyhow!("只能对比同一环境的指纹修订版本"));
+        }
+
+        Ok(FingerprintRevisionDiff {
+            changes: diff_fingerprints(&from.fingerprint, &to.fingerprint),
+            profile_id: from.profile_id,
+            from_revision: from.revision,
+            to_revision: to.revision,
+        })
+    }
+
+    fn row_to_entry(row: &sqlx::sqlite::SqliteRow) -> Result<FingerprintHistoryEntry> {
+        let fingerprint_str: String = row.try_get("fingerprint")?;
+        Ok(FingerprintHistoryEntry {
+            id: row.try_get("id")?,
+            profile_id: row.try_get("profile_id")?,
+            revision: row.try_get("revision")?,
+            fingerprint: serde_json::from_str(&fingerprint_str)?,
+            source: row.try_get("source")?,
+            author: row.try_get("author")?,
+            launch_seed: row.try_get("launch_seed")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fingerprint(user_agent: &str) -> Fingerprint {
+        serde_json::from_value(serde_json::json!({
+            "seed": 1,
+            "platform": "windows",
+            "browser": "chrome",
+            "user_agent": user_agent,
+            "hardware_concurrency": 8,
+            "device_memory": 8,
+            "screen_resolution": "1920x1080",
+            "timezone": "Asia/Shanghai",
+            "language": "zh-CN",
+            "canvas_noise": true,
+            "webgl_noise": true,
+            "audio_noise": true
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_diff_fingerprints() {
+        let old = fingerprint("UA/1");
+        let mut new = fingerprint("UA/2");
+        new.hardware_concurrency = 16;
+        new.webgl_vendor = Some("Google Inc. (NVIDIA)".to_string());
+
+        let changes = diff_fingerprints(&old, &new);
+        let fields: Vec<&str> = changes.iter().map(|c| c.field.as_str()).collect();
+        assert_eq!(fields, vec!["hardware_concurrency", "user_agent", "webgl_vendor"]);
+        assert_eq!(changes[1].old_value, serde_json::json!("UA/1"));
+        assert_eq!(changes[2].old_value, serde_json::Value::Null);
+
+        assert!(diff_fingerprints(&old, &old.clone()).is_empty());
+    }
+
+    #[test]
+    fn test_source_for_edit() {
+        let before = fingerprint("UA/1");
+        let mut edited = before.clone();
+        edited.language = "en-US".to_string();
+        assert_eq!(source_for_edit(&before, &edited), SOURCE_MANUAL_EDIT);
+
+        let mut templated = before.clone();
+        templated.template_id = Some("win11_desktop".to_string());
+        templated.template_revision = Some(1);
+        assert_eq!(source_for_edit(&before, &templated), SOURCE_TEMPLATE);
+
+        let mut edited_after_template = templated.clone();
+        edited_after_template.language = "en-US".to_string();
+        assert_eq!(source_for_edit(&templated, &edited_after_template), SOURCE_MANUAL_EDIT);
+    }
+}
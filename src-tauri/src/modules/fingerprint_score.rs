@@ -0,0 +1,87 @@
+// Fingerprint Score - 指纹真实度评分
+// 保存每个环境最近一次校验结果，用于列表展示和启动拦截
+use anyhow::Result;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool};
+
+use crate::modules::fingerprint::ValidationResult;
+
+/// 启动拦截阈值设置项（0 表示不拦截）
+pub const MIN_SCORE_SETTING_KEY: &str = "fingerprint_min_score";
+
+/// 环境的指纹评分
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileFingerprintScore {
+    pub profile_id: String,
+    pub score: u32,
+    pub valid: bool,
+    pub result: ValidationResult,
+    pub checked_at: String,
+}
+
+/// 指纹评分服务
+pub struct FingerprintScoreService {
+    pool: SqlitePool,
+}
+
+impl FingerprintScoreService {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// 保存校验结果（覆盖上一次）
+    pub async fn save(&self, profile_id: &str, result: &ValidationResult) -> Result<ProfileFingerprintScore> {
+        let score = ProfileFingerprintScore {
+            profile_id: profile_id.to_string(),
+            score: result.score,
+            valid: result.valid,
+            result: result.clone(),
+            checked_at: Utc::now().to_rfc3339(),
+        };
+
+        sqlx::query(
+            r#"
+            INSERT OR REPLACE INTO profile_fingerprint_scores (profile_id, score, valid, result, checked_at)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&score.profile_id)
+        .bind(score.score as i64)
+        .bind(score.valid)
+        .bind(serde_json::to_string(&score.result)?)
+        .bind(&score.checked_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(score)
+    }
+
+    /// 获取环境最近一次评分
+    pub async fn get(&self, profile_id: &str) -> Result<Option<ProfileFingerprintScore>> {
+        let row = sqlx::query(
+            r#"
+            SELECT profile_id, score, valid, result, checked_at
+            FROM profile_fingerprint_scores
+            WHERE profile_id = ?
+            "#,
+        )
+        .bind(profile_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.as_ref().map(Self::row_to_score).transpose()
+    }
+
+    fn row_to_score(row: &sqlx::sqlite::SqliteRow) -> Result<ProfileFingerprintScore> {
+        let result_str: String = row.try_get("result")?;
+        let score: i64 = row.try_get("score")?;
+        Ok(ProfileFingerprintScore {
+            profile_id: row.try_get("profile_id")?,
+            score: score as u32,
+            valid: row.try_get("valid")?,
+            result: serde_json::from_str(&result_str)?,
+            checked_at: row.try_get("checked_at")?,
+        })
+    }
+}
@@ -3,13 +3,64 @@
 
 use anyhow::{Context, Result};
 use futures_util::StreamExt;
+use sha2::{Digest, Sha256};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::fs::{self, File};
 use tokio::io::AsyncWriteExt;
 use tokio::sync::Mutex;
 use tracing::{error, info, warn};
 
+use super::config::DOWNLOAD_RETRY_COUNT;
+use super::app_updater::{install_kernel_package, KernelUpdateOutcome, KernelUpdateStatus};
+use super::kernel_registry::compare_versions;
+use super::signing::{self, TrustedKey};
+
+/// 当前平台的内核目录名 (kernel/{platform}/{version})
+#[cfg(target_os = "windows")]
+pub const KERNEL_PLATFORM_DIR: &str = "win32";
+#[cfg(target_os = "macos")]
+pub const KERNEL_PLATFORM_DIR: &str = "mac";
+#[cfg(target_os = "linux")]
+pub const KERNEL_PLATFORM_DIR: &str = "linux64";
+
+/// 应用资源目录（由 setup 设置，用于定位打包后的内嵌内核）
+static RESOURCE_DIR: std::sync::OnceLock<PathBuf> = std::sync::OnceLock::new();
+
+/// 下载临时文件所在目录（位于内核基础目录下）
+const DOWNLOAD_DIR_NAME: &str = ".downloads";
+
+/// 超过该时长未收到数据视为下载中断
+const STALL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 默认重试间隔（按重试次数递增）
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// 正在写入的下载临时文件，防止并发下载写同一文件
+static ACTIVE_DOWNLOADS: std::sync::Mutex<Vec<PathBuf>> = std::sync::Mutex::new(Vec::new());
+
+/// 占用下载临时文件，释放时自动移除
+struct DownloadSlot(PathBuf);
+
+impl DownloadSlot {
+    fn acquire(path: &Path) -> Result<Self> {
+        let mut active = ACTIVE_DOWNLOADS.lock().unwrap_or_else(|e| e.into_inner());
+        if active.iter().any(|p| p == path) {
+            return Err(anyhow::anyhow!("该内核版本正在下载中"));
+        }
+        active.push(path.to_path_buf());
+        Ok(Self(path.to_path_buf()))
+    }
+}
+
+impl Drop for DownloadSlot {
+    fn drop(&mut self) {
+        let mut active = ACTIVE_DOWNLOADS.lock().unwrap_or_else(|e| e.into_inner());
+        active.retain(|p| p != &self.0);
+    }
+}
+
 /// 下载进度信息
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct DownloadProgress {
@@ -44,6 +95,18 @@ pub struct KernelVersionInfo {
     pub source: String,
     pub files_count: u32,
     pub total_size_bytes: u64,
+    /// 内核压缩包 SHA256（可带 "sha256:" 前缀）
+    #[serde(default)]
+    pub sha256: Option<String>,
+    /// Ed25519 签名（base64），签名内容见 `kernel_signature_message`
+    #[serde(default)]
+    pub signature: Option<String>,
+    /// 备用下载地址
+    #[serde(default)]
+    pub mirrors: Vec<String>,
+    /// 安装时的更新通道（"beta" 内核只对固定了该版本的环境 / 分组生效）
+    #[serde(default)]
+    pub channel: Option<String>,
 }
 
 /// 内核下载器
@@ -54,6 +117,10 @@ pub struct KernelDownloader {
     kernel_version: String,
     /// 当前进度
     progress: Arc<Mutex<DownloadProgress>>,
+    /// 签名校验公钥
+    trusted_keys: Vec<TrustedKey>,
+    /// 重试间隔
+    retry_delay: Duration,
 }
 
 impl KernelDownloader {
@@ -73,6 +140,8 @@ impl KernelDownloader {
                 status: DownloadStatus::Idle,
                 message: String::new(),
             })),
+            trusted_keys: signing::trusted_keys(),
+            retry_delay: RETRY_DELAY,
         }
     }
 
@@ -113,19 +182,34 @@ impl KernelDownloader {
                 source: "local".to_string(),
                 files_count: 0,
                 total_size_bytes: 0,
+                sha256: None,
+                signature: None,
+                mirrors: Vec::new(),
+                channel: None,
             });
         }
         
         None
     }
 
-    /// 下载并安装内核
-    pub async fn download_and_install(
+    /// 下载并安装内核（断点续传 + 镜像切换 + SHA256 / 签名校验）
+    ///
+    /// 校验通过后经暂存目录解压、原子替换并冒烟测试（见 `app_updater::install_kernel_package`），
+    /// 失败时恢复旧内核；返回安装结果，由调用方记录更新历史
+    ///
+    /// # Arguments
+    /// * `urls` - 下载地址，按顺序尝试（主地址在前，镜像在后）
+    /// * `expected` - 期望的内核信息，必须包含 sha256（缺失时直接报错），提供 signature 时同时校验签名
+    pub async fn download_and_install_verified(
         &self,
-        download_url: &str,
+        urls: &[String],
+        expected: &KernelVersionInfo,
         progress_callback: impl Fn(DownloadProgress) + Send + 'static,
-    ) -> Result<()> {
-        info!("Starting kernel download from: {}", download_url);
+    ) -> Result<KernelUpdateOutcome> {
+        if expected.sha256.as_deref().is_none_or(|hash| hash.trim().is_empty()) {
+            return Err(anyhow::anyhow!("Kernel {} has no SHA256 to verify against", expected.version));
+        }
+        info!("Starting kernel {} download from: {:?}", self.kernel_version, urls);
 
         // 更新状态
         {
@@ -135,13 +219,23 @@ impl KernelDownloader {
             progress_callback(progress.clone());
         }
 
-        // 创建临时下载文件
-        let temp_dir = std::env::temp_dir();
-        let temp_file = temp_dir.join("chromium-kernel-download.zip");
+        // 每个下载使用独立的临时文件，保留在内核目录下以便中断后续传
+        let temp_file = self.download_temp_path(expected);
+        let _slot = DownloadSlot::acquire(&temp_file)?;
+        if let Some(parent) = temp_file.parent() {
+            fs::create_dir_all(parent)
+                .await
+                .context("Failed to create download directory")?;
+        }
 
         // 下载文件
-        self.download_file(download_url, &temp_file, &progress_callback)
-            .await?;
+        if let Err(e) = self.fetch_verified(urls, expected, &temp_file, &progress_callback).await {
+            let mut progress = self.progress.lock().await;
+            progress.status = DownloadStatus::Failed;
+            progress.message = e.to_string();
+            progress_callback(progress.clone());
+            return Err(e);
+        }
 
         // 更新状态为解压中
         {
@@ -151,79 +245,165 @@ impl KernelDownloader {
             progress_callback(progress.clone());
         }
 
-        // 确保内核目录存在
-        let kernel_dir = self.get_kernel_dir();
-        fs::create_dir_all(&kernel_dir)
-            .await
-            .context("Failed to create kernel directory")?;
-
-        // 解压文件
-        self.extract_zip(&temp_file, &kernel_dir).await?;
-
-        // 清理临时文件
-        let _ = fs::remove_file(&temp_file).await;
+        // 解压到暂存目录 → 替换 → 冒烟测试（成功后删除临时文件）
+        let outcome = install_kernel_package(&temp_file, &self.get_kernel_dir(), expected).await?;
 
-        // 更新状态为完成
+        // 更新状态
         {
             let mut progress = self.progress.lock().await;
-            progress.status = DownloadStatus::Completed;
-            progress.message = "Kernel installation completed!".to_string();
+            if outcome.status == KernelUpdateStatus::Installed {
+                progress.status = DownloadStatus::Completed;
+                progress.message = "Kernel installation completed!".to_string();
+            } else {
+                progress.status = DownloadStatus::Failed;
+                progress.message = outcome.message.clone().unwrap_or_default();
+            }
             progress_callback(progress.clone());
         }
 
-        info!("Kernel installation completed successfully");
-        Ok(())
+        info!("Kernel {} installation finished: {}", self.kernel_version, outcome.status.as_str());
+        Ok(outcome)
     }
 
-    /// Download file with progress callback
-    async fn download_file(
+    /// 下载临时文件路径：按版本和期望校验值区分，同一下载重试时复用以续传
+    fn download_temp_path(&self, expected: &KernelVersionInfo) -> PathBuf {
+        let identity = expected.sha256.as_deref().map(normalize_sha256).unwrap_or_default();
+        self.kernel_base_dir
+            .join(DOWNLOAD_DIR_NAME)
+            .join(format!("chromium-{}-{}.zip.part", self.kernel_version, &identity[..12.min(identity.len())]))
+    }
+
+    /// 依次尝试各下载地址，每个地址重试若干次（断点续传），下载完成后校验
+    async fn fetch_verified(
         &self,
-        url: &str,
+        urls: &[String],
+        expected: &KernelVersionInfo,
         dest: &Path,
         progress_callback: &impl Fn(DownloadProgress),
     ) -> Result<()> {
+        if urls.is_empty() {
+            return Err(anyhow::anyhow!("No download URL provided"));
+        }
+
         // Build client with User-Agent and redirect support
         let client = reqwest::Client::builder()
             .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36")
             .redirect(reqwest::redirect::Policy::limited(10))
+            .connect_timeout(std::time::Duration::from_secs(30))
             .build()
             .context("Failed to build HTTP client")?;
 
-        info!("Sending download request to: {}", url);
+        let mut errors = Vec::new();
+        for url in urls {
+            for attempt in 1..=DOWNLOAD_RETRY_COUNT {
+                match self.download_file(&client, url, dest, progress_callback).await {
+                    Ok(()) => match verify_download(dest, expected, &self.trusted_keys).await {
+                        Ok(()) => return Ok(()),
+                        Err(e) => {
+                            // 内容损坏：丢弃已下载数据，换下一个地址
+                            warn!("Kernel download from {} failed verification: {}", url, e);
+                            let _ = fs::remove_file(dest).await;
+                            errors.push(format!("{}: {}", url, e));
+                            break;
+                        }
+                    },
+                    Err(e) => {
+                        warn!("Kernel download from {} failed (attempt {}/{}): {}", url, attempt, DOWNLOAD_RETRY_COUNT, e);
+                        if attempt == DOWNLOAD_RETRY_COUNT {
+                            errors.push(format!("{}: {}", url, e));
+                        } else {
+                            tokio::time::sleep(self.retry_delay * attempt as u32).await;
+                        }
+                    }
+                }
+            }
+        }
 
-        let response = client
-            .get(url)
+        Err(anyhow::anyhow!("All download sources failed: {}", errors.join("；")))
+    }
+
+    /// Download file with progress callback, resuming from an existing partial file
+    async fn download_file(
+        &self,
+        client: &reqwest::Client,
+        url: &str,
+        dest: &Path,
+        progress_callback: &impl Fn(DownloadProgress),
+    ) -> Result<()> {
+        let existing = fs::metadata(dest).await.map(|m| m.len()).unwrap_or(0);
+
+        info!("Sending download request to: {} (resume from {} bytes)", url, existing);
+
+        let mut request = client.get(url);
+        if existing > 0 {
+            request = request.header(reqwest::header::RANGE, format!("bytes={}-", existing));
+        }
+        let response = request
             .send()
             .await
             .context("Failed to send download request")?;
 
         info!("Response status: {}", response.status());
 
-        if !response.status().is_success() {
+        let content_range = response
+            .headers()
+            .get(reqwest::header::CONTENT_RANGE)
+            .and_then(|v| v.to_str().ok())
+            .map(parse_content_range);
+
+        let status = response.status();
+        let (mut file, mut downloaded, total_size) = if existing > 0
+            && status == reqwest::StatusCode::RANGE_NOT_SATISFIABLE
+        {
+            // 请求的起点超出文件长度：本地文件已完整（总长度一致时）
+            return match content_range.and_then(|(_, total)| total) {
+                Some(total) if total == existing => Ok(()),
+                _ => {
+                    let _ = fs::remove_file(dest).await;
+                    Err(anyhow::anyhow!("Partial file is larger than the remote file"))
+                }
+            };
+        } else if existing > 0 && status == reqwest::StatusCode::PARTIAL_CONTENT {
+            if content_range.and_then(|(start, _)| start) != Some(existing) {
+                let _ = fs::remove_file(dest).await;
+                return Err(anyhow::anyhow!("Server resumed at an unexpected offset"));
+            }
+            let file = fs::OpenOptions::new()
+                .append(true)
+                .open(dest)
+                .await
+                .context("Failed to open partial download file")?;
+            (file, existing, response.content_length().map(|len| len + existing))
+        } else if status.is_success() {
+            // 服务器不支持续传时从头下载
+            let file = File::create(dest)
+                .await
+                .context("Failed to create download file")?;
+            (file, 0, response.content_length())
+        } else {
             return Err(anyhow::anyhow!(
                 "Download failed with status: {}",
-                response.status()
+                status
             ));
-        }
-
-        let total_size = response.content_length();
+        };
 
         {
             let mut progress = self.progress.lock().await;
             progress.total = total_size;
         }
 
-        let mut file = File::create(dest)
-            .await
-            .context("Failed to create download file")?;
-
-        let mut downloaded: u64 = 0;
+        let resumed_from = downloaded;
         let mut stream = response.bytes_stream();
         let start_time = std::time::Instant::now();
         let mut last_update = std::time::Instant::now();
 
-        while let Some(chunk) = stream.next().await {
-            let chunk = chunk.context("Failed to read chunk")?;
+        loop {
+            // 长时间无数据视为连接中断，交给重试续传
+            let chunk = match tokio::time::timeout(STALL_TIMEOUT, stream.next()).await {
+                Ok(Some(chunk)) => chunk.context("Failed to read chunk")?,
+                Ok(None) => break,
+                Err(_) => return Err(anyhow::anyhow!("Download stalled")),
+            };
             file.write_all(&chunk)
                 .await
                 .context("Failed to write chunk")?;
@@ -234,7 +414,7 @@ impl KernelDownloader {
             if last_update.elapsed().as_millis() >= 100 {
                 let elapsed = start_time.elapsed().as_secs_f64();
                 let speed = if elapsed > 0.0 {
-                    (downloaded as f64 / elapsed) as u64
+                    ((downloaded - resumed_from) as f64 / elapsed) as u64
                 } else {
                     0
                 };
@@ -254,6 +434,17 @@ impl KernelDownloader {
         }
 
         file.flush().await?;
+
+        if let Some(total) = total_size {
+            if downloaded < total {
+                return Err(anyhow::anyhow!(
+                    "Connection closed early ({} of {} bytes)",
+                    downloaded,
+                    total
+                ));
+            }
+        }
+
         info!("Download completed: {} bytes", downloaded);
         Ok(())
     }
@@ -305,6 +496,9 @@ impl KernelDownloader {
                 }
             }
 
+            #[cfg(unix)]
+            fix_executable_permissions(&dest_dir)?;
+
             info!("Extraction completed");
             Ok::<_, anyhow::Error>(())
         })
@@ -327,20 +521,7 @@ impl KernelDownloader {
 
     /// Get kernel executable path
     pub fn get_kernel_exe_path(&self) -> PathBuf {
-        let kernel_dir = self.get_kernel_dir();
-        // Kernel executable path varies by OS
-        #[cfg(target_os = "windows")]
-        {
-            kernel_dir.join("chrome.exe")
-        }
-        #[cfg(target_os = "macos")]
-        {
-            kernel_dir.join("Chromium.app").join("Contents").join("MacOS").join("Chromium")
-        }
-        #[cfg(target_os = "linux")]
-        {
-            kernel_dir.join("chrome")
-        }
+        kernel_exe_path(&self.get_kernel_dir())
     }
 
     /// Get bundled kernel path (from resources)
@@ -353,29 +534,26 @@ impl KernelDownloader {
     /// Get bundled kernel path with specific version
     /// If version is None, returns the latest version found
     pub fn get_bundled_kernel_path_with_version(version: Option<&str>) -> Option<PathBuf> {
-        let exe_path = std::env::current_exe().ok()?;
-        let exe_dir = exe_path.parent()?;
-        
-        #[cfg(target_os = "windows")]
-        {
-            // Check dev mode path
-            let dev_base = exe_dir.parent()?.parent()?.join("resources").join("kernel").join("win32");
-            if let Some(path) = Self::find_kernel_in_dir(&dev_base, version) {
-                return Some(path);
-            }
-            
-            // Check production path
-            let prod_base = exe_dir.join("resources").join("kernel").join("win32");
-            if let Some(path) = Self::find_kernel_in_dir(&prod_base, version) {
-                return Some(path);
-            }
+        Self::bundled_kernel_dirs()
+            .iter()
+            .find_map(|base| Self::find_kernel_in_dir(base, version))
+    }
+
+    /// 设置应用资源目录（Tauri resource_dir），仅首次设置生效
+    pub fn set_resource_dir(dir: PathBuf) {
+        let _ = RESOURCE_DIR.set(dir);
+    }
+
+    /// 内嵌内核的基础目录（开发模式优先，其次为生产模式）
+    pub fn bundled_kernel_dirs() -> Vec<PathBuf> {
+        match std::env::current_exe().ok().and_then(|p| p.parent().map(|d| d.to_path_buf())) {
+            Some(exe_dir) => bundled_kernel_dirs_for(&exe_dir, RESOURCE_DIR.get().map(PathBuf::as_path)),
+            None => Vec::new(),
         }
-        
-        None
     }
 
     /// Find kernel in directory, supporting versioned subdirectories
-    /// Directory structure: base_dir/{version}/chrome.exe
+    /// Directory structure: base_dir/{version}/chrome.exe (Linux: chrome, macOS: Chromium.app)
     fn find_kernel_in_dir(base_dir: &Path, version: Option<&str>) -> Option<PathBuf> {
         if !base_dir.exists() {
             return None;
@@ -383,87 +561,47 @@ impl KernelDownloader {
 
         // If specific version requested
         if let Some(ver) = version {
-            let kernel_path = base_dir.join(ver).join("chrome.exe");
+            let kernel_path = kernel_exe_path(&base_dir.join(ver));
             if kernel_path.exists() {
                 return Some(kernel_path);
             }
             return None;
         }
 
-        // Find latest version (sort by version number)
-        let mut versions = Vec::new();
+        // Find latest version (semantic version order, "139" > "99")
+        Self::list_versions_in_dir(base_dir)
+            .into_iter()
+            .next()
+            .map(|ver| kernel_exe_path(&base_dir.join(ver)))
+    }
+
+    /// 列出目录下包含内核可执行文件的版本子目录，按版本号降序
+    pub fn list_versions_in_dir(base_dir: &Path) -> Vec<String> {
+        let mut versions: Vec<String> = Vec::new();
         if let Ok(entries) = std::fs::read_dir(base_dir) {
             for entry in entries.flatten() {
-                if entry.path().is_dir() {
+                if entry.path().is_dir() && kernel_exe_path(&entry.path()).exists() {
                     if let Some(ver_name) = entry.file_name().to_str() {
-                        let kernel_path = entry.path().join("chrome.exe");
-                        if kernel_path.exists() {
-                            versions.push((ver_name.to_string(), kernel_path));
-                        }
+                        versions.push(ver_name.to_string());
                     }
                 }
             }
         }
-
-        if versions.is_empty() {
-            return None;
-        }
-
-        // Sort by version string (descending) and return the latest
-        versions.sort_by(|a, b| b.0.cmp(&a.0));
-        Some(versions[0].1.clone())
+        versions.sort_by(|a, b| compare_versions(b, a));
+        versions
     }
 
     /// List all bundled kernel versions
     pub fn list_bundled_versions() -> Vec<String> {
-        let exe_path = std::env::current_exe().ok();
-        if exe_path.is_none() {
-            return Vec::new();
-        }
-        let exe_dir = exe_path.unwrap().parent().map(|p| p.to_path_buf());
-        if exe_dir.is_none() {
-            return Vec::new();
-        }
-        let exe_dir = exe_dir.unwrap();
-        
-        let mut versions = Vec::new();
-        
-        #[cfg(target_os = "windows")]
-        {
-            // Check both dev and production paths
-            let dev_base = exe_dir.parent()
-                .and_then(|p| p.parent())
-                .map(|p| p.join("resources").join("kernel").join("win32"));
-            if let Some(base) = dev_base {
-                if let Ok(entries) = std::fs::read_dir(&base) {
-                    for entry in entries.flatten() {
-                        if entry.path().is_dir() {
-                            if let Some(ver) = entry.file_name().to_str() {
-                                if entry.path().join("chrome.exe").exists() {
-                                    versions.push(ver.to_string());
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-            
-            // Also check production path
-            let prod_base = exe_dir.join("resources").join("kernel").join("win32");
-            if let Ok(entries) = std::fs::read_dir(&prod_base) {
-                for entry in entries.flatten() {
-                    if entry.path().is_dir() {
-                        if let Some(ver) = entry.file_name().to_str() {
-                            if entry.path().join("chrome.exe").exists() && !versions.contains(&ver.to_string()) {
-                                versions.push(ver.to_string());
-                            }
-                        }
-                    }
+        let mut versions: Vec<String> = Vec::new();
+        for base in Self::bundled_kernel_dirs() {
+            for ver in Self::list_versions_in_dir(&base) {
+                if !versions.contains(&ver) {
+                    versions.push(ver);
                 }
             }
         }
-        
-        versions.sort_by(|a, b| b.cmp(a)); // Sort descending
+        versions.sort_by(|a, b| compare_versions(b, a)); // Sort descending
         versions
     }
 
@@ -644,6 +782,164 @@ impl KernelDownloader {
     }
 }
 
+/// 内嵌内核的候选目录（按优先级，去重）
+///
+/// - 开发模式: src-tauri/resources/kernel/{platform}、项目根目录/resources/kernel/{platform}
+/// - 生产模式: Tauri 资源目录，以及可执行文件旁的 resources/kernel/{platform}
+///   (macOS 为 Contents/Resources)
+fn bundled_kernel_dirs_for(exe_dir: &Path, resource_dir: Option<&Path>) -> Vec<PathBuf> {
+    let mut roots: Vec<PathBuf> = Vec::new();
+    // Check dev mode path (target/{profile}/ -> src-tauri/ -> project root)
+    if let Some(src_tauri) = exe_dir.parent().and_then(|p| p.parent()) {
+        roots.push(src_tauri.join("resources"));
+        if let Some(project_root) = src_tauri.parent() {
+            roots.push(project_root.join("resources"));
+        }
+    }
+    // Check production path
+    if let Some(resource_dir) = resource_dir {
+        roots.push(resource_dir.join("resources"));
+        roots.push(resource_dir.to_path_buf());
+    }
+    roots.push(exe_dir.join("resources"));
+    #[cfg(target_os = "macos")]
+    if let Some(contents) = exe_dir.parent() {
+        roots.push(contents.join("Resources").join("resources"));
+        roots.push(contents.join("Resources"));
+    }
+
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    for root in roots {
+        let dir = root.join("kernel").join(KERNEL_PLATFORM_DIR);
+        if !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    dirs
+}
+
+/// 补齐内核可执行权限（Windows 上打包的 zip 不含 Unix 权限位）
+///
+/// Linux: 主程序及 chrome_crashpad_handler 等辅助程序；macOS: .app 中 MacOS / Helpers 目录下的文件
+#[cfg(unix)]
+pub fn fix_executable_permissions(kernel_dir: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fn make_executable(path: &Path) -> Result<()> {
+        let mut perms = std::fs::metadata(path)?.permissions();
+        if perms.mode() & 0o111 != 0o111 {
+            perms.set_mode(perms.mode() | 0o755);
+            std::fs::set_permissions(path, perms)?;
+        }
+        Ok(())
+    }
+
+    fn walk(dir: &Path, in_exec_dir: bool) -> Result<()> {
+        for entry in std::fs::read_dir(dir)?.flatten() {
+            let path = entry.path();
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                let name = entry.file_name();
+                walk(&path, name == "MacOS" || name == "Helpers")?;
+            } else if file_type.is_file() && in_exec_dir {
+                make_executable(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    let exe = kernel_exe_path(kernel_dir);
+    if exe.exists() {
+        make_executable(&exe)?;
+    }
+    if cfg!(target_os = "macos") {
+        walk(kernel_dir, false)?;
+    } else {
+        for helper in ["chrome_crashpad_handler", "chrome-wrapper", "nacl_helper", "nacl_helper_bootstrap"] {
+            let path = kernel_dir.join(helper);
+            if path.exists() {
+                make_executable(&path)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 内核签名内容：绑定版本号与压缩包 SHA256，防止用旧版本包冒充新版本
+pub fn kernel_signature_message(version: &str, sha256: &str) -> String {
+    format!("chromium-kernel:{}:{}", version, normalize_sha256(sha256))
+}
+
+fn normalize_sha256(value: &str) -> String {
+    value.trim().trim_start_matches("sha256:").to_ascii_lowercase()
+}
+
+/// 解析 Content-Range 头，返回 (起始位置, 总长度)，如 "bytes 100-999/1000"、"bytes */1000"
+fn parse_content_range(value: &str) -> (Option<u64>, Option<u64>) {
+    let Some(range) = value.trim().strip_prefix("bytes ") else {
+        return (None, None);
+    };
+    let (span, total) = range.split_once('/').unwrap_or((range, "*"));
+    let start = span.split_once('-').and_then(|(start, _)| start.trim().parse().ok());
+    (start, total.trim().parse().ok())
+}
+
+/// 计算文件 SHA256
+async fn file_sha256(path: &Path) -> Result<String> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let mut file = std::fs::File::open(&path).context("Failed to open downloaded file")?;
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut file, &mut hasher)?;
+        Ok::<_, anyhow::Error>(format!("{:x}", hasher.finalize()))
+    })
+    .await?
+}
+
+/// 按期望的内核信息校验下载文件（SHA256 必须提供，签名可选）
+async fn verify_download(path: &Path, info: &KernelVersionInfo, keys: &[TrustedKey]) -> Result<()> {
+    let expected_hash = info
+        .sha256
+        .as_deref()
+        .map(normalize_sha256)
+        .filter(|hash| !hash.is_empty())
+        .ok_or_else(|| anyhow::anyhow!("Kernel {} has no SHA256 to verify against", info.version))?;
+
+    let actual = file_sha256(path).await?;
+    if actual != expected_hash {
+        return Err(anyhow::anyhow!(
+            "SHA256 mismatch (expected {}..., got {}...)",
+            &expected_hash[..8.min(expected_hash.len())],
+            &actual[..8]
+        ));
+    }
+    if let Some(signature) = info.signature.as_deref() {
+        let key_id = signing::verify_with_keys(
+            keys,
+            kernel_signature_message(&info.version, &actual).as_bytes(),
+            signature,
+        )?;
+        info!("Kernel package signature verified with key {}", key_id);
+    }
+    Ok(())
+}
+
+/// 内核目录中的可执行文件路径（按平台区分）
+pub fn kernel_exe_path(kernel_dir: &Path) -> PathBuf {
+    #[cfg(target_os = "windows")]
+    {
+        kernel_dir.join("chrome.exe")
+    }
+    #[cfg(target_os = "macos")]
+    {
+        kernel_dir.join("Chromium.app").join("Contents").join("MacOS").join("Chromium")
+    }
+    #[cfg(target_os = "linux")]
+    {
+        kernel_dir.join("chrome")
+    }
+}
+
 /// 默认内核下载 URL
 pub const DEFAULT_KERNEL_DOWNLOAD_URL: &str = "";
 
@@ -659,3 +955,219 @@ pub fn get_kernel_download_url(base_url: &str, version: &str) -> String {
     format!("{}/chromium-kernel-{}-v{}.zip", base_url, platform, version)
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::signing::tests::{sign, test_key_pair};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// 本地 HTTP 下载源：支持 Range；`cut_first` 为 Some(n) 时第一次响应只发送 n 字节后断开
+    async fn spawn_file_server(
+        body: Vec<u8>,
+        status: u16,
+        cut_first: Option<usize>,
+    ) -> (String, tokio::sync::mpsc::UnboundedReceiver<Option<u64>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let mut cut = cut_first;
+            loop {
+                let Ok((mut socket, _)) = listener.accept().await else { break };
+                let mut buf = vec![0u8; 4096];
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]).to_string();
+                let range_start = request
+                    .lines()
+                    .find_map(|l| l.strip_prefix("range: bytes=").or_else(|| l.strip_prefix("Range: bytes=")))
+                    .and_then(|r| r.trim_end_matches('-').parse::<u64>().ok());
+                let _ = tx.send(range_start);
+
+                if status != 200 {
+                    let _ = socket
+                        .write_all(format!("HTTP/1.1 {} Error\r\nContent-Length: 0\r\n\r\n", status).as_bytes())
+                        .await;
+                    continue;
+                }
+                let start = range_start.unwrap_or(0) as usize;
+                let (head, part) = match range_start {
+                    Some(_) => (
+                        format!(
+                            "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\nContent-Range: bytes {}-{}/{}\r\n\r\n",
+                            body.len() - start,
+                            start,
+                            body.len() - 1,
+                            body.len()
+                        ),
+                        &body[start..],
+                    ),
+                    None => (format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", body.len()), &body[..]),
+                };
+                let _ = socket.write_all(head.as_bytes()).await;
+                let send = cut.take().map(|n| n.min(part.len())).unwrap_or(part.len());
+                let _ = socket.write_all(&part[..send]).await;
+                let _ = socket.shutdown().await;
+            }
+        });
+        (format!("http://127.0.0.1:{}/kernel.zip", port), rx)
+    }
+
+    fn test_downloader(base: &Path, keys: Vec<TrustedKey>) -> KernelDownloader {
+        let mut downloader = KernelDownloader::new(base.to_path_buf(), "146".to_string());
+        downloader.trusted_keys = keys;
+        downloader.retry_delay = Duration::from_millis(10);
+        downloader
+    }
+
+    fn expected_info(body: &[u8]) -> KernelVersionInfo {
+        KernelVersionInfo {
+            version: "146.0.7680.80".to_string(),
+            build_date: "2026-10-01".to_string(),
+            platform: "win64".to_string(),
+            source: "remote".to_string(),
+            files_count: 0,
+            total_size_bytes: body.len() as u64,
+            sha256: Some(format!("sha256:{:x}", Sha256::digest(body))),
+            signature: None,
+            mirrors: Vec::new(),
+            channel: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_download_resumes_and_fails_over_to_mirror() {
+        let body: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let (broken, _) = spawn_file_server(Vec::new(), 503, None).await;
+        let (mirror, mut requests) = spawn_file_server(body.clone(), 200, Some(50_000)).await;
+
+        let (pair, key) = test_key_pair("test");
+        let mut info = expected_info(&body);
+        let digest = format!("{:x}", Sha256::digest(&body));
+        info.signature = Some(sign(&pair, kernel_signature_message(&info.version, &digest).as_bytes()));
+
+        let dir = tempfile::tempdir().unwrap();
+        let downloader = test_downloader(dir.path(), vec![key]);
+        let dest = dir.path().join("kernel.zip.part");
+        downloader
+            .fetch_verified(&[broken, mirror], &info, &dest, &|_| {})
+            .await
+            .unwrap();
+
+        assert_eq!(std::fs::read(&dest).unwrap(), body);
+        // 第一次请求从头下载，中断后从已下载位置续传
+        assert_eq!(requests.recv().await.unwrap(), None);
+        assert_eq!(requests.recv().await.unwrap(), Some(50_000));
+    }
+
+    #[tokio::test]
+    async fn test_download_rejects_corrupted_or_unsigned_package() {
+        let body = b"genuine kernel package".to_vec();
+        let (corrupted, _) = spawn_file_server(b"tampered kernel package".to_vec(), 200, None).await;
+        let (genuine, _) = spawn_file_server(body.clone(), 200, None).await;
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("kernel.zip.part");
+
+        // 校验失败的地址被跳过，换下一个镜像
+        let downloader = test_downloader(dir.path(), Vec::new());
+        let info = expected_info(&body);
+        downloader
+            .fetch_verified(&[corrupted.clone(), genuine], &info, &dest, &|_| {})
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), body);
+
+        let err = downloader
+            .fetch_verified(&[corrupted], &info, &dest, &|_| {})
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("SHA256 mismatch"));
+        assert!(!dest.exists());
+
+        // 签名不是受信任密钥签发的
+        let (other_pair, _) = test_key_pair("other");
+        let (_, trusted) = test_key_pair("trusted");
+        let mut signed = info.clone();
+        let digest = format!("{:x}", Sha256::digest(&body));
+        signed.signature = Some(sign(&other_pair, kernel_signature_message(&signed.version, &digest).as_bytes()));
+        std::fs::write(&dest, &body).unwrap();
+        assert!(verify_download(&dest, &signed, &[trusted]).await.is_err());
+
+        // 缺少 SHA256 时拒绝
+        let mut unhashed = info.clone();
+        unhashed.sha256 = None;
+        let err = verify_download(&dest, &unhashed, &[]).await.unwrap_err();
+        assert!(err.to_string().contains("no SHA256"));
+    }
+
+    #[test]
+    fn test_parse_content_range() {
+        assert_eq!(parse_content_range("bytes 100-999/1000"), (Some(100), Some(1000)));
+        assert_eq!(parse_content_range("bytes */1000"), (None, Some(1000)));
+        assert_eq!(parse_content_range("bytes 0-9/*"), (Some(0), None));
+        assert_eq!(parse_content_range("items 0-9/10"), (None, None));
+    }
+
+    /// 在 base/{version}/ 下创建当前平台布局的假内核
+    fn fake_kernel_layout(base: &Path, versions: &[&str]) {
+        for version in versions {
+            let exe = kernel_exe_path(&base.join(version));
+            std::fs::create_dir_all(exe.parent().unwrap()).unwrap();
+            std::fs::write(&exe, b"").unwrap();
+        }
+    }
+
+    #[test]
+    fn test_bundled_kernel_layout_discovery() {
+        let project = tempfile::tempdir().unwrap();
+        let exe_dir = project.path().join("src-tauri").join("target").join("debug");
+        let bundled = project.path().join("resources").join("kernel").join(KERNEL_PLATFORM_DIR);
+        fake_kernel_layout(&bundled, &["99", "146", "139"]);
+        // 缺少可执行文件的目录不算已安装
+        std::fs::create_dir_all(bundled.join("150")).unwrap();
+
+        let dirs = bundled_kernel_dirs_for(&exe_dir, Some(&project.path().join("app-resources")));
+        assert!(dirs.contains(&bundled));
+        assert!(dirs.contains(&project.path().join("app-resources").join("kernel").join(KERNEL_PLATFORM_DIR)));
+
+        assert_eq!(KernelDownloader::list_versions_in_dir(&bundled), vec!["146", "139", "99"]);
+        assert_eq!(
+            KernelDownloader::find_kernel_in_dir(&bundled, None).unwrap(),
+            kernel_exe_path(&bundled.join("146"))
+        );
+        assert_eq!(
+            KernelDownloader::find_kernel_in_dir(&bundled, Some("99")).unwrap(),
+            kernel_exe_path(&bundled.join("99"))
+        );
+        assert!(KernelDownloader::find_kernel_in_dir(&bundled, Some("150")).is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_fix_executable_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        fake_kernel_layout(dir.path(), &["146"]);
+        let kernel_dir = dir.path().join("146");
+        let exe = kernel_exe_path(&kernel_dir);
+        std::fs::set_permissions(&exe, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        fix_executable_permissions(&kernel_dir).unwrap();
+        assert_eq!(std::fs::metadata(&exe).unwrap().permissions().mode() & 0o111, 0o111);
+    }
+
+    #[test]
+    fn test_concurrent_downloads_use_separate_files() {
+        let downloader = KernelDownloader::new(PathBuf::from("/kernel"), "146".to_string());
+        let a = downloader.download_temp_path(&expected_info(b"package a"));
+        let b = downloader.download_temp_path(&expected_info(b"package b"));
+        assert_ne!(a, b);
+
+        let slot = DownloadSlot::acquire(&a).unwrap();
+        assert!(DownloadSlot::acquire(&a).is_err());
+        assert!(DownloadSlot::acquire(&b).is_ok());
+        drop(slot);
+        assert!(DownloadSlot::acquire(&a).is_ok());
+    }
+}
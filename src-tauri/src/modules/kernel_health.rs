@@ -0,0 +1,234 @@
+// Kernel Health - 内核安装后冒烟测试
+// 1. 内核版本的主版本号与期望一致：Windows 下 chrome.exe 的 `--version` 不向控制台输出（会直接启动浏览器），
+//    改为读取可执行文件的文件版本资源或同目录的 kernel_version.json；其他平台运行 `--version`
+// 2. 使用临时用户数据目录写入带标记 UA 的 bm_fingerprint.json，无头启动并输出页面中的 navigator.userAgent，
+//    确认内核确实读取并应用了指纹配置
+// 两个步骤都使用同一个临时用户数据目录，不会触碰用户的默认 profile
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::{info, warn};
+
+use super::config_writer::{ConfigWriter, FingerprintFileConfig};
+use super::kernel_registry::major_version;
+
+/// 单个冒烟测试步骤的超时时间
+pub const SMOKE_TEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 输出 UA 的测试页面
+const UA_PROBE_URL: &str = "data:text/html,<script>document.write('BM-UA['+navigator.userAgent+']')</script>";
+
+/// 冒烟测试结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmokeTestReport {
+    /// 内核版本（`--version` 输出，Windows 下为文件版本；无法获取时为空）
+    pub version_output: String,
+    /// 页面中的 UA 是否为测试指纹中的 UA
+    pub fingerprint_applied: bool,
+}
+
+/// 运行内核并收集标准输出（超时视为失败）
+async fn run_kernel(exe: &Path, args: &[String], timeout: Duration) -> Result<String> {
+    let mut cmd = Command::new(exe);
+    cmd.args(args).kill_on_drop(true);
+    let output = tokio::time::timeout(timeout, cmd.output())
+        .await
+        .map_err(|_| anyhow!("内核在 {} 秒内未退出", timeout.as_secs()))?
+        .context("启动内核失败")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "内核异常退出 ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// 读取内核版本（见模块说明），无法获取时返回空字符串
+async fn probe_version(exe: &Path, user_data_dir: &Path, timeout: Duration) -> Result<String> {
+    #[cfg(windows)]
+    {
+        let _ = (user_data_dir, timeout);
+        Ok(file_version_resource(exe)
+            .or_else(|| {
+                exe.parent()
+                    .and_then(super::kernel_registry::read_version_info)
+                    .map(|info| info.version)
+            })
+            .unwrap_or_default())
+    }
+    #[cfg(not(windows))]
+    {
+        let args = [
+            format!("--user-data-dir={}", user_data_dir.display()),
+            "--version".to_string(),
+        ];
+        Ok(run_kernel(exe, &args, timeout)
+            .await
+            .context("内核 --version 检查失败")?
+            .trim()
+            .to_string())
+    }
+}
+
+/// 读取可执行文件的文件版本资源（VS_FIXEDFILEINFO），如 "146.0.7680.80"
+#[cfg(windows)]
+fn file_version_resource(exe: &Path) -> Option<String> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows::core::{w, PCWSTR};
+    use windows::Win32::Storage::FileSystem::{
+        GetFileVersionInfoSizeW, GetFileVersionInfoW, VerQueryValueW, VS_FIXEDFILEINFO,
+    };
+
+    let path: Vec<u16> = exe.as_os_str().encode_wide().chain(Some(0)).collect();
+    unsafe {
+        let size = GetFileVersionInfoSizeW(PCWSTR(path.as_ptr()), None);
+        if size == 0 {
+            return None;
+        }
+        let mut data = vec![0u8; size as usize];
+        GetFileVersionInfoW(PCWSTR(path.as_ptr()), 0, size, data.as_mut_ptr().cast()).ok()?;
+
+        let mut info: *mut std::ffi::c_void = std::ptr::null_mut();
+        let mut len = 0u32;
+        let found = VerQueryValueW(data.as_ptr().cast(), w!("\\"), &mut info, &mut len);
+        if !found.as_bool() || info.is_null() || (len as usize) < std::mem::size_of::<VS_FIXEDFILEINFO>() {
+            return None;
+        }
+        let info = &*(info as *const VS_FIXEDFILEINFO);
+        Some(format!(
+            "{}.{}.{}.{}",
+            info.dwFileVersionMS >> 16,
+            info.dwFileVersionMS & 0xffff,
+            info.dwFileVersionLS >> 16,
+            info.dwFileVersionLS & 0xffff
+        ))
+    }
+}
+
+/// 对内核进行冒烟测试
+pub async fn smoke_test_kernel(exe: &Path, expected_version: &str) -> Result<SmokeTestReport> {
+    if !exe.exists() {
+        return Err(anyhow!("内核可执行文件不存在: {:?}", exe));
+    }
+
+    let user_data_dir = std::env::temp_dir().join(format!("bm-kernel-smoke-{}", uuid::Uuid::new_v4()));
+    let result = run_smoke_test(exe, expected_version, &user_data_dir).await;
+    let _ = tokio::fs::remove_dir_all(&user_data_dir).await;
+    result
+}
+
+async fn run_smoke_test(exe: &Path, expected_version: &str, user_data_dir: &Path) -> Result<SmokeTestReport> {
+    let timeout = SMOKE_TEST_TIMEOUT;
+    tokio::fs::create_dir_all(user_data_dir)
+        .await
+        .context("创建临时用户数据目录失败")?;
+
+    // Step 1: 版本
+    let version_output = probe_version(exe, user_data_dir, timeout).await?;
+    let reported_major = version_output
+        .split_whitespace()
+        .find_map(|token| major_version(token));
+    match (reported_major, major_version(expected_version)) {
+        (Some(reported), Some(expected)) if reported != expected => {
+            return Err(anyhow!(
+                "内核报告的版本 {} 与期望版本 {} 不一致",
+                version_output,
+                expected_version
+            ));
+        }
+        (None, _) => warn!("未能获取内核版本号，跳过版本比对"),
+        _ => {}
+    }
+
+    // Step 2: 临时环境读取指纹配置
+    let marker = format!("BMSmokeTest/{}", &uuid::Uuid::new_v4().simple().to_string()[..8]);
+    let mut config = FingerprintFileConfig::default();
+    config.ua.user_agent = format!(
+        "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/{}.0.0.0 Safari/537.36 {}",
+        major_version(expected_version).unwrap_or(0),
+        marker
+    );
+    ConfigWriter::write_fingerprint_config(user_data_dir, &config).map_err(|e| anyhow!(e))?;
+
+    let args = vec![
+        "--headless=new".to_string(),
+        "--no-first-run".to_string(),
+        "--disable-gpu".to_string(),
+        "--disable-background-networking".to_string(),
+        format!("--user-data-dir={}", user_data_dir.display()),
+        "--dump-dom".to_string(),
+        UA_PROBE_URL.to_string(),
+    ];
+    let dom = run_kernel(exe, &args, timeout).await.context("内核无头启动失败")?;
+
+    if !dom.contains(&marker) {
+        return Err(anyhow!("内核未应用 bm_fingerprint.json 中的指纹配置"));
+    }
+
+    info!("内核冒烟测试通过: {:?} ({})", exe, version_output);
+    Ok(SmokeTestReport {
+        version_output,
+        fingerprint_applied: true,
+    })
+}
+
+#[cfg(all(test, unix))]
+pub(crate) mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::PathBuf;
+
+    /// 模拟内核：`--version` 输出版本号（未指定用户数据目录时失败），`--dump-dom` 输出用户数据目录中指纹配置的 UA
+    pub(crate) fn write_fake_kernel(dir: &Path, version: &str, applies_fingerprint: bool) -> PathBuf {
+        std::fs::create_dir_all(dir).unwrap();
+        let ua_source = if applies_fingerprint {
+            r#"sed -n 's/.*"userAgent": *"\([^"]*\)".*/\1/p' "$dir/bm_fingerprint.json""#
+        } else {
+            "echo 'Mozilla/5.0 HeadlessChrome'"
+        };
+        let script = format!(
+            r#"#!/bin/sh
+for arg in "$@"; do
+  case "$arg" in
+    --version) version=1 ;;
+    --user-data-dir=*) dir="${{arg#--user-data-dir=}}" ;;
+  esac
+done
+[ -d "$dir" ] || {{ echo "no user data dir" >&2; exit 1; }}
+[ -n "$version" ] && {{ echo "Chromium {version}"; exit 0; }}
+echo "<html><body>BM-UA[$({ua_source})]</body></html>"
+"#
+        );
+        let exe = crate::modules::kernel_downloader::kernel_exe_path(dir);
+        std::fs::create_dir_all(exe.parent().unwrap()).unwrap();
+        std::fs::write(&exe, script).unwrap();
+        std::fs::set_permissions(&exe, std::fs::Permissions::from_mode(0o755)).unwrap();
+        exe
+    }
+
+    #[tokio::test]
+    async fn test_smoke_test_accepts_working_kernel() {
+        let dir = tempfile::tempdir().unwrap();
+        let exe = write_fake_kernel(dir.path(), "146.0.7680.80", true);
+        let report = smoke_test_kernel(&exe, "146.0.7680.80").await.unwrap();
+        assert!(report.fingerprint_applied);
+        assert_eq!(report.version_output, "Chromium 146.0.7680.80");
+    }
+
+    #[tokio::test]
+    async fn test_smoke_test_rejects_broken_kernels() {
+        let dir = tempfile::tempdir().unwrap();
+        let ignores_config = write_fake_kernel(&dir.path().join("a"), "146.0.7680.80", false);
+        let err = smoke_test_kernel(&ignores_config, "146").await.unwrap_err();
+        assert!(err.to_string().contains("bm_fingerprint.json"));
+
+        let wrong_version = write_fake_kernel(&dir.path().join("b"), "139.0.7258.154", true);
+        assert!(smoke_test_kernel(&wrong_version, "146").await.is_err());
+
+        assert!(smoke_test_kernel(&dir.path().join("missing"), "146").await.is_err());
+    }
+}
@@ -0,0 +1,500 @@
+// Kernel Registry - 多版本内核注册表
+// 多个内核版本并存于 kernel/{version}/，按语义化版本排序；
+// 环境 / 分组可固定内核版本，未固定时使用默认版本，启动前校验指纹浏览器版本与内核一致
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use uuid::Uuid;
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool};
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+
+use super::app_updater::KernelUpdateOutcome;
+use super::kernel_downloader::{kernel_exe_path, KernelDownloader, KernelVersionInfo};
+
+/// 默认内核版本设置项（为空时使用已安装的最新非 beta 版本）
+pub const DEFAULT_KERNEL_VERSION_SETTING_KEY: &str = "default_kernel_version";
+
+/// 固定对象：环境
+pub const PIN_SCOPE_PROFILE: &str = "profile";
+/// 固定对象：分组
+pub const PIN_SCOPE_GROUP: &str = "group";
+
+/// 解析版本号为数字分段（允许 "v" 前缀，如 "146"、"v139.0.7258.154"）
+fn parse_version(version: &str) -> Option<Vec<u64>> {
+    let trimmed = version.trim();
+    let trimmed = trimmed
+        .strip_prefix('v')
+        .or_else(|| trimmed.strip_prefix('V'))
+        .unwrap_or(trimmed);
+    if trimmed.is_empty() {
+        return None;
+    }
+    trimmed.split('.').map(|part| part.parse::<u64>().ok()).collect()
+}
+
+/// 按语义化版本比较（"99" < "139"），无法解析的版本排在可解析版本之前并按字符串比较
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    match (parse_version(a), parse_version(b)) {
+        (Some(x), Some(y)) => {
+            // 缺失的分段视为 0："146" 与 "146.0" 相等时再按字符串长度区分，保证排序稳定
+            let len = x.len().max(y.len());
+            (0..len)
+                .map(|i| x.get(i).copied().unwrap_or(0).cmp(&y.get(i).copied().unwrap_or(0)))
+                .find(|o| o.is_ne())
+                .unwrap_or_else(|| x.len().cmp(&y.len()))
+        }
+        (Some(_), None) => Ordering::Greater,
+        (None, Some(_)) => Ordering::Less,
+        (None, None) => a.cmp(b),
+    }
+}
+
+/// 版本主版本号（如 "146.0.7680.80" -> 146）
+pub fn major_version(version: &str) -> Option<u64> {
+    parse_version(version).and_then(|parts| parts.first().copied())
+}
+
+/// 版本是否匹配固定值：固定值的各分段是版本的前缀（"146" 匹配 "146.0.7680.80"）
+pub fn version_matches(pin: &str, version: &str) -> bool {
+    match (parse_version(pin), parse_version(version)) {
+        (Some(p), Some(v)) => p.len() <= v.len() && p.iter().zip(&v).all(|(a, b)| a == b),
+        _ => pin.trim() == version.trim(),
+    }
+}
+
+/// 从 User-Agent 中提取 Chrome 主版本号
+fn ua_major_version(user_agent: &str) -> Option<u64> {
+    let rest = user_agent.split("Chrome/").nth(1)?;
+    let major: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+    major.parse().ok()
+}
+
+/// 校验指纹浏览器版本与内核主版本一致
+///
+/// 指纹未设置 browser_version 时从 User-Agent 推断；两者都无法确定时不校验
+pub fn check_fingerprint_compat(
+    kernel_version: &str,
+    browser_version: Option<&str>,
+    user_agent: &str,
+) -> Result<()> {
+    let Some(kernel_major) = major_version(kernel_version) else {
+        return Ok(());
+    };
+    let fingerprint_major = browser_version
+        .filter(|v| !v.trim().is_empty())
+        .and_then(major_version)
+        .or_else(|| ua_major_version(user_agent));
+    match fingerprint_major {
+        Some(major) if major != kernel_major => Err(anyhow!(
+            "指纹浏览器版本 {} 与内核版本 {} 不一致，请调整指纹或为该环境固定内核版本 {}",
+            major,
+            kernel_version,
+            major
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// 内核来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KernelSource {
+    /// 下载或解压到应用数据目录
+    Installed,
+    /// 随安装包内嵌（只读）
+    Bundled,
+}
+
+/// 已安装的内核版本
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstalledKernel {
+    pub version: String,
+    pub exe_path: String,
+    pub source: KernelSource,
+    pub info: Option<KernelVersionInfo>,
+}
+
+impl InstalledKernel {
+    /// 是否为 beta 通道安装的内核
+    pub fn is_beta(&self) -> bool {
+        self.info.as_ref().and_then(|info| info.channel.as_deref()) == Some("beta")
+    }
+}
+
+/// 内核版本固定记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KernelPin {
+    pub scope: String,
+    pub target_id: String,
+    pub version: String,
+    pub updated_at: String,
+}
+
+/// 启动时解析出的内核
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolvedKernel {
+    pub version: String,
+    pub exe_path: String,
+    /// 版本来源：profile / group / default / latest
+    pub resolved_by: String,
+}
+
+/// 内核更新历史记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KernelUpdateRecord {
+    pub id: String,
+    pub version: String,
+    pub previous_version: Option<String>,
+    /// installed / rolled_back / failed
+    pub status: String,
+    pub message: Option<String>,
+    pub created_at: String,
+}
+
+/// 在已排序（降序）的内核列表中选择版本：指定版本时取匹配的最高版本，
+/// 否则取最新的非 beta 版本（beta 内核只对固定了该版本的环境 / 分组生效）
+pub fn select_kernel<'a>(kernels: &'a [InstalledKernel], requested: Option<&str>) -> Option<&'a InstalledKernel> {
+    match requested {
+        Some(pin) => kernels.iter().find(|k| version_matches(pin, &k.version)),
+        None => kernels.iter().find(|k| !k.is_beta()),
+    }
+}
+
+/// 内核注册表
+pub struct KernelRegistry {
+    pool: SqlitePool,
+    /// 下载 / 解压的内核目录 (例如: AppData/kernel/win32)
+    install_dir: PathBuf,
+    /// 内嵌内核目录
+    bundled_dirs: Vec<PathBuf>,
+}
+
+impl KernelRegistry {
+    pub fn new(pool: SqlitePool, install_dir: PathBuf) -> Self {
+        Self {
+            pool,
+            install_dir,
+            bundled_dirs: KernelDownloader::bundled_kernel_dirs(),
+        }
+    }
+
+    /// 下载 / 解压的内核目录
+    pub fn install_dir(&self) -> &Path {
+        &self.install_dir
+    }
+
+    /// 列出所有可用内核（按版本降序，同版本优先使用已安装的）
+    pub fn list_installed(&self) -> Vec<InstalledKernel> {
+        let mut kernels: Vec<InstalledKernel> = Vec::new();
+        let sources = std::iter::once((&self.install_dir, KernelSource::Installed))
+            .chain(self.bundled_dirs.iter().map(|dir| (dir, KernelSource::Bundled)));
+        for (base, source) in sources {
+            for version in KernelDownloader::list_versions_in_dir(base) {
+                if kernels.iter().any(|k| k.version == version) {
+                    continue;
+                }
+                let dir = base.join(&version);
+                kernels.push(InstalledKernel {
+                    exe_path: kernel_exe_path(&dir).display().to_string(),
+                    info: read_version_info(&dir),
+                    version,
+                    source,
+                });
+            }
+        }
+        kernels.sort_by(|a, b| compare_versions(&b.version, &a.version));
+        kernels
+    }
+
+    /// 查找匹配版本的内核（"146" 匹配已安装的最高 146.x）
+    pub fn find(&self, version: &str) -> Option<InstalledKernel> {
+        select_kernel(&self.list_installed(), Some(version)).cloned()
+    }
+
+    /// 删除已安装的内核版本（内嵌内核不可删除，被固定的版本需先解除固定）
+    pub async fn uninstall(&self, version: &str) -> Result<()> {
+        let dir = self.install_dir.join(version.trim());
+        if version.trim().is_empty() || version.contains(['/', '\\']) || version.contains("..") {
+            return Err(anyhow!("无效的内核版本: {}", version));
+        }
+        let pins = self.list_pins().await?;
+        if let Some(pin) = pins.iter().find(|p| version_matches(&p.version, version)) {
+            return Err(anyhow!(
+                "内核版本 {} 已被{} {} 固定，请先解除固定",
+                version,
+                if pin.scope == PIN_SCOPE_GROUP { "分组" } else { "环境" },
+                pin.target_id
+            ));
+        }
+        if !dir.exists() {
+            return Err(anyhow!("内核版本 {} 未安装或为内嵌内核", version));
+        }
+        tokio::fs::remove_dir_all(&dir).await?;
+        Ok(())
+    }
+
+    /// 固定环境 / 分组的内核版本，version 为 None 时解除固定
+    pub async fn set_pin(&self, scope: &str, target_id: &str, version: Option<&str>) -> Result<()> {
+        if scope != PIN_SCOPE_PROFILE && scope != PIN_SCOPE_GROUP {
+            return Err(anyhow!("无效的固定对象: {}", scope));
+        }
+        match version.map(str::trim).filter(|v| !v.is_empty()) {
+            Some(version) => {
+                if self.find(version).is_none() {
+                    return Err(anyhow!("内核版本 {} 未安装", version));
+                }
+                sqlx::query(
+                    r#"
+                    INSERT INTO kernel_pins (scope, target_id, version, updated_at)
+                    VALUES (?, ?, ?, ?)
+                    ON CONFLICT(scope, target_id) DO UPDATE SET
+                        version = excluded.version,
+                        updated_at = excluded.updated_at
+                    "#,
+                )
+                .bind(scope)
+                .bind(target_id)
+                .bind(version)
+                .bind(Utc::now().to_rfc3339())
+                .execute(&self.pool)
+                .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM kernel_pins WHERE scope = ? AND target_id = ?")
+                    .bind(scope)
+                    .bind(target_id)
+                    .execute(&self.pool)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 记录内核更新结果
+    pub async fn record_update(&self, outcome: &KernelUpdateOutcome) -> Result<KernelUpdateRecord> {
+        let record = KernelUpdateRecord {
+            id: Uuid::new_v4().to_string(),
+            version: outcome.version.clone(),
+            previous_version: outcome.previous_version.clone(),
+            status: outcome.status.as_str().to_string(),
+            message: outcome.message.clone(),
+            created_at: Utc::now().to_rfc3339(),
+        };
+        sqlx::query(
+            r#"
+            INSERT INTO kernel_update_history (id, version, previous_version, status, message, created_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&record.id)
+        .bind(&record.version)
+        .bind(&record.previous_version)
+        .bind(&record.status)
+        .bind(&record.message)
+        .bind(&record.created_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(record)
+    }
+
+    /// 内核更新历史（最新在前）
+    pub async fn update_history(&self, limit: u32) -> Result<Vec<KernelUpdateRecord>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, version, previous_version, status, message, created_at
+            FROM kernel_update_history
+            ORDER BY created_at DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(KernelUpdateRecord {
+                    id: row.try_get("id")?,
+                    version: row.try_get("version")?,
+                    previous_version: row.try_get("previous_version")?,
+                    status: row.try_get("status")?,
+                    message: row.try_get("message")?,
+                    created_at: row.try_get("created_at")?,
+                })
+            })
+            .collect()
+    }
+
+    /// 列出所有固定记录
+    pub async fn list_pins(&self) -> Result<Vec<KernelPin>> {
+        let rows = sqlx::query(
+            "SELECT scope, target_id, version, updated_at FROM kernel_pins ORDER BY scope, target_id",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(KernelPin {
+                    scope: row.try_get("scope")?,
+                    target_id: row.try_get("target_id")?,
+                    version: row.try_get("version")?,
+                    updated_at: row.try_get("updated_at")?,
+                })
+            })
+            .collect()
+    }
+
+    /// 环境生效的固定版本：环境固定优先，其次为所在分组的固定
+    pub async fn pinned_version(&self, profile_id: &str) -> Result<Option<(String, String)>> {
+        let row = sqlx::query(
+            r#"
+            SELECT k.scope, k.version
+            FROM kernel_pins k
+            LEFT JOIN profiles p ON p.id = ?
+            WHERE (k.scope = 'profile' AND k.target_id = ?)
+               OR (k.scope = 'group' AND k.target_id = p.group_id)
+            ORDER BY CASE k.scope WHEN 'profile' THEN 0 ELSE 1 END
+            LIMIT 1
+            "#,
+        )
+        .bind(profile_id)
+        .bind(profile_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(|r| Ok((r.try_get("scope")?, r.try_get("version")?))).transpose()
+    }
+
+    /// 解析环境启动使用的内核：环境固定 → 分组固定 → 默认版本 → 最新非 beta 版本
+    ///
+    /// 固定版本未安装时报错，不会静默回退到其他版本
+    pub async fn resolve(&self, profile_id: &str, default_version: Option<&str>) -> Result<Option<ResolvedKernel>> {
+        let kernels = self.list_installed();
+        let (requested, resolved_by) = match self.pinned_version(profile_id).await? {
+            Some((scope, version)) => (Some(version), scope),
+            None => match default_version.map(str::trim).filter(|v| !v.is_empty()) {
+                Some(version) => (Some(version.to_string()), "default".to_string()),
+                None => (None, "latest".to_string()),
+            },
+        };
+
+        match select_kernel(&kernels, requested.as_deref()) {
+            Some(kernel) => Ok(Some(ResolvedKernel {
+                version: kernel.version.clone(),
+                exe_path: kernel.exe_path.clone(),
+                resolved_by,
+            })),
+            None => match requested {
+                Some(version) => Err(anyhow!("内核版本 {} 未安装，请先下载该版本", version)),
+                None => Ok(None),
+            },
+        }
+    }
+}
+
+/// 读取内核目录下的 kernel_version.json
+pub(crate) fn read_version_info(kernel_dir: &Path) -> Option<KernelVersionInfo> {
+    let content = std::fs::read_to_string(kernel_dir.join("kernel_version.json")).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// 推断自定义内核路径的版本：优先 kernel_version.json，其次为所在目录名
+pub fn version_of_exe(exe_path: &Path) -> Option<String> {
+    let dir = exe_path.parent()?;
+    if let Some(info) = read_version_info(dir) {
+        return Some(info.version.trim_start_matches(['v', 'V']).to_string());
+    }
+    let name = dir.file_name()?.to_str()?;
+    parse_version(name).map(|_| name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kernel(version: &str) -> InstalledKernel {
+        InstalledKernel {
+            version: version.to_string(),
+            exe_path: format!("/kernel/{}/chrome", version),
+            source: KernelSource::Installed,
+            info: None,
+        }
+    }
+
+    #[test]
+    fn test_compare_versions_is_numeric() {
+        assert_eq!(compare_versions("139", "99"), Ordering::Greater);
+        assert_eq!(compare_versions("146.0.7680.80", "146.0.7680.9"), Ordering::Greater);
+        assert_eq!(compare_versions("v139", "139"), Ordering::Equal);
+        assert_eq!(compare_versions("146", "146.0.1"), Ordering::Less);
+        assert_eq!(compare_versions("nightly", "99"), Ordering::Less);
+
+        let mut versions = vec!["99", "146", "139.0.7258.154", "139"];
+        versions.sort_by(|a, b| compare_versions(b, a));
+        assert_eq!(versions, vec!["146", "139.0.7258.154", "139", "99"]);
+    }
+
+    #[test]
+    fn test_select_kernel_by_pin_prefix() {
+        let kernels = vec![kernel("146.0.7680.80"), kernel("139.0.7258.154"), kernel("139.0.7258.66"), kernel("99")];
+        assert_eq!(select_kernel(&kernels, None).unwrap().version, "146.0.7680.80");
+        assert_eq!(select_kernel(&kernels, Some("139")).unwrap().version, "139.0.7258.154");
+        assert_eq!(select_kernel(&kernels, Some("139.0.7258.66")).unwrap().version, "139.0.7258.66");
+        assert!(select_kernel(&kernels, Some("13")).is_none());
+        assert!(select_kernel(&kernels, Some("120")).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_beta_kernel_only_for_pinned_profiles() {
+        let dir = tempfile::tempdir().unwrap();
+        let install_kernel = |version: &str, channel: &str| {
+            let kernel_dir = dir.path().join(version);
+            let exe = kernel_exe_path(&kernel_dir);
+            std::fs::create_dir_all(exe.parent().unwrap()).unwrap();
+            std::fs::write(&exe, b"").unwrap();
+            let info = serde_json::json!({
+                "version": version, "build_date": "", "platform": "win64", "source": "auto_update",
+                "files_count": 0, "total_size_bytes": 0, "channel": channel,
+            });
+            std::fs::write(kernel_dir.join("kernel_version.json"), info.to_string()).unwrap();
+        };
+        install_kernel("139.0.7258.154", "stable");
+        install_kernel("146.0.7680.80", "beta");
+
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::query("CREATE TABLE profiles (id TEXT PRIMARY KEY, group_id TEXT)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("CREATE TABLE kernel_pins (scope TEXT, target_id TEXT, version TEXT, updated_at TEXT, PRIMARY KEY (scope, target_id))")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let registry = KernelRegistry { pool, install_dir: dir.path().to_path_buf(), bundled_dirs: Vec::new() };
+
+        // 未固定的环境仍使用之前的版本
+        let resolved = registry.resolve("p1", None).await.unwrap().unwrap();
+        assert_eq!((resolved.version.as_str(), resolved.resolved_by.as_str()), ("139.0.7258.154", "latest"));
+
+        // 固定到 beta 版本的环境使用 beta 内核
+        registry.set_pin(PIN_SCOPE_PROFILE, "p2", Some("146")).await.unwrap();
+        assert_eq!(registry.resolve("p2", None).await.unwrap().unwrap().version, "146.0.7680.80");
+        assert_eq!(registry.resolve("p1", None).await.unwrap().unwrap().version, "139.0.7258.154");
+    }
+
+    #[test]
+    fn test_fingerprint_compat() {
+        let ua139 = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/139.0.0.0 Safari/537.36";
+        assert!(check_fingerprint_compat("146", Some("139"), ua139).is_err());
+        assert!(check_fingerprint_compat("139.0.7258.154", Some("139"), ua139).is_ok());
+        // 未设置 browser_version 时从 UA 推断
+        assert!(check_fingerprint_compat("146", None, ua139).is_err());
+        assert!(check_fingerprint_compat("146", Some(""), "custom agent").is_ok());
+    }
+}
@@ -14,11 +14,18 @@ pub mod recycle_bin;  // Recycle bin
 pub mod proxy;  // Proxy management
 pub mod proxy_checker;  // Proxy health check
 pub mod proxy_bridge;  // Proxy bridge (SOCKS5 auth)
+pub mod traffic;  // Traffic usage and quotas
 pub mod logger;  // Logger system
 pub mod config_writer;  // Config file generation
 pub mod fingerprint;  // Fingerprint generation
+pub mod fingerprint_history;  // Fingerprint history
+pub mod fingerprint_score;  // Fingerprint realism score
 pub mod extension;  // Extension management
 pub mod kernel_downloader;  // Kernel download and management
+pub mod kernel_registry;  // Multi-version kernel registry
+pub mod kernel_health;  // Kernel post-install smoke test
+pub mod signing;  // Ed25519 signature verification
+pub mod crash_report;  // Browser crash reports and restart policy
 pub mod app_updater;  // 应用自动更新
 
 pub use profile::ProfileService;
@@ -34,7 +41,11 @@ pub use proxy_checker::{ProxyChecker, ProxyCheckResult};  // ✅ 代理健康检
 pub use logger::{Logger, LoggerConfig, LogFileInfo};  // ✅ 日志系统
 pub use config_writer::ConfigWriter;  // ✅ P0 导出配置写入器
 pub use fingerprint::FingerprintGenerator;  // ✅ 导出指纹生成器
+pub use fingerprint_history::{FingerprintHistoryService, FingerprintHistoryEntry};
+pub use fingerprint_score::{FingerprintScoreService, ProfileFingerprintScore};
 pub use extension::{ExtensionService, Extension, CreateExtensionDto, UpdateExtensionDto};  // ✅ 扩展管理
 pub use proxy_bridge::{ProxyBridge, ProxyBridgeConfig, ProxyBridgeManager, BridgeStats};
+pub use traffic::TrafficService;
 pub use kernel_downloader::{KernelDownloader, DownloadProgress, DownloadStatus, KernelVersionInfo};
+pub use kernel_registry::{KernelRegistry, InstalledKernel, KernelPin};
 pub use app_updater::{UpdateInfo, UpdateDownloadProgress, UpdateDownloadStatus, DownloadSource, UpdateComponent};
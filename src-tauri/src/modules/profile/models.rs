@@ -18,6 +18,16 @@ pub enum ProxyType {
     Socks5,
 }
 
+impl ProxyType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProxyType::Http => "http",
+            ProxyType::Https => "https",
+            ProxyType::Socks5 => "socks5",
+        }
+    }
+}
+
 /// 代理配置
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProxyConfig {
@@ -26,6 +36,28 @@ pub struct ProxyConfig {
     pub port: u16,
     pub username: Option<String>,
     pub password: Option<String>,
+    /// 前置跳板（按顺序经过，最后由本代理出口）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chain: Vec<ProxyHopConfig>,
+    /// 上行限速（字节/秒）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upload_limit: Option<u64>,
+    /// 下行限速（字节/秒）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download_limit: Option<u64>,
+    /// 备用代理（proxies 表 ID，按顺序在本代理连接失败时切换）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fallback_proxy_ids: Vec<String>,
+}
+
+/// 代理链中的前置跳板
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProxyHopConfig {
+    pub r#type: ProxyType,
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
 }
 
 /// 指纹配置
@@ -92,6 +124,11 @@ pub struct Fingerprint {
     pub geolocation_longitude: Option<f64>,
     pub geolocation_accuracy: Option<f64>,
     pub geolocation_prompt: Option<String>,    // 'ask' | 'allow' | 'block'
+    
+    // --- 设备模板追溯 ---
+    pub template_id: Option<String>,        // 生成指纹所用的设备模板 ID
+    pub template_revision: Option<u32>,     // 生成时的模板修订号
+    pub launch_seed: Option<String>,        // 启动时随机指纹使用的种子（写入内核 seed）
 }
 
 /// 偏好设置配置
@@ -155,7 +192,7 @@ pub struct PreferencesConfig {
     
     // 其他选项
     #[serde(default)]
-    pub random_fingerprint_on_start: bool,
+    pub random_fingerprint_on_start: bool, // 每次启动重新生成指纹（保留平台/语言/代理地理信息）
     #[serde(default)]
     pub show_password_save_prompt: bool,
     #[serde(default)]
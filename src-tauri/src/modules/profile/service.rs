@@ -3,7 +3,8 @@ use sqlx::{Sqlite, SqlitePool, QueryBuilder, Row};
 use uuid::Uuid;
 use chrono::Utc;
 
-use super::models::{Profile, CreateProfileDto, UpdateProfileDto, ProfileStatus};
+use crate::modules::fingerprint_history::{self, record_revision};
+use super::models::{Profile, Fingerprint, CreateProfileDto, UpdateProfileDto, ProfileStatus};
 
 fn parse_datetime(value: String) -> Result<chrono::DateTime<chrono::Utc>> {
     if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(&value) {
@@ -62,6 +63,14 @@ impl ProfileService {
         .execute(&mut *tx)
         .await?;
 
+        // 记录初始指纹版本
+        let source = if dto.fingerprint.template_id.is_some() {
+            fingerprint_history::SOURCE_TEMPLATE
+        } else {
+            fingerprint_history::SOURCE_MANUAL_EDIT
+        };
+        record_revision(&mut *tx, &id, None, &dto.fingerprint, source, None, None).await?;
+
         // 如果有偏好设置，则插入偏好设置表
         if let Some(pref) = preferences_json {
             sqlx::query(
@@ -193,10 +202,15 @@ impl ProfileService {
 
     /// 更新环境
     pub async fn update_profile(&self, id: &str, dto: UpdateProfileDto) -> Result<Profile> {
+        self.update_profile_by(id, dto, None).await
+    }
+
+    /// 更新环境（记录指纹变更的操作人）
+    pub async fn update_profile_by(&self, id: &str, dto: UpdateProfileDto, author: Option<&str>) -> Result<Profile> {
         let now = Utc::now();
         
         // 处理 fingerprint merge
-        let fingerprint_json = if let Some(patch_fp) = dto.fingerprint {
+        let fingerprint_change = if let Some(patch_fp) = dto.fingerprint {
             // 获取现有的 fingerprint
             let existing_profile = self.get_profile(id).await?;
             let existing_fp_value = serde_json::to_value(&existing_profile.fingerprint)?;
@@ -206,11 +220,16 @@ impl ProfileService {
             let merger = crate::modules::FingerprintMerger::new();
             let merged_value = merger.merge(&existing_fp_value, &patch_fp_value)
                 .map_err(|e| anyhow::anyhow!(e))?;
+            let merged: Fingerprint = serde_json::from_value(merged_value)?;
             
-            Some(serde_json::to_string(&merged_value)?)
+            Some((existing_profile.fingerprint, merged))
         } else {
             None
         };
+        let fingerprint_json = fingerprint_change
+            .as_ref()
+            .map(|(_, merged)| serde_json::to_string(merged))
+            .transpose()?;
         
         let proxy_json = dto.proxy.as_ref().map(|p| serde_json::to_string(p)).transpose()?;
         let preferences_json = dto.preferences.as_ref().map(|p| serde_json::to_string(p)).transpose()?;
@@ -251,6 +270,12 @@ impl ProfileService {
 
         qb.build().execute(&mut *tx).await?;
 
+        // 记录指纹修订版本
+        if let Some((existing, merged)) = &fingerprint_change {
+            let source = fingerprint_history::source_for_edit(existing, merged);
+            record_revision(&mut *tx, id, Some(existing), merged, source, author, None).await?;
+        }
+
         // 如果有偏好设置，则更新偏好设置表
         if let Some(pref_json) = preferences_json {
             sqlx::query(
@@ -269,6 +294,32 @@ impl ProfileService {
         self.get_profile(id).await
     }
 
+    /// 整体替换环境指纹（不经过合并，用于启动时重新生成指纹和回滚），并记录修订版本
+    pub async fn replace_fingerprint(
+        &self,
+        id: &str,
+        fingerprint: &Fingerprint,
+        source: &str,
+        author: Option<&str>,
+        launch_seed: Option<&str>,
+    ) -> Result<()> {
+        let existing = self.get_profile(id).await?.fingerprint;
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("UPDATE profiles SET fingerprint = ?, updated_at = ? WHERE id = ?")
+            .bind(serde_json::to_string(fingerprint)?)
+            .bind(Utc::now().to_rfc3339())
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        record_revision(&mut *tx, id, Some(&existing), fingerprint, source, author, launch_seed).await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
     /// 删除环境（软删除，移入回收站）
     pub async fn delete_profile(&self, id: &str) -> Result<()> {
         let now = chrono::Utc::now().to_rfc3339();
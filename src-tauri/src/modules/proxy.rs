@@ -6,6 +6,9 @@ use serde::{Deserialize, Serialize};
 use chrono::Utc;
 use anyhow::Result;
 
+/// 代理状态变化事件（桥接检测到代理不可用或恢复时发送）
+pub const PROXY_STATUS_EVENT: &str = "proxy:status";
+
 /// 代理状态
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
@@ -365,6 +368,28 @@ impl ProxyService {
         Ok(())
     }
 
+    /// 按地址查找代理 ID
+    pub async fn find_id_by_address(&self, host: &str, port: u16) -> Result<Option<String>> {
+        let id = sqlx::query_scalar("SELECT id FROM proxies WHERE host = ? AND port = ? LIMIT 1")
+            .bind(host)
+            .bind(port.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(id)
+    }
+
+    /// 更新代理状态（已过期的代理保持过期状态）
+    pub async fn update_status(&self, id: &str, status: ProxyStatus) -> Result<()> {
+        sqlx::query("UPDATE proxies SET status = ?, updated_at = ? WHERE id = ? AND status != 'expired'")
+            .bind(status.to_string())
+            .bind(Utc::now().to_rfc3339())
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
     /// 静态检测函数：不依赖数据库，直接检测配置
     pub async fn test_proxy_config(host: &str, port: &str) -> serde_json::Value {
         use tokio::net::TcpStream;
@@ -1,32 +1,100 @@
 // Proxy Bridge - 代理桥接模块
-// 解决 Chromium 原生不支持带密码 SOCKS5 代理的问题
-// 架构：Chrome → 本地 HTTP 代理 (127.0.0.1:port) → 上游 SOCKS5 代理 (含认证)
+// 解决 Chromium 原生不支持带认证代理的问题（每个窗口都会弹出认证框）
+// 架构：Chrome → 本地 HTTP / SOCKS5 代理 (127.0.0.1:port) → 上游 SOCKS5 / HTTP / HTTPS(TLS) 代理 (含认证)
+// 本地端口同时接受 HTTP 与 SOCKS5 入站（按首字节区分），SOCKS5 入站可选本地用户名/密码认证
+// 上游可配置为代理链：先经过前置跳板（如公司跳板机），逐跳建立隧道，最后由 upstream 出口
+// 出口可配置有序备用代理（连接失败时切换），用户名中的 {rand} 会替换为可轮换的会话 ID
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::future::Future;
 use std::net::SocketAddr;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::pin::Pin;
+use std::sync::{Arc, OnceLock};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
 use tokio::net::{TcpListener, TcpStream, UdpSocket};
-use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
-use tokio::sync::Mutex;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, ReadBuf};
+use tokio::sync::{mpsc, Mutex, Notify};
+use tokio::task::JoinHandle;
 use tracing::{info, warn, error, debug};
 use serde::{Serialize, Deserialize};
 
 /// 代理桥接配置
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct ProxyBridgeConfig {
     /// 上游代理主机
     pub upstream_host: String,
     /// 上游代理端口
     pub upstream_port: u16,
-    /// 上游代理协议 (socks5, http, https)
+    /// 上游代理协议 (socks5, http, https)，https 表示与代理之间使用 TLS
     pub upstream_type: String,
     /// 上游代理用户名（可选）
     pub username: Option<String>,
     /// 上游代理密码（可选）
     pub password: Option<String>,
-    /// 是否启用 UDP 支持（用于 WebRTC）
+    /// 是否启用 UDP 支持（用于 WebRTC，仅无前置跳板时可用）
     pub enable_udp: bool,
+    /// 前置跳板（按顺序经过，最后经由 upstream_* 出口）
+    pub chain: Vec<ProxyHop>,
+    /// 备用出口代理（主代理连接失败时按顺序切换）
+    pub fallbacks: Vec<ProxyHop>,
+    /// 上行限速（字节/秒，None 表示不限速）
+    pub upload_limit: Option<u64>,
+    /// 下行限速（字节/秒，None 表示不限速）
+    pub download_limit: Option<u64>,
+    /// SOCKS5 入站本地认证用户名（与 local_password 同时设置时生效：SOCKS5 使用用户名/密码认证，
+    /// UDP 中继只接受已认证 ASSOCIATE 的客户端地址；浏览器使用的 HTTP 入站不受影响）
+    pub local_username: Option<String>,
+    /// SOCKS5 入站本地认证密码
+    pub local_password: Option<String>,
+    /// 连接日志容量（按 Profile 保留最近的连接记录，0 表示不记录）
+    pub connection_log_capacity: usize,
+}
+
+/// 代理链中的一跳
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProxyHop {
+    pub host: String,
+    pub port: u16,
+    /// 代理协议 (socks5, http, https)
+    #[serde(rename = "type")]
+    pub proxy_type: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl ProxyBridgeConfig {
+    /// 完整代理链（前置跳板 + 出口代理）
+    pub fn hops(&self) -> Vec<ProxyHop> {
+        let mut hops = self.chain.clone();
+        hops.push(ProxyHop {
+            host: self.upstream_host.clone(),
+            port: self.upstream_port,
+            proxy_type: self.upstream_type.clone(),
+            username: self.username.clone(),
+            password: self.password.clone(),
+        });
+        hops
+    }
+}
+
+/// 本地 SOCKS5 入站认证用户名设置项
+pub const LOCAL_SOCKS_USERNAME_SETTING_KEY: &str = "proxy_bridge_socks_username";
+/// 本地 SOCKS5 入站认证密码设置项
+pub const LOCAL_SOCKS_PASSWORD_SETTING_KEY: &str = "proxy_bridge_socks_password";
+
+/// 桥接对外暴露的本地端点
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BridgeEndpoints {
+    /// HTTP 代理地址（http://127.0.0.1:port）
+    pub http: String,
+    /// SOCKS5 代理地址（与 HTTP 共用端口）
+    pub socks5: String,
+    /// SOCKS5 UDP 中继地址（未启用 UDP 时为空）
+    pub udp: Option<String>,
+    /// SOCKS5 入站是否需要认证
+    pub socks5_auth_required: bool,
 }
 
 /// 代理桥接统计信息
@@ -48,6 +116,10 @@ pub struct BridgeStats {
     pub udp_packets_sent: u64,
     /// UDP 数据包接收数
     pub udp_packets_received: u64,
+    /// 丢弃的 UDP 数据包数（格式错误、分片或队列已满）
+    pub udp_packets_dropped: u64,
+    /// 活跃 UDP 会话数（每个客户端地址一个 ASSOCIATE）
+    pub udp_active_sessions: u64,
 }
 
 /// 代理桥接实例
@@ -56,14 +128,30 @@ pub struct ProxyBridge {
     pub profile_id: String,
     /// 本地 TCP 监听端口
     pub local_port: u16,
-    /// 本地 UDP 监听端口（用于 WebRTC）
+    /// 本地 UDP 监听端口（用于 WebRTC，与 TCP 端口号相同，未启用时为 0）
     pub local_udp_port: u16,
+    /// 已绑定、尚未启动的端口（启动时取出）
+    ports: std::sync::Mutex<Option<BridgePorts>>,
     /// 上游配置
     config: ProxyBridgeConfig,
     /// 运行状态
     running: Arc<AtomicBool>,
+    /// 停止通知（唤醒监听循环，尽快释放端口）
+    shutdown: Arc<Notify>,
+    /// 监听循环任务
+    tasks: std::sync::Mutex<Vec<JoinHandle<()>>>,
     /// 统计信息
     stats: Arc<BridgeStatsInner>,
+    /// UDP 会话空闲超时
+    udp_idle_timeout: Duration,
+    /// TCP 限速器（所有连接共享）
+    limiter: Arc<BridgeLimiter>,
+    /// 连接日志（未启用时为空）
+    journal: Option<Arc<ConnectionJournal>>,
+    /// 出口代理（主代理 + 备用代理）
+    upstreams: Arc<UpstreamPool>,
+    /// 已建立的 UDP ASSOCIATE（启用本地认证时用于校验 UDP 数据报来源）
+    udp_associations: Arc<UdpAssociations>,
 }
 
 /// 内部统计结构（支持原子更新）
@@ -76,6 +164,8 @@ struct BridgeStatsInner {
     started_at: i64,
     udp_packets_sent: AtomicU64,
     udp_packets_received: AtomicU64,
+    udp_packets_dropped: AtomicU64,
+    udp_active_sessions: AtomicU64,
 }
 
 impl BridgeStatsInner {
@@ -89,6 +179,8 @@ impl BridgeStatsInner {
             started_at: chrono::Utc::now().timestamp_millis(),
             udp_packets_sent: AtomicU64::new(0),
             udp_packets_received: AtomicU64::new(0),
+            udp_packets_dropped: AtomicU64::new(0),
+            udp_active_sessions: AtomicU64::new(0),
         }
     }
     
@@ -102,39 +194,49 @@ impl BridgeStatsInner {
             started_at: self.started_at,
             udp_packets_sent: self.udp_packets_sent.load(Ordering::Relaxed),
             udp_packets_received: self.udp_packets_received.load(Ordering::Relaxed),
+            udp_packets_dropped: self.udp_packets_dropped.load(Ordering::Relaxed),
+            udp_active_sessions: self.udp_active_sessions.load(Ordering::Relaxed),
         }
     }
 }
 
 impl ProxyBridge {
-    /// 创建新的代理桥接实例
-    pub fn new(profile_id: String, config: ProxyBridgeConfig) -> Self {
-        let local_port = Self::find_free_port(50000);
-        let local_udp_port = if config.enable_udp {
-            Self::find_free_port(local_port + 1) // UDP 端口在 TCP 端口后面
-        } else {
-            0
-        };
+    /// 创建新的代理桥接实例（使用由 PortAllocator 预先绑定的端口）
+    pub fn new(profile_id: String, config: ProxyBridgeConfig, ports: BridgePorts) -> Self {
+        let local_port = ports.port;
+        let local_udp_port = if ports.udp.is_some() { ports.port } else { 0 };
+        
+        let limiter = Arc::new(BridgeLimiter::new(config.upload_limit, config.download_limit));
+        let upstreams = Arc::new(UpstreamPool::new(&profile_id, &config));
         
         Self {
             profile_id,
             local_port,
             local_udp_port,
+            ports: std::sync::Mutex::new(Some(ports)),
             config,
             running: Arc::new(AtomicBool::new(false)),
+            shutdown: Arc::new(Notify::new()),
+            tasks: std::sync::Mutex::new(Vec::new()),
             stats: Arc::new(BridgeStatsInner::new()),
+            udp_idle_timeout: DEFAULT_UDP_IDLE_TIMEOUT,
+            limiter,
+            journal: None,
+            upstreams,
+            udp_associations: Arc::new(UdpAssociations::default()),
         }
     }
     
-    /// 查找可用端口（从指定端口开始）
-    fn find_free_port(start: u16) -> u16 {
-        for port in start..60000 {
-            if std::net::TcpListener::bind(("127.0.0.1", port)).is_ok() {
-                return port;
-            }
-        }
-        // 如果所有端口都不可用，返回 0 让系统分配
-        0
+    /// 设置 UDP 会话空闲超时（超时后关闭该客户端的 ASSOCIATE）
+    pub fn with_udp_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.udp_idle_timeout = timeout;
+        self
+    }
+    
+    /// 记录每个连接的目标到连接日志
+    pub fn with_connection_journal(mut self, journal: Arc<ConnectionJournal>) -> Self {
+        self.journal = Some(journal);
+        self
     }
     
     /// 获取本地 TCP 代理地址（供浏览器 HTTP 流量使用）
@@ -142,6 +244,21 @@ impl ProxyBridge {
         format!("http://127.0.0.1:{}", self.local_port)
     }
     
+    /// 获取本地 SOCKS5 代理地址（与 HTTP 共用端口，域名由上游远程解析）
+    pub fn local_socks_addr(&self) -> String {
+        format!("socks5://127.0.0.1:{}", self.local_port)
+    }
+    
+    /// 获取本地全部端点
+    pub fn endpoints(&self) -> BridgeEndpoints {
+        BridgeEndpoints {
+            http: self.local_addr(),
+            socks5: self.local_socks_addr(),
+            udp: self.local_udp_addr(),
+            socks5_auth_required: local_credentials(&self.config).is_some(),
+        }
+    }
+    
     /// 获取本地 UDP 代理地址（供 WebRTC 使用）
     pub fn local_udp_addr(&self) -> Option<String> {
         if self.local_udp_port > 0 {
@@ -156,6 +273,52 @@ impl ProxyBridge {
         self.stats.to_stats()
     }
     
+    /// 调整限速（对已建立的连接立即生效）
+    pub fn set_rate_limits(&self, upload_limit: Option<u64>, download_limit: Option<u64>) {
+        self.limiter.upload.set_rate(upload_limit);
+        self.limiter.download.set_rate(download_limit);
+    }
+    
+    /// 取出自上次调用以来新增的流量（按实际经过的出口代理分别计算）
+    ///
+    /// 有新增流量的出口代理各返回一条；当前出口代理总会返回一条（可能为 0），
+    /// 以便调用方知道运行中的桥接正在使用哪个代理
+    pub fn take_traffic_deltas(&self) -> Vec<TrafficDelta> {
+        let active = self.is_running();
+        self.upstreams
+            .take_traffic()
+            .into_iter()
+            .map(|(hop, bytes_sent, bytes_received)| TrafficDelta {
+                profile_id: self.profile_id.clone(),
+                upstream_host: hop.host,
+                upstream_port: hop.port,
+                bytes_sent,
+                bytes_received,
+                active,
+            })
+            .collect()
+    }
+    
+    /// 轮换会话 ID（用户名 / 密码未配置 {rand} 时返回 None），已建立的连接不受影响
+    pub fn rotate_session(&self) -> Option<String> {
+        if !self.upstreams.has_session_template() {
+            return None;
+        }
+        let session = self.upstreams.rotate_session();
+        info!(profile_id = %self.profile_id, session = %session, "代理会话已轮换");
+        Some(session)
+    }
+    
+    /// 各出口代理的健康状态
+    pub fn upstream_health(&self) -> Vec<UpstreamHealth> {
+        self.upstreams.health()
+    }
+    
+    /// 取出自上次调用以来出口代理的可用性变化
+    pub fn take_upstream_status_changes(&self) -> Vec<UpstreamStatusChange> {
+        self.upstreams.take_changes()
+    }
+    
     /// 检查是否正在运行
     pub fn is_running(&self) -> bool {
         self.running.load(Ordering::Relaxed)
@@ -167,10 +330,18 @@ impl ProxyBridge {
             return Ok(()); // 已在运行
         }
         
-        // 1. 启动 TCP 监听（HTTP CONNECT 代理）
-        let addr = SocketAddr::from(([127, 0, 0, 1], self.local_port));
-        let listener = TcpListener::bind(addr).await
-            .map_err(|e| format!("绑定 TCP 端口 {} 失败: {}", self.local_port, e))?;
+        validate_chain(&self.config.hops())?;
+        for fallback in &self.config.fallbacks {
+            let mut hops = self.config.chain.clone();
+            hops.push(fallback.clone());
+            validate_chain(&hops).map_err(|e| format!("备用代理 {}:{} 无效: {}", fallback.host, fallback.port, e))?;
+        }
+        
+        // 1. 接管已绑定的 TCP 监听（HTTP / SOCKS5 代理）
+        let ports = self.ports.lock().unwrap().take()
+            .ok_or_else(|| format!("代理桥接端口 {} 已释放，请重新分配", self.local_port))?;
+        let listener = TcpListener::from_std(ports.tcp)
+            .map_err(|e| format!("接管 TCP 端口 {} 失败: {}", self.local_port, e))?;
         
         self.running.store(true, Ordering::Relaxed);
         
@@ -184,12 +355,18 @@ impl ProxyBridge {
         );
         
         let running = Arc::clone(&self.running);
+        let shutdown = Arc::clone(&self.shutdown);
         let config = self.config.clone();
+        let upstreams = Arc::clone(&self.upstreams);
         let stats = Arc::clone(&self.stats);
+        let limiter = Arc::clone(&self.limiter);
+        let journal = self.journal.clone();
+        let udp_associations = Arc::clone(&self.udp_associations);
         let profile_id = self.profile_id.clone();
+        let udp_port = if self.config.enable_udp && self.config.chain.is_empty() { self.local_udp_port } else { 0 };
         
         // 2. 启动 TCP 监听循环
-        tokio::spawn(async move {
+        let accept_task = tokio::spawn(async move {
             while running.load(Ordering::Relaxed) {
                 tokio::select! {
                     result = listener.accept() => {
@@ -205,15 +382,26 @@ impl ProxyBridge {
                                 stats.active_connections.fetch_add(1, Ordering::Relaxed);
                                 
                                 let config = config.clone();
+                                let upstreams = Arc::clone(&upstreams);
+                                let udp_associations = Arc::clone(&udp_associations);
                                 let stats = Arc::clone(&stats);
                                 let profile_id = profile_id.clone();
+                                // 客户端侧计量：读取为上行，写入为下行
+                                let client_stream = MeteredStream::new(client_stream, Arc::clone(&stats), Arc::clone(&limiter));
+                                let mut tracker = ConnectionTracker::new(journal.clone(), client_stream.counters());
                                 
                                 tokio::spawn(async move {
-                                    if let Err(e) = handle_connection(
+                                    let result = handle_connection(
                                         client_stream, 
+                                        client_addr,
                                         &config,
-                                        &stats,
-                                    ).await {
+                                        &upstreams,
+                                        udp_port,
+                                        &udp_associations,
+                                        &mut tracker,
+                                    ).await;
+                                    tracker.finish(result.as_ref().err().cloned());
+                                    if let Err(e) = result {
                                         debug!(
                                             profile_id = %profile_id,
                                             error = %e,
@@ -234,8 +422,9 @@ impl ProxyBridge {
                             }
                         }
                     }
+                    _ = shutdown.notified() => break,
                     _ = tokio::time::sleep(std::time::Duration::from_secs(1)) => {
-                        // 定期检查是否应该停止
+                        // 定期检查是否应该停止（兜底错过的停止通知）
                         if !running.load(Ordering::Relaxed) {
                             break;
                         }
@@ -245,20 +434,27 @@ impl ProxyBridge {
             
             info!(profile_id = %profile_id, "TCP 代理桥接监听循环已退出");
         });
+        self.tasks.lock().unwrap().push(accept_task);
         
         // 3. 如果启用 UDP，启动 UDP 转发（用于 WebRTC）
-        if self.config.enable_udp && self.local_udp_port > 0 {
-            self.start_udp_relay().await?;
+        // UDP ASSOCIATE 无法穿过前置跳板，仅直连 SOCKS5 出口时启用（端口分配时已据此决定是否绑定）
+        if let Some(udp) = ports.udp {
+            if let Err(e) = self.start_udp_relay(udp) {
+                self.stop();
+                return Err(e);
+            }
         }
         
         Ok(())
     }
     
     /// 启动 UDP 中继（SOCKS5 UDP ASSOCIATE）
-    async fn start_udp_relay(&self) -> Result<(), String> {
-        let udp_addr = SocketAddr::from(([127, 0, 0, 1], self.local_udp_port));
-        let local_socket = UdpSocket::bind(udp_addr).await
-            .map_err(|e| format!("绑定 UDP 端口 {} 失败: {}", self.local_udp_port, e))?;
+    ///
+    /// 本地 UDP 端口作为 SOCKS5 UDP 中继端点：客户端发送带 SOCKS5 UDP 头的数据报，
+    /// 每个客户端地址对应一个独立的上游 ASSOCIATE，响应按客户端分发。
+    fn start_udp_relay(&self, socket: std::net::UdpSocket) -> Result<(), String> {
+        let local_socket = UdpSocket::from_std(socket)
+            .map_err(|e| format!("接管 UDP 端口 {} 失败: {}", self.local_udp_port, e))?;
         
         info!(
             profile_id = %self.profile_id,
@@ -267,104 +463,83 @@ impl ProxyBridge {
         );
         
         let running = Arc::clone(&self.running);
-        let config = self.config.clone();
+        let shutdown = Arc::clone(&self.shutdown);
+        let upstreams = Arc::clone(&self.upstreams);
         let stats = Arc::clone(&self.stats);
         let profile_id = self.profile_id.clone();
+        let idle_timeout = self.udp_idle_timeout;
+        let associations = Arc::clone(&self.udp_associations);
+        let auth_required = local_credentials(&self.config).is_some();
         
-        tokio::spawn(async move {
-            // 建立 SOCKS5 UDP ASSOCIATE 连接
-            match establish_udp_associate(&config).await {
-                Ok((control_stream, relay_addr)) => {
-                    info!(
-                        profile_id = %profile_id,
-                        relay_addr = %relay_addr,
-                        "SOCKS5 UDP ASSOCIATE 建立成功"
-                    );
-                    
-                    // 创建到中继服务器的 UDP 套接字
-                    let relay_socket = match UdpSocket::bind("0.0.0.0:0").await {
-                        Ok(s) => s,
-                        Err(e) => {
-                            error!(profile_id = %profile_id, error = %e, "创建 UDP 中继套接字失败");
-                            return;
-                        }
-                    };
-                    
-                    // 连接到中继地址
-                    if let Err(e) = relay_socket.connect(&relay_addr).await {
-                        error!(profile_id = %profile_id, error = %e, "连接 UDP 中继失败");
-                        return;
-                    }
-                    
-                    // UDP 转发循环 - 使用两个独立缓冲区避免借用冲突
-                    let local_socket = Arc::new(local_socket);
-                    let relay_socket = Arc::new(relay_socket);
-                    let mut client_addr: Option<SocketAddr> = None;
-                    
-                    let mut recv_buf = vec![0u8; 65535];  // 接收客户端数据
-                    let mut relay_buf = vec![0u8; 65535]; // 接收中继响应
-                    
-                    while running.load(Ordering::Relaxed) {
-                        tokio::select! {
-                            // 从客户端（浏览器）接收 UDP 数据
-                            result = local_socket.recv_from(&mut recv_buf) => {
-                                match result {
-                                    Ok((n, addr)) => {
-                                        client_addr = Some(addr);
-                                        
-                                        // 封装为 SOCKS5 UDP 数据包格式
-                                        // [RSV(2) | FRAG(1) | ATYP(1) | DST.ADDR | DST.PORT | DATA]
-                                        // 这里简化处理，直接转发（需要从数据包中解析目标地址）
-                                        if let Err(e) = relay_socket.send(&recv_buf[..n]).await {
-                                            debug!(profile_id = %profile_id, error = %e, "发送 UDP 数据失败");
-                                        } else {
-                                            stats.udp_packets_sent.fetch_add(1, Ordering::Relaxed);
-                                        }
-                                    }
-                                    Err(e) => {
-                                        debug!(profile_id = %profile_id, error = %e, "接收 UDP 数据失败");
-                                    }
-                                }
+        let relay_task = tokio::spawn(async move {
+            let local_socket = Arc::new(local_socket);
+            // 客户端地址 -> 会话发送队列（会话结束后队列关闭，下个数据包会重建会话）
+            let mut sessions: HashMap<SocketAddr, mpsc::Sender<Vec<u8>>> = HashMap::new();
+            let mut recv_buf = vec![0u8; 65535];
+            
+            while running.load(Ordering::Relaxed) {
+                tokio::select! {
+                    result = local_socket.recv_from(&mut recv_buf) => {
+                        let (n, client_addr) = match result {
+                            Ok(r) => r,
+                            Err(e) => {
+                                debug!(profile_id = %profile_id, error = %e, "接收 UDP 数据失败");
+                                continue;
                             }
-                            // 从中继服务器接收 UDP 响应
-                            result = relay_socket.recv(&mut relay_buf) => {
-                                match result {
-                                    Ok(n) => {
-                                        if let Some(addr) = client_addr {
-                                            if let Err(e) = local_socket.send_to(&relay_buf[..n], addr).await {
-                                                debug!(profile_id = %profile_id, error = %e, "转发 UDP 响应失败");
-                                            } else {
-                                                stats.udp_packets_received.fetch_add(1, Ordering::Relaxed);
-                                            }
-                                        }
-                                    }
-                                    Err(e) => {
-                                        debug!(profile_id = %profile_id, error = %e, "接收 UDP 响应失败");
-                                    }
-                                }
+                        };
+                        
+                        // 启用本地认证时只接受已认证 ASSOCIATE 的客户端地址
+                        if auth_required && !associations.permits(client_addr) {
+                            debug!(profile_id = %profile_id, client = %client_addr, "丢弃未关联客户端的 UDP 数据包");
+                            stats.udp_packets_dropped.fetch_add(1, Ordering::Relaxed);
+                            continue;
+                        }
+                        
+                        // 校验并重新封装客户端数据报（不支持分片）
+                        let packet = match parse_udp_packet(&recv_buf[..n]) {
+                            Ok((target, payload)) => encode_udp_packet(&target, payload),
+                            Err(e) => {
+                                debug!(profile_id = %profile_id, client = %client_addr, error = %e, "丢弃 UDP 数据包");
+                                stats.udp_packets_dropped.fetch_add(1, Ordering::Relaxed);
+                                continue;
                             }
-                            _ = tokio::time::sleep(std::time::Duration::from_secs(1)) => {
-                                if !running.load(Ordering::Relaxed) {
-                                    break;
-                                }
+                        };
+                        
+                        let sender = match sessions.get(&client_addr) {
+                            Some(sender) if !sender.is_closed() => sender.clone(),
+                            _ => {
+                                let (sender, receiver) = mpsc::channel(UDP_SESSION_QUEUE_SIZE);
+                                tokio::spawn(run_udp_session(
+                                    client_addr,
+                                    receiver,
+                                    Arc::clone(&local_socket),
+                                    Arc::clone(&upstreams),
+                                    Arc::clone(&stats),
+                                    idle_timeout,
+                                    profile_id.clone(),
+                                ));
+                                sessions.insert(client_addr, sender.clone());
+                                sender
                             }
+                        };
+                        
+                        if sender.try_send(packet).is_err() {
+                            stats.udp_packets_dropped.fetch_add(1, Ordering::Relaxed);
                         }
                     }
-                    
-                    // 保持控制连接存活（SOCKS5 规范要求）
-                    drop(control_stream);
-                }
-                Err(e) => {
-                    error!(
-                        profile_id = %profile_id,
-                        error = %e,
-                        "SOCKS5 UDP ASSOCIATE 建立失败"
-                    );
+                    _ = shutdown.notified() => break,
+                    _ = tokio::time::sleep(Duration::from_secs(1)) => {
+                        // 清理已结束的会话
+                        sessions.retain(|_, sender| !sender.is_closed());
+                    }
                 }
             }
             
+            // 丢弃发送队列后，各会话会关闭各自的控制连接
+            drop(sessions);
             info!(profile_id = %profile_id, "UDP 中继已退出");
         });
+        self.tasks.lock().unwrap().push(relay_task);
         
         Ok(())
     }
@@ -372,88 +547,238 @@ impl ProxyBridge {
     /// 停止代理桥接
     pub fn stop(&self) {
         self.running.store(false, Ordering::Relaxed);
+        self.shutdown.notify_waiters();
         info!(
             profile_id = %self.profile_id,
             local_port = self.local_port,
             "代理桥接已停止"
         );
     }
+    
+    /// 停止代理桥接并等待监听循环退出（返回后端口已关闭，可安全复用）
+    pub async fn shutdown(&self) {
+        self.stop();
+        let tasks: Vec<JoinHandle<()>> = self.tasks.lock().unwrap().drain(..).collect();
+        for task in tasks {
+            let _ = task.await;
+        }
+        self.ports.lock().unwrap().take();
+    }
 }
 
+/// 上游连接（明文 TCP 或 TLS）
+pub(crate) trait UpstreamIo: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> UpstreamIo for T {}
+
+/// 已建立隧道的上游连接
+pub(crate) type UpstreamStream = Box<dyn UpstreamIo>;
+
 /// 处理单个连接
 async fn handle_connection(
-    mut client: TcpStream,
+    client: ClientStream,
+    client_addr: SocketAddr,
     config: &ProxyBridgeConfig,
-    stats: &BridgeStatsInner,
+    upstreams: &UpstreamPool,
+    udp_port: u16,
+    udp_associations: &Arc<UdpAssociations>,
+    tracker: &mut ConnectionTracker,
 ) -> Result<(), String> {
-    // 1. 解析 HTTP CONNECT 请求
-    let target = parse_http_connect(&mut client).await?;
-    
-    // 2. 连接上游代理
-    let mut upstream = match config.upstream_type.as_str() {
-        "socks5" => connect_socks5_proxy(
-            &config.upstream_host,
-            config.upstream_port,
-            config.username.as_deref(),
-            config.password.as_deref(),
-            &target.0,
-            target.1,
-        ).await?,
-        "http" | "https" => connect_http_proxy(
-            &config.upstream_host,
-            config.upstream_port,
-            config.username.as_deref(),
-            config.password.as_deref(),
-            &target.0,
-            target.1,
-        ).await?,
-        _ => return Err(format!("不支持的代理类型: {}", config.upstream_type)),
-    };
+    let mut client = BufReader::new(client);
+    
+    // 0. 首字节为 0x05 时按 SOCKS5 处理，否则按 HTTP 处理
+    let first = client.fill_buf().await
+        .map_err(|e| format!("读取请求失败: {}", e))?
+        .first()
+        .copied();
+    match first {
+        None => return Err("连接已关闭".to_string()),
+        Some(0x05) => {
+            let udp = (udp_port > 0).then_some((udp_port, udp_associations));
+            return handle_socks5_inbound(client, client_addr, config, upstreams, udp, tracker).await;
+        }
+        Some(_) => {}
+    }
+    
+    // 1. 读取请求头
+    let head = read_http_head(&mut client).await?
+        .ok_or_else(|| "连接已关闭".to_string())?;
+    let request = HttpHead::parse(&head)?;
+    debug!(request = %request.start_line, "收到 HTTP 请求");
     
-    // 3. 发送 HTTP 200 响应给客户端
-    client.write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n").await
+    // 2. 普通 HTTP 请求（absolute-form）：逐个请求转发
+    if !request.method().eq_ignore_ascii_case("CONNECT") {
+        return forward_http_requests(client, request, upstreams, tracker).await;
+    }
+    
+    // 3. CONNECT 隧道：连接上游代理
+    let (host, port) = parse_host_port(request.target())?;
+    tracker.begin(&host, port);
+    let mut upstream = upstreams.connect(&host, port, &tracker.counters).await?;
+    
+    // 4. 发送 HTTP 200 响应给客户端
+    client.get_mut().write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n").await
         .map_err(|e| format!("发送响应失败: {}", e))?;
     
-    // 4. 双向流量转发
-    let (bytes_sent, bytes_received) = copy_bidirectional(&mut client, &mut upstream).await?;
+    // 客户端可能在收到 200 之前就发送了数据（已被缓冲）
+    let buffered = client.buffer().to_vec();
+    if !buffered.is_empty() {
+        upstream.write_all(&buffered).await
+            .map_err(|e| format!("转发缓冲数据失败: {}", e))?;
+    }
+    let mut client = client.into_inner();
     
-    stats.bytes_sent.fetch_add(bytes_sent, Ordering::Relaxed);
-    stats.bytes_received.fetch_add(bytes_received, Ordering::Relaxed);
+    // 5. 双向流量转发（字节数由 MeteredStream 计入统计）
+    copy_bidirectional(&mut client, &mut upstream).await?;
     
     Ok(())
 }
 
-/// 解析 HTTP CONNECT 请求
-async fn parse_http_connect(stream: &mut TcpStream) -> Result<(String, u16), String> {
-    let mut buf = vec![0u8; 4096];
-    let n = stream.read(&mut buf).await
-        .map_err(|e| format!("读取请求失败: {}", e))?;
-    
-    if n == 0 {
-        return Err("连接已关闭".to_string());
+/// 校验代理链：至少一跳，协议受支持，地址有效且不重复
+pub fn validate_chain(hops: &[ProxyHop]) -> Result<(), String> {
+    if hops.is_empty() {
+        return Err("代理链为空".to_string());
     }
     
-    let request = String::from_utf8_lossy(&buf[..n]);
-    debug!(request = %request, "收到 HTTP 请求");
-    
-    // 解析 "CONNECT example.com:443 HTTP/1.1"
-    let first_line = request.lines().next()
-        .ok_or("空请求")?;
-    
-    let parts: Vec<&str> = first_line.split_whitespace().collect();
-    if parts.len() < 2 {
-        return Err(format!("无效的请求格式: {}", first_line));
+    for (i, hop) in hops.iter().enumerate() {
+        if hop.host.trim().is_empty() || hop.port == 0 {
+            return Err(format!("代理链第 {} 跳地址无效: {}:{}", i + 1, hop.host, hop.port));
+        }
+        if !matches!(hop.proxy_type.to_lowercase().as_str(), "socks5" | "http" | "https") {
+            return Err(format!("代理链第 {} 跳协议不受支持: {}", i + 1, hop.proxy_type));
+        }
+        if hops[..i].iter().any(|prev| prev.host.eq_ignore_ascii_case(&hop.host) && prev.port == hop.port) {
+            return Err(format!("代理链第 {} 跳重复: {}:{}", i + 1, hop.host, hop.port));
+        }
     }
     
-    let method = parts[0].to_uppercase();
-    if method != "CONNECT" {
-        return Err(format!("不支持的方法: {}", method));
+    Ok(())
+}
+
+/// 建立上游隧道失败的原因
+#[derive(Debug, Clone)]
+pub(crate) enum ConnectError {
+    /// 代理本身不可用：TCP 连接、TLS 握手、代理认证或协议错误（可切换备用代理）
+    Proxy(String),
+    /// 代理正常但目标不可达：SOCKS5 REP ≠ 0、CONNECT 返回非 2xx（切换代理无济于事）
+    Target(String),
+}
+
+impl ConnectError {
+    /// 附加上下文（如代理链中的跳数），保持失败类型不变
+    fn context(self, prefix: String) -> Self {
+        match self {
+            Self::Proxy(e) => Self::Proxy(format!("{}: {}", prefix, e)),
+            Self::Target(e) => Self::Target(format!("{}: {}", prefix, e)),
+        }
+    }
+}
+
+impl std::fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Proxy(e) | Self::Target(e) => f.write_str(e),
+        }
+    }
+}
+
+impl From<ConnectError> for String {
+    fn from(e: ConnectError) -> Self {
+        e.to_string()
+    }
+}
+
+/// 逐跳建立代理链隧道：TCP 连接第一跳，之后每一跳在上一跳的隧道内握手，
+/// 最后一跳连接目标地址。hop_latencies 记录到达每一跳所用的毫秒数
+/// - socks5: SOCKS5 CONNECT（含用户名/密码认证）
+/// - http:   明文 HTTP CONNECT（含 Proxy-Authorization）
+/// - https:  先与代理建立 TLS，再在 TLS 内发送 HTTP CONNECT
+pub(crate) async fn connect_chain(
+    hops: &[ProxyHop],
+    target_host: &str,
+    target_port: u16,
+    hop_latencies: &mut Vec<u64>,
+) -> Result<UpstreamStream, ConnectError> {
+    let first = hops.first().ok_or_else(|| ConnectError::Proxy("代理链为空".to_string()))?;
+    
+    let start = std::time::Instant::now();
+    let stream = TcpStream::connect((first.host.as_str(), first.port)).await
+        .map_err(|e| ConnectError::Proxy(format!("连接代理 {}:{} 失败: {}", first.host, first.port, e)))?;
+    hop_latencies.push(start.elapsed().as_millis() as u64);
+    let mut stream: UpstreamStream = Box::new(stream);
+    
+    for (i, hop) in hops.iter().enumerate() {
+        let (next_host, next_port) = match hops.get(i + 1) {
+            Some(next) => (next.host.as_str(), next.port),
+            None => (target_host, target_port),
+        };
+        
+        let start = std::time::Instant::now();
+        // 中间跳无法到达下一跳属于代理链故障，只有最后一跳的目标失败归为目标不可达
+        stream = hop_handshake(stream, hop, next_host, next_port).await
+            .map_err(|e| {
+                let e = if i + 1 < hops.len() { ConnectError::Proxy(e.to_string()) } else { e };
+                if hops.len() > 1 {
+                    e.context(format!("代理链第 {} 跳 ({}:{}) 失败", i + 1, hop.host, hop.port))
+                } else {
+                    e
+                }
+            })?;
+        if i + 1 < hops.len() {
+            hop_latencies.push(start.elapsed().as_millis() as u64);
+        }
     }
     
-    let target = parts[1];
-    let (host, port) = parse_host_port(target)?;
+    Ok(stream)
+}
+
+/// 在已连接到代理的流上完成握手，使其隧道指向下一地址
+async fn hop_handshake(
+    stream: UpstreamStream,
+    hop: &ProxyHop,
+    target_host: &str,
+    target_port: u16,
+) -> Result<UpstreamStream, ConnectError> {
+    let username = hop.username.as_deref();
+    let password = hop.password.as_deref();
+    
+    match hop.proxy_type.to_lowercase().as_str() {
+        "socks5" => {
+            let mut stream = stream;
+            socks5_connect_handshake(&mut stream, username, password, target_host, target_port).await?;
+            Ok(stream)
+        }
+        "http" => {
+            let mut stream = stream;
+            http_connect_handshake(&mut stream, username, password, target_host, target_port).await?;
+            Ok(stream)
+        }
+        "https" => {
+            let mut stream = tls_connect(stream, &hop.host).await.map_err(ConnectError::Proxy)?;
+            http_connect_handshake(&mut stream, username, password, target_host, target_port).await?;
+            Ok(Box::new(stream))
+        }
+        _ => Err(ConnectError::Proxy(format!("不支持的代理类型: {}", hop.proxy_type))),
+    }
+}
+
+/// 经前置跳板连接到代理本身（HTTPS 代理完成 TLS 握手），不发送 CONNECT
+async fn connect_proxy(chain: &[ProxyHop], proxy: &ProxyHop) -> Result<UpstreamStream, ConnectError> {
+    let stream: UpstreamStream = if chain.is_empty() {
+        let stream = TcpStream::connect((proxy.host.as_str(), proxy.port)).await
+            .map_err(|e| ConnectError::Proxy(format!("连接代理 {}:{} 失败: {}", proxy.host, proxy.port, e)))?;
+        Box::new(stream)
+    } else {
+        // 前置跳板无法到达出口代理，对出口而言同样是代理故障
+        connect_chain(chain, &proxy.host, proxy.port, &mut Vec::new()).await
+            .map_err(|e| ConnectError::Proxy(e.to_string()))?
+    };
     
-    Ok((host, port))
+    if proxy.proxy_type.eq_ignore_ascii_case("https") {
+        Ok(Box::new(tls_connect(stream, &proxy.host).await.map_err(ConnectError::Proxy)?))
+    } else {
+        Ok(stream)
+    }
 }
 
 /// 解析 host:port 格式
@@ -484,73 +809,18 @@ fn parse_host_port(target: &str) -> Result<(String, u16), String> {
     }
 }
 
-/// 连接 SOCKS5 代理（含认证）
-async fn connect_socks5_proxy(
-    proxy_host: &str,
-    proxy_port: u16,
+/// 在已建立的连接上完成 SOCKS5 握手、认证和 CONNECT
+async fn socks5_connect_handshake<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
     username: Option<&str>,
     password: Option<&str>,
     target_host: &str,
     target_port: u16,
-) -> Result<TcpStream, String> {
-    // 1. 连接到 SOCKS5 代理服务器
-    let proxy_addr = format!("{}:{}", proxy_host, proxy_port);
-    let mut stream = TcpStream::connect(&proxy_addr).await
-        .map_err(|e| format!("连接 SOCKS5 代理失败: {}", e))?;
-    
-    // 2. SOCKS5 握手
-    let auth_required = username.is_some() && password.is_some();
-    
-    // 发送支持的认证方法
-    if auth_required {
-        // 0x02: 用户名/密码认证
-        stream.write_all(&[0x05, 0x02, 0x00, 0x02]).await
-            .map_err(|e| format!("发送认证方法失败: {}", e))?;
-    } else {
-        // 0x00: 无认证
-        stream.write_all(&[0x05, 0x01, 0x00]).await
-            .map_err(|e| format!("发送认证方法失败: {}", e))?;
-    }
-    
-    // 读取服务器选择的认证方法
-    let mut response = [0u8; 2];
-    stream.read_exact(&mut response).await
-        .map_err(|e| format!("读取认证响应失败: {}", e))?;
-    
-    if response[0] != 0x05 {
-        return Err(format!("无效的 SOCKS 版本: {}", response[0]));
-    }
-    
-    // 3. 如果需要用户名/密码认证
-    if response[1] == 0x02 {
-        let user = username.ok_or("需要用户名")?;
-        let pass = password.ok_or("需要密码")?;
-        
-        // 构建认证请求: [版本, 用户名长度, 用户名, 密码长度, 密码]
-        let mut auth_request = vec![0x01]; // 认证子协议版本
-        auth_request.push(user.len() as u8);
-        auth_request.extend(user.as_bytes());
-        auth_request.push(pass.len() as u8);
-        auth_request.extend(pass.as_bytes());
-        
-        stream.write_all(&auth_request).await
-            .map_err(|e| format!("发送认证请求失败: {}", e))?;
-        
-        // 读取认证结果
-        let mut auth_response = [0u8; 2];
-        stream.read_exact(&mut auth_response).await
-            .map_err(|e| format!("读取认证结果失败: {}", e))?;
-        
-        if auth_response[1] != 0x00 {
-            return Err("SOCKS5 认证失败".to_string());
-        }
-        
-        debug!("SOCKS5 认证成功");
-    } else if response[1] == 0xFF {
-        return Err("SOCKS5 服务器不支持所提供的认证方法".to_string());
-    }
+) -> Result<(), ConnectError> {
+    // 1-2. SOCKS5 握手与认证
+    socks5_authenticate(stream, username, password).await.map_err(ConnectError::Proxy)?;
     
-    // 4. 发送 CONNECT 请求
+    // 3. 发送 CONNECT 请求
     let mut connect_request = vec![
         0x05, // SOCKS 版本
         0x01, // CONNECT 命令
@@ -558,16 +828,13 @@ async fn connect_socks5_proxy(
     ];
     
     // 地址类型和目标地址
-    if target_host.parse::<std::net::Ipv4Addr>().is_ok() {
+    if let Ok(addr) = target_host.parse::<std::net::Ipv4Addr>() {
         // IPv4 地址
         connect_request.push(0x01);
-        for octet in target_host.split('.') {
-            connect_request.push(octet.parse::<u8>().unwrap());
-        }
-    } else if target_host.parse::<std::net::Ipv6Addr>().is_ok() {
+        connect_request.extend(addr.octets());
+    } else if let Ok(addr) = target_host.parse::<std::net::Ipv6Addr>() {
         // IPv6 地址
         connect_request.push(0x04);
-        let addr: std::net::Ipv6Addr = target_host.parse().unwrap();
         connect_request.extend(addr.octets());
     } else {
         // 域名
@@ -577,16 +844,15 @@ async fn connect_socks5_proxy(
     }
     
     // 端口（大端序）
-    connect_request.push((target_port >> 8) as u8);
-    connect_request.push((target_port & 0xFF) as u8);
+    connect_request.extend(target_port.to_be_bytes());
     
     stream.write_all(&connect_request).await
-        .map_err(|e| format!("发送 CONNECT 请求失败: {}", e))?;
+        .map_err(|e| ConnectError::Proxy(format!("发送 CONNECT 请求失败: {}", e)))?;
     
-    // 5. 读取 CONNECT 响应
-    let mut connect_response = [0u8; 10];
-    stream.read_exact(&mut connect_response[..4]).await
-        .map_err(|e| format!("读取 CONNECT 响应失败: {}", e))?;
+    // 4. 读取 CONNECT 响应
+    let mut connect_response = [0u8; 4];
+    stream.read_exact(&mut connect_response).await
+        .map_err(|e| ConnectError::Proxy(format!("读取 CONNECT 响应失败: {}", e)))?;
     
     if connect_response[1] != 0x00 {
         let error_msg = match connect_response[1] {
@@ -600,7 +866,7 @@ async fn connect_socks5_proxy(
             0x08 => "不支持的地址类型",
             _ => "未知错误",
         };
-        return Err(format!("SOCKS5 CONNECT 失败: {}", error_msg));
+        return Err(ConnectError::Target(format!("SOCKS5 CONNECT 失败: {}", error_msg)));
     }
     
     // 读取剩余的响应（绑定地址和端口）
@@ -631,98 +897,29 @@ async fn connect_socks5_proxy(
         "SOCKS5 连接建立成功"
     );
     
-    Ok(stream)
+    Ok(())
 }
 
-/// 连接 HTTP/HTTPS 代理
-async fn connect_http_proxy(
-    proxy_host: &str,
-    proxy_port: u16,
+/// SOCKS5 握手：协商认证方法，需要时完成用户名/密码认证
+async fn socks5_authenticate<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
     username: Option<&str>,
     password: Option<&str>,
-    target_host: &str,
-    target_port: u16,
-) -> Result<TcpStream, String> {
-    // 连接到 HTTP 代理服务器
-    let proxy_addr = format!("{}:{}", proxy_host, proxy_port);
-    let mut stream = TcpStream::connect(&proxy_addr).await
-        .map_err(|e| format!("连接 HTTP 代理失败: {}", e))?;
-    
-    // 构建 CONNECT 请求
-    let mut request = format!(
-        "CONNECT {}:{} HTTP/1.1\r\nHost: {}:{}\r\n",
-        target_host, target_port, target_host, target_port
-    );
-    
-    // 添加代理认证
-    if let (Some(user), Some(pass)) = (username, password) {
-        use base64::Engine;
-        let credentials = format!("{}:{}", user, pass);
-        let encoded = base64::engine::general_purpose::STANDARD.encode(credentials);
-        request.push_str(&format!("Proxy-Authorization: Basic {}\r\n", encoded));
-    }
-    
-    request.push_str("\r\n");
-    
-    stream.write_all(request.as_bytes()).await
-        .map_err(|e| format!("发送 CONNECT 请求失败: {}", e))?;
-    
-    // 读取响应
-    let mut buf = vec![0u8; 4096];
-    let n = stream.read(&mut buf).await
-        .map_err(|e| format!("读取响应失败: {}", e))?;
-    
-    let response = String::from_utf8_lossy(&buf[..n]);
-    
-    // 检查状态码
-    if !response.contains("200") {
-        return Err(format!("HTTP 代理连接失败: {}", response.lines().next().unwrap_or("")));
-    }
-    
-    debug!(
-        target = %format!("{}:{}", target_host, target_port),
-        "HTTP 代理连接建立成功"
-    );
-    
-    Ok(stream)
-}
-
-/// 双向流量转发
-async fn copy_bidirectional(
-    client: &mut TcpStream,
-    server: &mut TcpStream,
-) -> Result<(u64, u64), String> {
-    let (mut client_reader, mut client_writer) = client.split();
-    let (mut server_reader, mut server_writer) = server.split();
-    
-    let client_to_server = tokio::io::copy(&mut client_reader, &mut server_writer);
-    let server_to_client = tokio::io::copy(&mut server_reader, &mut client_writer);
-    
-    let result = tokio::try_join!(client_to_server, server_to_client)
-        .map_err(|e| format!("流量转发失败: {}", e))?;
-    
-    Ok(result)
-}
-
-/// 建立 SOCKS5 UDP ASSOCIATE 连接
-/// 返回控制连接和 UDP 中继地址
-async fn establish_udp_associate(config: &ProxyBridgeConfig) -> Result<(TcpStream, String), String> {
-    // 1. 连接到 SOCKS5 代理服务器
-    let proxy_addr = format!("{}:{}", config.upstream_host, config.upstream_port);
-    let mut stream = TcpStream::connect(&proxy_addr).await
-        .map_err(|e| format!("连接 SOCKS5 代理失败: {}", e))?;
-    
-    // 2. SOCKS5 握手
-    let auth_required = config.username.is_some() && config.password.is_some();
+) -> Result<(), String> {
+    let auth_required = username.is_some() && password.is_some();
     
+    // 发送支持的认证方法
     if auth_required {
+        // 0x02: 用户名/密码认证
         stream.write_all(&[0x05, 0x02, 0x00, 0x02]).await
             .map_err(|e| format!("发送认证方法失败: {}", e))?;
     } else {
+        // 0x00: 无认证
         stream.write_all(&[0x05, 0x01, 0x00]).await
             .map_err(|e| format!("发送认证方法失败: {}", e))?;
     }
     
+    // 读取服务器选择的认证方法
     let mut response = [0u8; 2];
     stream.read_exact(&mut response).await
         .map_err(|e| format!("读取认证响应失败: {}", e))?;
@@ -731,12 +928,13 @@ async fn establish_udp_associate(config: &ProxyBridgeConfig) -> Result<(TcpStrea
         return Err(format!("无效的 SOCKS 版本: {}", response[0]));
     }
     
-    // 3. 如果需要用户名/密码认证
+    // 如果需要用户名/密码认证
     if response[1] == 0x02 {
-        let user = config.username.as_deref().ok_or("需要用户名")?;
-        let pass = config.password.as_deref().ok_or("需要密码")?;
+        let user = username.ok_or("需要用户名")?;
+        let pass = password.ok_or("需要密码")?;
         
-        let mut auth_request = vec![0x01];
+        // 构建认证请求: [版本, 用户名长度, 用户名, 密码长度, 密码]
+        let mut auth_request = vec![0x01]; // 认证子协议版本
         auth_request.push(user.len() as u8);
         auth_request.extend(user.as_bytes());
         auth_request.push(pass.len() as u8);
@@ -745,6 +943,7 @@ async fn establish_udp_associate(config: &ProxyBridgeConfig) -> Result<(TcpStrea
         stream.write_all(&auth_request).await
             .map_err(|e| format!("发送认证请求失败: {}", e))?;
         
+        // 读取认证结果
         let mut auth_response = [0u8; 2];
         stream.read_exact(&mut auth_response).await
             .map_err(|e| format!("读取认证结果失败: {}", e))?;
@@ -753,28 +952,1734 @@ async fn establish_udp_associate(config: &ProxyBridgeConfig) -> Result<(TcpStrea
             return Err("SOCKS5 认证失败".to_string());
         }
         
-        debug!("SOCKS5 UDP ASSOCIATE 认证成功");
+        debug!("SOCKS5 认证成功");
     } else if response[1] == 0xFF {
         return Err("SOCKS5 服务器不支持所提供的认证方法".to_string());
     }
     
-    // 4. 发送 UDP ASSOCIATE 请求 (CMD = 0x03)
-    // DST.ADDR 和 DST.PORT 设为 0，表示客户端会从任意地址发送
-    let udp_associate_request = vec![
-        0x05, // SOCKS 版本
-        0x03, // UDP ASSOCIATE 命令
-        0x00, // 保留字节
-        0x01, // 地址类型: IPv4
-        0x00, 0x00, 0x00, 0x00, // 地址: 0.0.0.0
-        0x00, 0x00, // 端口: 0
-    ];
+    Ok(())
+}
+
+/// 与 HTTPS 代理建立 TLS 连接（使用内置的 Web PKI 根证书校验代理证书）
+async fn tls_connect<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: S,
+    server_name: &str,
+) -> Result<tokio_rustls::client::TlsStream<S>, String> {
+    use tokio_rustls::rustls;
+    
+    static TLS_CONFIG: OnceLock<Arc<rustls::ClientConfig>> = OnceLock::new();
+    let config = match TLS_CONFIG.get() {
+        Some(config) => Arc::clone(config),
+        None => {
+            let roots = rustls::RootCertStore {
+                roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+            };
+            let config = rustls::ClientConfig::builder_with_provider(Arc::new(
+                rustls::crypto::ring::default_provider(),
+            ))
+            .with_safe_default_protocol_versions()
+            .map_err(|e| format!("初始化 TLS 配置失败: {}", e))?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+            Arc::clone(TLS_CONFIG.get_or_init(|| Arc::new(config)))
+        }
+    };
     
-    stream.write_all(&udp_associate_request).await
-        .map_err(|e| format!("发送 UDP ASSOCIATE 请求失败: {}", e))?;
+    let server_name = rustls::pki_types::ServerName::try_from(server_name.to_string())
+        .map_err(|e| format!("无效的代理主机名 {}: {}", server_name, e))?;
     
-    // 5. 读取响应
-    let mut response_header = [0u8; 4];
-    stream.read_exact(&mut response_header).await
+    tokio_rustls::TlsConnector::from(config)
+        .connect(server_name, stream)
+        .await
+        .map_err(|e| format!("与 HTTPS 代理建立 TLS 连接失败: {}", e))
+}
+
+/// 在已建立的连接上发送 HTTP CONNECT 请求（含 Proxy-Authorization）
+async fn http_connect_handshake<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    username: Option<&str>,
+    password: Option<&str>,
+    target_host: &str,
+    target_port: u16,
+) -> Result<(), ConnectError> {
+    // IPv6 目标需要加方括号
+    let authority = if target_host.contains(':') {
+        format!("[{}]:{}", target_host, target_port)
+    } else {
+        format!("{}:{}", target_host, target_port)
+    };
+    
+    // 构建 CONNECT 请求
+    let mut request = format!(
+        "CONNECT {} HTTP/1.1\r\nHost: {}\r\n",
+        authority, authority
+    );
+    
+    // 添加代理认证
+    if let Some(auth) = proxy_authorization(username, password) {
+        request.push_str(&format!("Proxy-Authorization: {}\r\n", auth));
+    }
+    
+    request.push_str("\r\n");
+    
+    stream.write_all(request.as_bytes()).await
+        .map_err(|e| ConnectError::Proxy(format!("发送 CONNECT 请求失败: {}", e)))?;
+    
+    // 读取响应头（逐字节读到空行，避免读走隧道数据）
+    let head = read_http_head_unbuffered(stream).await.map_err(ConnectError::Proxy)?;
+    let status_line = head.lines().next().unwrap_or("");
+    
+    // 检查状态码：407 为代理认证失败，其余非 2xx 为目标不可达
+    match parse_status_code(status_line) {
+        Some(200..=299) => {}
+        Some(407) => return Err(ConnectError::Proxy(format!("HTTP 代理认证失败: {}", status_line))),
+        Some(_) => return Err(ConnectError::Target(format!("HTTP 代理连接失败: {}", status_line))),
+        None => return Err(ConnectError::Proxy(format!("HTTP 代理响应无效: {}", status_line))),
+    }
+    
+    debug!(
+        target = %authority,
+        "HTTP 代理连接建立成功"
+    );
+    
+    Ok(())
+}
+
+/// 构建 Basic 认证头的值
+fn proxy_authorization(username: Option<&str>, password: Option<&str>) -> Option<String> {
+    use base64::Engine;
+    
+    let (user, pass) = match (username, password) {
+        (Some(user), Some(pass)) => (user, pass),
+        (Some(user), None) => (user, ""),
+        _ => return None,
+    };
+    let credentials = format!("{}:{}", user, pass);
+    Some(format!("Basic {}", base64::engine::general_purpose::STANDARD.encode(credentials)))
+}
+
+/// 逐字节读取 HTTP 响应头（以 \r\n\r\n 结束），用于无缓冲的隧道握手
+async fn read_http_head_unbuffered<S: AsyncRead + Unpin>(stream: &mut S) -> Result<String, String> {
+    let mut head = Vec::with_capacity(256);
+    let mut byte = [0u8; 1];
+    
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() >= 16 * 1024 {
+            return Err("HTTP 响应头过长".to_string());
+        }
+        let n = stream.read(&mut byte).await
+            .map_err(|e| format!("读取响应失败: {}", e))?;
+        if n == 0 {
+            return Err("代理在响应完成前关闭了连接".to_string());
+        }
+        head.push(byte[0]);
+    }
+    
+    Ok(String::from_utf8_lossy(&head).to_string())
+}
+
+/// 解析状态行中的状态码（如 "HTTP/1.1 200 Connection established"）
+fn parse_status_code(status_line: &str) -> Option<u16> {
+    let mut parts = status_line.split_whitespace();
+    let version = parts.next()?;
+    if !version.starts_with("HTTP/") {
+        return None;
+    }
+    parts.next()?.parse().ok()
+}
+
+/// 双向流量转发，返回 (上行字节数, 下行字节数)
+async fn copy_bidirectional<C, S>(client: &mut C, server: &mut S) -> Result<(u64, u64), String>
+where
+    C: AsyncRead + AsyncWrite + Unpin + ?Sized,
+    S: AsyncRead + AsyncWrite + Unpin + ?Sized,
+{
+    tokio::io::copy_bidirectional(client, server)
+        .await
+        .map_err(|e| format!("流量转发失败: {}", e))
+}
+
+// ==================== 上游故障转移与会话轮换 ====================
+
+/// 用户名 / 密码中的会话占位符（轮换型代理的 sticky session）
+pub const SESSION_PLACEHOLDER: &str = "{rand}";
+/// 连续失败多少次后认为上游不可用（避免目标站点偶发失败误判代理）
+const UPSTREAM_FAILURE_THRESHOLD: u32 = 3;
+/// 会话 ID 长度
+const SESSION_ID_LEN: usize = 8;
+
+/// 上游健康状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpstreamHealth {
+    pub host: String,
+    pub port: u16,
+    #[serde(rename = "type")]
+    pub proxy_type: String,
+    /// 是否为当前使用的上游
+    pub active: bool,
+    pub healthy: bool,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+}
+
+/// 上游可用性变化（用于回写代理状态）
+#[derive(Debug, Clone)]
+pub struct UpstreamStatusChange {
+    pub profile_id: String,
+    pub host: String,
+    pub port: u16,
+    pub healthy: bool,
+    pub error: Option<String>,
+}
+
+/// 单个上游的运行状态
+struct UpstreamState {
+    hop: ProxyHop,
+    consecutive_failures: AtomicU32,
+    healthy: AtomicBool,
+    last_error: std::sync::Mutex<Option<String>>,
+    traffic: Arc<UpstreamTraffic>,
+}
+
+/// 经由某个上游、尚未取走的流量
+#[derive(Default)]
+struct UpstreamTraffic {
+    sent: AtomicU64,
+    received: AtomicU64,
+}
+
+/// 桥接的上游集合：主代理 + 有序备用代理，共用前置跳板
+///
+/// 新连接经当前上游建立；当前上游连续 UPSTREAM_FAILURE_THRESHOLD 次代理级失败后按顺序切换到下一个，
+/// 成功后后续连接沿用该上游。目标不可达（代理已响应拒绝）不触发切换。
+struct UpstreamPool {
+    profile_id: String,
+    chain: Vec<ProxyHop>,
+    upstreams: Vec<UpstreamState>,
+    active: AtomicUsize,
+    /// 当前会话 ID（替换用户名 / 密码中的 {rand}）
+    session: std::sync::Mutex<String>,
+    /// 尚未取走的可用性变化
+    changes: std::sync::Mutex<Vec<UpstreamStatusChange>>,
+}
+
+impl UpstreamPool {
+    fn new(profile_id: &str, config: &ProxyBridgeConfig) -> Self {
+        let primary = ProxyHop {
+            host: config.upstream_host.clone(),
+            port: config.upstream_port,
+            proxy_type: config.upstream_type.clone(),
+            username: config.username.clone(),
+            password: config.password.clone(),
+        };
+        let upstreams = std::iter::once(primary)
+            .chain(config.fallbacks.iter().cloned())
+            .map(|hop| UpstreamState {
+                hop,
+                consecutive_failures: AtomicU32::new(0),
+                healthy: AtomicBool::new(true),
+                last_error: std::sync::Mutex::new(None),
+                traffic: Arc::default(),
+            })
+            .collect();
+        
+        Self {
+            profile_id: profile_id.to_string(),
+            chain: config.chain.clone(),
+            upstreams,
+            active: AtomicUsize::new(0),
+            session: std::sync::Mutex::new(new_session_id()),
+            changes: std::sync::Mutex::new(Vec::new()),
+        }
+    }
+    
+    /// 是否配置了会话占位符
+    fn has_session_template(&self) -> bool {
+        self.upstreams.iter().any(|u| {
+            [&u.hop.username, &u.hop.password]
+                .iter()
+                .any(|v| v.as_deref().is_some_and(|v| v.contains(SESSION_PLACEHOLDER)))
+        })
+    }
+    
+    /// 更换会话 ID，之后的新连接使用新会话
+    fn rotate_session(&self) -> String {
+        let session = new_session_id();
+        *self.session.lock().unwrap() = session.clone();
+        session
+    }
+    
+    /// 替换会话占位符后的上游
+    fn resolved_upstream(&self, index: usize) -> ProxyHop {
+        let session = self.session.lock().unwrap().clone();
+        let mut hop = self.upstreams[index].hop.clone();
+        for value in [&mut hop.username, &mut hop.password].into_iter().flatten() {
+            *value = value.replace(SESSION_PLACEHOLDER, &session);
+        }
+        hop
+    }
+    
+    /// 当前使用的上游（已替换会话占位符）及其流量计数
+    fn current(&self) -> (ProxyHop, Arc<UpstreamTraffic>) {
+        let index = self.active.load(Ordering::Relaxed);
+        (self.resolved_upstream(index), Arc::clone(&self.upstreams[index].traffic))
+    }
+    
+    /// 取出各上游新增的流量：有流量的上游与当前上游各一条
+    fn take_traffic(&self) -> Vec<(ProxyHop, u64, u64)> {
+        let active = self.active.load(Ordering::Relaxed);
+        self.upstreams
+            .iter()
+            .enumerate()
+            .filter_map(|(i, u)| {
+                let sent = u.traffic.sent.swap(0, Ordering::Relaxed);
+                let received = u.traffic.received.swap(0, Ordering::Relaxed);
+                (i == active || sent > 0 || received > 0).then(|| (u.hop.clone(), sent, received))
+            })
+            .collect()
+    }
+    
+    /// 经由上游连接目标，失败时依次尝试其余上游（之后该连接的流量计入实际使用的上游）
+    async fn connect(
+        &self,
+        target_host: &str,
+        target_port: u16,
+        counters: &ConnectionCounters,
+    ) -> Result<UpstreamStream, String> {
+        self.connect_with(counters, |hops| async move {
+            connect_chain(&hops, target_host, target_port, &mut Vec::new()).await
+        }).await
+    }
+    
+    /// 为普通 HTTP 请求建立上游连接：出口为 HTTP / HTTPS 代理时直接连到代理（请求以 absolute-form 转发），
+    /// 出口为 SOCKS5 时建立到目标的隧道
+    async fn connect_http(
+        &self,
+        target_host: &str,
+        target_port: u16,
+        counters: &ConnectionCounters,
+    ) -> Result<HttpUpstream, String> {
+        self.connect_with(counters, |mut hops| async move {
+            let exit = hops.pop().ok_or_else(|| ConnectError::Proxy("代理链为空".to_string()))?;
+            if matches!(exit.proxy_type.to_lowercase().as_str(), "http" | "https") {
+                let stream = connect_proxy(&hops, &exit).await?;
+                return Ok(HttpUpstream {
+                    tunnel: None,
+                    proxy_auth: proxy_authorization(exit.username.as_deref(), exit.password.as_deref()),
+                    stream: BufReader::new(stream),
+                });
+            }
+            hops.push(exit);
+            let stream = connect_chain(&hops, target_host, target_port, &mut Vec::new()).await?;
+            Ok(HttpUpstream {
+                tunnel: Some((target_host.to_string(), target_port)),
+                proxy_auth: None,
+                stream: BufReader::new(stream),
+            })
+        }).await
+    }
+    
+    /// 经当前上游连接（前置跳板 + 出口）；当前上游连续代理级失败达到阈值后，
+    /// 依次尝试其余上游，成功后后续连接沿用该上游。目标不可达不计入失败、不切换上游
+    async fn connect_with<T, F, Fut>(&self, counters: &ConnectionCounters, mut attempt: F) -> Result<T, String>
+    where
+        F: FnMut(Vec<ProxyHop>) -> Fut,
+        Fut: Future<Output = Result<T, ConnectError>>,
+    {
+        let start = self.active.load(Ordering::Relaxed);
+        let mut errors = Vec::new();
+        
+        for offset in 0..self.upstreams.len() {
+            let index = (start + offset) % self.upstreams.len();
+            let mut hops = self.chain.clone();
+            hops.push(self.resolved_upstream(index));
+            
+            match attempt(hops).await {
+                Ok(stream) => {
+                    self.record_success(index);
+                    counters.bind_upstream(Arc::clone(&self.upstreams[index].traffic));
+                    if index != start {
+                        self.active.store(index, Ordering::Relaxed);
+                        let upstream = &self.upstreams[index].hop;
+                        warn!(
+                            profile_id = %self.profile_id,
+                            upstream = %format!("{}:{}", upstream.host, upstream.port),
+                            "上游代理已切换"
+                        );
+                    }
+                    return Ok(stream);
+                }
+                Err(ConnectError::Target(e)) => {
+                    // 代理本身可用
+                    self.record_success(index);
+                    errors.push(e);
+                    return Err(errors.join("；"));
+                }
+                Err(ConnectError::Proxy(e)) => {
+                    let healthy = self.record_failure(index, &e);
+                    errors.push(e);
+                    if offset == 0 && healthy {
+                        // 未达到连续失败阈值，暂不切换
+                        return Err(errors.join("；"));
+                    }
+                }
+            }
+        }
+        
+        Err(errors.join("；"))
+    }
+    
+    fn record_success(&self, index: usize) {
+        let upstream = &self.upstreams[index];
+        upstream.consecutive_failures.store(0, Ordering::Relaxed);
+        if !upstream.healthy.swap(true, Ordering::Relaxed) {
+            self.push_change(upstream, true, None);
+        }
+    }
+    
+    /// 记录代理级失败，返回该上游是否仍视为可用（未达到连续失败阈值）
+    fn record_failure(&self, index: usize, error: &str) -> bool {
+        let upstream = &self.upstreams[index];
+        *upstream.last_error.lock().unwrap() = Some(error.to_string());
+        let failures = upstream.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if failures >= UPSTREAM_FAILURE_THRESHOLD && upstream.healthy.swap(false, Ordering::Relaxed) {
+            self.push_change(upstream, false, Some(error.to_string()));
+        }
+        failures < UPSTREAM_FAILURE_THRESHOLD
+    }
+    
+    fn push_change(&self, upstream: &UpstreamState, healthy: bool, error: Option<String>) {
+        self.changes.lock().unwrap().push(UpstreamStatusChange {
+            profile_id: self.profile_id.clone(),
+            host: upstream.hop.host.clone(),
+            port: upstream.hop.port,
+            healthy,
+            error,
+        });
+    }
+    
+    fn take_changes(&self) -> Vec<UpstreamStatusChange> {
+        std::mem::take(&mut *self.changes.lock().unwrap())
+    }
+    
+    fn health(&self) -> Vec<UpstreamHealth> {
+        let active = self.active.load(Ordering::Relaxed);
+        self.upstreams
+            .iter()
+            .enumerate()
+            .map(|(i, u)| UpstreamHealth {
+                host: u.hop.host.clone(),
+                port: u.hop.port,
+                proxy_type: u.hop.proxy_type.clone(),
+                active: i == active,
+                healthy: u.healthy.load(Ordering::Relaxed),
+                consecutive_failures: u.consecutive_failures.load(Ordering::Relaxed),
+                last_error: u.last_error.lock().unwrap().clone(),
+            })
+            .collect()
+    }
+}
+
+/// 生成会话 ID（小写字母与数字）
+fn new_session_id() -> String {
+    use rand::Rng;
+    const CHARSET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+    let mut rng = rand::thread_rng();
+    (0..SESSION_ID_LEN)
+        .map(|_| CHARSET[rng.gen_range(0..CHARSET.len())] as char)
+        .collect()
+}
+
+// ==================== 限速与计量 ====================
+
+/// 浏览器侧连接（经计量与限速）
+type ClientStream = MeteredStream<TcpStream>;
+
+/// 令牌桶（字节/秒，允许 1 秒突发，可透支，透支后等待令牌补足）
+struct TokenBucket {
+    /// (速率（0 表示不限速）, 当前令牌数, 上次补充时间)
+    state: std::sync::Mutex<(u64, f64, tokio::time::Instant)>,
+}
+
+impl TokenBucket {
+    fn new(rate: Option<u64>) -> Self {
+        let rate = rate.unwrap_or(0);
+        Self {
+            state: std::sync::Mutex::new((rate, rate as f64, tokio::time::Instant::now())),
+        }
+    }
+    
+    fn set_rate(&self, rate: Option<u64>) {
+        let mut state = self.state.lock().unwrap();
+        let rate = rate.unwrap_or(0);
+        *state = (rate, state.1.min(rate as f64), tokio::time::Instant::now());
+    }
+    
+    /// 令牌透支时返回需要等待的时间
+    fn wait_time(&self) -> Option<Duration> {
+        let mut state = self.state.lock().unwrap();
+        let (rate, tokens, last) = *state;
+        if rate == 0 {
+            return None;
+        }
+        let now = tokio::time::Instant::now();
+        let tokens = (tokens + now.duration_since(last).as_secs_f64() * rate as f64).min(rate as f64);
+        *state = (rate, tokens, now);
+        (tokens < 0.0).then(|| Duration::from_secs_f64(-tokens / rate as f64))
+    }
+    
+    fn consume(&self, n: usize) {
+        let mut state = self.state.lock().unwrap();
+        if state.0 > 0 {
+            state.1 -= n as f64;
+        }
+    }
+}
+
+/// 桥接限速器（上行、下行各一个令牌桶，所有连接共享）
+struct BridgeLimiter {
+    upload: TokenBucket,
+    download: TokenBucket,
+}
+
+impl BridgeLimiter {
+    fn new(upload: Option<u64>, download: Option<u64>) -> Self {
+        Self {
+            upload: TokenBucket::new(upload),
+            download: TokenBucket::new(download),
+        }
+    }
+}
+
+/// 计量并限速的流：读取计为上行（bytes_sent），写入计为下行（bytes_received）
+struct MeteredStream<S> {
+    inner: S,
+    stats: Arc<BridgeStatsInner>,
+    limiter: Arc<BridgeLimiter>,
+    counters: Arc<ConnectionCounters>,
+    read_delay: Option<Pin<Box<tokio::time::Sleep>>>,
+    write_delay: Option<Pin<Box<tokio::time::Sleep>>>,
+}
+
+impl<S> MeteredStream<S> {
+    fn new(inner: S, stats: Arc<BridgeStatsInner>, limiter: Arc<BridgeLimiter>) -> Self {
+        Self { inner, stats, limiter, counters: Arc::default(), read_delay: None, write_delay: None }
+    }
+    
+    /// 本连接的字节计数
+    fn counters(&self) -> Arc<ConnectionCounters> {
+        Arc::clone(&self.counters)
+    }
+}
+
+/// 等待令牌桶补足（未透支时立即返回）
+fn poll_throttle(
+    delay: &mut Option<Pin<Box<tokio::time::Sleep>>>,
+    bucket: &TokenBucket,
+    cx: &mut Context<'_>,
+) -> Poll<()> {
+    loop {
+        if let Some(sleep) = delay.as_mut() {
+            ready!(sleep.as_mut().poll(cx));
+            *delay = None;
+        }
+        match bucket.wait_time() {
+            Some(wait) => *delay = Some(Box::pin(tokio::time::sleep(wait))),
+            None => return Poll::Ready(()),
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for MeteredStream<S> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        ready!(poll_throttle(&mut this.read_delay, &this.limiter.upload, cx));
+        
+        let before = buf.filled().len();
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        let n = buf.filled().len() - before;
+        this.limiter.upload.consume(n);
+        this.stats.bytes_sent.fetch_add(n as u64, Ordering::Relaxed);
+        this.counters.add(n as u64, 0);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for MeteredStream<S> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(poll_throttle(&mut this.write_delay, &this.limiter.download, cx));
+        
+        let n = ready!(Pin::new(&mut this.inner).poll_write(cx, buf))?;
+        this.limiter.download.consume(n);
+        this.stats.bytes_received.fetch_add(n as u64, Ordering::Relaxed);
+        this.counters.add(0, n as u64);
+        Poll::Ready(Ok(n))
+    }
+    
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+    
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+// ==================== 连接日志 ====================
+
+/// 连接日志容量设置项（0 或未设置表示不记录）
+pub const CONNECTION_LOG_CAPACITY_SETTING_KEY: &str = "proxy_bridge_connection_log_capacity";
+/// 连接日志容量上限
+pub const MAX_CONNECTION_LOG_CAPACITY: usize = 100_000;
+/// 域名统计最多保留的域名数（超出后不再统计新域名）
+const MAX_DOMAIN_STATS: usize = 10_000;
+
+/// 单条连接记录（HTTP keep-alive 连接切换目标时分段记录）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionRecord {
+    /// 开始时间（毫秒时间戳）
+    pub timestamp: i64,
+    /// 目标主机（域名或 IP，域名为浏览器请求的原始域名）
+    pub host: String,
+    pub port: u16,
+    /// 上行字节数
+    pub bytes_sent: u64,
+    /// 下行字节数
+    pub bytes_received: u64,
+    pub duration_ms: u64,
+    /// 失败原因（成功时为空）
+    pub error: Option<String>,
+}
+
+/// 按目标域名汇总的统计
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DomainStats {
+    pub domain: String,
+    pub connections: u64,
+    pub failed_connections: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// 首次 / 最近访问时间（毫秒时间戳）
+    pub first_seen: i64,
+    pub last_seen: i64,
+}
+
+/// Profile 的连接日志（有界环形缓冲）与域名统计，桥接停止后仍保留
+pub struct ConnectionJournal {
+    capacity: usize,
+    inner: std::sync::Mutex<JournalInner>,
+}
+
+struct JournalInner {
+    records: VecDeque<ConnectionRecord>,
+    domains: HashMap<String, DomainStats>,
+}
+
+impl ConnectionJournal {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: std::sync::Mutex::new(JournalInner {
+                records: VecDeque::with_capacity(capacity.min(1024)),
+                domains: HashMap::new(),
+            }),
+        }
+    }
+    
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    
+    fn record(&self, record: ConnectionRecord) {
+        let mut inner = self.inner.lock().unwrap();
+        
+        let domain = record.host.to_ascii_lowercase();
+        let tracked = inner.domains.len() < MAX_DOMAIN_STATS || inner.domains.contains_key(&domain);
+        if tracked {
+            let stats = inner.domains.entry(domain.clone()).or_insert_with(|| DomainStats {
+                domain,
+                connections: 0,
+                failed_connections: 0,
+                bytes_sent: 0,
+                bytes_received: 0,
+                first_seen: record.timestamp,
+                last_seen: record.timestamp,
+            });
+            stats.connections += 1;
+            if record.error.is_some() {
+                stats.failed_connections += 1;
+            }
+            stats.bytes_sent += record.bytes_sent;
+            stats.bytes_received += record.bytes_received;
+            stats.last_seen = stats.last_seen.max(record.timestamp);
+        }
+        
+        if inner.records.len() >= self.capacity {
+            inner.records.pop_front();
+        }
+        inner.records.push_back(record);
+    }
+    
+    /// 最近的连接记录（新的在前）
+    pub fn recent(&self, limit: usize) -> Vec<ConnectionRecord> {
+        let inner = self.inner.lock().unwrap();
+        inner.records.iter().rev().take(limit).cloned().collect()
+    }
+    
+    /// 连接数最多的域名（连接数相同时按流量排序）
+    pub fn top_domains(&self, limit: usize) -> Vec<DomainStats> {
+        let inner = self.inner.lock().unwrap();
+        let mut domains: Vec<DomainStats> = inner.domains.values().cloned().collect();
+        domains.sort_by(|a, b| {
+            b.connections
+                .cmp(&a.connections)
+                .then((b.bytes_sent + b.bytes_received).cmp(&(a.bytes_sent + a.bytes_received)))
+                .then(a.domain.cmp(&b.domain))
+        });
+        domains.truncate(limit);
+        domains
+    }
+    
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.records.clear();
+        inner.domains.clear();
+    }
+}
+
+/// 单个连接的字节计数（由 MeteredStream 更新）
+#[derive(Default)]
+struct ConnectionCounters {
+    sent: AtomicU64,
+    received: AtomicU64,
+    /// 连接当前经过的上游（建立上游连接后设置）
+    upstream: std::sync::Mutex<Option<Arc<UpstreamTraffic>>>,
+}
+
+impl ConnectionCounters {
+    fn add(&self, sent: u64, received: u64) {
+        self.sent.fetch_add(sent, Ordering::Relaxed);
+        self.received.fetch_add(received, Ordering::Relaxed);
+        if let Some(traffic) = self.upstream.lock().unwrap().as_ref() {
+            traffic.sent.fetch_add(sent, Ordering::Relaxed);
+            traffic.received.fetch_add(received, Ordering::Relaxed);
+        }
+    }
+    
+    /// 之后的流量计入该上游；首次设置时连同此前（握手阶段）的字节一并计入
+    fn bind_upstream(&self, traffic: Arc<UpstreamTraffic>) {
+        let mut upstream = self.upstream.lock().unwrap();
+        if upstream.is_none() {
+            traffic.sent.fetch_add(self.sent.load(Ordering::Relaxed), Ordering::Relaxed);
+            traffic.received.fetch_add(self.received.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+        *upstream = Some(traffic);
+    }
+}
+
+/// 正在记录的目标
+struct OpenConnection {
+    host: String,
+    port: u16,
+    timestamp: i64,
+    started: std::time::Instant,
+    sent_base: u64,
+    received_base: u64,
+}
+
+/// 单个入站连接的日志记录器（未启用连接日志时不记录）
+struct ConnectionTracker {
+    journal: Option<Arc<ConnectionJournal>>,
+    counters: Arc<ConnectionCounters>,
+    current: Option<OpenConnection>,
+}
+
+impl ConnectionTracker {
+    fn new(journal: Option<Arc<ConnectionJournal>>, counters: Arc<ConnectionCounters>) -> Self {
+        Self { journal, counters, current: None }
+    }
+    
+    /// 开始记录新目标（结束上一个目标的记录）
+    fn begin(&mut self, host: &str, port: u16) {
+        self.finish(None);
+        if self.journal.is_none() {
+            return;
+        }
+        self.current = Some(OpenConnection {
+            host: host.to_string(),
+            port,
+            timestamp: chrono::Utc::now().timestamp_millis(),
+            started: std::time::Instant::now(),
+            sent_base: self.counters.sent.load(Ordering::Relaxed),
+            received_base: self.counters.received.load(Ordering::Relaxed),
+        });
+    }
+    
+    /// 结束当前目标的记录
+    fn finish(&mut self, error: Option<String>) {
+        let (Some(journal), Some(open)) = (self.journal.as_ref(), self.current.take()) else {
+            return;
+        };
+        journal.record(ConnectionRecord {
+            timestamp: open.timestamp,
+            host: open.host,
+            port: open.port,
+            bytes_sent: self.counters.sent.load(Ordering::Relaxed).saturating_sub(open.sent_base),
+            bytes_received: self.counters.received.load(Ordering::Relaxed).saturating_sub(open.received_base),
+            duration_ms: open.started.elapsed().as_millis() as u64,
+            error,
+        });
+    }
+}
+
+// ==================== SOCKS5 入站 ====================
+
+/// SOCKS5 入站本地认证凭据（用户名和密码都非空时启用，HTTP 入站始终无需认证）
+fn local_credentials(config: &ProxyBridgeConfig) -> Option<(&str, &str)> {
+    match (config.local_username.as_deref(), config.local_password.as_deref()) {
+        (Some(user), Some(pass)) if !user.is_empty() && !pass.is_empty() => Some((user, pass)),
+        _ => None,
+    }
+}
+
+/// 已建立的 UDP ASSOCIATE 客户端地址（端口为 0 表示接受该 IP 的任意端口）
+#[derive(Default)]
+struct UdpAssociations {
+    next_id: AtomicU64,
+    clients: std::sync::Mutex<HashMap<u64, SocketAddr>>,
+}
+
+impl UdpAssociations {
+    /// 登记关联，返回的守卫释放（控制连接断开）时撤销
+    fn register(self: &Arc<Self>, client: SocketAddr) -> UdpAssociationGuard {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.clients.lock().unwrap().insert(id, client);
+        UdpAssociationGuard { associations: Arc::clone(self), id }
+    }
+    
+    /// 数据报来源是否属于某个已建立的关联
+    fn permits(&self, source: SocketAddr) -> bool {
+        self.clients.lock().unwrap().values().any(|client| {
+            client.ip() == source.ip() && (client.port() == 0 || client.port() == source.port())
+        })
+    }
+}
+
+struct UdpAssociationGuard {
+    associations: Arc<UdpAssociations>,
+    id: u64,
+}
+
+impl Drop for UdpAssociationGuard {
+    fn drop(&mut self) {
+        self.associations.clients.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// 处理 SOCKS5 入站连接（CONNECT / UDP ASSOCIATE）
+///
+/// udp 为 UDP 中继端口与关联表（未启用 UDP 时为 None）
+async fn handle_socks5_inbound(
+    mut client: BufReader<ClientStream>,
+    client_addr: SocketAddr,
+    config: &ProxyBridgeConfig,
+    upstreams: &UpstreamPool,
+    udp: Option<(u16, &Arc<UdpAssociations>)>,
+    tracker: &mut ConnectionTracker,
+) -> Result<(), String> {
+    // 1. 方法协商
+    let mut header = [0u8; 2];
+    client.read_exact(&mut header).await
+        .map_err(|e| format!("读取 SOCKS5 握手失败: {}", e))?;
+    let mut methods = vec![0u8; header[1] as usize];
+    client.read_exact(&mut methods).await
+        .map_err(|e| format!("读取 SOCKS5 认证方法失败: {}", e))?;
+    
+    let credentials = local_credentials(config);
+    let method = if credentials.is_some() { 0x02 } else { 0x00 };
+    if !methods.contains(&method) {
+        let _ = client.get_mut().write_all(&[0x05, 0xFF]).await;
+        return Err("SOCKS5 客户端未提供可接受的认证方法".to_string());
+    }
+    client.get_mut().write_all(&[0x05, method]).await
+        .map_err(|e| format!("发送认证方法失败: {}", e))?;
+    
+    // 2. 用户名/密码认证
+    if let Some((user, pass)) = credentials {
+        let mut version = [0u8; 2];
+        client.read_exact(&mut version).await
+            .map_err(|e| format!("读取认证请求失败: {}", e))?;
+        let mut username = vec![0u8; version[1] as usize];
+        client.read_exact(&mut username).await
+            .map_err(|e| format!("读取用户名失败: {}", e))?;
+        let mut len = [0u8; 1];
+        client.read_exact(&mut len).await
+            .map_err(|e| format!("读取密码长度失败: {}", e))?;
+        let mut password = vec![0u8; len[0] as usize];
+        client.read_exact(&mut password).await
+            .map_err(|e| format!("读取密码失败: {}", e))?;
+        
+        if username != user.as_bytes() || password != pass.as_bytes() {
+            let _ = client.get_mut().write_all(&[0x01, 0x01]).await;
+            return Err("SOCKS5 本地认证失败".to_string());
+        }
+        client.get_mut().write_all(&[0x01, 0x00]).await
+            .map_err(|e| format!("发送认证结果失败: {}", e))?;
+    }
+    
+    // 3. 读取请求 [VER | CMD | RSV | ATYP | DST.ADDR | DST.PORT]
+    let mut request = [0u8; 3];
+    client.read_exact(&mut request).await
+        .map_err(|e| format!("读取 SOCKS5 请求失败: {}", e))?;
+    let target = read_target_addr(&mut client).await?;
+    
+    match (request[1], udp) {
+        // CONNECT：域名原样交给上游解析（远程 DNS）
+        (0x01, _) => {
+            let (host, port) = match &target {
+                TargetAddr::Ip(addr) => (addr.ip().to_string(), addr.port()),
+                TargetAddr::Domain(domain, port) => (domain.clone(), *port),
+            };
+            debug!(target = %format!("{}:{}", host, port), "收到 SOCKS5 CONNECT 请求");
+            tracker.begin(&host, port);
+            
+            let mut upstream = match upstreams.connect(&host, port, &tracker.counters).await {
+                Ok(upstream) => upstream,
+                Err(e) => {
+                    let _ = client.get_mut().write_all(&socks5_reply(0x01, None)).await;
+                    return Err(e);
+                }
+            };
+            client.get_mut().write_all(&socks5_reply(0x00, None)).await
+                .map_err(|e| format!("发送 SOCKS5 响应失败: {}", e))?;
+            
+            let buffered = client.buffer().to_vec();
+            if !buffered.is_empty() {
+                upstream.write_all(&buffered).await
+                    .map_err(|e| format!("转发缓冲数据失败: {}", e))?;
+            }
+            let mut client = client.into_inner();
+            
+            copy_bidirectional(&mut client, &mut upstream).await?;
+            Ok(())
+        }
+        // UDP ASSOCIATE：返回本地 UDP 中继地址，控制连接断开前保持关联。
+        // 关联绑定到本连接的客户端 IP 与请求中声明的源端口（0 表示尚未确定）
+        (0x03, Some((udp_port, associations))) => {
+            let source_port = match &target {
+                TargetAddr::Ip(addr) => addr.port(),
+                TargetAddr::Domain(_, port) => *port,
+            };
+            let _association = associations.register(SocketAddr::new(client_addr.ip(), source_port));
+            
+            let relay = SocketAddr::from(([127, 0, 0, 1], udp_port));
+            client.get_mut().write_all(&socks5_reply(0x00, Some(relay))).await
+                .map_err(|e| format!("发送 SOCKS5 响应失败: {}", e))?;
+            
+            let mut buf = [0u8; 64];
+            while client.read(&mut buf).await.map_err(|e| e.to_string())? > 0 {}
+            Ok(())
+        }
+        (cmd, _) => {
+            let _ = client.get_mut().write_all(&socks5_reply(0x07, None)).await;
+            Err(format!("不支持的 SOCKS5 命令: {}", cmd))
+        }
+    }
+}
+
+/// 读取 SOCKS5 请求中的 ATYP | DST.ADDR | DST.PORT
+async fn read_target_addr<R: AsyncRead + Unpin>(reader: &mut R) -> Result<TargetAddr, String> {
+    let mut buf = vec![0u8; 2];
+    reader.read_exact(&mut buf).await
+        .map_err(|e| format!("读取目标地址失败: {}", e))?;
+    // 已读取 ATYP 与地址首字节（域名时为长度字节）
+    let total = match buf[0] {
+        0x01 => 1 + 4 + 2,
+        0x04 => 1 + 16 + 2,
+        0x03 => 2 + buf[1] as usize + 2,
+        atyp => return Err(format!("不支持的地址类型: {}", atyp)),
+    };
+    buf.resize(total, 0);
+    reader.read_exact(&mut buf[2..]).await
+        .map_err(|e| format!("读取目标地址失败: {}", e))?;
+    
+    TargetAddr::decode(&buf).map(|(target, _)| target)
+}
+
+/// 构建 SOCKS5 响应 [VER | REP | RSV | ATYP | BND.ADDR | BND.PORT]
+fn socks5_reply(rep: u8, bound: Option<SocketAddr>) -> Vec<u8> {
+    let bound = bound.unwrap_or_else(|| SocketAddr::from(([0, 0, 0, 0], 0)));
+    let mut reply = vec![0x05, rep, 0x00];
+    TargetAddr::Ip(bound).encode(&mut reply);
+    reply
+}
+
+// ==================== HTTP 转发（absolute-form） ====================
+
+/// 请求/响应头最大长度
+const MAX_HTTP_HEAD_SIZE: usize = 64 * 1024;
+
+/// 逐跳（hop-by-hop）头，转发时移除
+const HOP_BY_HOP_HEADERS: &[&str] = &[
+    "connection",
+    "proxy-connection",
+    "keep-alive",
+    "proxy-authorization",
+    "proxy-authenticate",
+    "te",
+    "trailer",
+    "upgrade",
+];
+
+/// HTTP 请求/响应头
+#[derive(Debug, Clone)]
+struct HttpHead {
+    /// 起始行（请求行或状态行）
+    start_line: String,
+    headers: Vec<(String, String)>,
+}
+
+impl HttpHead {
+    /// 解析原始头部文本（含结尾空行）
+    fn parse(raw: &str) -> Result<Self, String> {
+        let mut lines = raw.split("\r\n");
+        let start_line = lines.next().unwrap_or("").trim().to_string();
+        if start_line.is_empty() {
+            return Err("空请求".to_string());
+        }
+
+        let mut headers = Vec::new();
+        for line in lines.filter(|l| !l.is_empty()) {
+            let (name, value) = line.split_once(':')
+                .ok_or_else(|| format!("无效的头部: {}", line))?;
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+
+        Ok(Self { start_line, headers })
+    }
+
+    fn method(&self) -> &str {
+        self.start_line.split_whitespace().next().unwrap_or("")
+    }
+
+    fn target(&self) -> &str {
+        self.start_line.split_whitespace().nth(1).unwrap_or("")
+    }
+
+    /// 请求行或状态行中的 HTTP 版本
+    fn version(&self) -> &str {
+        let mut parts = self.start_line.split_whitespace();
+        let first = parts.next().unwrap_or("");
+        if first.starts_with("HTTP/") {
+            first
+        } else {
+            parts.nth(1).unwrap_or("HTTP/1.1")
+        }
+    }
+
+    fn status_code(&self) -> Option<u16> {
+        parse_status_code(&self.start_line)
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn has_token(&self, name: &str, token: &str) -> bool {
+        self.headers.iter()
+            .filter(|(n, _)| n.eq_ignore_ascii_case(name))
+            .flat_map(|(_, v)| v.split(','))
+            .any(|t| t.trim().eq_ignore_ascii_case(token))
+    }
+
+    fn set_header(&mut self, name: &str, value: &str) {
+        self.headers.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+        self.headers.push((name.to_string(), value.to_string()));
+    }
+
+    /// 是否保持连接（HTTP/1.1 默认保持，HTTP/1.0 需显式 keep-alive）
+    fn keep_alive(&self) -> bool {
+        let close = self.has_token("Connection", "close") || self.has_token("Proxy-Connection", "close");
+        if self.version().eq_ignore_ascii_case("HTTP/1.0") {
+            !close && (self.has_token("Connection", "keep-alive") || self.has_token("Proxy-Connection", "keep-alive"))
+        } else {
+            !close
+        }
+    }
+
+    /// 移除逐跳头（包括 Connection 中列出的头）
+    fn strip_hop_by_hop(&mut self) {
+        let listed: Vec<String> = self.headers.iter()
+            .filter(|(n, _)| n.eq_ignore_ascii_case("Connection") || n.eq_ignore_ascii_case("Proxy-Connection"))
+            .flat_map(|(_, v)| v.split(',').map(|t| t.trim().to_ascii_lowercase()))
+            .collect();
+        self.headers.retain(|(n, _)| {
+            let name = n.to_ascii_lowercase();
+            !HOP_BY_HOP_HEADERS.contains(&name.as_str()) && !listed.contains(&name)
+        });
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut out = String::with_capacity(256);
+        out.push_str(&self.start_line);
+        out.push_str("\r\n");
+        for (name, value) in &self.headers {
+            out.push_str(name);
+            out.push_str(": ");
+            out.push_str(value);
+            out.push_str("\r\n");
+        }
+        out.push_str("\r\n");
+        out.into_bytes()
+    }
+}
+
+/// 消息体的长度界定方式
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BodyKind {
+    None,
+    Length(u64),
+    Chunked,
+    /// 读到连接关闭为止（仅响应）
+    UntilClose,
+}
+
+impl BodyKind {
+    fn of_request(head: &HttpHead) -> Result<Self, String> {
+        if head.has_token("Transfer-Encoding", "chunked") {
+            return Ok(BodyKind::Chunked);
+        }
+        match head.header("Content-Length") {
+            Some(len) => len.parse::<u64>()
+                .map(BodyKind::Length)
+                .map_err(|_| format!("无效的 Content-Length: {}", len)),
+            None => Ok(BodyKind::None),
+        }
+    }
+
+    fn of_response(head: &HttpHead, request_method: &str) -> Result<Self, String> {
+        let status = head.status_code().unwrap_or(200);
+        if request_method.eq_ignore_ascii_case("HEAD") || (100..200).contains(&status) || status == 204 || status == 304 {
+            return Ok(BodyKind::None);
+        }
+        if head.has_token("Transfer-Encoding", "chunked") {
+            return Ok(BodyKind::Chunked);
+        }
+        match head.header("Content-Length") {
+            Some(len) => len.parse::<u64>()
+                .map(BodyKind::Length)
+                .map_err(|_| format!("无效的 Content-Length: {}", len)),
+            None => Ok(BodyKind::UntilClose),
+        }
+    }
+}
+
+/// 从缓冲读取器读取 HTTP 头（读到空行），连接在读到任何数据前关闭时返回 None
+async fn read_http_head<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<Option<String>, String> {
+    let mut head = Vec::with_capacity(512);
+
+    loop {
+        let n = reader.read_until(b'\n', &mut head).await
+            .map_err(|e| format!("读取请求失败: {}", e))?;
+        if n == 0 {
+            if head.is_empty() {
+                return Ok(None);
+            }
+            return Err("连接在头部读取完成前关闭".to_string());
+        }
+        if head.len() > MAX_HTTP_HEAD_SIZE {
+            return Err("HTTP 头部过长".to_string());
+        }
+        // 跳过请求之间多余的空行
+        if head == b"\r\n" || head == b"\n" {
+            head.clear();
+            continue;
+        }
+        if head.ends_with(b"\r\n\r\n") || head.ends_with(b"\n\n") {
+            return Ok(Some(String::from_utf8_lossy(&head).to_string()));
+        }
+    }
+}
+
+/// 解析 absolute-form 请求目标（http://host[:port]/path），返回 (host, port, origin-form 路径)
+fn parse_absolute_uri(target: &str) -> Result<(String, u16, String), String> {
+    let rest = match target.split_once("://") {
+        Some((scheme, rest)) if scheme.eq_ignore_ascii_case("http") => rest,
+        Some((scheme, _)) => return Err(format!("不支持的请求协议: {}", scheme)),
+        None => return Err(format!("代理请求必须使用绝对地址: {}", target)),
+    };
+
+    let (authority, path) = match rest.find(|c| c == '/' || c == '?') {
+        Some(pos) => (&rest[..pos], &rest[pos..]),
+        None => (rest, "/"),
+    };
+    let path = if path.starts_with('?') { format!("/{}", path) } else { path.to_string() };
+    // 去掉 userinfo
+    let authority = authority.rsplit('@').next().unwrap_or(authority);
+    if authority.is_empty() {
+        return Err(format!("请求地址缺少主机: {}", target));
+    }
+
+    let has_port = if authority.starts_with('[') {
+        authority.contains("]:")
+    } else {
+        authority.contains(':')
+    };
+    let (host, port) = if has_port {
+        parse_host_port(authority)?
+    } else {
+        (authority.trim_start_matches('[').trim_end_matches(']').to_string(), 80)
+    };
+
+    Ok((host, port, path))
+}
+
+/// 改写转发给上游的请求头：origin-form 请求行、Host、移除逐跳头、保持上游连接
+fn rewrite_request_head(request: &HttpHead, host: &str, port: u16, path: &str) -> HttpHead {
+    let mut head = request.clone();
+    head.start_line = format!("{} {} {}", request.method(), path, request.version());
+    head.strip_hop_by_hop();
+
+    let host_value = match (host.contains(':'), port) {
+        (true, 80) => format!("[{}]", host),
+        (true, _) => format!("[{}]:{}", host, port),
+        (false, 80) => host.to_string(),
+        (false, _) => format!("{}:{}", host, port),
+    };
+    head.set_header("Host", &host_value);
+    head.set_header("Connection", "keep-alive");
+    head
+}
+
+/// 改写经 HTTP / HTTPS 出口代理转发的请求头：保留 absolute-form 请求行，附加出口代理认证
+fn rewrite_proxy_request_head(request: &HttpHead, host: &str, port: u16, proxy_auth: Option<&str>) -> HttpHead {
+    let mut head = rewrite_request_head(request, host, port, "/");
+    head.start_line = request.start_line.clone();
+    if let Some(auth) = proxy_auth {
+        head.set_header("Proxy-Authorization", auth);
+    }
+    head
+}
+
+/// 幂等且无请求体的请求可在新连接上安全重发
+fn is_retryable_request(method: &str, body: BodyKind) -> bool {
+    body == BodyKind::None
+        && ["GET", "HEAD", "OPTIONS", "TRACE", "DELETE"].iter().any(|m| method.eq_ignore_ascii_case(m))
+}
+
+/// 按长度界定方式复制消息体，返回复制的字节数
+async fn copy_body<R, W>(reader: &mut R, writer: &mut W, kind: BodyKind) -> Result<u64, String>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    match kind {
+        BodyKind::None => Ok(0),
+        BodyKind::Length(len) => {
+            copy_exact(reader, writer, len).await
+        }
+        BodyKind::Chunked => copy_chunked(reader, writer).await,
+        BodyKind::UntilClose => tokio::io::copy(reader, writer).await
+            .map_err(|e| format!("转发消息体失败: {}", e)),
+    }
+}
+
+/// 原样转发 chunked 编码的消息体（包括结尾的 trailer）
+async fn copy_chunked<R, W>(reader: &mut R, writer: &mut W) -> Result<u64, String>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    let mut total = 0u64;
+    let mut line = Vec::with_capacity(32);
+
+    loop {
+        line.clear();
+        let n = reader.read_until(b'\n', &mut line).await
+            .map_err(|e| format!("读取 chunk 失败: {}", e))?;
+        if n == 0 {
+            return Err("连接在 chunk 传输完成前关闭".to_string());
+        }
+        writer.write_all(&line).await.map_err(|e| format!("转发 chunk 失败: {}", e))?;
+        total += n as u64;
+
+        let size_str = String::from_utf8_lossy(&line);
+        let size_str = size_str.trim().split(';').next().unwrap_or("").trim();
+        let size = u64::from_str_radix(size_str, 16)
+            .map_err(|_| format!("无效的 chunk 大小: {}", size_str))?;
+
+        if size == 0 {
+            // trailer，直到空行
+            loop {
+                line.clear();
+                let n = reader.read_until(b'\n', &mut line).await
+                    .map_err(|e| format!("读取 chunk trailer 失败: {}", e))?;
+                if n == 0 {
+                    return Err("连接在 chunk 传输完成前关闭".to_string());
+                }
+                writer.write_all(&line).await.map_err(|e| format!("转发 chunk 失败: {}", e))?;
+                total += n as u64;
+                if line == b"\r\n" || line == b"\n" {
+                    return Ok(total);
+                }
+            }
+        }
+
+        // chunk 数据 + CRLF
+        total += copy_exact(reader, writer, size + 2).await?;
+    }
+}
+
+/// 精确复制 len 字节
+async fn copy_exact<R, W>(reader: &mut R, writer: &mut W, len: u64) -> Result<u64, String>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    let copied = tokio::io::copy(&mut (&mut *reader).take(len), writer).await
+        .map_err(|e| format!("转发消息体失败: {}", e))?;
+    if copied != len {
+        return Err("连接在消息体传输完成前关闭".to_string());
+    }
+    Ok(copied)
+}
+
+/// 经代理链发送一次 HTTP GET（用于代理链检测），返回状态码与响应体
+/// 使用 HTTP/1.0 请求，避免响应使用 chunked 编码
+pub(crate) async fn http_get_via_chain(
+    hops: &[ProxyHop],
+    url: &str,
+    hop_latencies: &mut Vec<u64>,
+) -> Result<(u16, Vec<u8>), String> {
+    let (host, port, path) = parse_absolute_uri(url)?;
+    let mut stream = BufReader::new(connect_chain(hops, &host, port, hop_latencies).await?);
+    
+    let request = format!(
+        "GET {} HTTP/1.0\r\nHost: {}\r\nAccept: */*\r\nConnection: close\r\n\r\n",
+        path, host
+    );
+    stream.get_mut().write_all(request.as_bytes()).await
+        .map_err(|e| format!("发送请求失败: {}", e))?;
+    
+    let head = read_http_head(&mut stream).await?
+        .ok_or_else(|| "目标在响应前关闭了连接".to_string())?;
+    let head = HttpHead::parse(&head)?;
+    let status = head.status_code().ok_or_else(|| format!("无效的响应: {}", head.start_line))?;
+    
+    let mut body = Vec::new();
+    copy_body(&mut stream, &mut body, BodyKind::of_response(&head, "GET")?).await?;
+    
+    Ok((status, body))
+}
+
+/// 上游 HTTP 连接：HTTP / HTTPS 出口上的连接可用于任意目标，SOCKS5 隧道按目标地址复用
+struct HttpUpstream {
+    /// 隧道目标（直连出口代理时为 None）
+    tunnel: Option<(String, u16)>,
+    /// 发给出口代理的 Proxy-Authorization
+    proxy_auth: Option<String>,
+    stream: BufReader<UpstreamStream>,
+}
+
+impl HttpUpstream {
+    fn serves(&self, host: &str, port: u16) -> bool {
+        self.tunnel.as_ref().is_none_or(|(h, p)| h == host && *p == port)
+    }
+}
+
+/// 发送请求并读取最终响应头（转发 1xx 临时响应）
+///
+/// 返回 Ok(None) 表示上游在返回任何响应之前关闭了连接
+async fn send_http_request(
+    client: &mut BufReader<ClientStream>,
+    up: &mut HttpUpstream,
+    request: &HttpHead,
+    host: &str,
+    port: u16,
+    path: &str,
+    request_body: BodyKind,
+) -> Result<Option<HttpHead>, String> {
+    let head = match up.tunnel {
+        Some(_) => rewrite_request_head(request, host, port, path),
+        None => rewrite_proxy_request_head(request, host, port, up.proxy_auth.as_deref()),
+    };
+    if let Err(e) = up.stream.get_mut().write_all(&head.to_bytes()).await {
+        return match request_body {
+            BodyKind::None => Ok(None),
+            _ => Err(format!("转发请求失败: {}", e)),
+        };
+    }
+    copy_body(client, up.stream.get_mut(), request_body).await?;
+    up.stream.get_mut().flush().await
+        .map_err(|e| format!("转发请求失败: {}", e))?;
+    
+    let mut interim = false;
+    loop {
+        let raw = match read_http_head(&mut up.stream).await {
+            Ok(Some(raw)) => raw,
+            Ok(None) | Err(_) if !interim => return Ok(None),
+            Ok(None) => return Err("上游在响应前关闭了连接".to_string()),
+            Err(e) => return Err(e),
+        };
+        let response = HttpHead::parse(&raw)?;
+        match response.status_code() {
+            Some(code) if (100..200).contains(&code) && code != 101 => {
+                client.get_mut().write_all(raw.as_bytes()).await
+                    .map_err(|e| format!("转发响应失败: {}", e))?;
+                interim = true;
+            }
+            Some(_) => return Ok(Some(response)),
+            None => return Err(format!("无效的响应: {}", response.start_line)),
+        }
+    }
+}
+
+/// 转发普通 HTTP 请求（absolute-form），支持客户端与上游的 keep-alive
+///
+/// 出口为 HTTP / HTTPS 代理时直接把 absolute-form 请求（附 Proxy-Authorization）发给代理，
+/// 出口为 SOCKS5 时经隧道以 origin-form 发给源站
+async fn forward_http_requests(
+    mut client: BufReader<ClientStream>,
+    mut request: HttpHead,
+    upstreams: &UpstreamPool,
+    tracker: &mut ConnectionTracker,
+) -> Result<(), String> {
+    let mut upstream: Option<HttpUpstream> = None;
+    let mut target: Option<(String, u16)> = None;
+
+    loop {
+        let (host, port, path) = parse_absolute_uri(request.target())?;
+        let method = request.method().to_string();
+        let client_keep_alive = request.keep_alive();
+        let request_body = BodyKind::of_request(&request)?;
+        if target.as_ref() != Some(&(host.clone(), port)) {
+            tracker.begin(&host, port);
+            target = Some((host.clone(), port));
+        }
+
+        // 1-3. 复用或建立上游连接，转发请求并读取响应头；
+        // 复用的连接在响应前被上游关闭时，幂等请求在新连接上重试一次
+        let mut retried = false;
+        let response = loop {
+            let reused = upstream.as_ref().is_some_and(|u| u.serves(&host, port));
+            if !reused {
+                upstream = Some(upstreams.connect_http(&host, port, &tracker.counters).await?);
+            }
+            let up = upstream.as_mut().expect("upstream 已建立");
+            match send_http_request(&mut client, up, &request, &host, port, &path, request_body).await? {
+                Some(response) => break response,
+                None if reused && !retried && is_retryable_request(&method, request_body) => {
+                    debug!(target = %format!("{}:{}", host, port), "复用的上游连接已关闭，重试请求");
+                    upstream = None;
+                    retried = true;
+                }
+                None => return Err("上游在响应前关闭了连接".to_string()),
+            }
+        };
+        let up = upstream.as_mut().expect("upstream 已建立");
+
+        // 4. 协议升级（如 WebSocket）：转为双向隧道
+        if response.status_code() == Some(101) {
+            let head = response.to_bytes();
+            client.get_mut().write_all(&head).await
+                .map_err(|e| format!("转发响应失败: {}", e))?;
+            let buffered = up.stream.buffer().to_vec();
+            client.get_mut().write_all(&buffered).await
+                .map_err(|e| format!("转发响应失败: {}", e))?;
+
+            let mut upstream = upstream.take().expect("upstream 已建立").stream.into_inner();
+            let mut client = client.into_inner();
+            copy_bidirectional(&mut client, &mut upstream).await?;
+            return Ok(());
+        }
+
+        // 5. 转发响应头和响应体
+        let response_body = BodyKind::of_response(&response, &method)?;
+        let upstream_keep_alive = response.keep_alive() && response_body != BodyKind::UntilClose;
+        let keep_client = client_keep_alive && response_body != BodyKind::UntilClose;
+
+        let mut client_head = response.clone();
+        client_head.strip_hop_by_hop();
+        if !keep_client {
+            client_head.set_header("Connection", "close");
+        } else if request.version().eq_ignore_ascii_case("HTTP/1.0") {
+            client_head.set_header("Connection", "keep-alive");
+        }
+        let head_bytes = client_head.to_bytes();
+        client.get_mut().write_all(&head_bytes).await
+            .map_err(|e| format!("转发响应失败: {}", e))?;
+        copy_body(&mut up.stream, client.get_mut(), response_body).await?;
+        client.get_mut().flush().await
+            .map_err(|e| format!("转发响应失败: {}", e))?;
+
+        if !upstream_keep_alive {
+            upstream = None;
+        }
+        if !keep_client {
+            return Ok(());
+        }
+
+        // 6. 等待同一连接上的下一个请求
+        request = match read_http_head(&mut client).await? {
+            Some(raw) => HttpHead::parse(&raw)?,
+            None => return Ok(()),
+        };
+        if request.method().eq_ignore_ascii_case("CONNECT") {
+            return Err("不支持在普通 HTTP 请求之后发送 CONNECT".to_string());
+        }
+    }
+}
+
+// ==================== SOCKS5 UDP 中继 ====================
+
+/// UDP 会话默认空闲超时
+pub const DEFAULT_UDP_IDLE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// 单个会话待发送数据包队列长度（队列满时丢包）
+const UDP_SESSION_QUEUE_SIZE: usize = 256;
+
+/// SOCKS5 目标地址（ATYP | DST.ADDR | DST.PORT）
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TargetAddr {
+    Ip(SocketAddr),
+    Domain(String, u16),
+}
+
+impl TargetAddr {
+    /// 编码为 ATYP | ADDR | PORT
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            TargetAddr::Ip(SocketAddr::V4(addr)) => {
+                out.push(0x01);
+                out.extend_from_slice(&addr.ip().octets());
+                out.extend_from_slice(&addr.port().to_be_bytes());
+            }
+            TargetAddr::Ip(SocketAddr::V6(addr)) => {
+                out.push(0x04);
+                out.extend_from_slice(&addr.ip().octets());
+                out.extend_from_slice(&addr.port().to_be_bytes());
+            }
+            TargetAddr::Domain(domain, port) => {
+                out.push(0x03);
+                out.push(domain.len() as u8);
+                out.extend_from_slice(domain.as_bytes());
+                out.extend_from_slice(&port.to_be_bytes());
+            }
+        }
+    }
+    
+    /// 从 ATYP 开始解码，返回地址和占用的字节数
+    fn decode(buf: &[u8]) -> Result<(Self, usize), String> {
+        let atyp = *buf.first().ok_or("缺少地址类型")?;
+        let (addr_len, offset) = match atyp {
+            0x01 => (4, 1),
+            0x04 => (16, 1),
+            0x03 => (*buf.get(1).ok_or("缺少域名长度")? as usize, 2),
+            _ => return Err(format!("不支持的地址类型: {}", atyp)),
+        };
+        let end = offset + addr_len + 2;
+        if buf.len() < end {
+            return Err("地址数据不完整".to_string());
+        }
+        
+        let addr = &buf[offset..offset + addr_len];
+        let port = u16::from_be_bytes([buf[end - 2], buf[end - 1]]);
+        let target = match atyp {
+            0x01 => {
+                let ip: [u8; 4] = addr.try_into().unwrap();
+                TargetAddr::Ip(SocketAddr::from((ip, port)))
+            }
+            0x04 => {
+                let ip: [u8; 16] = addr.try_into().unwrap();
+                TargetAddr::Ip(SocketAddr::from((ip, port)))
+            }
+            _ => {
+                let domain = std::str::from_utf8(addr)
+                    .map_err(|_| "域名不是有效的 UTF-8".to_string())?;
+                TargetAddr::Domain(domain.to_string(), port)
+            }
+        };
+        
+        Ok((target, end))
+    }
+}
+
+/// 解析 SOCKS5 UDP 数据报 [RSV(2) | FRAG(1) | ATYP(1) | DST.ADDR | DST.PORT | DATA]
+/// 不支持分片重组，FRAG 非 0 的数据报直接拒绝
+fn parse_udp_packet(buf: &[u8]) -> Result<(TargetAddr, &[u8]), String> {
+    if buf.len() < 4 {
+        return Err("UDP 数据报过短".to_string());
+    }
+    if buf[0] != 0 || buf[1] != 0 {
+        return Err("UDP 数据报保留字段非 0".to_string());
+    }
+    if buf[2] != 0 {
+        return Err(format!("不支持 UDP 分片: FRAG={}", buf[2]));
+    }
+    
+    let (target, len) = TargetAddr::decode(&buf[3..])?;
+    Ok((target, &buf[3 + len..]))
+}
+
+/// 封装 SOCKS5 UDP 数据报
+fn encode_udp_packet(target: &TargetAddr, payload: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(payload.len() + 22);
+    packet.extend_from_slice(&[0x00, 0x00, 0x00]);
+    target.encode(&mut packet);
+    packet.extend_from_slice(payload);
+    packet
+}
+
+/// 单个客户端的 UDP 会话：独立的 ASSOCIATE 控制连接与中继套接字
+/// 空闲超时、控制连接断开或中继端退出时结束
+async fn run_udp_session(
+    client_addr: SocketAddr,
+    mut packets: mpsc::Receiver<Vec<u8>>,
+    local_socket: Arc<UdpSocket>,
+    upstreams: Arc<UpstreamPool>,
+    stats: Arc<BridgeStatsInner>,
+    idle_timeout: Duration,
+    profile_id: String,
+) {
+    stats.udp_active_sessions.fetch_add(1, Ordering::Relaxed);
+    
+    // 会话固定使用建立时的出口代理，流量计入该代理
+    let (upstream, traffic) = upstreams.current();
+    let session = relay_udp_session(client_addr, &mut packets, &local_socket, &upstream, &stats, &traffic, idle_timeout);
+    match session.await {
+        Ok(()) => debug!(profile_id = %profile_id, client = %client_addr, "UDP 会话已结束"),
+        Err(e) => warn!(profile_id = %profile_id, client = %client_addr, error = %e, "UDP 会话异常结束"),
+    }
+    
+    // 会话结束时丢弃尚未发送的数据包
+    packets.close();
+    while packets.try_recv().is_ok() {
+        stats.udp_packets_dropped.fetch_add(1, Ordering::Relaxed);
+    }
+    stats.udp_active_sessions.fetch_sub(1, Ordering::Relaxed);
+}
+
+async fn relay_udp_session(
+    client_addr: SocketAddr,
+    packets: &mut mpsc::Receiver<Vec<u8>>,
+    local_socket: &UdpSocket,
+    upstream: &ProxyHop,
+    stats: &BridgeStatsInner,
+    traffic: &UpstreamTraffic,
+    idle_timeout: Duration,
+) -> Result<(), String> {
+    let (mut control_stream, relay_addr) = establish_udp_associate(upstream).await?;
+    
+    let relay_addr = tokio::net::lookup_host(&relay_addr).await
+        .map_err(|e| format!("解析 UDP 中继地址失败: {}", e))?
+        .next()
+        .ok_or_else(|| format!("无法解析 UDP 中继地址: {}", relay_addr))?;
+    let bind_addr = if relay_addr.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+    let relay_socket = UdpSocket::bind(bind_addr).await
+        .map_err(|e| format!("创建 UDP 中继套接字失败: {}", e))?;
+    // connect 后只接收来自中继地址的数据报
+    relay_socket.connect(relay_addr).await
+        .map_err(|e| format!("连接 UDP 中继失败: {}", e))?;
+    
+    debug!(client = %client_addr, relay_addr = %relay_addr, "SOCKS5 UDP ASSOCIATE 建立成功");
+    
+    let mut relay_buf = vec![0u8; 65535];
+    let mut control_buf = [0u8; 64];
+    let idle = tokio::time::sleep(idle_timeout);
+    tokio::pin!(idle);
+    
+    loop {
+        tokio::select! {
+            packet = packets.recv() => {
+                // 中继端已退出
+                let Some(packet) = packet else { return Ok(()) };
+                match relay_socket.send(&packet).await {
+                    Ok(_) => {
+                        stats.udp_packets_sent.fetch_add(1, Ordering::Relaxed);
+                        stats.bytes_sent.fetch_add(packet.len() as u64, Ordering::Relaxed);
+                        traffic.sent.fetch_add(packet.len() as u64, Ordering::Relaxed);
+                    }
+                    Err(e) => {
+                        debug!(client = %client_addr, error = %e, "发送 UDP 数据失败");
+                        stats.udp_packets_dropped.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+                idle.as_mut().reset(tokio::time::Instant::now() + idle_timeout);
+            }
+            result = relay_socket.recv(&mut relay_buf) => {
+                let n = match result {
+                    Ok(n) => n,
+                    Err(e) => {
+                        debug!(client = %client_addr, error = %e, "接收 UDP 响应失败");
+                        continue;
+                    }
+                };
+                // 中继返回的数据报同样须为完整的 SOCKS5 UDP 数据报
+                let reply = match parse_udp_packet(&relay_buf[..n]) {
+                    Ok((source, payload)) => encode_udp_packet(&source, payload),
+                    Err(e) => {
+                        debug!(client = %client_addr, error = %e, "丢弃 UDP 响应");
+                        stats.udp_packets_dropped.fetch_add(1, Ordering::Relaxed);
+                        continue;
+                    }
+                };
+                match local_socket.send_to(&reply, client_addr).await {
+                    Ok(_) => {
+                        stats.udp_packets_received.fetch_add(1, Ordering::Relaxed);
+                        stats.bytes_received.fetch_add(reply.len() as u64, Ordering::Relaxed);
+                        traffic.received.fetch_add(reply.len() as u64, Ordering::Relaxed);
+                    }
+                    Err(e) => debug!(client = %client_addr, error = %e, "转发 UDP 响应失败"),
+                }
+                idle.as_mut().reset(tokio::time::Instant::now() + idle_timeout);
+            }
+            // SOCKS5 规范：控制连接断开时 UDP 关联随之失效
+            result = control_stream.read(&mut control_buf) => {
+                return match result {
+                    Ok(0) => Err("SOCKS5 控制连接已关闭".to_string()),
+                    Ok(_) => Err("SOCKS5 控制连接收到意外数据".to_string()),
+                    Err(e) => Err(format!("SOCKS5 控制连接异常: {}", e)),
+                };
+            }
+            _ = &mut idle => {
+                debug!(client = %client_addr, "UDP 会话空闲超时");
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// 建立 SOCKS5 UDP ASSOCIATE 连接
+/// 返回控制连接和 UDP 中继地址
+async fn establish_udp_associate(upstream: &ProxyHop) -> Result<(TcpStream, String), String> {
+    // 1. 连接到 SOCKS5 代理服务器
+    let proxy_addr = format!("{}:{}", upstream.host, upstream.port);
+    let mut stream = TcpStream::connect(&proxy_addr).await
+        .map_err(|e| format!("连接 SOCKS5 代理失败: {}", e))?;
+    
+    // 2-3. SOCKS5 握手与认证
+    socks5_authenticate(&mut stream, upstream.username.as_deref(), upstream.password.as_deref()).await?;
+    
+    // 4. 发送 UDP ASSOCIATE 请求 (CMD = 0x03)
+    // DST.ADDR 和 DST.PORT 设为 0，表示客户端会从任意地址发送
+    let udp_associate_request = vec![
+        0x05, // SOCKS 版本
+        0x03, // UDP ASSOCIATE 命令
+        0x00, // 保留字节
+        0x01, // 地址类型: IPv4
+        0x00, 0x00, 0x00, 0x00, // 地址: 0.0.0.0
+        0x00, 0x00, // 端口: 0
+    ];
+    
+    stream.write_all(&udp_associate_request).await
+        .map_err(|e| format!("发送 UDP ASSOCIATE 请求失败: {}", e))?;
+    
+    // 5. 读取响应
+    let mut response_header = [0u8; 4];
+    stream.read_exact(&mut response_header).await
         .map_err(|e| format!("读取 UDP ASSOCIATE 响应失败: {}", e))?;
     
     if response_header[1] != 0x00 {
@@ -805,7 +2710,7 @@ async fn establish_udp_associate(config: &ProxyBridgeConfig) -> Result<(TcpStrea
             
             // 如果服务器返回 0.0.0.0，使用代理服务器地址
             if ip == "0.0.0.0" {
-                format!("{}:{}", config.upstream_host, port)
+                format!("{}:{}", upstream.host, port)
             } else {
                 format!("{}:{}", ip, port)
             }
@@ -849,25 +2754,177 @@ async fn establish_udp_associate(config: &ProxyBridgeConfig) -> Result<(TcpStrea
             return Err(format!("不支持的地址类型: {}", response_header[3]));
         }
     };
-    
-    debug!(relay_addr = %relay_addr, "UDP 中继地址解析成功");
-    
-    // 返回控制连接和中继地址
-    // 注意：必须保持控制连接存活，否则 UDP ASSOCIATE 会失效
-    Ok((stream, relay_addr))
+    
+    debug!(relay_addr = %relay_addr, "UDP 中继地址解析成功");
+    
+    // 返回控制连接和中继地址
+    // 注意：必须保持控制连接存活，否则 UDP ASSOCIATE 会失效
+    Ok((stream, relay_addr))
+}
+
+// ==================== 端口分配 ====================
+
+/// 桥接端口范围设置项（格式：起始-结束，如 50000-59999）
+pub const PORT_RANGE_SETTING_KEY: &str = "proxy_bridge_port_range";
+/// 默认桥接端口范围
+pub const DEFAULT_PORT_RANGE: (u16, u16) = (50000, 59999);
+
+/// 解析端口范围（起始-结束，起始端口不低于 1024）
+pub fn parse_port_range(value: &str) -> Result<(u16, u16), String> {
+    let invalid = || format!("端口范围格式应为 起始-结束（如 50000-59999）: {}", value);
+    let (start, end) = value.trim().split_once('-').ok_or_else(invalid)?;
+    let start: u16 = start.trim().parse().map_err(|_| invalid())?;
+    let end: u16 = end.trim().parse().map_err(|_| invalid())?;
+    if start < 1024 || start > end {
+        return Err(format!("无效的端口范围 {}-{}：起始端口需不低于 1024 且不大于结束端口", start, end));
+    }
+    Ok((start, end))
+}
+
+/// 已绑定的桥接端口（TCP 监听与可选的 UDP 中继使用同一端口号）
+pub struct BridgePorts {
+    pub port: u16,
+    tcp: std::net::TcpListener,
+    udp: Option<std::net::UdpSocket>,
+}
+
+/// 桥接端口分配器
+///
+/// 在配置的范围内直接绑定端口并把监听交给桥接（不存在“探测后释放再绑定”的竞态）。
+/// 每个 Profile 优先使用上次分配的端口，其次使用由 Profile ID 决定的固定起点，
+/// 因此同一 Profile 在多次启动（包括应用重启）之间通常得到相同端口。
+pub struct PortAllocator {
+    state: std::sync::Mutex<AllocatorState>,
+}
+
+struct AllocatorState {
+    range: (u16, u16),
+    /// 已分配给运行中桥接的端口
+    in_use: HashSet<u16>,
+    /// Profile 上次分配的端口
+    sticky: HashMap<String, u16>,
+}
+
+impl PortAllocator {
+    pub fn new(start: u16, end: u16) -> Result<Self, String> {
+        parse_port_range(&format!("{}-{}", start, end))?;
+        Ok(Self {
+            state: std::sync::Mutex::new(AllocatorState {
+                range: (start, end),
+                in_use: HashSet::new(),
+                sticky: HashMap::new(),
+            }),
+        })
+    }
+    
+    /// 调整端口范围（只影响之后的分配）
+    pub fn set_range(&self, start: u16, end: u16) -> Result<(), String> {
+        parse_port_range(&format!("{}-{}", start, end))?;
+        self.state.lock().unwrap().range = (start, end);
+        Ok(())
+    }
+    
+    /// 为 Profile 分配并绑定端口（with_udp 时同一端口号同时绑定 UDP）
+    pub fn allocate(&self, profile_id: &str, with_udp: bool) -> Result<BridgePorts, String> {
+        let mut state = self.state.lock().unwrap();
+        let (start, end) = state.range;
+        let size = (end - start) as u32 + 1;
+        
+        // 候选顺序：上次分配的端口 → 由 Profile ID 决定的起点开始环形遍历
+        let preferred = start as u32 + profile_port_offset(profile_id) % size;
+        let sticky = state.sticky.get(profile_id).copied().filter(|p| (start..=end).contains(p));
+        let candidates = sticky.into_iter().chain(
+            (0..size).map(|i| (start as u32 + (preferred - start as u32 + i) % size) as u16),
+        );
+        
+        for port in candidates {
+            if state.in_use.contains(&port) {
+                continue;
+            }
+            let Some(ports) = bind_bridge_ports(port, with_udp) else {
+                continue;
+            };
+            state.in_use.insert(port);
+            state.sticky.insert(profile_id.to_string(), port);
+            return Ok(ports);
+        }
+        
+        Err(format!(
+            "端口范围 {}-{} 内没有可用端口（已分配 {} 个），请在设置中扩大代理桥接端口范围",
+            start,
+            end,
+            state.in_use.len()
+        ))
+    }
+    
+    /// 释放端口（桥接停止后调用）
+    pub fn release(&self, port: u16) {
+        self.state.lock().unwrap().in_use.remove(&port);
+    }
+
+}
+
+impl Default for PortAllocator {
+    fn default() -> Self {
+        Self::new(DEFAULT_PORT_RANGE.0, DEFAULT_PORT_RANGE.1).expect("默认端口范围有效")
+    }
+}
+
+/// Profile ID 的稳定哈希（FNV-1a，跨版本、跨进程一致）
+fn profile_port_offset(profile_id: &str) -> u32 {
+    profile_id.bytes().fold(0x811c9dc5u32, |hash, b| (hash ^ b as u32).wrapping_mul(0x01000193))
+}
+
+/// 在 127.0.0.1 上绑定 TCP（及 UDP）端口，任一失败则放弃该端口
+fn bind_bridge_ports(port: u16, with_udp: bool) -> Option<BridgePorts> {
+    let tcp = std::net::TcpListener::bind(("127.0.0.1", port)).ok()?;
+    tcp.set_nonblocking(true).ok()?;
+    let udp = if with_udp {
+        let udp = std::net::UdpSocket::bind(("127.0.0.1", port)).ok()?;
+        udp.set_nonblocking(true).ok()?;
+        Some(udp)
+    } else {
+        None
+    };
+    Some(BridgePorts { port, tcp, udp })
 }
 
 // ==================== 代理桥接管理器 ====================
 
+/// 待持久化的流量增量
+#[derive(Debug, Clone)]
+pub struct TrafficDelta {
+    pub profile_id: String,
+    /// 出口代理地址
+    pub upstream_host: String,
+    pub upstream_port: u16,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// 桥接是否仍在运行
+    pub active: bool,
+}
+
 /// 代理桥接管理器（管理所有 Profile 的桥接实例）
 pub struct ProxyBridgeManager {
     bridges: Arc<Mutex<HashMap<String, Arc<ProxyBridge>>>>,
+    /// 已停止桥接尚未取走的流量
+    pending_traffic: Mutex<Vec<TrafficDelta>>,
+    /// 已停止桥接尚未取走的上游可用性变化
+    pending_status: Mutex<Vec<UpstreamStatusChange>>,
+    /// Profile 的连接日志（桥接停止后保留，便于事后排查）
+    journals: Mutex<HashMap<String, Arc<ConnectionJournal>>>,
+    /// 本地端口分配器
+    ports: PortAllocator,
 }
 
 impl ProxyBridgeManager {
     pub fn new() -> Self {
         Self {
             bridges: Arc::new(Mutex::new(HashMap::new())),
+            pending_traffic: Mutex::new(Vec::new()),
+            pending_status: Mutex::new(Vec::new()),
+            journals: Mutex::new(HashMap::new()),
+            ports: PortAllocator::default(),
         }
     }
     
@@ -885,10 +2942,36 @@ impl ProxyBridgeManager {
                 return Ok(bridge.local_addr());
             }
         }
+        if let Some(stale) = bridges.remove(profile_id) {
+            stale.shutdown().await;
+            self.ports.release(stale.local_port);
+        }
         
-        // 创建新的桥接实例
-        let bridge = Arc::new(ProxyBridge::new(profile_id.to_string(), config));
-        bridge.start().await?;
+        // 分配端口（绑定后直接交给桥接）
+        let with_udp = config.enable_udp && config.chain.is_empty();
+        let ports = self.ports.allocate(profile_id, with_udp)?;
+        
+        // 创建新的桥接实例（启用连接日志时沿用该 Profile 已有的日志，容量变化时重建）
+        let capacity = config.connection_log_capacity.min(MAX_CONNECTION_LOG_CAPACITY);
+        let mut bridge = ProxyBridge::new(profile_id.to_string(), config, ports);
+        if capacity > 0 {
+            let mut journals = self.journals.lock().await;
+            let journal = journals
+                .entry(profile_id.to_string())
+                .and_modify(|j| {
+                    if j.capacity() != capacity {
+                        *j = Arc::new(ConnectionJournal::new(capacity));
+                    }
+                })
+                .or_insert_with(|| Arc::new(ConnectionJournal::new(capacity)));
+            bridge = bridge.with_connection_journal(Arc::clone(journal));
+        }
+        let bridge = Arc::new(bridge);
+        if let Err(e) = bridge.start().await {
+            bridge.shutdown().await;
+            self.ports.release(bridge.local_port);
+            return Err(e);
+        }
         
         let local_addr = bridge.local_addr();
         bridges.insert(profile_id.to_string(), bridge);
@@ -901,12 +2984,99 @@ impl ProxyBridgeManager {
         let mut bridges = self.bridges.lock().await;
         
         if let Some(bridge) = bridges.remove(profile_id) {
-            bridge.stop();
+            bridge.shutdown().await;
+            self.ports.release(bridge.local_port);
+            self.pending_traffic.lock().await.extend(bridge.take_traffic_deltas());
+            self.pending_status.lock().await.extend(bridge.take_upstream_status_changes());
         }
         
         Ok(())
     }
     
+    /// 取出所有桥接自上次调用以来的流量增量（含已停止的桥接）
+    pub async fn take_traffic_deltas(&self) -> Vec<TrafficDelta> {
+        let mut deltas: Vec<TrafficDelta> = self.pending_traffic.lock().await.drain(..).collect();
+        let bridges = self.bridges.lock().await;
+        deltas.extend(bridges.values().flat_map(|bridge| bridge.take_traffic_deltas()));
+        deltas
+    }
+    
+    /// 放回未能持久化的流量增量（下次取出时一并返回）
+    ///
+    /// 放回的增量不再标记为运行中，运行中的桥接下次会返回新的增量
+    pub async fn requeue_traffic_deltas(&self, deltas: Vec<TrafficDelta>) {
+        let mut pending = self.pending_traffic.lock().await;
+        pending.extend(deltas.into_iter().map(|delta| TrafficDelta { active: false, ..delta }));
+    }
+    
+    /// 取出所有桥接出口代理的可用性变化（含已停止的桥接）
+    pub async fn take_upstream_status_changes(&self) -> Vec<UpstreamStatusChange> {
+        let mut changes: Vec<UpstreamStatusChange> = self.pending_status.lock().await.drain(..).collect();
+        let bridges = self.bridges.lock().await;
+        changes.extend(bridges.values().flat_map(|bridge| bridge.take_upstream_status_changes()));
+        changes
+    }
+    
+    /// 轮换运行中桥接的代理会话，返回新的会话 ID
+    pub async fn rotate_session(&self, profile_id: &str) -> Result<String, String> {
+        let bridges = self.bridges.lock().await;
+        let bridge = bridges
+            .get(profile_id)
+            .filter(|b| b.is_running())
+            .ok_or_else(|| "该环境没有运行中的代理桥接".to_string())?;
+        bridge
+            .rotate_session()
+            .ok_or_else(|| format!("代理用户名或密码中未配置会话占位符 {}", SESSION_PLACEHOLDER))
+    }
+    
+    /// 获取桥接各出口代理的健康状态
+    pub async fn get_upstream_health(&self, profile_id: &str) -> Option<Vec<UpstreamHealth>> {
+        let bridges = self.bridges.lock().await;
+        bridges.get(profile_id).map(|b| b.upstream_health())
+    }
+    
+    /// 调整运行中桥接的限速，桥接不存在时返回 false
+    pub async fn set_rate_limits(&self, profile_id: &str, upload_limit: Option<u64>, download_limit: Option<u64>) -> bool {
+        let bridges = self.bridges.lock().await;
+        match bridges.get(profile_id) {
+            Some(bridge) => {
+                bridge.set_rate_limits(upload_limit, download_limit);
+                true
+            }
+            None => false,
+        }
+    }
+    
+    /// 调整桥接端口范围（只影响之后启动的桥接）
+    pub fn set_port_range(&self, start: u16, end: u16) -> Result<(), String> {
+        self.ports.set_range(start, end)
+    }
+    
+    /// 获取 Profile 最近的连接记录（新的在前），未启用连接日志时返回空
+    pub async fn get_connection_log(&self, profile_id: &str, limit: usize) -> Vec<ConnectionRecord> {
+        let journals = self.journals.lock().await;
+        journals.get(profile_id).map(|j| j.recent(limit)).unwrap_or_default()
+    }
+    
+    /// 获取 Profile 访问最多的目标域名
+    pub async fn get_top_domains(&self, profile_id: &str, limit: usize) -> Vec<DomainStats> {
+        let journals = self.journals.lock().await;
+        journals.get(profile_id).map(|j| j.top_domains(limit)).unwrap_or_default()
+    }
+    
+    /// 清空 Profile 的连接日志与域名统计
+    pub async fn clear_connection_log(&self, profile_id: &str) {
+        if let Some(journal) = self.journals.lock().await.get(profile_id) {
+            journal.clear();
+        }
+    }
+    
+    /// 获取桥接本地端点（供外部脚本使用 SOCKS5 / HTTP 入站）
+    pub async fn get_bridge_endpoints(&self, profile_id: &str) -> Option<BridgeEndpoints> {
+        let bridges = self.bridges.lock().await;
+        bridges.get(profile_id).filter(|b| b.is_running()).map(|b| b.endpoints())
+    }
+    
     /// 获取桥接统计信息
     pub async fn get_bridge_stats(&self, profile_id: &str) -> Option<BridgeStats> {
         let bridges = self.bridges.lock().await;
@@ -925,15 +3095,22 @@ impl ProxyBridgeManager {
     /// 停止所有代理桥接
     pub async fn stop_all(&self) {
         let mut bridges = self.bridges.lock().await;
+        let mut pending = self.pending_traffic.lock().await;
+        let mut pending_status = self.pending_status.lock().await;
         for (_, bridge) in bridges.drain() {
-            bridge.stop();
+            bridge.shutdown().await;
+            self.ports.release(bridge.local_port);
+            pending.extend(bridge.take_traffic_deltas());
+            pending_status.extend(bridge.take_upstream_status_changes());
         }
     }
     
-    /// 检查是否需要桥接（带密码的 SOCKS5）
+    /// 检查是否需要桥接（任意带认证的上游代理）
+    /// Chromium 的 --proxy-server 无法携带凭据，带认证的代理都需要经由本地桥接注入认证信息
     pub fn needs_bridge(proxy_type: &str, username: &Option<String>, password: &Option<String>) -> bool {
-        proxy_type.to_lowercase() == "socks5" 
-            && (username.is_some() || password.is_some())
+        let has_credentials = username.as_deref().map_or(false, |u| !u.is_empty())
+            || password.as_deref().map_or(false, |p| !p.is_empty());
+        matches!(proxy_type.to_lowercase().as_str(), "socks5" | "http" | "https") && has_credentials
     }
 }
 
@@ -960,12 +3137,878 @@ mod tests {
         assert!(ProxyBridgeManager::needs_bridge("socks5", &Some("user".to_string()), &Some("pass".to_string())));
         assert!(ProxyBridgeManager::needs_bridge("SOCKS5", &Some("user".to_string()), &None));
         assert!(!ProxyBridgeManager::needs_bridge("socks5", &None, &None));
-        assert!(!ProxyBridgeManager::needs_bridge("http", &Some("user".to_string()), &Some("pass".to_string())));
+        assert!(ProxyBridgeManager::needs_bridge("http", &Some("user".to_string()), &Some("pass".to_string())));
+        assert!(ProxyBridgeManager::needs_bridge("https", &Some("user".to_string()), &Some("pass".to_string())));
+        assert!(!ProxyBridgeManager::needs_bridge("http", &None, &None));
+        assert!(!ProxyBridgeManager::needs_bridge("http", &Some(String::new()), &None));
+    }
+    
+    #[test]
+    fn test_parse_status_code() {
+        assert_eq!(parse_status_code("HTTP/1.1 200 Connection established"), Some(200));
+        assert_eq!(parse_status_code("HTTP/1.0 407 Proxy Authentication Required"), Some(407));
+        assert_eq!(parse_status_code("SSH-2.0-OpenSSH"), None);
+    }
+    
+    #[test]
+    fn test_proxy_authorization() {
+        assert_eq!(proxy_authorization(Some("user"), Some("pass")).as_deref(), Some("Basic dXNlcjpwYXNz"));
+        assert_eq!(proxy_authorization(None, None), None);
+    }
+    
+    /// 本地假 HTTP 代理：校验 Proxy-Authorization 后建立隧道并回显数据
+    async fn spawn_fake_http_proxy(expected_auth: &'static str) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let head = read_http_head_unbuffered(&mut stream).await.unwrap();
+            if !head.starts_with("CONNECT example.com:443 ") || !head.contains(expected_auth) {
+                stream.write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n").await.unwrap();
+                return;
+            }
+            stream.write_all(b"HTTP/1.1 200 Connection established\r\n\r\n").await.unwrap();
+            let mut buf = [0u8; 5];
+            stream.read_exact(&mut buf).await.unwrap();
+            stream.write_all(&buf).await.unwrap();
+        });
+        port
+    }
+    
+    #[tokio::test]
+    async fn test_http_upstream_sends_credentials() {
+        let port = spawn_fake_http_proxy("Proxy-Authorization: Basic dXNlcjpwYXNz").await;
+        let config = ProxyBridgeConfig {
+            upstream_host: "127.0.0.1".to_string(),
+            upstream_port: port,
+            upstream_type: "http".to_string(),
+            username: Some("user".to_string()),
+            password: Some("pass".to_string()),
+            ..Default::default()
+        };
+        
+        let mut upstream = UpstreamPool::new("test", &config).connect("example.com", 443, &ConnectionCounters::default()).await.unwrap();
+        upstream.write_all(b"hello").await.unwrap();
+        let mut buf = [0u8; 5];
+        upstream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+    }
+    
+    #[tokio::test]
+    async fn test_http_upstream_auth_rejected() {
+        let port = spawn_fake_http_proxy("Proxy-Authorization: Basic dXNlcjpwYXNz").await;
+        let config = ProxyBridgeConfig {
+            upstream_host: "127.0.0.1".to_string(),
+            upstream_port: port,
+            upstream_type: "http".to_string(),
+            username: Some("user".to_string()),
+            password: Some("wrong".to_string()),
+            ..Default::default()
+        };
+        
+        let err = UpstreamPool::new("test", &config).connect("example.com", 443, &ConnectionCounters::default()).await.err().unwrap();
+        assert!(err.contains("认证失败"));
+    }
+    
+    fn fallback_config(primary_port: u16, fallback_port: u16, username: &str) -> ProxyBridgeConfig {
+        ProxyBridgeConfig {
+            upstream_host: "127.0.0.1".to_string(),
+            upstream_port: primary_port,
+            upstream_type: "http".to_string(),
+            username: Some(username.to_string()),
+            password: Some("pass".to_string()),
+            fallbacks: vec![ProxyHop {
+                host: "127.0.0.1".to_string(),
+                port: fallback_port,
+                proxy_type: "http".to_string(),
+                username: Some("user".to_string()),
+                password: Some("pass".to_string()),
+            }],
+            ..Default::default()
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_upstream_failover() {
+        // 主代理端口无人监听：连续失败达到阈值前不切换，达到后切换到备用代理
+        let unused = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let fallback_port = spawn_fake_http_proxy("Proxy-Authorization: Basic dXNlcjpwYXNz").await;
+        let pool = UpstreamPool::new("test", &fallback_config(unused, fallback_port, "user"));
+        
+        for _ in 1..UPSTREAM_FAILURE_THRESHOLD {
+            assert!(pool.connect("example.com", 443, &ConnectionCounters::default()).await.is_err());
+            assert!(pool.health()[0].active);
+        }
+        assert!(pool.take_changes().is_empty());
+        
+        let counters = ConnectionCounters::default();
+        counters.add(3, 0);
+        let mut upstream = pool.connect("example.com", 443, &counters).await.unwrap();
+        upstream.write_all(b"hello").await.unwrap();
+        let mut buf = [0u8; 5];
+        upstream.read_exact(&mut buf).await.unwrap();
+        counters.add(5, 5);
+        
+        let health = pool.health();
+        assert!(!health[0].active && health[1].active);
+        assert_eq!(health[0].consecutive_failures, UPSTREAM_FAILURE_THRESHOLD);
+        assert!(health[0].last_error.is_some());
+        assert_eq!(pool.current().0.port, fallback_port);
+        let changes = pool.take_changes();
+        assert_eq!(changes.len(), 1);
+        assert!(!changes[0].healthy && changes[0].port == unused);
+        
+        // 流量（含建立上游前的字节）只计入实际使用的备用代理
+        let traffic = pool.take_traffic();
+        assert_eq!(traffic.len(), 1);
+        assert_eq!((traffic[0].0.port, traffic[0].1, traffic[0].2), (fallback_port, 8, 5));
+    }
+    
+    #[tokio::test]
+    async fn test_upstream_target_refused_does_not_fail_over() {
+        // 代理可用但拒绝连接目标（CONNECT 返回 502）
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let primary_port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let _ = read_http_head_unbuffered(&mut stream).await;
+                let _ = stream.write_all(b"HTTP/1.1 502 Bad Gateway\r\n\r\n").await;
+            }
+        });
+        let fallback_port = spawn_fake_http_proxy("Proxy-Authorization: Basic dXNlcjpwYXNz").await;
+        let pool = UpstreamPool::new("test", &fallback_config(primary_port, fallback_port, "user"));
+        
+        for _ in 0..UPSTREAM_FAILURE_THRESHOLD + 1 {
+            let err = pool.connect("example.com", 443, &ConnectionCounters::default()).await.err().unwrap();
+            assert!(err.contains("502"), "{}", err);
+        }
+        
+        let health = pool.health();
+        assert!(health[0].active && health[0].healthy);
+        assert_eq!(health[0].consecutive_failures, 0);
+        assert!(pool.take_changes().is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_upstream_marked_unhealthy_after_repeated_failures() {
+        let unused = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let mut config = fallback_config(unused, unused, "user");
+        config.fallbacks.clear();
+        let pool = UpstreamPool::new("test", &config);
+        
+        for _ in 0..UPSTREAM_FAILURE_THRESHOLD + 1 {
+            assert!(pool.connect("example.com", 443, &ConnectionCounters::default()).await.is_err());
+        }
+        let changes = pool.take_changes();
+        assert_eq!(changes.len(), 1);
+        assert!(!changes[0].healthy);
+        assert_eq!(changes[0].port, unused);
+    }
+    
+    #[test]
+    fn test_session_template_rotation() {
+        let pool = UpstreamPool::new("test", &fallback_config(1, 2, "user-session-{rand}"));
+        assert!(pool.has_session_template());
+        
+        let first = pool.current().0.username.unwrap();
+        assert!(first.starts_with("user-session-") && !first.contains(SESSION_PLACEHOLDER));
+        assert_eq!(first.len(), "user-session-".len() + SESSION_ID_LEN);
+        assert_eq!(pool.current().0.username.unwrap(), first);
+        
+        let session = pool.rotate_session();
+        assert_eq!(pool.current().0.username.unwrap(), format!("user-session-{}", session));
+        
+        let plain = UpstreamPool::new("test", &fallback_config(1, 2, "user"));
+        assert!(!plain.has_session_template());
+    }
+    
+    /// 测试用端口（默认范围内实际绑定，与并行测试不冲突）
+    fn test_ports(profile_id: &str, with_udp: bool) -> BridgePorts {
+        PortAllocator::default().allocate(profile_id, with_udp).unwrap()
+    }
+    
+    #[test]
+    fn test_parse_port_range() {
+        assert_eq!(parse_port_range("50000-59999").unwrap(), (50000, 59999));
+        assert_eq!(parse_port_range(" 40000 - 40000 ").unwrap(), (40000, 40000));
+        assert!(parse_port_range("80-90").is_err());
+        assert!(parse_port_range("50010-50000").is_err());
+        assert!(parse_port_range("50000").is_err());
+    }
+    
+    #[test]
+    fn test_port_allocator_sticky_and_exhaustion() {
+        let allocator = PortAllocator::new(47100, 47163).unwrap();
+        let ports = allocator.allocate("profile-a", true).unwrap();
+        let port = ports.port;
+        assert!((47100..=47163).contains(&port));
+        assert!(ports.udp.is_some());
+        
+        // 释放后同一 Profile 重新分配到相同端口
+        drop(ports);
+        allocator.release(port);
+        let ports = allocator.allocate("profile-a", false).unwrap();
+        assert_eq!(ports.port, port);
+        
+        // 单端口范围被占用时明确报错，而不是返回 0
+        let single = PortAllocator::new(port, port).unwrap();
+        let err = single.allocate("profile-b", false).err().unwrap();
+        assert!(err.contains(&format!("{}-{}", port, port)));
+    }
+    
+    #[tokio::test]
+    async fn test_manager_reuses_sticky_port_after_stop() {
+        let manager = ProxyBridgeManager::new();
+        manager.set_port_range(47700, 47763).unwrap();
+        let config = ProxyBridgeConfig {
+            upstream_host: "127.0.0.1".to_string(),
+            upstream_port: 1080,
+            upstream_type: "socks5".to_string(),
+            enable_udp: true,
+            ..Default::default()
+        };
+        
+        let first = manager.start_bridge("sticky", config.clone()).await.unwrap();
+        manager.stop_bridge("sticky").await.unwrap();
+        // 停止后监听已关闭，立即重启仍能拿到同一端口
+        let second = manager.start_bridge("sticky", config).await.unwrap();
+        assert_eq!(first, second);
+        manager.stop_all().await;
+    }
+    
+    #[test]
+    fn test_port_allocator_parallel_unique() {
+        let allocator = Arc::new(PortAllocator::new(47200, 47599).unwrap());
+        let handles: Vec<_> = (0..200)
+            .map(|i| {
+                let allocator = Arc::clone(&allocator);
+                std::thread::spawn(move || allocator.allocate(&format!("profile-{}", i), false).unwrap())
+            })
+            .collect();
+        let ports: Vec<BridgePorts> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        let unique: HashSet<u16> = ports.iter().map(|p| p.port).collect();
+        assert_eq!(unique.len(), 200);
+    }
+    
+    #[test]
+    fn test_parse_absolute_uri() {
+        assert_eq!(
+            parse_absolute_uri("http://example.com/a/b?x=1").unwrap(),
+            ("example.com".to_string(), 80, "/a/b?x=1".to_string())
+        );
+        assert_eq!(
+            parse_absolute_uri("http://example.com:8080").unwrap(),
+            ("example.com".to_string(), 8080, "/".to_string())
+        );
+        assert_eq!(
+            parse_absolute_uri("http://[::1]:8080/x").unwrap(),
+            ("::1".to_string(), 8080, "/x".to_string())
+        );
+        assert!(parse_absolute_uri("/relative").is_err());
+        assert!(parse_absolute_uri("ftp://example.com/").is_err());
+    }
+    
+    #[test]
+    fn test_rewrite_request_head() {
+        let request = HttpHead::parse(
+            "GET http://example.com:8080/x HTTP/1.1\r\nHost: example.com:8080\r\nProxy-Connection: keep-alive\r\nProxy-Authorization: Basic abc\r\nConnection: X-Trace\r\nX-Trace: 1\r\nAccept: */*\r\n\r\n",
+        ).unwrap();
+        let head = rewrite_request_head(&request, "example.com", 8080, "/x");
+        
+        assert_eq!(head.start_line, "GET /x HTTP/1.1");
+        assert_eq!(head.header("Host"), Some("example.com:8080"));
+        assert_eq!(head.header("Accept"), Some("*/*"));
+        assert_eq!(head.header("Connection"), Some("keep-alive"));
+        assert!(head.header("Proxy-Connection").is_none());
+        assert!(head.header("Proxy-Authorization").is_none());
+        assert!(head.header("X-Trace").is_none());
+    }
+    
+    /// 本地假 HTTP 代理：直接响应 absolute-form 请求，记录收到的请求与连接序号
+    /// 每个连接的第一个响应使用 Content-Length，之后使用 chunked；
+    /// 每个连接处理 max_requests 个请求后不再响应并关闭连接（模拟空闲连接被上游回收）
+    async fn spawn_fake_forward_proxy(
+        max_requests: usize,
+    ) -> (u16, tokio::sync::mpsc::UnboundedReceiver<(usize, String, Vec<u8>)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        
+        tokio::spawn(async move {
+            for conn in 0.. {
+                let (stream, _) = listener.accept().await.unwrap();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    let mut stream = BufReader::new(stream);
+                    for served in 0..max_requests {
+                        let Some(raw) = read_http_head(&mut stream).await.unwrap() else {
+                            return;
+                        };
+                        let head = HttpHead::parse(&raw).unwrap();
+                        let mut body = Vec::new();
+                        copy_body(&mut stream, &mut body, BodyKind::of_request(&head).unwrap()).await.unwrap();
+                        tx.send((conn, raw, body)).unwrap();
+                        
+                        let response: &[u8] = if served == 0 {
+                            b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nKeep-Alive: timeout=5\r\n\r\nhello"
+                        } else {
+                            b"HTTP/1.1 201 Created\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n"
+                        };
+                        stream.get_mut().write_all(response).await.unwrap();
+                    }
+                });
+            }
+        });
+        
+        (port, rx)
+    }
+    
+    async fn read_response(stream: &mut BufReader<TcpStream>, method: &str) -> (HttpHead, Vec<u8>, usize) {
+        let raw = read_http_head(stream).await.unwrap().unwrap();
+        let head = HttpHead::parse(&raw).unwrap();
+        let mut body = Vec::new();
+        let copied = copy_body(stream, &mut body, BodyKind::of_response(&head, method).unwrap()).await.unwrap();
+        (head, body, raw.len() + copied as usize)
+    }
+    
+    #[tokio::test]
+    async fn test_bridge_forwards_absolute_form_requests() {
+        let (upstream_port, mut seen) = spawn_fake_forward_proxy(usize::MAX).await;
+        let bridge = ProxyBridge::new("test-profile".to_string(), ProxyBridgeConfig {
+            upstream_host: "127.0.0.1".to_string(),
+            upstream_port,
+            upstream_type: "http".to_string(),
+            username: Some("user".to_string()),
+            password: Some("pass".to_string()),
+            ..Default::default()
+        }, test_ports("test-profile", false));
+        bridge.start().await.unwrap();
+        
+        let client = TcpStream::connect(("127.0.0.1", bridge.local_port)).await.unwrap();
+        let mut client = BufReader::new(client);
+        let mut received = 0;
+        
+        // 请求 1：GET（Content-Length 响应）
+        client.get_mut().write_all(
+            b"GET http://example.com/a?x=1 HTTP/1.1\r\nHost: example.com\r\nProxy-Connection: keep-alive\r\nProxy-Authorization: Basic bGVhaw==\r\n\r\n",
+        ).await.unwrap();
+        let (head, body, n) = read_response(&mut client, "GET").await;
+        received += n;
+        assert_eq!(head.status_code(), Some(200));
+        assert_eq!(body, b"hello");
+        assert!(head.header("Keep-Alive").is_none());
+        
+        // 请求 2：同一连接上的 POST（chunked 响应）
+        client.get_mut().write_all(
+            b"POST http://example.com/b HTTP/1.1\r\nHost: example.com\r\nContent-Length: 4\r\n\r\nping",
+        ).await.unwrap();
+        let (head, body, n) = read_response(&mut client, "POST").await;
+        received += n;
+        assert_eq!(head.status_code(), Some(201));
+        assert_eq!(body, b"3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n");
+        
+        // HTTP 出口代理在同一连接上收到 absolute-form 请求，带出口认证而不是客户端的认证
+        let (conn, first, _) = seen.recv().await.unwrap();
+        assert_eq!(conn, 0);
+        assert!(first.starts_with("GET http://example.com/a?x=1 HTTP/1.1\r\n"));
+        assert!(first.contains("Host: example.com\r\n"));
+        assert!(first.contains("Proxy-Authorization: Basic dXNlcjpwYXNz\r\n"));
+        assert!(!first.contains("bGVhaw=="));
+        assert!(!first.contains("Proxy-Connection"));
+        let (conn, second, body) = seen.recv().await.unwrap();
+        assert_eq!(conn, 0);
+        assert!(second.starts_with("POST http://example.com/b HTTP/1.1\r\n"));
+        assert_eq!(body, b"ping");
+        
+        // 字节统计
+        drop(client);
+        for _ in 0..50 {
+            if bridge.get_stats().active_connections == 0 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        let stats = bridge.get_stats();
+        assert_eq!(stats.total_connections, 1);
+        assert_eq!(stats.failed_connections, 0);
+        assert_eq!(stats.bytes_received, received as u64);
+        assert!(stats.bytes_sent > 0);
+        
+        bridge.stop();
+    }
+    
+    #[tokio::test]
+    async fn test_bridge_retries_idempotent_request_on_closed_upstream() {
+        // 上游每个连接只响应一个请求，之后直接关闭
+        let (upstream_port, mut seen) = spawn_fake_forward_proxy(1).await;
+        let bridge = ProxyBridge::new(
+            "retry-profile".to_string(),
+            ProxyBridgeConfig {
+                upstream_host: "127.0.0.1".to_string(),
+                upstream_port,
+                upstream_type: "http".to_string(),
+                ..Default::default()
+            },
+            test_ports("retry-profile", false),
+        );
+        bridge.start().await.unwrap();
+        
+        let client = TcpStream::connect(("127.0.0.1", bridge.local_port)).await.unwrap();
+        let mut client = BufReader::new(client);
+        for path in ["/a", "/b"] {
+            client.get_mut().write_all(
+                format!("GET http://example.com{} HTTP/1.1\r\nHost: example.com\r\n\r\n", path).as_bytes(),
+            ).await.unwrap();
+            let (head, body, _) = read_response(&mut client, "GET").await;
+            assert_eq!(head.status_code(), Some(200));
+            assert_eq!(body, b"hello");
+        }
+        // 第二个 GET 在新连接上重发
+        assert_eq!(seen.recv().await.unwrap().0, 0);
+        assert_eq!(seen.recv().await.unwrap().0, 1);
+        
+        // 非幂等请求不重发，客户端连接被关闭
+        client.get_mut().write_all(
+            b"POST http://example.com/c HTTP/1.1\r\nHost: example.com\r\nContent-Length: 4\r\n\r\nping",
+        ).await.unwrap();
+        assert!(read_http_head(&mut client).await.unwrap().is_none());
+        assert!(seen.try_recv().is_err());
+        
+        bridge.stop();
+    }
+    
+    fn socks_inbound_bridge(upstream_port: u16) -> ProxyBridge {
+        ProxyBridge::new("socks-test".to_string(), ProxyBridgeConfig {
+            upstream_host: "127.0.0.1".to_string(),
+            upstream_port,
+            upstream_type: "http".to_string(),
+            username: Some("user".to_string()),
+            password: Some("pass".to_string()),
+            local_username: Some("local".to_string()),
+            local_password: Some("secret".to_string()),
+            ..Default::default()
+        }, test_ports("socks-test", false))
+    }
+    
+    async fn socks5_login(stream: &mut TcpStream, password: &str) -> [u8; 2] {
+        stream.write_all(&[0x05, 0x01, 0x02]).await.unwrap();
+        let mut method = [0u8; 2];
+        stream.read_exact(&mut method).await.unwrap();
+        assert_eq!(method, [0x05, 0x02]);
+        
+        let mut auth = vec![0x01, 5];
+        auth.extend_from_slice(b"local");
+        auth.push(password.len() as u8);
+        auth.extend_from_slice(password.as_bytes());
+        stream.write_all(&auth).await.unwrap();
+        let mut result = [0u8; 2];
+        stream.read_exact(&mut result).await.unwrap();
+        result
+    }
+    
+    #[tokio::test]
+    async fn test_socks5_inbound_connect_with_local_auth() {
+        let upstream_port = spawn_fake_http_proxy("Proxy-Authorization: Basic dXNlcjpwYXNz").await;
+        let bridge = socks_inbound_bridge(upstream_port);
+        bridge.start().await.unwrap();
+        assert!(bridge.endpoints().socks5_auth_required);
+        
+        let mut client = TcpStream::connect(("127.0.0.1", bridge.local_port)).await.unwrap();
+        assert_eq!(socks5_login(&mut client, "secret").await, [0x01, 0x00]);
+        
+        // 以域名发起 CONNECT，由上游解析
+        let mut request = vec![0x05, 0x01, 0x00];
+        TargetAddr::Domain("example.com".to_string(), 443).encode(&mut request);
+        client.write_all(&request).await.unwrap();
+        let mut reply = [0u8; 10];
+        client.read_exact(&mut reply).await.unwrap();
+        assert_eq!(reply[1], 0x00);
+        
+        client.write_all(b"hello").await.unwrap();
+        let mut buf = [0u8; 5];
+        client.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+        
+        drop(client);
+        for _ in 0..50 {
+            if bridge.get_stats().active_connections == 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        // 客户端侧计量，包含 SOCKS5 握手字节
+        let stats = bridge.get_stats();
+        assert_eq!(stats.total_connections, 1);
+        assert_eq!(stats.bytes_sent, 3 + 14 + 18 + 5);
+        assert_eq!(stats.bytes_received, 2 + 2 + 10 + 5);
+        
+        let deltas = bridge.take_traffic_deltas();
+        assert_eq!(deltas.len(), 1);
+        assert_eq!((deltas[0].upstream_port, deltas[0].bytes_sent, deltas[0].bytes_received), (upstream_port, 40, 19));
+        // 取走后只剩当前出口代理的空增量
+        let deltas = bridge.take_traffic_deltas();
+        assert_eq!(deltas.len(), 1);
+        assert_eq!((deltas[0].bytes_sent, deltas[0].bytes_received), (0, 0));
+        
+        bridge.stop();
+    }
+    
+    #[tokio::test]
+    async fn test_socks5_inbound_rejects_bad_credentials() {
+        let bridge = socks_inbound_bridge(1);
+        bridge.start().await.unwrap();
+        
+        let mut client = TcpStream::connect(("127.0.0.1", bridge.local_port)).await.unwrap();
+        assert_eq!(socks5_login(&mut client, "wrong").await, [0x01, 0x01]);
+        
+        // 需要认证时不接受无认证方法
+        let mut client = TcpStream::connect(("127.0.0.1", bridge.local_port)).await.unwrap();
+        client.write_all(&[0x05, 0x01, 0x00]).await.unwrap();
+        let mut method = [0u8; 2];
+        client.read_exact(&mut method).await.unwrap();
+        assert_eq!(method, [0x05, 0xFF]);
+        
+        bridge.stop();
+    }
+    
+    #[tokio::test]
+    async fn test_http_inbound_ignores_local_socks_auth() {
+        let upstream_port = spawn_fake_http_proxy("Proxy-Authorization: Basic dXNlcjpwYXNz").await;
+        let bridge = socks_inbound_bridge(upstream_port);
+        bridge.start().await.unwrap();
+        
+        // 浏览器通过 HTTP 入站连接，不携带凭据；本地 SOCKS5 认证不应影响它
+        let mut client = BufReader::new(TcpStream::connect(("127.0.0.1", bridge.local_port)).await.unwrap());
+        client.get_mut().write_all(b"CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n\r\n").await.unwrap();
+        assert!(read_http_head(&mut client).await.unwrap().unwrap().starts_with("HTTP/1.1 200"));
+        client.get_mut().write_all(b"hello").await.unwrap();
+        let mut buf = [0u8; 5];
+        client.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+        
+        bridge.stop();
+    }
+    
+    #[test]
+    fn test_udp_associations_bound_to_client() {
+        let associations = Arc::new(UdpAssociations::default());
+        let any_port = associations.register("127.0.0.1:0".parse().unwrap());
+        let fixed = associations.register("10.0.0.2:5000".parse().unwrap());
+        
+        assert!(associations.permits("127.0.0.1:40000".parse().unwrap()));
+        assert!(associations.permits("10.0.0.2:5000".parse().unwrap()));
+        assert!(!associations.permits("10.0.0.2:5001".parse().unwrap()));
+        assert!(!associations.permits("10.0.0.3:5000".parse().unwrap()));
+        
+        // 控制连接断开后关联撤销
+        drop(any_port);
+        assert!(!associations.permits("127.0.0.1:40000".parse().unwrap()));
+        drop(fixed);
+        assert!(!associations.permits("10.0.0.2:5000".parse().unwrap()));
+    }
+    
+    #[tokio::test]
+    async fn test_connection_journal_records_targets() {
+        let upstream_port = spawn_fake_http_proxy("Proxy-Authorization: Basic dXNlcjpwYXNz").await;
+        let journal = Arc::new(ConnectionJournal::new(16));
+        let bridge = socks_inbound_bridge(upstream_port).with_connection_journal(Arc::clone(&journal));
+        bridge.start().await.unwrap();
+        
+        // 成功的隧道
+        let mut client = TcpStream::connect(("127.0.0.1", bridge.local_port)).await.unwrap();
+        assert_eq!(socks5_login(&mut client, "secret").await, [0x01, 0x00]);
+        let mut request = vec![0x05, 0x01, 0x00];
+        TargetAddr::Domain("example.com".to_string(), 443).encode(&mut request);
+        client.write_all(&request).await.unwrap();
+        let mut reply = [0u8; 10];
+        client.read_exact(&mut reply).await.unwrap();
+        client.write_all(b"hello").await.unwrap();
+        let mut buf = [0u8; 5];
+        client.read_exact(&mut buf).await.unwrap();
+        drop(client);
+        
+        // 上游已关闭，连接失败
+        let mut client = TcpStream::connect(("127.0.0.1", bridge.local_port)).await.unwrap();
+        assert_eq!(socks5_login(&mut client, "secret").await, [0x01, 0x00]);
+        let mut request = vec![0x05, 0x01, 0x00];
+        TargetAddr::Domain("Tracker.Example".to_string(), 8443).encode(&mut request);
+        client.write_all(&request).await.unwrap();
+        client.read_exact(&mut reply).await.unwrap();
+        assert_eq!(reply[1], 0x01);
+        
+        for _ in 0..50 {
+            if journal.recent(10).len() == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let records = journal.recent(10);
+        assert_eq!(records.len(), 2);
+        assert_eq!((records[1].host.as_str(), records[1].port), ("example.com", 443));
+        // 从解析出目标后开始计量：上行为隧道数据，下行为 SOCKS5 响应 + 隧道数据
+        assert_eq!((records[1].bytes_sent, records[1].bytes_received), (5, 10 + 5));
+        assert!(records[1].error.is_none());
+        assert_eq!((records[0].host.as_str(), records[0].port), ("Tracker.Example", 8443));
+        assert!(records[0].error.is_some());
+        
+        let domains = journal.top_domains(5);
+        assert_eq!(domains.len(), 2);
+        let tracker = domains.iter().find(|d| d.domain == "tracker.example").unwrap();
+        assert_eq!((tracker.connections, tracker.failed_connections), (1, 1));
+        
+        bridge.stop();
+    }
+    
+    #[test]
+    fn test_connection_journal_is_bounded() {
+        let journal = ConnectionJournal::new(2);
+        for (i, host) in ["a.com", "b.com", "b.com"].iter().enumerate() {
+            journal.record(ConnectionRecord {
+                timestamp: i as i64,
+                host: host.to_string(),
+                port: 443,
+                bytes_sent: 10,
+                bytes_received: 100,
+                duration_ms: 1,
+                error: (i == 0).then(|| "失败".to_string()),
+            });
+        }
+        
+        let recent = journal.recent(10);
+        assert_eq!(recent.iter().map(|r| r.timestamp).collect::<Vec<_>>(), vec![2, 1]);
+        
+        // 域名统计不受日志容量限制
+        let domains = journal.top_domains(10);
+        assert_eq!(domains[0].domain, "b.com");
+        assert_eq!(domains[0].connections, 2);
+        assert_eq!(domains[0].bytes_received, 200);
+        assert_eq!(domains[1].failed_connections, 1);
+        
+        journal.clear();
+        assert!(journal.recent(10).is_empty() && journal.top_domains(10).is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_metered_stream_rate_limit() {
+        let stats = Arc::new(BridgeStatsInner::new());
+        let limiter = Arc::new(BridgeLimiter::new(None, Some(40_000)));
+        let (client, mut peer) = tokio::io::duplex(256 * 1024);
+        let mut client = MeteredStream::new(client, Arc::clone(&stats), limiter);
+        
+        // 突发 40KB 后透支 40KB，需等待约 1 秒令牌补足
+        let start = std::time::Instant::now();
+        for _ in 0..5 {
+            client.write_all(&[0u8; 20_000]).await.unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(900), "{:?}", start.elapsed());
+        assert_eq!(stats.bytes_received.load(Ordering::Relaxed), 100_000);
+        
+        // 上行未限速
+        peer.write_all(&[1u8; 1000]).await.unwrap();
+        let mut buf = [0u8; 1000];
+        client.read_exact(&mut buf).await.unwrap();
+        assert_eq!(stats.bytes_sent.load(Ordering::Relaxed), 1000);
+    }
+    
+    #[test]
+    fn test_udp_packet_roundtrip() {
+        let targets = [
+            TargetAddr::Ip("1.2.3.4:3478".parse().unwrap()),
+            TargetAddr::Ip("[2001:db8::1]:443".parse().unwrap()),
+            TargetAddr::Domain("stun.example.com".to_string(), 19302),
+        ];
+        for target in targets {
+            let packet = encode_udp_packet(&target, b"payload");
+            let (decoded, payload) = parse_udp_packet(&packet).unwrap();
+            assert_eq!(decoded, target);
+            assert_eq!(payload, b"payload");
+        }
+        
+        assert_eq!(
+            encode_udp_packet(&TargetAddr::Ip("1.2.3.4:80".parse().unwrap()), b"x"),
+            vec![0, 0, 0, 0x01, 1, 2, 3, 4, 0, 80, b'x'],
+        );
     }
     
     #[test]
-    fn test_find_free_port() {
-        let port = ProxyBridge::find_free_port(50000);
-        assert!(port >= 50000 || port == 0);
+    fn test_parse_udp_packet_rejects_invalid() {
+        // 分片
+        assert!(parse_udp_packet(&[0, 0, 1, 0x01, 1, 2, 3, 4, 0, 80, b'x']).is_err());
+        // 保留字段非 0
+        assert!(parse_udp_packet(&[0, 1, 0, 0x01, 1, 2, 3, 4, 0, 80]).is_err());
+        // 地址不完整
+        assert!(parse_udp_packet(&[0, 0, 0, 0x01, 1, 2, 3]).is_err());
+        assert!(parse_udp_packet(&[0, 0, 0, 0x03, 10, b'a', b'b']).is_err());
+        // 未知地址类型
+        assert!(parse_udp_packet(&[0, 0, 0, 0x05, 1, 2, 3, 4, 0, 80]).is_err());
+    }
+    
+    /// 本地 SOCKS5 UDP 替身：每个 ASSOCIATE 绑定独立的 UDP 端口，
+    /// 把收到的数据报以 "echo:" 前缀原路返回；控制连接关闭时通过通道通知
+    async fn spawn_fake_socks5_udp() -> (u16, tokio::sync::mpsc::UnboundedReceiver<&'static str>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        
+        tokio::spawn(async move {
+            loop {
+                let (mut control, _) = listener.accept().await.unwrap();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    let mut greeting = [0u8; 3];
+                    control.read_exact(&mut greeting).await.unwrap();
+                    control.write_all(&[0x05, 0x00]).await.unwrap();
+                    
+                    let mut request = [0u8; 10];
+                    control.read_exact(&mut request).await.unwrap();
+                    assert_eq!(request[1], 0x03);
+                    
+                    let relay = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+                    let relay_port = relay.local_addr().unwrap().port().to_be_bytes();
+                    control.write_all(&[0x05, 0x00, 0x00, 0x01, 127, 0, 0, 1, relay_port[0], relay_port[1]]).await.unwrap();
+                    tx.send("associate").unwrap();
+                    
+                    let mut buf = vec![0u8; 65535];
+                    let mut control_buf = [0u8; 16];
+                    loop {
+                        tokio::select! {
+                            result = relay.recv_from(&mut buf) => {
+                                let (n, from) = result.unwrap();
+                                let (target, payload) = parse_udp_packet(&buf[..n]).unwrap();
+                                let reply = encode_udp_packet(&target, &[b"echo:", payload].concat());
+                                relay.send_to(&reply, from).await.unwrap();
+                            }
+                            result = control.read(&mut control_buf) => {
+                                if matches!(result, Ok(0) | Err(_)) {
+                                    tx.send("closed").unwrap();
+                                    return;
+                                }
+                            }
+                        }
+                    }
+                });
+            }
+        });
+        
+        (port, rx)
+    }
+    
+    async fn start_udp_bridge(upstream_port: u16, idle_timeout: Duration) -> ProxyBridge {
+        let bridge = ProxyBridge::new("udp-test".to_string(), ProxyBridgeConfig {
+            upstream_host: "127.0.0.1".to_string(),
+            upstream_port,
+            upstream_type: "socks5".to_string(),
+            enable_udp: true,
+            ..Default::default()
+        }, test_ports("udp-test", true))
+        .with_udp_idle_timeout(idle_timeout);
+        bridge.start().await.unwrap();
+        bridge
+    }
+    
+    async fn recv_udp(socket: &UdpSocket) -> Vec<u8> {
+        let mut buf = vec![0u8; 65535];
+        let n = tokio::time::timeout(Duration::from_secs(5), socket.recv(&mut buf))
+            .await
+            .expect("等待 UDP 响应超时")
+            .unwrap();
+        buf.truncate(n);
+        buf
+    }
+    
+    #[tokio::test]
+    async fn test_udp_relay_demultiplexes_clients() {
+        let (upstream_port, mut events) = spawn_fake_socks5_udp().await;
+        let bridge = start_udp_bridge(upstream_port, DEFAULT_UDP_IDLE_TIMEOUT).await;
+        let relay_addr = bridge.local_udp_addr().unwrap();
+        
+        let client_a = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let client_b = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client_a.connect(&relay_addr).await.unwrap();
+        client_b.connect(&relay_addr).await.unwrap();
+        
+        let stun = TargetAddr::Ip("1.2.3.4:3478".parse().unwrap());
+        let quic = TargetAddr::Domain("quic.example.com".to_string(), 443);
+        client_a.send(&encode_udp_packet(&stun, b"from-a")).await.unwrap();
+        client_b.send(&encode_udp_packet(&quic, b"from-b")).await.unwrap();
+        
+        let reply = recv_udp(&client_a).await;
+        let (source, payload) = parse_udp_packet(&reply).unwrap();
+        assert_eq!(source, stun);
+        assert_eq!(payload, b"echo:from-a");
+        
+        let reply = recv_udp(&client_b).await;
+        let (source, payload) = parse_udp_packet(&reply).unwrap();
+        assert_eq!(source, quic);
+        assert_eq!(payload, b"echo:from-b");
+        
+        // 每个客户端各自建立一个 ASSOCIATE
+        assert_eq!(events.recv().await, Some("associate"));
+        assert_eq!(events.recv().await, Some("associate"));
+        
+        let stats = bridge.get_stats();
+        assert_eq!(stats.udp_packets_sent, 2);
+        assert_eq!(stats.udp_packets_received, 2);
+        assert_eq!(stats.udp_active_sessions, 2);
+        
+        bridge.stop();
+    }
+    
+    #[tokio::test]
+    async fn test_udp_relay_drops_fragments() {
+        let (upstream_port, _events) = spawn_fake_socks5_udp().await;
+        let bridge = start_udp_bridge(upstream_port, DEFAULT_UDP_IDLE_TIMEOUT).await;
+        
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client.connect(bridge.local_udp_addr().unwrap()).await.unwrap();
+        
+        let target = TargetAddr::Ip("1.2.3.4:3478".parse().unwrap());
+        let mut fragment = encode_udp_packet(&target, b"part");
+        fragment[2] = 0x01;
+        client.send(&fragment).await.unwrap();
+        client.send(&encode_udp_packet(&target, b"whole")).await.unwrap();
+        
+        // 只有完整的数据报被转发
+        let reply = recv_udp(&client).await;
+        assert_eq!(parse_udp_packet(&reply).unwrap().1, b"echo:whole");
+        
+        let stats = bridge.get_stats();
+        assert_eq!(stats.udp_packets_dropped, 1);
+        assert_eq!(stats.udp_packets_sent, 1);
+        
+        bridge.stop();
+    }
+    
+    #[tokio::test]
+    async fn test_udp_session_idle_timeout() {
+        let (upstream_port, mut events) = spawn_fake_socks5_udp().await;
+        let bridge = start_udp_bridge(upstream_port, Duration::from_millis(200)).await;
+        
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client.connect(bridge.local_udp_addr().unwrap()).await.unwrap();
+        
+        let target = TargetAddr::Ip("1.2.3.4:3478".parse().unwrap());
+        client.send(&encode_udp_packet(&target, b"ping")).await.unwrap();
+        recv_udp(&client).await;
+        assert_eq!(events.recv().await, Some("associate"));
+        
+        // 空闲超时后关闭控制连接
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv()).await.unwrap();
+        assert_eq!(event, Some("closed"));
+        for _ in 0..50 {
+            if bridge.get_stats().udp_active_sessions == 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(bridge.get_stats().udp_active_sessions, 0);
+        
+        // 再次发送时重新建立会话
+        client.send(&encode_udp_packet(&target, b"again")).await.unwrap();
+        let reply = recv_udp(&client).await;
+        assert_eq!(parse_udp_packet(&reply).unwrap().1, b"echo:again");
+        assert_eq!(events.recv().await, Some("associate"));
+        
+        bridge.stop();
     }
 }
@@ -38,6 +38,35 @@ pub fn validate_kernel_path(path: &str) -> Result<(), String> {
     Ok(())
 }
 
+/// 校验指纹真实度启动阈值（0-100，0 表示不拦截）
+pub fn validate_min_fingerprint_score(value: &str) -> Result<(), String> {
+    match value.trim().parse::<u32>() {
+        Ok(score) if score <= 100 => Ok(()),
+        _ => Err(format!("指纹评分阈值必须是 0-100 的整数: {}", value)),
+    }
+}
+
+/// 校验浏览器正常关闭的等待时间（秒）
+pub fn validate_shutdown_timeout(value: &str) -> Result<(), String> {
+    use crate::modules::browser_manager::MAX_SHUTDOWN_TIMEOUT_SECS;
+    match value.trim().parse::<u64>() {
+        Ok(secs) if (1..=MAX_SHUTDOWN_TIMEOUT_SECS).contains(&secs) => Ok(()),
+        _ => Err(format!("关闭等待时间必须是 1-{} 秒: {}", MAX_SHUTDOWN_TIMEOUT_SECS, value)),
+    }
+}
+
+/// 校验代理连接日志容量设置（0 表示不记录）
+pub fn validate_connection_log_capacity(value: &str) -> Result<(), String> {
+    match value.trim().parse::<usize>() {
+        Ok(capacity) if capacity <= crate::modules::proxy_bridge::MAX_CONNECTION_LOG_CAPACITY => Ok(()),
+        _ => Err(format!(
+            "连接日志容量必须是 0-{} 的整数: {}",
+            crate::modules::proxy_bridge::MAX_CONNECTION_LOG_CAPACITY,
+            value
+        )),
+    }
+}
+
 /// 校验 user_data_dir 设置
 pub fn validate_user_data_dir(path: &str) -> Result<(), String> {
     let path_obj = Path::new(path);
@@ -112,4 +141,19 @@ mod tests {
         let result = validate_user_data_dir(temp_dir.path().to_str().unwrap());
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_validate_min_fingerprint_score() {
+        assert!(validate_min_fingerprint_score("0").is_ok());
+        assert!(validate_min_fingerprint_score("70").is_ok());
+        assert!(validate_min_fingerprint_score("101").is_err());
+        assert!(validate_min_fingerprint_score("high").is_err());
+    }
+
+    #[test]
+    fn test_validate_shutdown_timeout() {
+        assert!(validate_shutdown_timeout("10").is_ok());
+        assert!(validate_shutdown_timeout("0").is_err());
+        assert!(validate_shutdown_timeout("301").is_err());
+    }
 }
@@ -0,0 +1,167 @@
+// Signing - Ed25519 签名校验
+// 内核 / 更新包的签名校验，公钥在编译时通过 BM_UPDATE_PUBLIC_KEYS 嵌入
+// 密钥轮换：新旧公钥同时嵌入一个版本周期，服务端切换到新密钥签名后，
+// 旧密钥 ID 加入 BM_UPDATE_REVOKED_KEYS 即不再被信任
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use ring::signature::{UnparsedPublicKey, ED25519};
+use tracing::warn;
+
+/// 受信任的公钥（编译时嵌入，逗号分隔的 "key_id:base64"，支持多把密钥并存）
+const EMBEDDED_PUBLIC_KEYS: Option<&str> = option_env!("BM_UPDATE_PUBLIC_KEYS");
+
+/// 已吊销的密钥 ID（编译时嵌入，逗号分隔）
+const REVOKED_KEY_IDS: Option<&str> = option_env!("BM_UPDATE_REVOKED_KEYS");
+
+/// 受信任的 Ed25519 公钥
+#[derive(Debug, Clone)]
+pub struct TrustedKey {
+    pub key_id: String,
+    pub public_key: Vec<u8>,
+}
+
+/// 解析单个 "key_id:base64" 形式的公钥
+fn parse_public_key(entry: &str) -> Result<TrustedKey> {
+    let (key_id, encoded) = entry
+        .split_once(':')
+        .ok_or_else(|| anyhow!("公钥格式错误，应为 key_id:base64: {}", entry))?;
+    let public_key = BASE64
+        .decode(encoded.trim())
+        .map_err(|e| anyhow!("公钥 {} 解码失败: {}", key_id, e))?;
+    if public_key.len() != 32 {
+        return Err(anyhow!("公钥 {} 长度无效（Ed25519 公钥为 32 字节）", key_id));
+    }
+    Ok(TrustedKey {
+        key_id: key_id.trim().to_string(),
+        public_key,
+    })
+}
+
+/// 解析 "key_id:base64,key_id:base64" 形式的公钥列表
+///
+/// 无效的条目被跳过并记录日志，一把配置错误的密钥不影响其余密钥
+pub fn parse_public_keys(value: &str) -> Vec<TrustedKey> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| match parse_public_key(entry) {
+            Ok(key) => Some(key),
+            Err(e) => {
+                warn!("忽略无效的签名公钥配置: {}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// 去除已吊销的密钥
+pub fn without_revoked(keys: Vec<TrustedKey>, revoked: &str) -> Vec<TrustedKey> {
+    let revoked: Vec<&str> = revoked.split(',').map(str::trim).filter(|id| !id.is_empty()).collect();
+    keys.into_iter()
+        .filter(|key| !revoked.contains(&key.key_id.as_str()))
+        .collect()
+}
+
+/// 应用内嵌的受信任公钥（不含已吊销的密钥）
+pub fn trusted_keys() -> Vec<TrustedKey> {
+    let keys = EMBEDDED_PUBLIC_KEYS.map(parse_public_keys).unwrap_or_default();
+    without_revoked(keys, REVOKED_KEY_IDS.unwrap_or_default())
+}
+
+/// 使用给定公钥列表校验签名（base64），任一公钥通过即返回其 key_id
+pub fn verify_with_keys(keys: &[TrustedKey], message: &[u8], signature: &str) -> Result<String> {
+    if keys.is_empty() {
+        return Err(anyhow!("未配置签名公钥，无法校验签名"));
+    }
+    let signature = BASE64
+        .decode(signature.trim())
+        .map_err(|e| anyhow!("签名解码失败: {}", e))?;
+    keys.iter()
+        .find(|key| {
+            UnparsedPublicKey::new(&ED25519, &key.public_key)
+                .verify(message, &signature)
+                .is_ok()
+        })
+        .map(|key| key.key_id.clone())
+        .ok_or_else(|| anyhow!("签名校验失败，文件可能已被篡改"))
+}
+
+/// 按密钥 ID 校验签名；未指定密钥 ID 时尝试所有受信任公钥
+pub fn verify_with_key_id(
+    keys: &[TrustedKey],
+    key_id: Option<&str>,
+    message: &[u8],
+    signature: &str,
+) -> Result<String> {
+    match key_id.map(str::trim).filter(|id| !id.is_empty()) {
+        Some(key_id) => {
+            let key = keys
+                .iter()
+                .find(|key| key.key_id == key_id)
+                .ok_or_else(|| anyhow!("签名密钥 {} 不受信任（未知或已吊销）", key_id))?;
+            verify_with_keys(std::slice::from_ref(key), message, signature)
+        }
+        None => verify_with_keys(keys, message, signature),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use ring::rand::SystemRandom;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    /// 生成测试密钥对，返回 (签名密钥, 对应的受信任公钥)
+    pub(crate) fn test_key_pair(key_id: &str) -> (Ed25519KeyPair, TrustedKey) {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let key = TrustedKey {
+            key_id: key_id.to_string(),
+            public_key: pair.public_key().as_ref().to_vec(),
+        };
+        (pair, key)
+    }
+
+    pub(crate) fn sign(pair: &Ed25519KeyPair, message: &[u8]) -> String {
+        BASE64.encode(pair.sign(message).as_ref())
+    }
+
+    #[test]
+    fn test_verify_with_rotated_keys() {
+        let (old_pair, old_key) = test_key_pair("2025");
+        let (new_pair, new_key) = test_key_pair("2026");
+        let keys = vec![new_key, old_key];
+
+        assert_eq!(verify_with_keys(&keys, b"payload", &sign(&old_pair, b"payload")).unwrap(), "2025");
+        assert_eq!(verify_with_keys(&keys, b"payload", &sign(&new_pair, b"payload")).unwrap(), "2026");
+        assert!(verify_with_keys(&keys, b"tampered", &sign(&new_pair, b"payload")).is_err());
+        assert!(verify_with_keys(&[], b"payload", &sign(&new_pair, b"payload")).is_err());
+
+        // 指定密钥 ID 时只使用该密钥；吊销后不再被信任
+        let signature = sign(&old_pair, b"payload");
+        assert!(verify_with_key_id(&keys, Some("2025"), b"payload", &signature).is_ok());
+        assert!(verify_with_key_id(&keys, Some("2026"), b"payload", &signature).is_err());
+        let keys = without_revoked(keys, "2025");
+        let err = verify_with_key_id(&keys, Some("2025"), b"payload", &signature).unwrap_err();
+        assert!(err.to_string().contains("不受信任"));
+    }
+
+    #[test]
+    fn test_parse_public_keys() {
+        let (_, key) = test_key_pair("a");
+        let encoded = format!("a:{}, b:{}", BASE64.encode(&key.public_key), BASE64.encode(&key.public_key));
+        let keys = parse_public_keys(&encoded);
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[1].key_id, "b");
+        assert!(parse_public_keys("a:AAAA").is_empty());
+        assert!(parse_public_keys("no-separator").is_empty());
+        assert!(parse_public_key("a:AAAA").unwrap_err().to_string().contains("长度无效"));
+
+        // 无效条目只跳过自身，其余密钥仍受信任
+        let mixed = format!("broken:AAAA, no-separator, a:{}", BASE64.encode(&key.public_key));
+        let keys = parse_public_keys(&mixed);
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].key_id, "a");
+    }
+}
@@ -0,0 +1,638 @@
+// Traffic - 代理流量统计与配额
+// 按环境/代理设置日/月流量配额，桥接流量按周期（UTC）累计到配额上并持久化
+// 桥接流量同时按环境、代理、日期（UTC）累计入库，支持按环境/分组/标签/代理汇总查询与 CSV 导出（用于与代理商对账）
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqliteConnection, SqlitePool};
+use uuid::Uuid;
+
+/// 流量写入数据库的间隔（秒）
+pub const TRAFFIC_FLUSH_INTERVAL_SECS: u64 = 30;
+
+/// 配额预警 / 超额事件
+pub const TRAFFIC_QUOTA_EVENT: &str = "traffic:quota";
+
+/// 配额对象：环境
+pub const SCOPE_PROFILE: &str = "profile";
+/// 配额对象：代理
+pub const SCOPE_PROXY: &str = "proxy";
+
+/// 配额周期：按日
+pub const PERIOD_DAILY: &str = "daily";
+/// 配额周期：按月
+pub const PERIOD_MONTHLY: &str = "monthly";
+
+/// 配额使用级别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuotaLevel {
+    Normal,
+    Warning,
+    Exceeded,
+}
+
+impl QuotaLevel {
+    fn as_str(&self) -> &'static str {
+        match self {
+            QuotaLevel::Normal => "normal",
+            QuotaLevel::Warning => "warning",
+            QuotaLevel::Exceeded => "exceeded",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "warning" => QuotaLevel::Warning,
+            "exceeded" => QuotaLevel::Exceeded,
+            _ => QuotaLevel::Normal,
+        }
+    }
+}
+
+/// 流量配额
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrafficQuota {
+    pub id: String,
+    pub scope: String,
+    pub target_id: String,
+    pub period: String,
+    pub limit_bytes: i64,
+    pub warn_percent: i64,
+    pub stop_on_exceed: bool,
+    pub created_at: String,
+    pub updated_at: String,
+    /// 用量所在周期
+    #[serde(skip)]
+    pub usage_period: Option<String>,
+    /// 该周期内已用字节数
+    #[serde(skip)]
+    pub used_bytes: i64,
+}
+
+/// 设置配额参数（同一对象、同一周期只保留一条）
+#[derive(Debug, Clone, Deserialize)]
+pub struct SetTrafficQuotaDto {
+    pub scope: String,
+    pub target_id: String,
+    pub period: String,
+    pub limit_bytes: i64,
+    pub warn_percent: Option<i64>,
+    #[serde(default)]
+    pub stop_on_exceed: bool,
+}
+
+/// 配额当前状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrafficQuotaStatus {
+    pub quota: TrafficQuota,
+    /// 当前周期（YYYY-MM-DD 或 YYYY-MM）
+    pub period_key: String,
+    pub used_bytes: i64,
+    pub level: QuotaLevel,
+}
+
+/// 用量汇总维度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrafficGroupBy {
+    Profile,
+    Group,
+    /// 一个环境有多个标签时，其流量计入每个标签
+    Tag,
+    Proxy,
+    Day,
+}
+
+/// 用量查询参数（日期为 UTC，YYYY-MM-DD，含首尾）
+#[derive(Debug, Clone, Deserialize)]
+pub struct TrafficUsageQuery {
+    pub group_by: TrafficGroupBy,
+    pub from: String,
+    pub to: String,
+    /// 仅统计指定环境
+    pub profile_id: Option<String>,
+    /// 仅统计指定代理（proxy_key）
+    pub proxy_key: Option<String>,
+}
+
+/// 用量汇总行
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrafficUsageRow {
+    /// 维度标识（环境 ID / 分组 ID / 标签 ID / proxy_key / 日期），未分组、无标签时为空
+    pub key: String,
+    /// 显示名称
+    pub name: String,
+    pub bytes_sent: i64,
+    pub bytes_received: i64,
+    pub total_bytes: i64,
+}
+
+/// CSV 导出表头（按日明细）
+const CSV_HEADER: &str = "day,profile_id,profile_name,group_name,proxy_key,proxy_name,bytes_sent,bytes_received,total_bytes";
+
+/// 校验日期范围
+fn validate_date_range(from: &str, to: &str) -> Result<()> {
+    for value in [from, to] {
+        chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .map_err(|_| anyhow!("无效的日期: {}（格式应为 YYYY-MM-DD）", value))?;
+    }
+    if from > to {
+        return Err(anyhow!("开始日期不能晚于结束日期"));
+    }
+    Ok(())
+}
+
+/// CSV 字段转义：包含逗号、引号或换行时加引号，引号加倍
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// 当前日期（UTC）
+pub fn day_key(now: DateTime<Utc>) -> String {
+    now.format("%Y-%m-%d").to_string()
+}
+
+/// 配额周期标识
+pub fn period_key(period: &str, now: DateTime<Utc>) -> String {
+    match period {
+        PERIOD_MONTHLY => now.format("%Y-%m").to_string(),
+        _ => day_key(now),
+    }
+}
+
+/// 根据用量计算配额级别
+pub fn evaluate_quota(limit_bytes: i64, warn_percent: i64, used_bytes: i64) -> QuotaLevel {
+    if used_bytes >= limit_bytes {
+        QuotaLevel::Exceeded
+    } else if used_bytes as i128 * 100 >= limit_bytes as i128 * warn_percent as i128 {
+        QuotaLevel::Warning
+    } else {
+        QuotaLevel::Normal
+    }
+}
+
+/// 计算配额当前状态：用量不属于当前周期时视为 0
+pub fn quota_status(quota: TrafficQuota, now: DateTime<Utc>) -> TrafficQuotaStatus {
+    let period_key = period_key(&quota.period, now);
+    let used_bytes = if quota.usage_period.as_deref() == Some(period_key.as_str()) {
+        quota.used_bytes
+    } else {
+        0
+    };
+    TrafficQuotaStatus {
+        level: evaluate_quota(quota.limit_bytes, quota.warn_percent, used_bytes),
+        period_key,
+        used_bytes,
+        quota,
+    }
+}
+
+/// 是否需要通知：同一周期内每个级别只通知一次
+fn needs_notification(level: QuotaLevel, period_key: &str, notified: Option<(&str, QuotaLevel)>) -> bool {
+    if level == QuotaLevel::Normal {
+        return false;
+    }
+    match notified {
+        Some((period, notified_level)) if period == period_key => level > notified_level,
+        _ => true,
+    }
+}
+
+/// 一条待入库的流量（环境 + 代理）
+#[derive(Debug, Clone)]
+pub struct TrafficRecord {
+    pub profile_id: String,
+    pub proxy_key: String,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+/// 累加流量
+async fn record_usage(conn: &mut SqliteConnection, record: &TrafficRecord, now: DateTime<Utc>) -> Result<()> {
+    if record.bytes_sent == 0 && record.bytes_received == 0 {
+        return Ok(());
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO traffic_usage (profile_id, proxy_key, day, bytes_sent, bytes_received, updated_at)
+        VALUES (?, ?, ?, ?, ?, ?)
+        ON CONFLICT(profile_id, proxy_key, day) DO UPDATE SET
+            bytes_sent = bytes_sent + excluded.bytes_sent,
+            bytes_received = bytes_received + excluded.bytes_received,
+            updated_at = excluded.updated_at
+        "#,
+    )
+    .bind(&record.profile_id)
+    .bind(&record.proxy_key)
+    .bind(day_key(now))
+    .bind(record.bytes_sent as i64)
+    .bind(record.bytes_received as i64)
+    .bind(now.to_rfc3339())
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// 将流量计入适用于该环境 / 代理的配额，进入新周期时用量重新计数
+async fn add_quota_usage(
+    conn: &mut SqliteConnection,
+    profile_id: &str,
+    proxy_key: &str,
+    bytes: u64,
+    now: DateTime<Utc>,
+) -> Result<()> {
+    if bytes == 0 {
+        return Ok(());
+    }
+
+    for period in [PERIOD_DAILY, PERIOD_MONTHLY] {
+        let key = period_key(period, now);
+        sqlx::query(
+            r#"
+            UPDATE traffic_quotas SET
+                used_bytes = CASE WHEN usage_period = ? THEN used_bytes + ? ELSE ? END,
+                usage_period = ?
+            WHERE period = ?
+              AND ((scope = 'profile' AND target_id = ?) OR (scope = 'proxy' AND target_id = ?))
+            "#,
+        )
+        .bind(&key)
+        .bind(bytes as i64)
+        .bind(bytes as i64)
+        .bind(&key)
+        .bind(period)
+        .bind(profile_id)
+        .bind(proxy_key)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// 流量统计服务
+pub struct TrafficService {
+    pool: SqlitePool,
+}
+
+impl TrafficService {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// 代理标识：已登记的代理使用 proxies.id，否则使用 host:port
+    pub async fn resolve_proxy_key(&self, host: &str, port: u16) -> Result<String> {
+        let id: Option<String> = sqlx::query_scalar("SELECT id FROM proxies WHERE host = ? AND port = ? LIMIT 1")
+            .bind(host)
+            .bind(port.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(id.unwrap_or_else(|| format!("{}:{}", host, port)))
+    }
+
+    /// 在同一事务中累加流量并计入配额（失败时整体回滚，调用方可原样重试）
+    pub async fn record_traffic(&self, records: &[TrafficRecord], now: DateTime<Utc>) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        for record in records {
+            record_usage(&mut tx, record, now).await?;
+            add_quota_usage(&mut tx, &record.profile_id, &record.proxy_key, record.bytes_sent + record.bytes_received, now).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// 设置配额（覆盖同一对象、同一周期的已有配额）
+    pub async fn set_quota(&self, dto: SetTrafficQuotaDto) -> Result<TrafficQuota> {
+        if dto.scope != SCOPE_PROFILE && dto.scope != SCOPE_PROXY {
+            return Err(anyhow!("未知的配额对象: {}", dto.scope));
+        }
+        if dto.period != PERIOD_DAILY && dto.period != PERIOD_MONTHLY {
+            return Err(anyhow!("未知的配额周期: {}", dto.period));
+        }
+        if dto.limit_bytes <= 0 {
+            return Err(anyhow!("配额必须大于 0"));
+        }
+        let warn_percent = dto.warn_percent.unwrap_or(80);
+        if !(1..=100).contains(&warn_percent) {
+            return Err(anyhow!("预警阈值必须在 1-100 之间"));
+        }
+
+        let now = Utc::now().to_rfc3339();
+        sqlx::query(
+            r#"
+            INSERT INTO traffic_quotas (id, scope, target_id, period, limit_bytes, warn_percent, stop_on_exceed, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(scope, target_id, period) DO UPDATE SET
+                limit_bytes = excluded.limit_bytes,
+                warn_percent = excluded.warn_percent,
+                stop_on_exceed = excluded.stop_on_exceed,
+                notified_period = NULL,
+                notified_level = NULL,
+                updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(&dto.scope)
+        .bind(&dto.target_id)
+        .bind(&dto.period)
+        .bind(dto.limit_bytes)
+        .bind(warn_percent)
+        .bind(dto.stop_on_exceed)
+        .bind(&now)
+        .bind(&now)
+        .execute(&self.pool)
+        .await?;
+
+        let row = sqlx::query("SELECT * FROM traffic_quotas WHERE scope = ? AND target_id = ? AND period = ?")
+            .bind(&dto.scope)
+            .bind(&dto.target_id)
+            .bind(&dto.period)
+            .fetch_one(&self.pool)
+            .await?;
+        Self::row_to_quota(&row)
+    }
+
+    /// 删除配额
+    pub async fn delete_quota(&self, id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM traffic_quotas WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// 列出所有配额
+    pub async fn list_quotas(&self) -> Result<Vec<TrafficQuota>> {
+        let rows = sqlx::query("SELECT * FROM traffic_quotas ORDER BY created_at")
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(Self::row_to_quota).collect()
+    }
+
+    /// 适用于指定环境 / 代理的配额
+    pub async fn quotas_for(&self, profile_id: &str, proxy_key: &str) -> Result<Vec<TrafficQuota>> {
+        let rows = sqlx::query(
+            "SELECT * FROM traffic_quotas WHERE (scope = 'profile' AND target_id = ?) OR (scope = 'proxy' AND target_id = ?)",
+        )
+        .bind(profile_id)
+        .bind(proxy_key)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(Self::row_to_quota).collect()
+    }
+
+    /// 所有配额的当前状态
+    pub async fn list_quota_statuses(&self, now: DateTime<Utc>) -> Result<Vec<TrafficQuotaStatus>> {
+        Ok(self
+            .list_quotas()
+            .await?
+            .into_iter()
+            .map(|quota| quota_status(quota, now))
+            .collect())
+    }
+
+    /// 检查所有配额，返回本周期内首次达到预警或超额的配额（并记录已通知）
+    pub async fn check_quotas(&self, now: DateTime<Utc>) -> Result<Vec<TrafficQuotaStatus>> {
+        let rows = sqlx::query("SELECT * FROM traffic_quotas").fetch_all(&self.pool).await?;
+
+        let mut crossed = Vec::new();
+        for row in &rows {
+            let notified_period: Option<String> = row.try_get("notified_period")?;
+            let notified_level: Option<String> = row.try_get("notified_level")?;
+            let status = quota_status(Self::row_to_quota(row)?, now);
+
+            let notified = notified_period
+                .as_deref()
+                .zip(notified_level.as_deref().map(QuotaLevel::parse));
+            if !needs_notification(status.level, &status.period_key, notified) {
+                continue;
+            }
+
+            sqlx::query("UPDATE traffic_quotas SET notified_period = ?, notified_level = ? WHERE id = ?")
+                .bind(&status.period_key)
+                .bind(status.level.as_str())
+                .bind(&status.quota.id)
+                .execute(&self.pool)
+                .await?;
+            crossed.push(status);
+        }
+
+        Ok(crossed)
+    }
+
+    /// 按维度汇总指定日期范围内的用量（按总量降序，按日汇总时按日期升序）
+    pub async fn aggregate_usage(&self, query: &TrafficUsageQuery) -> Result<Vec<TrafficUsageRow>> {
+        validate_date_range(&query.from, &query.to)?;
+
+        let (key, name, join, order) = match query.group_by {
+            TrafficGroupBy::Profile => (
+                "u.profile_id",
+                "COALESCE(p.name, u.profile_id)",
+                "",
+                "total_bytes DESC",
+            ),
+            TrafficGroupBy::Group => (
+                "COALESCE(p.group_id, '')",
+                "COALESCE(g.name, '未分组')",
+                "LEFT JOIN groups g ON g.id = p.group_id",
+                "total_bytes DESC",
+            ),
+            TrafficGroupBy::Tag => (
+                "COALESCE(t.id, '')",
+                "COALESCE(t.name, '无标签')",
+                "LEFT JOIN profile_tags pt ON pt.profile_id = u.profile_id LEFT JOIN tags t ON t.id = pt.tag_id",
+                "total_bytes DESC",
+            ),
+            TrafficGroupBy::Proxy => (
+                "u.proxy_key",
+                "COALESCE(x.name, u.proxy_key)",
+                "LEFT JOIN proxies x ON x.id = u.proxy_key",
+                "total_bytes DESC",
+            ),
+            TrafficGroupBy::Day => ("u.day", "u.day", "", "u.day ASC"),
+        };
+
+        let sql = format!(
+            r#"
+            SELECT {key} AS key, {name} AS name,
+                   SUM(u.bytes_sent) AS bytes_sent, SUM(u.bytes_received) AS bytes_received,
+                   SUM(u.bytes_sent + u.bytes_received) AS total_bytes
+            FROM traffic_usage u
+            LEFT JOIN profiles p ON p.id = u.profile_id
+            {join}
+            WHERE u.day BETWEEN ? AND ?
+              AND (? IS NULL OR u.profile_id = ?)
+              AND (? IS NULL OR u.proxy_key = ?)
+            GROUP BY {key}
+            ORDER BY {order}
+            "#
+        );
+
+        let rows = sqlx::query(&sql)
+            .bind(&query.from)
+            .bind(&query.to)
+            .bind(&query.profile_id)
+            .bind(&query.profile_id)
+            .bind(&query.proxy_key)
+            .bind(&query.proxy_key)
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(TrafficUsageRow {
+                    key: row.try_get("key")?,
+                    name: row.try_get("name")?,
+                    bytes_sent: row.try_get("bytes_sent")?,
+                    bytes_received: row.try_get("bytes_received")?,
+                    total_bytes: row.try_get("total_bytes")?,
+                })
+            })
+            .collect()
+    }
+
+    /// 导出日期范围内的按日明细（环境 × 代理 × 日期）为 CSV
+    pub async fn export_usage_csv(&self, query: &TrafficUsageQuery) -> Result<String> {
+        validate_date_range(&query.from, &query.to)?;
+
+        let rows = sqlx::query(
+            r#"
+            SELECT u.day, u.profile_id, COALESCE(p.name, '') AS profile_name, COALESCE(g.name, '') AS group_name,
+                   u.proxy_key, COALESCE(x.name, '') AS proxy_name, u.bytes_sent, u.bytes_received
+            FROM traffic_usage u
+            LEFT JOIN profiles p ON p.id = u.profile_id
+            LEFT JOIN groups g ON g.id = p.group_id
+            LEFT JOIN proxies x ON x.id = u.proxy_key
+            WHERE u.day BETWEEN ? AND ?
+              AND (? IS NULL OR u.profile_id = ?)
+              AND (? IS NULL OR u.proxy_key = ?)
+            ORDER BY u.day ASC, u.proxy_key ASC, u.profile_id ASC
+            "#,
+        )
+        .bind(&query.from)
+        .bind(&query.to)
+        .bind(&query.profile_id)
+        .bind(&query.profile_id)
+        .bind(&query.proxy_key)
+        .bind(&query.proxy_key)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut csv = String::from(CSV_HEADER);
+        csv.push('\n');
+        for row in &rows {
+            let bytes_sent: i64 = row.try_get("bytes_sent")?;
+            let bytes_received: i64 = row.try_get("bytes_received")?;
+            let text_fields = ["day", "profile_id", "profile_name", "group_name", "proxy_key", "proxy_name"]
+                .iter()
+                .map(|column| row.try_get::<String, _>(*column).map(|v| csv_field(&v)))
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            csv.push_str(&format!(
+                "{},{},{},{}\n",
+                text_fields.join(","),
+                bytes_sent,
+                bytes_received,
+                bytes_sent + bytes_received
+            ));
+        }
+
+        Ok(csv)
+    }
+
+    fn row_to_quota(row: &sqlx::sqlite::SqliteRow) -> Result<TrafficQuota> {
+        Ok(TrafficQuota {
+            id: row.try_get("id")?,
+            scope: row.try_get("scope")?,
+            target_id: row.try_get("target_id")?,
+            period: row.try_get("period")?,
+            limit_bytes: row.try_get("limit_bytes")?,
+            warn_percent: row.try_get("warn_percent")?,
+            stop_on_exceed: row.try_get("stop_on_exceed")?,
+            created_at: row.try_get("created_at")?,
+            updated_at: row.try_get("updated_at")?,
+            usage_period: row.try_get("usage_period")?,
+            used_bytes: row.try_get("used_bytes")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_evaluate_quota() {
+        assert_eq!(evaluate_quota(1000, 80, 0), QuotaLevel::Normal);
+        assert_eq!(evaluate_quota(1000, 80, 799), QuotaLevel::Normal);
+        assert_eq!(evaluate_quota(1000, 80, 800), QuotaLevel::Warning);
+        assert_eq!(evaluate_quota(1000, 80, 1000), QuotaLevel::Exceeded);
+    }
+
+    #[test]
+    fn test_quota_notifications_once_per_level_and_period() {
+        assert!(!needs_notification(QuotaLevel::Normal, "2026-10", None));
+        assert!(needs_notification(QuotaLevel::Warning, "2026-10", None));
+        assert!(!needs_notification(QuotaLevel::Warning, "2026-10", Some(("2026-10", QuotaLevel::Warning))));
+        assert!(needs_notification(QuotaLevel::Exceeded, "2026-10", Some(("2026-10", QuotaLevel::Warning))));
+        assert!(!needs_notification(QuotaLevel::Warning, "2026-10", Some(("2026-10", QuotaLevel::Exceeded))));
+        // 新周期重新通知
+        assert!(needs_notification(QuotaLevel::Warning, "2026-11", Some(("2026-10", QuotaLevel::Exceeded))));
+    }
+
+    #[test]
+    fn test_quota_usage_counts_current_period_only() {
+        let now = Utc.with_ymd_and_hms(2026, 10, 1, 8, 0, 0).unwrap();
+        let quota = TrafficQuota {
+            id: "q1".to_string(),
+            scope: SCOPE_PROFILE.to_string(),
+            target_id: "p1".to_string(),
+            period: PERIOD_MONTHLY.to_string(),
+            limit_bytes: 1000,
+            warn_percent: 80,
+            stop_on_exceed: true,
+            created_at: now.to_rfc3339(),
+            updated_at: now.to_rfc3339(),
+            usage_period: Some("2026-10".to_string()),
+            used_bytes: 1200,
+        };
+        let status = quota_status(quota.clone(), now);
+        assert_eq!(status.used_bytes, 1200);
+        assert_eq!(status.level, QuotaLevel::Exceeded);
+
+        // 上个周期的用量不计入本周期
+        let status = quota_status(TrafficQuota { usage_period: Some("2026-09".to_string()), ..quota }, now);
+        assert_eq!(status.used_bytes, 0);
+        assert_eq!(status.level, QuotaLevel::Normal);
+    }
+
+    #[test]
+    fn test_validate_date_range() {
+        assert!(validate_date_range("2026-10-01", "2026-10-31").is_ok());
+        assert!(validate_date_range("2026-10-01", "2026-10-01").is_ok());
+        assert!(validate_date_range("2026-10-31", "2026-10-01").is_err());
+        assert!(validate_date_range("2026-10", "2026-10-31").is_err());
+    }
+
+    #[test]
+    fn test_csv_field_escaping() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("line\nbreak"), "\"line\nbreak\"");
+    }
+
+    #[test]
+    fn test_period_key() {
+        let now = Utc.with_ymd_and_hms(2026, 3, 9, 23, 59, 0).unwrap();
+        assert_eq!(period_key(PERIOD_DAILY, now), "2026-03-09");
+        assert_eq!(period_key(PERIOD_MONTHLY, now), "2026-03");
+    }
+}
@@ -445,6 +445,18 @@ pub fn set_window_bounds(hwnd_ptr: isize, x: i32, y: i32, width: i32, height: i3
     }
 }
 
+/// 向指定进程的主窗口发送 WM_CLOSE（请求浏览器正常关闭），返回发送的窗口数
+#[cfg(windows)]
+pub fn close_windows_for_pid(pid: u32) -> usize {
+    use windows::Win32::Foundation::WPARAM;
+    use windows::Win32::UI::WindowsAndMessaging::{PostMessageW, WM_CLOSE};
+
+    collect_main_windows_for_pids(&[pid])
+        .into_iter()
+        .filter(|hwnd| unsafe { PostMessageW(*hwnd, WM_CLOSE, WPARAM(0), LPARAM(0)) }.is_ok())
+        .count()
+}
+
 // ==================== 非 Windows 平台的占位实现 ====================
 
 #[cfg(not(windows))]
@@ -467,6 +479,11 @@ pub async fn collect_window_details_with_retry(_pids: &[u32], _max_retries: u32,
     Vec::new()
 }
 
+#[cfg(not(windows))]
+pub fn close_windows_for_pid(_pid: u32) -> usize {
+    0
+}
+
 #[cfg(not(windows))]
 pub fn rename_window(_hwnd_ptr: isize, _new_title: &str) -> Result<(), String> {
     Err("窗口操作仅支持 Windows 平台".to_string())